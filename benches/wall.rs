@@ -1,8 +1,4 @@
-use azul_tiles_rs::{
-    gamestate::Gamestate,
-    players::{MoveRankPlayer2, MoveWeightPlayer},
-    runner::Runner,
-};
+use azul_tiles_rs::{gamestate::Gamestate, players::MoveRankPlayer2, runner::Runner};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
@@ -0,0 +1,219 @@
+//! Ending decided games early
+//!
+//! Long tournaments spend much of their time playing out games whose winner is already
+//! clear. With an [Adjudication] a [Runner](crate::runner::Runner) stops a game in what
+//! may be its final round when either no play can change the winner, or a reference search
+//! has found one player ahead by more than a margin for several plies in a row.
+//! The game is scored with the predicted scores and the reason kept in the results
+
+use crate::{
+    analysis::{self, Limit},
+    gamestate::{Gamestate, State},
+    playerboard::PlayerBoard,
+};
+
+/// Most points a tile placed on the wall can score: 5 across, 5 down and the bonuses
+/// for completing its row, column and colour
+const MAX_TILE_POINTS: u8 = 5 + 5 + 2 + 7 + 10;
+
+/// Most points lost for a full floor
+const MAX_FLOOR_PENALTY: u8 = 14;
+
+/// When to end a game early, set on a runner with
+/// [Runner::with_adjudication](crate::runner::Runner::with_adjudication)
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Adjudication {
+    /// Lead in points the reference search must find, no search without one
+    pub margin: Option<f32>,
+    /// Consecutive plies the search must find the same player ahead by the margin
+    pub plies: u8,
+    /// Depth of the reference search
+    pub depth: u8,
+    /// End games where the leader can't be caught, whatever is played
+    pub insurmountable: bool,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Self {
+            margin: None,
+            plies: 4,
+            depth: 2,
+            insurmountable: true,
+        }
+    }
+}
+
+/// Why a game was ended early
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdjudicationReason {
+    /// The reference search found a lead of at least the margin
+    Evaluation,
+    /// The trailing player can't score enough to catch up
+    Insurmountable,
+}
+
+/// Games ended early for each reason
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AdjudicationCount {
+    pub evaluation: u32,
+    pub insurmountable: u32,
+}
+
+impl AdjudicationCount {
+    pub fn total(&self) -> u32 {
+        self.evaluation + self.insurmountable
+    }
+}
+
+impl std::ops::AddAssign<AdjudicationReason> for AdjudicationCount {
+    fn add_assign(&mut self, rhs: AdjudicationReason) {
+        match rhs {
+            AdjudicationReason::Evaluation => self.evaluation += 1,
+            AdjudicationReason::Insurmountable => self.insurmountable += 1,
+        }
+    }
+}
+
+impl std::ops::AddAssign for AdjudicationCount {
+    fn add_assign(&mut self, rhs: Self) {
+        self.evaluation += rhs.evaluation;
+        self.insurmountable += rhs.insurmountable;
+    }
+}
+
+/// Applies an [Adjudication] to one game, move by move
+#[derive(Debug, Clone)]
+pub struct Adjudicator {
+    rules: Adjudication,
+    /// Player the search has found ahead by the margin, and for how many plies
+    streak: Option<(u8, u8)>,
+}
+
+impl Adjudicator {
+    pub fn new(rules: Adjudication) -> Self {
+        Self {
+            rules,
+            streak: None,
+        }
+    }
+
+    /// Check the position after a move, returning the reason and the scores
+    /// to give the game if it is decided
+    pub fn check(&mut self, gs: &Gamestate<2, 6>) -> Option<(AdjudicationReason, [u8; 2])> {
+        if gs.state() != State::RoundActive || gs.rounds_left() > 1 {
+            self.streak = None;
+            return None;
+        }
+        if self.rules.insurmountable && insurmountable(gs) {
+            return Some((AdjudicationReason::Insurmountable, gs.predicted_scores()));
+        }
+        let margin = self.rules.margin?;
+        let analysis = analysis::analyse(gs, Limit::Depth(self.rules.depth));
+        let best = analysis.best()?;
+        if best.eval.abs() < margin {
+            self.streak = None;
+            return None;
+        }
+        let leader = if best.eval > 0.0 { 0 } else { 1 };
+        let plies = match self.streak {
+            Some((player, plies)) if player == leader => plies + 1,
+            _ => 1,
+        };
+        self.streak = Some((leader, plies));
+        if plies < self.rules.plies {
+            return None;
+        }
+        // Scores at the end of the line the search expects
+        let mut end = gs.clone();
+        for &m in &best.pv {
+            end.play_move(m);
+        }
+        Some((AdjudicationReason::Evaluation, end.predicted_scores()))
+    }
+}
+
+/// Whether the game ends this round with the player ahead on predicted score winning
+/// however the round is played
+pub fn insurmountable(gs: &Gamestate<2, 6>) -> bool {
+    if !gs.boards().iter().any(|b| b.simulate_wall().has_full_row()) {
+        return false;
+    }
+    let scores = gs.predicted_scores();
+    let (leader, trailer) = if scores[0] > scores[1] {
+        (0, 1)
+    } else {
+        (1, 0)
+    };
+    let boards = gs.boards();
+    // The leader can only lose floor points, the trailer can only gain points
+    // from the rows it can still complete
+    let worst = MAX_FLOOR_PENALTY + gs.handicaps()[leader].floor_penalty;
+    let leader_least =
+        scores[leader].saturating_sub(worst.saturating_sub(boards[leader].floor_penalty()));
+    let trailer_most = scores[trailer] as u16
+        + MAX_TILE_POINTS as u16 * completable_rows(&boards[trailer], gs.tiles_left()) as u16;
+    leader_least as u16 > trailer_most
+}
+
+/// Most pattern rows that could be filled with `tiles` more tiles
+fn completable_rows(board: &PlayerBoard, tiles: u8) -> u8 {
+    let mut needed = board
+        .row_iter()
+        .map(|(row, r)| row.capacity() - r.count())
+        .filter(|&n| n > 0)
+        .collect::<Vec<_>>();
+    needed.sort();
+    let mut left = tiles;
+    let mut rows = 0;
+    for n in needed {
+        if n > left {
+            break;
+        }
+        left -= n;
+        rows += 1;
+    }
+    rows
+}
+
+#[cfg(test)]
+mod test {
+    use super::{insurmountable, Adjudication, AdjudicationReason, Adjudicator};
+    use crate::{
+        gamestate::{Gamestate, State},
+        players::{MoveRankPlayer2, Player},
+    };
+
+    #[test]
+    fn adjudication() {
+        // Nothing is decided at the start
+        let gs = Gamestate::new_2_player_with_seed(3, 0);
+        assert!(!insurmountable(&gs));
+        let mut adjudicator = Adjudicator::new(Adjudication {
+            margin: Some(0.0),
+            plies: 1,
+            ..Default::default()
+        });
+        assert_eq!(adjudicator.check(&gs), None);
+
+        // Play until the final round, where any lead is enough
+        let mut gs = gs;
+        let mut player = MoveRankPlayer2;
+        let adjudicated = loop {
+            let m = player.pick_move(&gs, gs.get_moves());
+            if gs.play_move(m) == State::RoundEnd && gs.end_round().game_over() {
+                break None;
+            }
+            if let Some(result) = adjudicator.check(&gs) {
+                break Some(result);
+            }
+        };
+        let (reason, scores) = adjudicated.expect("Game is adjudicated");
+        assert_eq!(gs.rounds_left(), 1);
+        if reason == AdjudicationReason::Insurmountable {
+            assert_eq!(scores, gs.predicted_scores());
+        }
+    }
+}
@@ -0,0 +1,354 @@
+//! Position analysis for tooling
+//!
+//! Fixed depth alpha-beta search within the current round that returns
+//! an evaluation and principal variation for every root move,
+//! along with search statistics.
+//! Every root move gets a line, deeper in the tree moves that lead to the same position
+//! are only searched once.
+//! Evaluations are the predicted score margin of player 0's side, see
+//! [Gamestate::predicted_margin], which every other side is assumed to play against
+
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::{
+    gamestate::{Gamestate, Move, State},
+    players::evaluate::{evaluate_positions, Evaluate},
+};
+
+/// Limit on how long to analyse a position for
+#[derive(Debug, Clone, Copy)]
+pub enum Limit {
+    /// Search to a fixed depth
+    Depth(u8),
+    /// Iteratively deepen until the time is up
+    Time(Duration),
+}
+
+/// Evaluation and principal variation after a root move
+#[derive(Debug, Clone)]
+pub struct Line {
+    /// Evaluation from player 0's perspective
+    pub eval: f32,
+    /// Root move followed by the best replies
+    pub pv: Vec<Move>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchStats {
+    /// Nodes visited, including leaves
+    pub nodes: u64,
+    /// Deepest completed search depth
+    pub depth: u8,
+    /// Time spent searching
+    pub elapsed: Duration,
+}
+
+/// Result of analysing a position
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    /// Line for each root move, best first for the player to move
+    pub lines: Vec<Line>,
+    pub stats: SearchStats,
+}
+
+impl Analysis {
+    /// Best line for the player to move
+    pub fn best(&self) -> Option<&Line> {
+        self.lines.first()
+    }
+}
+
+/// Analyse the position for the current player
+///
+/// Searches up to the end of the current round only, as the tiles dealt
+/// in the next round are not known.
+/// Depth 1 is always finished, so every root move has a line however short the time
+pub fn analyse<const P: usize, const F: usize, R: Rng + Clone, const C: usize>(
+    gs: &Gamestate<P, F, R, C>,
+    limit: Limit,
+) -> Analysis {
+    let start = Instant::now();
+    let (max_depth, deadline) = match limit {
+        Limit::Depth(depth) => (depth.max(1), None),
+        Limit::Time(time) => (u8::MAX, Some(start + time)),
+    };
+    let mut search = Search {
+        nodes: 0,
+        deadline: None,
+        aborted: false,
+    };
+    let sign = sign(gs);
+    let mut lines = Vec::new();
+    let mut depth = 0;
+    for d in 1..=max_depth {
+        search.deadline = deadline.filter(|_| d > 1);
+        let mut depth_lines = Vec::new();
+        for m in gs.get_moves() {
+            let mut child = gs.clone();
+            child.play_move(m);
+            let mut pv = vec![m];
+            let eval = search.child(
+                &mut child,
+                sign,
+                d - 1,
+                f32::NEG_INFINITY,
+                f32::INFINITY,
+                &mut pv,
+            );
+            depth_lines.push(Line {
+                eval: sign * eval,
+                pv,
+            });
+        }
+        if search.aborted {
+            break;
+        }
+        depth_lines.sort_by(|a, b| (sign * b.eval).total_cmp(&(sign * a.eval)));
+        lines = depth_lines;
+        depth = d;
+        // Stop early if every line reaches the end of the round
+        if lines.iter().all(|l| l.pv.len() < d as usize) {
+            break;
+        }
+    }
+    Analysis {
+        lines,
+        stats: SearchStats {
+            nodes: search.nodes,
+            depth,
+            elapsed: start.elapsed(),
+        },
+    }
+}
+
+/// Count the leaf nodes of the move tree to `depth`, for validating move generation
+///
+/// Rounds are ended as they complete, so the tiles for the next round
+/// come from the gamestate's rng and the count is fixed for a seeded game.
+/// Games that finish before `depth` count as a single leaf
+pub fn perft<const P: usize, const F: usize, R: Rng + Clone, const C: usize>(
+    gs: &Gamestate<P, F, R, C>,
+    depth: u8,
+) -> u64 {
+    if depth == 0 || gs.state() == State::GameEnd {
+        return 1;
+    }
+    gs.get_moves()
+        .into_iter()
+        .map(|m| perft(&play(gs, m), depth - 1))
+        .sum()
+}
+
+/// Leaf counts for each move from the position, in move generation order
+pub fn perft_divide<const P: usize, const F: usize, R: Rng + Clone, const C: usize>(
+    gs: &Gamestate<P, F, R, C>,
+    depth: u8,
+) -> Vec<(Move, u64)> {
+    gs.get_moves()
+        .into_iter()
+        .map(|m| (m, perft(&play(gs, m), depth.saturating_sub(1))))
+        .collect()
+}
+
+/// Clone and play the move, ending the round if required
+fn play<const P: usize, const F: usize, R: Rng + Clone, const C: usize>(
+    gs: &Gamestate<P, F, R, C>,
+    m: Move,
+) -> Gamestate<P, F, R, C> {
+    let mut child = gs.clone();
+    if child.play_move(m) == State::RoundEnd {
+        child.end_round();
+    }
+    child
+}
+
+/// +1 if a player on player 0's side is to move, -1 for the other sides
+///
+/// The current player is not advanced by the last move of the round,
+/// so the player to move at the end of the round is the next player
+fn sign<const P: usize, const F: usize, R: Rng + Clone, const C: usize>(
+    gs: &Gamestate<P, F, R, C>,
+) -> f32 {
+    let mut player = gs.current_player() as usize;
+    if gs.state() == State::RoundEnd {
+        player = (player + 1) % P;
+    }
+    let ours = if gs.teams() {
+        player.is_multiple_of(2)
+    } else {
+        player == 0
+    };
+    if ours {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+/// Evaluation of every legal move of each position, for player 0, found by evaluating
+/// the positions after all the moves together with [evaluate_positions]
+pub fn evaluate_moves<const P: usize, const F: usize>(
+    positions: &[Gamestate<P, F>],
+    evaluator: &mut (dyn Evaluate<P, F> + Send),
+    threads: usize,
+) -> Vec<Vec<(Move, f32)>> {
+    let moves = positions
+        .iter()
+        .map(|gs| gs.get_moves())
+        .collect::<Vec<_>>();
+    let children = positions
+        .iter()
+        .zip(&moves)
+        .flat_map(|(gs, moves)| {
+            moves.iter().map(|&m| {
+                let mut child = gs.clone();
+                child.play_move(m);
+                child
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut values = evaluate_positions(&children, evaluator, threads).into_iter();
+    moves
+        .into_iter()
+        .map(|moves| moves.into_iter().zip(values.by_ref()).collect())
+        .collect()
+}
+
+/// Evaluates positions by searching them to a fixed depth, for player 0.
+/// The children of a position evaluated one ply shallower than [analyse]'s depth
+/// get the same evaluations as its lines
+#[derive(Debug, Clone, Copy)]
+pub struct SearchEvaluator(pub u8);
+
+impl<const P: usize, const F: usize> Evaluate<P, F> for SearchEvaluator {
+    fn evaluate(&mut self, gs: &Gamestate<P, F>) -> f32 {
+        let mut search = Search {
+            nodes: 0,
+            deadline: None,
+            aborted: false,
+        };
+        let eval = search.negamax(
+            &mut gs.clone(),
+            self.0,
+            f32::NEG_INFINITY,
+            f32::INFINITY,
+            &mut Vec::new(),
+        );
+        sign(gs) * eval
+    }
+}
+
+struct Search {
+    nodes: u64,
+    deadline: Option<Instant>,
+    aborted: bool,
+}
+
+impl Search {
+    /// Returns the evaluation for the player to move and appends the best line to pv.
+    /// Moves are applied and taken back in place, leaving gs as it was
+    fn negamax<const P: usize, const F: usize, R: Rng + Clone, const C: usize>(
+        &mut self,
+        gs: &mut Gamestate<P, F, R, C>,
+        depth: u8,
+        mut alpha: f32,
+        beta: f32,
+        pv: &mut Vec<Move>,
+    ) -> f32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.deadline.is_some_and(|d| Instant::now() > d) {
+            self.aborted = true;
+        }
+        if depth == 0 || gs.state() != State::RoundActive || self.aborted {
+            return sign(gs) * gs.predicted_margin();
+        }
+        let mover = sign(gs);
+        let mut best = f32::NEG_INFINITY;
+        let mut best_line = Vec::new();
+        for m in gs.get_distinct_moves() {
+            let undo = gs.apply(m);
+            let mut line = vec![m];
+            let eval = self.child(gs, mover, depth - 1, alpha, beta, &mut line);
+            gs.unapply(undo);
+            if eval > best {
+                best = eval;
+                best_line = line;
+            }
+            alpha = alpha.max(eval);
+            if alpha >= beta {
+                break;
+            }
+        }
+        pv.extend(best_line);
+        best
+    }
+
+    /// [Search::negamax] of the position after a move, for the side with sign `mover`
+    /// that played it, which may be to move again when more than two sides play
+    fn child<const P: usize, const F: usize, R: Rng + Clone, const C: usize>(
+        &mut self,
+        gs: &mut Gamestate<P, F, R, C>,
+        mover: f32,
+        depth: u8,
+        alpha: f32,
+        beta: f32,
+        pv: &mut Vec<Move>,
+    ) -> f32 {
+        if sign(gs) == mover {
+            self.negamax(gs, depth, alpha, beta, pv)
+        } else {
+            -self.negamax(gs, depth, -beta, -alpha, pv)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{analyse, perft, perft_divide, Limit, SearchEvaluator};
+    use crate::{gamestate::Gamestate, players::evaluate::Evaluate};
+
+    #[test]
+    fn analyse_position() {
+        let gs = Gamestate::new_2_player_with_seed(5, 0);
+        let analysis = analyse(&gs, Limit::Depth(2));
+        assert_eq!(analysis.lines.len(), gs.get_moves().len());
+        assert_eq!(analysis.stats.depth, 2);
+        assert!(analysis.lines.iter().all(|l| l.pv.len() == 2));
+        // Sorted best first for player 0
+        assert!(analysis.lines.windows(2).all(|w| w[0].eval >= w[1].eval));
+
+        // Evaluating the children a ply shallower gives the same evaluations
+        for line in &analysis.lines {
+            let mut child = gs.clone();
+            child.play_move(line.pv[0]);
+            assert_eq!(SearchEvaluator(1).evaluate(&child), line.eval);
+        }
+
+        // Depth 1 is finished even without any time
+        let timed = analyse(&gs, Limit::Time(Duration::ZERO));
+        assert_eq!(timed.lines.len(), gs.get_moves().len());
+        assert!(timed.stats.depth >= 1);
+    }
+
+    #[test]
+    fn more_players() {
+        let gs = Gamestate::<3, 8>::new(5, 0);
+        let analysis = analyse(&gs, Limit::Depth(3));
+        assert_eq!(analysis.lines.len(), gs.get_moves().len());
+        assert!(analysis.lines.iter().all(|l| l.pv.len() == 3));
+        assert_eq!(perft(&gs, 1), gs.get_moves().len() as u64);
+    }
+
+    #[test]
+    fn perft_counts() {
+        let gs = Gamestate::new_2_player_with_seed(0, 0);
+        assert_eq!(perft(&gs, 0), 1);
+        assert_eq!(perft(&gs, 1), gs.get_moves().len() as u64);
+        let divide = perft_divide(&gs, 2);
+        assert_eq!(divide.iter().map(|d| d.1).sum::<u64>(), perft(&gs, 2));
+    }
+}
@@ -0,0 +1,125 @@
+//! Random mid-game positions, for fuzzing the rules and testing players
+//!
+//! Every position is reached by uniformly random legal moves from a new game, so it is one
+//! a real game can get to and passes [Gamestate::check_invariants]. All randomness comes
+//! from a `u64` seed, so proptest shrinks a failing position by shrinking its seed.
+//! Use [gamestate_strategy] or `any::<Gamestate<P, F>>()` in a `proptest!` block.
+//!
+//! Enabled by the `proptest` feature
+
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    num,
+    strategy::{Map, Strategy},
+};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::gamestate::{Gamestate, State};
+
+/// Last round a position is drawn from, before random play risks ending the game
+const LAST_ROUND: u16 = 4;
+
+/// Position in round 1 to 4 with moves left to play, drawn from `seed`
+pub fn random_position<const P: usize, const F: usize>(seed: u64) -> Gamestate<P, F> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut gs = Gamestate::new(rng.gen(), rng.gen_range(0..P as u8));
+    let round = rng.gen_range(1..=LAST_ROUND);
+    // Enough moves to empty every factory, so any point in the round can be picked
+    let mut plies = rng.gen_range(0..F * 4);
+    // Start of the latest round, always with moves left to play
+    let mut start = gs.clone();
+    loop {
+        match gs.state() {
+            State::RoundActive if gs.round() < round => {
+                play_random(&mut gs, &mut rng);
+            }
+            State::RoundActive if plies > 0 => {
+                let before = gs.clone();
+                play_random(&mut gs, &mut rng);
+                if gs.state() != State::RoundActive {
+                    return before;
+                }
+                plies -= 1;
+            }
+            State::RoundActive => return gs,
+            State::RoundEnd => {
+                if gs.end_round().state == State::RoundActive {
+                    start = gs.clone();
+                }
+            }
+            // Random play completed a row early, go back to the start of the round
+            State::GameEnd => return start,
+        }
+    }
+}
+
+/// Strategy for positions from [random_position]
+pub fn gamestate_strategy<const P: usize, const F: usize>() -> GamestateStrategy<P, F> {
+    any::<u64>().prop_map(random_position as fn(u64) -> Gamestate<P, F>)
+}
+
+/// Strategy returned by [gamestate_strategy]
+pub type GamestateStrategy<const P: usize, const F: usize> =
+    Map<num::u64::Any, fn(u64) -> Gamestate<P, F>>;
+
+impl<const P: usize, const F: usize> Arbitrary for Gamestate<P, F> {
+    type Parameters = ();
+    type Strategy = GamestateStrategy<P, F>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        gamestate_strategy()
+    }
+}
+
+fn play_random<const P: usize, const F: usize>(gs: &mut Gamestate<P, F>, rng: &mut SmallRng) {
+    let moves = gs.get_moves();
+    gs.play_move(*moves.choose(rng).unwrap());
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::{collection::vec, prelude::*, sample::Index};
+
+    use super::{gamestate_strategy, random_position};
+    use crate::gamestate::{Gamestate, State};
+
+    #[test]
+    fn positions_cover_rounds() {
+        let mut rounds = [0; 5];
+        for seed in 0..100 {
+            let gs: Gamestate<2, 6> = random_position(seed);
+            assert_eq!(random_position::<2, 6>(seed).core(), gs.core());
+            rounds[gs.round() as usize] += 1;
+        }
+        assert!(rounds[1..].iter().all(|&n| n > 0));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn positions_keep_invariants(gs in gamestate_strategy::<2, 6>()) {
+            prop_assert_eq!(gs.state(), State::RoundActive);
+            prop_assert_eq!(gs.check_invariants(), Ok(()));
+        }
+
+        /// Play on from a position, checking the rules hold after every move and round
+        #[test]
+        fn play_keeps_invariants(
+            mut gs in any::<Gamestate<3, 8>>(),
+            picks in vec(any::<Index>(), 1..200),
+        ) {
+            for pick in picks {
+                let state = match gs.state() {
+                    State::RoundActive => {
+                        let moves = gs.get_moves();
+                        gs.play_move(*pick.get(&moves))
+                    }
+                    State::RoundEnd => gs.end_round().state,
+                    State::GameEnd => break,
+                };
+                prop_assert_eq!(gs.check_invariants(), Ok(()), "{:?}", state);
+            }
+        }
+    }
+}
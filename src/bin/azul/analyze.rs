@@ -0,0 +1,79 @@
+//! `azul analyze`: search a position and report the best lines
+
+use std::time::Duration;
+
+use azul_tiles_rs::{
+    analysis::{self, Limit},
+    gamestate::State,
+};
+
+use crate::{
+    engine,
+    position::{format_move, PositionArgs},
+    Error,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(flatten)]
+    position: PositionArgs,
+    /// Time to search for
+    #[arg(long, value_parser = engine::parse_duration, default_value = "5s")]
+    time: Duration,
+    /// Search to a fixed depth instead of for a time
+    #[arg(long)]
+    depth: Option<u8>,
+    /// Engine to ask for its choice of move as well as the analysis
+    #[arg(long, default_value = "minimax")]
+    engine: String,
+    /// Number of alternative moves to show
+    #[arg(long, default_value_t = 5)]
+    alternatives: usize,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let gs = args.position.gamestate()?;
+    if gs.state() != State::RoundActive {
+        return Err("No moves to analyse in this position".into());
+    }
+    println!(
+        "Round {}, player {} to move, scores {:?}",
+        gs.round(),
+        gs.current_player(),
+        gs.scores()
+    );
+
+    let limit = match args.depth {
+        Some(depth) => Limit::Depth(depth),
+        None => Limit::Time(args.time),
+    };
+    let analysis = analysis::analyse(&gs, limit);
+    let best = analysis.best().ok_or("No legal moves")?;
+    println!("Best move: {}", format_move(&best.pv[0]));
+    println!("Evaluation: {:+.1}", best.eval);
+    println!(
+        "PV: {}",
+        best.pv
+            .iter()
+            .map(format_move)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    println!(
+        "Depth {}, {} nodes in {:.2?} ({:.0} nodes/s)",
+        analysis.stats.depth,
+        analysis.stats.nodes,
+        analysis.stats.elapsed,
+        analysis.stats.nodes as f64 / analysis.stats.elapsed.as_secs_f64()
+    );
+
+    println!("Alternatives:");
+    for line in analysis.lines.iter().skip(1).take(args.alternatives) {
+        println!("  {:+6.1}  {}", line.eval, format_move(&line.pv[0]));
+    }
+
+    let mut player = engine::engine(&args.engine, args.time)?;
+    let choice = player.pick_move(&gs, gs.get_moves());
+    println!("{} plays: {}", player.name(), format_move(&choice));
+    Ok(())
+}
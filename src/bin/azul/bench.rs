@@ -0,0 +1,100 @@
+//! `azul bench`: measure engine throughput on this machine
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use azul_tiles_rs::{
+    analysis::{self, Limit},
+    gamestate::{Gamestate, Move, State},
+};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{engine, Error};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Time to spend on each measurement
+    #[arg(long, value_parser = engine::parse_duration, default_value = "2s")]
+    time: Duration,
+    /// Seed for the random playouts
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let mut rng = SmallRng::seed_from_u64(args.seed);
+
+    // Random playouts
+    let start = Instant::now();
+    let mut games = 0;
+    while start.elapsed() < args.time {
+        black_box(random_game(&mut rng, |_, _| ()));
+        games += 1;
+    }
+    report("Random playouts", games, "games", start.elapsed());
+
+    // Collect positions and moves from random games to time the other operations
+    let mut positions = Vec::new();
+    while positions.len() < 10_000 {
+        random_game(&mut rng, |gs, m| positions.push((gs.clone(), m)));
+    }
+
+    let start = Instant::now();
+    let mut moves = 0;
+    while start.elapsed() < args.time {
+        for (gs, _) in &positions {
+            moves += black_box(gs.get_moves()).len() as u64;
+        }
+    }
+    report("Move generation", moves, "moves", start.elapsed());
+
+    let start = Instant::now();
+    let mut played = 0;
+    while start.elapsed() < args.time {
+        for (gs, m) in &positions {
+            let mut gs = gs.clone();
+            black_box(gs.play_move(*m));
+        }
+        played += positions.len() as u64;
+    }
+    report("play_move (with clone)", played, "moves", start.elapsed());
+
+    let start = Instant::now();
+    let mut nodes = 0;
+    for (gs, _) in positions.iter().step_by(97) {
+        let remaining = args.time.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            break;
+        }
+        nodes += analysis::analyse(gs, Limit::Time(remaining.min(args.time / 10)))
+            .stats
+            .nodes;
+    }
+    report("Alpha-beta search", nodes, "nodes", start.elapsed());
+    Ok(())
+}
+
+/// Play a game of random moves, calling `f` before each move is played
+fn random_game(rng: &mut SmallRng, mut f: impl FnMut(&Gamestate<2, 6>, Move)) -> Gamestate<2, 6> {
+    let mut gs = Gamestate::new_2_player_with_seed(rng.gen(), 0);
+    while gs.state() != State::GameEnd {
+        let moves = gs.get_moves();
+        let m = moves[rng.gen_range(0..moves.len())];
+        f(&gs, m);
+        if gs.play_move(m) == State::RoundEnd {
+            gs.end_round();
+        }
+    }
+    gs
+}
+
+fn report(name: &str, count: u64, unit: &str, elapsed: Duration) {
+    println!(
+        "{:<24} {:>12.0} {}/s",
+        name,
+        count as f64 / elapsed.as_secs_f64(),
+        unit
+    );
+}
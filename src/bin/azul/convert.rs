@@ -0,0 +1,189 @@
+//! `azul convert`: translate game records between formats
+//!
+//! Formats:
+//! - `json`: a single game record
+//! - `jsonl`: one game record per line, as written by `azul selfplay`
+//! - `args`: position arguments for `azul analyze` and `azul perft`,
+//!   one game per line
+//! - `text`: moves in readable notation, output only
+
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use azul_tiles_rs::gamestate::{Gamestate, State};
+
+use crate::{position::format_move, record::GameRecord, Error};
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Jsonl,
+    Args,
+    Text,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Input file, reads from stdin if not given
+    input: Option<PathBuf>,
+    /// Output file, writes to stdout if not given
+    #[arg(long, short)]
+    out: Option<PathBuf>,
+    #[arg(long, value_enum)]
+    from: Format,
+    #[arg(long, value_enum)]
+    to: Format,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let mut input = String::new();
+    match &args.input {
+        Some(path) => {
+            input = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {path:?}: {e}"))?
+        }
+        None => {
+            std::io::stdin().read_to_string(&mut input)?;
+        }
+    }
+    let records = parse(&input, args.from)?;
+    let output = write(&records, args.to)?;
+    match &args.out {
+        Some(path) => std::fs::write(path, output)?,
+        None => std::io::stdout().write_all(output.as_bytes())?,
+    }
+    Ok(())
+}
+
+fn parse(input: &str, format: Format) -> Result<Vec<GameRecord>, Error> {
+    match format {
+        Format::Json => Ok(vec![serde_json::from_str(input)?]),
+        Format::Jsonl => input
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| Ok(serde_json::from_str(l)?))
+            .collect(),
+        Format::Args => input
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(parse_args)
+            .collect(),
+        Format::Text => Err("Text format can only be written".into()),
+    }
+}
+
+/// Parse `--seed S --first-player P --moves a,b,c` and replay to find the scores
+fn parse_args(line: &str) -> Result<GameRecord, Error> {
+    let mut record = GameRecord {
+        seed: 0,
+        first_player: 0,
+        players: Default::default(),
+        moves: Vec::new(),
+        scores: [0; 2],
+        handicaps: Default::default(),
+        variant: Default::default(),
+        opening: 0,
+    };
+    let mut words = line.split_whitespace();
+    while let Some(flag) = words.next() {
+        let value = words.next().ok_or(format!("Missing value for {flag}"))?;
+        let invalid = |_| format!("Invalid value for {flag}: {value}");
+        match flag {
+            "--seed" => record.seed = value.parse().map_err(invalid)?,
+            "--first-player" => record.first_player = value.parse().map_err(invalid)?,
+            "--variant" => record.variant = value.parse()?,
+            "--moves" => {
+                record.moves = value
+                    .split(',')
+                    .map(|m| m.parse().map_err(invalid))
+                    .collect::<Result<_, _>>()?
+            }
+            _ => return Err(format!("Unknown argument {flag}").into()),
+        }
+    }
+    record.scores = replay(&record, |_, _| ())?.scores();
+    Ok(record)
+}
+
+/// Replay the game, calling `f` with each position and move index
+fn replay(
+    record: &GameRecord,
+    mut f: impl FnMut(&Gamestate<2, 6>, usize),
+) -> Result<Gamestate<2, 6>, Error> {
+    let mut gs = record.replay().start()?;
+    for &index in &record.moves {
+        let m = gs
+            .get_moves()
+            .into_iter()
+            .find(|m| m.to_index() == index)
+            .ok_or(format!("Move {index} is not legal"))?;
+        f(&gs, index);
+        if gs.play_move(m) == State::RoundEnd {
+            gs.end_round();
+        }
+    }
+    Ok(gs)
+}
+
+fn write(records: &[GameRecord], format: Format) -> Result<String, Error> {
+    let mut out = String::new();
+    match format {
+        Format::Json => {
+            let [record] = records else {
+                return Err(format!("Json holds one game, found {}", records.len()).into());
+            };
+            out = serde_json::to_string_pretty(record)?;
+            out += "\n";
+        }
+        Format::Jsonl => {
+            for record in records {
+                out += &serde_json::to_string(record)?;
+                out += "\n";
+            }
+        }
+        Format::Args => {
+            for record in records {
+                out += &format!(
+                    "--seed {} --first-player {} --moves {}",
+                    record.seed,
+                    record.first_player,
+                    record
+                        .moves
+                        .iter()
+                        .map(|m| m.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                );
+                if !record.variant.is_standard() {
+                    out += &format!(" --variant {}", record.variant);
+                }
+                out += "\n";
+            }
+        }
+        Format::Text => {
+            for record in records {
+                out += &format!(
+                    "{} vs {}, seed {}, {}\n",
+                    record.players[0], record.players[1], record.seed, record.variant
+                );
+                let gs = replay(record, |gs, index| {
+                    let m = gs
+                        .get_moves()
+                        .into_iter()
+                        .find(|m| m.to_index() == index)
+                        .unwrap();
+                    out += &format!(
+                        "R{} P{} {}\n",
+                        gs.round(),
+                        gs.current_player(),
+                        format_move(&m)
+                    );
+                })?;
+                out += &format!("Scores {:?}\n", gs.scores());
+            }
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,26 @@
+//! Creating players from their names on the command line
+//!
+//! Names are looked up in the player registry, see [Registry::load_default],
+//! so registered configurations can be used anywhere an engine is expected
+
+use std::{sync::OnceLock, time::Duration};
+
+use azul_tiles_rs::{error::AzulError, players::Player, registry::Registry};
+
+use crate::Error;
+
+pub use azul_tiles_rs::registry::parse_duration;
+
+/// Registry loaded on first use
+pub fn registry() -> Result<&'static Registry, AzulError> {
+    static REGISTRY: OnceLock<Result<Registry, AzulError>> = OnceLock::new();
+    REGISTRY
+        .get_or_init(Registry::load_default)
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+/// Create a player by name, with a time limit per move for searching players
+pub fn engine(name: &str, time: Duration) -> Result<Box<dyn Player<2, 6>>, Error> {
+    Ok(registry()?.player(name, time)?)
+}
@@ -0,0 +1,147 @@
+//! `azul first-move`: measure the advantage of moving first
+//!
+//! Each engine plays itself under each variant, on the same seeds with the seats swapped,
+//! so the only difference between the players is who starts. Pairs are played in chunks
+//! seeded from `--seed`, which keeps the results the same for any number of threads
+
+use std::{sync::Mutex, time::Duration};
+
+use azul_tiles_rs::{
+    playerboard::Handicap,
+    runner::{FirstMoveStudy, Runner},
+    variant::Variant,
+};
+
+use crate::{engine, Error};
+
+/// Game pairs played by one runner
+const CHUNK: u32 = 50;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Engines to measure, each playing against itself
+    #[arg(long, value_delimiter = ',', default_value = "rank2,rank,random")]
+    players: Vec<String>,
+    /// Rules to compare, separated by `;` as rules themselves use commas
+    #[arg(long, value_delimiter = ';', default_value = "standard")]
+    variants: Vec<Variant>,
+    /// Game pairs for each engine and variant
+    #[arg(long, default_value_t = 1000)]
+    pairs: u32,
+    /// Time per move for searching players
+    #[arg(long, value_parser = engine::parse_duration, default_value = "10ms")]
+    time: Duration,
+    /// Seed for the games, shared by every engine and variant
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Handicap for the player moving second, eg `start=2`
+    #[arg(long, default_value = "")]
+    handicap: Handicap,
+    /// Number of runners at once, defaults to the number of cpus
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Print results as json lines instead of a table
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(serde::Serialize)]
+struct Row<'a> {
+    player: &'a str,
+    variant: &'a Variant,
+    handicap: &'a Handicap,
+    games: u32,
+    win_rate: f64,
+    win_rate_interval: (f64, f64),
+    margin: f64,
+    margin_interval: (f64, f64),
+    effect_size: f64,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    for name in &args.players {
+        engine::engine(name, args.time)?;
+    }
+    let studies = args.players.len() * args.variants.len();
+    let chunks = args.pairs.div_ceil(CHUNK);
+    let queue = Mutex::new(
+        (0..studies)
+            .flat_map(|study| (0..chunks).map(move |chunk| (study, chunk)))
+            .collect::<Vec<_>>(),
+    );
+    let results = Mutex::new(vec![FirstMoveStudy::default(); studies]);
+    let threads = args.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+
+    std::thread::scope(|s| {
+        let handles = (0..threads)
+            .map(|_| {
+                s.spawn(|| -> Result<(), Error> {
+                    loop {
+                        let Some((study, chunk)) = queue.lock().unwrap().pop() else {
+                            return Ok(());
+                        };
+                        let name = &args.players[study / args.variants.len()];
+                        let variant = args.variants[study % args.variants.len()];
+                        let players = [
+                            engine::engine(name, args.time)?,
+                            engine::engine(name, args.time)?,
+                        ];
+                        let pairs = CHUNK.min(args.pairs - chunk * CHUNK);
+                        let result = Runner::new_2_player(players, Some(args.seed + chunk as u64))
+                            .with_variant(variant)?
+                            .with_second_player_handicap(args.handicap)
+                            .run_first_move_study(pairs);
+                        results.lock().unwrap()[study] += result;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .try_for_each(|h| h.join().map_err(|_| "Study thread panicked".to_string())?)
+    })?;
+
+    let results = results.into_inner().unwrap();
+    if !args.json {
+        println!(
+            "{:<10} {:<20} {:>6} {:>22} {:>24} {:>7}",
+            "player", "variant", "games", "first wins (95% CI)", "margin (95% CI)", "d"
+        );
+    }
+    for (study, result) in results.iter().enumerate() {
+        let variant = &args.variants[study % args.variants.len()];
+        let row = Row {
+            player: &args.players[study / args.variants.len()],
+            variant,
+            handicap: &args.handicap,
+            games: result.games(),
+            win_rate: result.win_rate.mean(),
+            win_rate_interval: result.win_rate.interval(),
+            margin: result.margin.mean(),
+            margin_interval: result.margin.interval(),
+            effect_size: result.effect_size(),
+        };
+        if args.json {
+            println!("{}", serde_json::to_string(&row)?);
+        } else {
+            println!(
+                "{:<10} {:<20} {:>6} {:>6.1}% ({:>5.1}, {:>5.1}) {:>+7.2} ({:>+6.2}, {:>+6.2}) {:>+7.3}",
+                row.player,
+                row.variant.to_string(),
+                row.games,
+                row.win_rate * 100.0,
+                row.win_rate_interval.0 * 100.0,
+                row.win_rate_interval.1 * 100.0,
+                row.margin,
+                row.margin_interval.0,
+                row.margin_interval.1,
+                row.effect_size
+            );
+        }
+    }
+    Ok(())
+}
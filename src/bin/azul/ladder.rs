@@ -0,0 +1,185 @@
+//! `azul ladder`: persistent Elo ratings for engine configurations
+//!
+//! Members and their ratings are stored in a json file so the ladder
+//! can be extended as engines change
+
+use std::{path::PathBuf, time::Duration};
+
+use azul_tiles_rs::{elo, runner::Runner};
+
+use crate::{engine, Error};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// File the ladder is stored in
+    #[arg(long, default_value = "ladder.json")]
+    file: PathBuf,
+    #[command(subcommand)]
+    command: LadderCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum LadderCommand {
+    /// Register an engine configuration
+    Add {
+        /// Unique name for this member
+        name: String,
+        /// Engine to use, see `azul analyze --help`
+        #[arg(long)]
+        engine: String,
+        /// Time per move for searching engines
+        #[arg(long, value_parser = engine::parse_duration, default_value = "100ms")]
+        time: Duration,
+    },
+    /// Play gauntlets against the other members and update ratings
+    Run {
+        /// Only run the gauntlet for this member
+        #[arg(long)]
+        member: Option<String>,
+        /// Game pairs against each opponent
+        #[arg(long, default_value_t = 5)]
+        games: u32,
+        /// Rating change factor per game
+        #[arg(long, default_value_t = 16.0)]
+        k: f64,
+        /// Seed for the games, random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Print the members by rating
+    Show,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct Member {
+    name: String,
+    engine: String,
+    time: Duration,
+    rating: f64,
+    games: u32,
+    wins: u32,
+    losses: u32,
+    draws: u32,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Ladder {
+    members: Vec<Member>,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let mut ladder: Ladder = match std::fs::read_to_string(&args.file) {
+        Ok(s) => serde_json::from_str(&s).map_err(|e| format!("Invalid {:?}: {e}", args.file))?,
+        Err(_) => Ladder::default(),
+    };
+
+    match args.command {
+        LadderCommand::Add { name, engine, time } => {
+            if ladder.members.iter().any(|m| m.name == name) {
+                return Err(format!("{name} is already on the ladder").into());
+            }
+            engine::engine(&engine, time)?;
+            // Registered players can say where they are expected to rank
+            let rating = engine::registry()?
+                .config(&engine)
+                .elo
+                .unwrap_or(elo::INITIAL_RATING);
+            ladder.members.push(Member {
+                name,
+                engine,
+                time,
+                rating,
+                games: 0,
+                wins: 0,
+                losses: 0,
+                draws: 0,
+            });
+        }
+        LadderCommand::Run {
+            member,
+            games,
+            k,
+            seed,
+        } => {
+            let gauntlets = match member {
+                Some(name) => vec![ladder
+                    .members
+                    .iter()
+                    .position(|m| m.name == name)
+                    .ok_or(format!("{name} is not on the ladder"))?],
+                None => (0..ladder.members.len()).collect(),
+            };
+            for i in gauntlets {
+                for j in (0..ladder.members.len()).filter(|&j| j != i) {
+                    play(&mut ladder, i, j, games, k, seed)?;
+                    // Save after each matchup so progress isn't lost
+                    save(&ladder, &args.file)?;
+                }
+            }
+        }
+        LadderCommand::Show => (),
+    }
+    save(&ladder, &args.file)?;
+    show(&ladder);
+    Ok(())
+}
+
+fn play(
+    ladder: &mut Ladder,
+    i: usize,
+    j: usize,
+    games: u32,
+    k: f64,
+    seed: Option<u64>,
+) -> Result<(), Error> {
+    let (a, b) = (&ladder.members[i], &ladder.members[j]);
+    let players = [
+        engine::engine(&a.engine, a.time)?,
+        engine::engine(&b.engine, b.time)?,
+    ];
+    let result = Runner::new_2_player(players, seed).run_matchup(games);
+    let (rating_a, rating_b) = elo::update(a.rating, b.rating, &result, k);
+    println!(
+        "{} vs {}: {:?}, {:.0} -> {:.0}",
+        a.name, b.name, result.winner_count, a.rating, rating_a
+    );
+    for (index, rating, w) in [
+        (i, rating_a, result.winner_count),
+        (j, rating_b, result.winner_count.invert()),
+    ] {
+        let m = &mut ladder.members[index];
+        m.rating = rating;
+        m.games += w.player0 + w.player1 + w.draw;
+        m.wins += w.player0;
+        m.losses += w.player1;
+        m.draws += w.draw;
+    }
+    Ok(())
+}
+
+fn save(ladder: &Ladder, path: &PathBuf) -> Result<(), Error> {
+    let s = serde_json::to_string_pretty(ladder)?;
+    std::fs::write(path, s).map_err(|e| format!("Failed to write {path:?}: {e}"))?;
+    Ok(())
+}
+
+fn show(ladder: &Ladder) {
+    let mut members = ladder.members.iter().collect::<Vec<_>>();
+    members.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+    println!(
+        "{:<4} {:<24} {:>7} {:>6} {:>6} {:>6} {:>6}",
+        "", "Name", "Rating", "Games", "Wins", "Losses", "Draws"
+    );
+    for (rank, m) in members.iter().enumerate() {
+        println!(
+            "{:<4} {:<24} {:>7.0} {:>6} {:>6} {:>6} {:>6}",
+            rank + 1,
+            m.name,
+            m.rating,
+            m.games,
+            m.wins,
+            m.losses,
+            m.draws
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Command line tools for analysing and running games
+//!
+//! Run `azul help` for a list of commands
+
+use azul_tiles_rs::memory::TrackingAllocator;
+use clap::{Parser, Subcommand};
+
+mod analyze;
+mod bench;
+mod convert;
+mod engine;
+mod first_move;
+mod ladder;
+mod openings;
+mod perft;
+mod players;
+mod position;
+mod puzzles;
+mod record;
+mod regress;
+mod replay;
+mod selfplay;
+mod solitaire;
+mod tournament;
+mod tune;
+
+/// Error a command fails with, any error converts to it with `?`
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Lets runners report the memory each player uses
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
+#[derive(Parser)]
+#[command(name = "azul", about = "Azul engine command line tools")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the best move, evaluation and alternatives for a position
+    Analyze(analyze::Args),
+    /// Play games and write the records and training samples
+    Selfplay(selfplay::Args),
+    /// Run a round robin tournament from a config file
+    Tournament(tournament::Args),
+    /// Print a recorded game move by move
+    Replay(replay::Args),
+    /// Measure engine throughput
+    Bench(bench::Args),
+    /// Generate a pack of positions with a single best move
+    Puzzles(puzzles::Args),
+    /// Count move tree nodes from a position
+    Perft(perft::Args),
+    /// Convert game records between formats
+    Convert(convert::Args),
+    /// Maintain Elo ratings for engine configurations
+    Ladder(ladder::Args),
+    /// Play single player games against a par score
+    Solitaire(solitaire::Args),
+    /// Measure the advantage of moving first
+    FirstMove(first_move::Args),
+    /// Tune the heuristic evaluator's weights
+    Tune(tune::Args),
+    /// Generate a suite of balanced start positions
+    Openings(openings::Args),
+    /// List the built in and registered players
+    Players(players::Args),
+    /// Compare an engine's moves and evaluations with a baseline trace
+    Regress(regress::Args),
+}
+
+fn main() {
+    env_logger::init();
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Analyze(args) => analyze::run(args),
+        Command::Selfplay(args) => selfplay::run(args),
+        Command::Tournament(args) => tournament::run(args),
+        Command::Replay(args) => replay::run(args),
+        Command::Bench(args) => bench::run(args),
+        Command::Puzzles(args) => puzzles::run(args),
+        Command::Perft(args) => perft::run(args),
+        Command::Convert(args) => convert::run(args),
+        Command::Ladder(args) => ladder::run(args),
+        Command::Solitaire(args) => solitaire::run(args),
+        Command::FirstMove(args) => first_move::run(args),
+        Command::Tune(args) => tune::run(args),
+        Command::Openings(args) => openings::run(args),
+        Command::Players(args) => players::run(args),
+        Command::Regress(args) => regress::run(args),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,77 @@
+//! `azul openings`: generate a suite of balanced start positions
+//!
+//! Each seed is dealt and opened with random moves, then searched. Positions the search
+//! scores close to level are kept, and can be played from by `azul tournament`
+//! with `positions = "<file>"` so engine comparisons depend less on the deal
+
+use std::path::PathBuf;
+
+use azul_tiles_rs::{
+    analysis::{self, Limit},
+    gamestate::Gamestate,
+    opening::{Opening, PositionSuite, StartPosition},
+};
+
+use crate::Error;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Positions to find
+    #[arg(long, default_value_t = 50)]
+    count: usize,
+    /// Random moves played into the first round
+    #[arg(long, default_value_t = 2)]
+    plies: u8,
+    /// Search depth used to evaluate each position
+    #[arg(long, default_value_t = 3)]
+    depth: u8,
+    /// Largest evaluation in points, for either player, of a kept position
+    #[arg(long, default_value_t = 1.0)]
+    max_eval: f32,
+    /// Seed of the first position tried
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Seeds to try before giving up, defaults to 20 for each position
+    #[arg(long)]
+    tries: Option<u64>,
+    /// File to write the suite to
+    #[arg(long, default_value = "openings.json")]
+    out: PathBuf,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let tries = args.tries.unwrap_or(args.count as u64 * 20);
+    let opening = Opening::random(args.plies);
+    let mut suite = PositionSuite {
+        evaluated_by: format!("depth {}", args.depth),
+        positions: Vec::new(),
+    };
+    for seed in args.seed..args.seed + tries {
+        if suite.positions.len() == args.count {
+            break;
+        }
+        let mut gs: Gamestate<2, 6> = Gamestate::new_2_player_with_seed(seed, 0);
+        let moves = opening.play(&mut gs, seed);
+        let Some(best) = analysis::analyse(&gs, Limit::Depth(args.depth))
+            .best()
+            .cloned()
+        else {
+            continue;
+        };
+        if best.eval.abs() <= args.max_eval {
+            eprintln!("Seed {seed}: {:+.2}", best.eval);
+            suite.positions.push(StartPosition {
+                seed,
+                moves: moves.iter().map(|m| m.to_index()).collect(),
+                eval: best.eval,
+            });
+        }
+    }
+    eprintln!(
+        "Found {} of {} positions",
+        suite.positions.len(),
+        args.count
+    );
+    suite.save(&args.out)?;
+    Ok(())
+}
@@ -0,0 +1,44 @@
+//! `azul perft`: count move tree nodes to validate move generation
+
+use std::time::Instant;
+
+use azul_tiles_rs::analysis;
+
+use crate::{
+    position::{format_move, PositionArgs},
+    Error,
+};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(flatten)]
+    position: PositionArgs,
+    /// Depth of the move tree
+    #[arg(long, default_value_t = 3)]
+    depth: u8,
+    /// Print the count for each move from the position
+    #[arg(long)]
+    divide: bool,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let gs = args.position.gamestate()?;
+    let start = Instant::now();
+    let nodes = if args.divide {
+        let divide = analysis::perft_divide(&gs, args.depth);
+        for (m, count) in &divide {
+            println!("{}: {}", format_move(m), count);
+        }
+        divide.iter().map(|(_, count)| count).sum()
+    } else {
+        analysis::perft(&gs, args.depth)
+    };
+    let elapsed = start.elapsed();
+    println!("perft({}) = {}", args.depth, nodes);
+    println!(
+        "{:.2?}, {:.0} nodes/s",
+        elapsed,
+        nodes as f64 / elapsed.as_secs_f64()
+    );
+    Ok(())
+}
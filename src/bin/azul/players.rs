@@ -0,0 +1,43 @@
+//! `azul players`: list the players that can be created by name
+
+use azul_tiles_rs::{
+    presets::Difficulty,
+    registry::{DEFAULT_REGISTRY, ENGINES, REGISTRY_ENV},
+};
+use strum::IntoEnumIterator;
+
+use crate::{engine, Error};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {}
+
+pub fn run(_args: Args) -> Result<(), Error> {
+    let registry = engine::registry()?;
+    println!("Built in engines: {}", ENGINES.join(", "));
+    println!(
+        "Difficulties: {}",
+        Difficulty::iter()
+            .map(|d| d.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    println!("Models: heuristic:<weights file>, value:<value network file>");
+    if registry.players.is_empty() {
+        println!(
+            "No registered players, add them to {DEFAULT_REGISTRY} or the file in {REGISTRY_ENV}"
+        );
+        return Ok(());
+    }
+    println!();
+    println!("{:<20} {:<10} {:>6}  Description", "Name", "Engine", "Elo");
+    for (name, config) in &registry.players {
+        println!(
+            "{:<20} {:<10} {:>6}  {}",
+            name,
+            config.engine,
+            config.elo.map_or("-".to_string(), |e| format!("{e:.0}")),
+            config.description.as_deref().unwrap_or("")
+        );
+    }
+    Ok(())
+}
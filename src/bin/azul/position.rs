@@ -0,0 +1,66 @@
+//! Building positions from command line arguments
+
+use azul_tiles_rs::{
+    gamestate::{Destination, Gamestate, Move, State},
+    variant::Variant,
+};
+
+use crate::Error;
+
+/// Arguments describing a position as a seeded game and the moves played
+#[derive(clap::Args, Debug, Clone)]
+pub struct PositionArgs {
+    /// Seed used to deal the tiles
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+    /// Player to move first
+    #[arg(long, default_value_t = 0)]
+    pub first_player: u8,
+    /// Comma separated move indices played from the start of the game
+    #[arg(long, value_delimiter = ',')]
+    pub moves: Vec<usize>,
+    /// Rules of the game, eg `rounds=12`
+    #[arg(long, default_value = "standard")]
+    pub variant: Variant,
+}
+
+impl PositionArgs {
+    /// Replay the moves, ending rounds as they complete
+    pub fn gamestate(&self) -> Result<Gamestate<2, 6>, Error> {
+        if self.first_player > 1 {
+            return Err(format!("Invalid first player {}", self.first_player).into());
+        }
+        let mut gs = Gamestate::new_2_player_with_seed(self.seed, self.first_player);
+        gs.set_variant(self.variant)?;
+        for (i, &index) in self.moves.iter().enumerate() {
+            if gs.state() == State::GameEnd {
+                return Err(format!("Game is over before move {}", i + 1).into());
+            }
+            let m = gs
+                .get_moves()
+                .into_iter()
+                .find(|m| m.to_index() == index)
+                .ok_or(format!("Move {} ({index}) is not legal", i + 1))?;
+            if gs.play_move(m) == State::RoundEnd {
+                gs.end_round();
+            }
+        }
+        Ok(gs)
+    }
+}
+
+/// Format a move with its index for use with `--moves`
+pub fn format_move(m: &Move) -> String {
+    let dest = match m.destination {
+        Destination::Row(row) => format!("row {}", row as usize + 1),
+        Destination::Floor => "floor".into(),
+    };
+    format!(
+        "{} {:?} from {} to {} [{}]",
+        m.count,
+        m.tile,
+        m.source.0,
+        dest,
+        m.to_index()
+    )
+}
@@ -0,0 +1,146 @@
+//! `azul puzzles`: find positions with a single clearly best move
+
+use std::{path::PathBuf, time::Duration};
+
+use azul_tiles_rs::{
+    analysis::{self, SearchEvaluator},
+    gamestate::{Gamestate, Move, State},
+};
+
+use crate::{engine, position::format_move, record, Error};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Players used to generate positions, as comma separated engine names
+    #[arg(long, value_delimiter = ',', default_value = "rank2,rank2")]
+    players: Vec<String>,
+    /// Number of games to sample positions from
+    #[arg(long, default_value_t = 10)]
+    games: u32,
+    /// Analyse every nth position of each game
+    #[arg(long, default_value_t = 5)]
+    every: usize,
+    /// Search depth used to solve each position
+    #[arg(long, default_value_t = 4)]
+    depth: u8,
+    /// How much better the best move must be than the second best
+    #[arg(long, default_value_t = 3.0)]
+    margin: f32,
+    /// Seed of the first game
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Number of threads evaluating positions, defaults to the number of cpus
+    #[arg(long)]
+    threads: Option<usize>,
+    /// File to write the puzzle pack to
+    #[arg(long, default_value = "puzzles.json")]
+    out: PathBuf,
+}
+
+/// Position reached by replaying `moves` from a seeded game,
+/// which can be loaded with `azul analyze --seed --first-player --moves`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Puzzle {
+    pub seed: u64,
+    pub first_player: u8,
+    pub moves: Vec<usize>,
+    /// Index of the best move
+    pub solution: usize,
+    /// Evaluation after the best move, from player 0's perspective
+    pub eval: f32,
+    /// Difference in evaluation to the second best move for the player to move
+    pub margin: f32,
+    /// Depth the position was solved to
+    pub depth: u8,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    if args.players.len() != 2 {
+        return Err("Expected two players".into());
+    }
+    let time = Duration::from_millis(100);
+    let mut players = [
+        engine::engine(&args.players[0], time)?,
+        engine::engine(&args.players[1], time)?,
+    ];
+
+    // Children searched a ply less deep give the evaluations of an analysis to `depth`
+    let mut evaluator = SearchEvaluator(args.depth.max(1) - 1);
+    let threads = args.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+    let mut puzzles = Vec::new();
+    for game in 0..args.games {
+        let seed = args.seed + game as u64;
+        let (record, _) = record::play_game(
+            &mut players,
+            seed,
+            0,
+            Default::default(),
+            Default::default(),
+            &Default::default(),
+        )?;
+        // Sample the positions, then evaluate the children of all of them at once
+        let mut gs: Gamestate<2, 6> = Gamestate::new_2_player_with_seed(seed, 0);
+        let (mut plies, mut positions) = (Vec::new(), Vec::new());
+        for (i, &index) in record.moves.iter().enumerate() {
+            if i % args.every.max(1) == 0 {
+                plies.push(i);
+                positions.push(gs.clone());
+            }
+            let m = gs
+                .get_moves()
+                .into_iter()
+                .find(|m| m.to_index() == index)
+                .expect("Recorded move is legal");
+            if gs.play_move(m) == State::RoundEnd {
+                gs.end_round();
+            }
+        }
+        let evals = analysis::evaluate_moves(&positions, &mut evaluator, threads);
+        for ((i, gs), evals) in plies.into_iter().zip(&positions).zip(evals) {
+            if let Some(puzzle) = solve(gs, evals, args.margin) {
+                eprintln!(
+                    "Game {} move {}: {} by {:.1}",
+                    game + 1,
+                    i + 1,
+                    format_move(&puzzle.0),
+                    puzzle.2
+                );
+                puzzles.push(Puzzle {
+                    seed,
+                    first_player: 0,
+                    moves: record.moves[..i].to_vec(),
+                    solution: puzzle.0.to_index(),
+                    eval: puzzle.1,
+                    margin: puzzle.2,
+                    depth: args.depth,
+                });
+            }
+        }
+    }
+
+    eprintln!("Found {} puzzles", puzzles.len());
+    let file = std::fs::File::create(&args.out)
+        .map_err(|e| format!("Failed to create {:?}: {e}", args.out))?;
+    serde_json::to_writer_pretty(file, &puzzles)?;
+    Ok(())
+}
+
+/// Return the best move, its evaluation and the margin to the next best
+/// if it is better by at least `margin`
+fn solve(
+    gs: &Gamestate<2, 6>,
+    mut evals: Vec<(Move, f32)>,
+    margin: f32,
+) -> Option<(Move, f32, f32)> {
+    // Evaluations are for player 0, sort best first for the player to move
+    let sign = if gs.current_player() == 0 { 1.0 } else { -1.0 };
+    evals.sort_by(|a, b| (sign * b.1).total_cmp(&(sign * a.1)));
+    let (best, eval) = *evals.first()?;
+    let second = evals.get(1)?.1;
+    let diff = (eval - second).abs();
+    (diff >= margin).then_some((best, eval, diff))
+}
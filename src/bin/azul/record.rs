@@ -0,0 +1,123 @@
+//! Recording games played by the CLI
+
+use azul_tiles_rs::{
+    gamestate::{Gamestate, State},
+    opening::Opening,
+    playerboard::Handicap,
+    players::{
+        features::{StateEncoder, StateFeaturesV1},
+        Player,
+    },
+    record::Replay,
+    variant::Variant,
+};
+
+use crate::Error;
+
+/// Everything needed to replay a game
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct GameRecord {
+    pub seed: u64,
+    pub first_player: u8,
+    /// Names of the players
+    pub players: [String; 2],
+    /// Move indices in the order they were played
+    pub moves: Vec<usize>,
+    pub scores: [u8; 2],
+    /// Handicaps given to each player, none in older records
+    #[serde(default)]
+    pub handicaps: [Handicap; 2],
+    /// Rules of the game, standard in older records
+    #[serde(default)]
+    pub variant: Variant,
+    /// Moves at the start of [GameRecord::moves] chosen by an [Opening] rather than the players
+    #[serde(default)]
+    pub opening: u8,
+}
+
+impl GameRecord {
+    /// Seed, rules and moves of the game
+    pub fn replay(&self) -> Replay {
+        Replay {
+            seed: self.seed,
+            first_player: self.first_player,
+            variant: self.variant,
+            handicaps: self.handicaps,
+            moves: self.moves.clone(),
+        }
+    }
+}
+
+/// Training tuple for a single decision
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Sample {
+    /// Encoded gamestate from [StateFeaturesV1]
+    pub state: Vec<f32>,
+    /// Index of the move that was played
+    pub action: usize,
+    /// Indices of all legal moves
+    pub legal: Vec<usize>,
+    /// Player that made the move
+    pub player: u8,
+    /// 1 for a win, 0 for a draw and -1 for a loss, for the player that moved
+    pub outcome: f32,
+    /// Final score difference for the player that moved
+    pub margin: f32,
+}
+
+/// Play a game between two players, recording the moves and samples.
+/// Moves played by the `opening` are recorded but not sampled, as the players didn't choose them.
+/// Fails if two players can't play the variant
+pub fn play_game(
+    players: &mut [Box<dyn Player<2, 6>>; 2],
+    seed: u64,
+    first_player: u8,
+    variant: Variant,
+    handicaps: [Handicap; 2],
+    opening: &Opening,
+) -> Result<(GameRecord, Vec<Sample>), Error> {
+    let mut gs = Gamestate::new_2_player_with_seed(seed, first_player);
+    gs.set_variant(variant)?;
+    gs.set_handicaps(handicaps);
+    gs.record_moves(true);
+    let opening = opening.play(&mut gs, seed).len() as u8;
+    let mut samples = Vec::new();
+    while gs.state() != State::GameEnd {
+        let legal = gs.get_moves();
+        let player = gs.current_player();
+        let state = StateFeaturesV1.encode(&gs);
+        let legal_indices = legal.iter().map(|m| m.to_index()).collect();
+        let m = players[player as usize].pick_move(&gs, legal);
+        samples.push(Sample {
+            state,
+            action: m.to_index(),
+            legal: legal_indices,
+            player,
+            outcome: 0.0,
+            margin: 0.0,
+        });
+        if gs.play_move(m) == State::RoundEnd {
+            gs.end_round();
+        }
+    }
+    let scores = gs.scores();
+    for sample in &mut samples {
+        let (own, other) = match sample.player {
+            0 => (scores[0], scores[1]),
+            _ => (scores[1], scores[0]),
+        };
+        sample.margin = own as f32 - other as f32;
+        sample.outcome = sample.margin.signum();
+    }
+    let record = GameRecord {
+        seed,
+        first_player,
+        players: [players[0].name(), players[1].name()],
+        moves: gs.move_history().iter().map(|p| p.m.to_index()).collect(),
+        scores,
+        handicaps,
+        variant,
+        opening,
+    };
+    Ok((record, samples))
+}
@@ -0,0 +1,146 @@
+//! `azul regress`: compare an engine's moves and evaluations with a baseline
+//!
+//! Trace an engine on one build with `--out base.json`, then on another with
+//! `--baseline base.json` to list every position where it now plays or evaluates
+//! differently. `--against` traces the baseline from a second engine in the same build,
+//! eg to compare two registered configurations
+
+use std::{path::PathBuf, time::Duration};
+
+use azul_tiles_rs::{
+    gamestate::{Gamestate, State},
+    regression::{diff, trace_game, Difference, Trace},
+};
+
+use crate::{engine, position::format_move, Error};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Engine to trace, defaults to the baseline's engine or rank2
+    #[arg(long)]
+    engine: Option<String>,
+    /// Trace from another build to compare with, whose seeds and depth are used
+    #[arg(long, conflicts_with = "against")]
+    baseline: Option<PathBuf>,
+    /// Engine to trace as the baseline
+    #[arg(long)]
+    against: Option<String>,
+    /// Games to trace without a baseline file
+    #[arg(long, default_value_t = 10)]
+    games: u64,
+    /// Seed of the first game
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Search depth of the evaluations recorded at each position
+    #[arg(long, default_value_t = 2)]
+    depth: u8,
+    /// Time per move for engines that search for a time, which won't repeat exactly
+    #[arg(long, value_parser = engine::parse_duration, default_value = "100ms")]
+    time: Duration,
+    /// Smallest change in evaluation that is reported
+    #[arg(long, default_value_t = 0.01)]
+    tolerance: f32,
+    /// Differences to print
+    #[arg(long, default_value_t = 20)]
+    show: usize,
+    /// File to write the trace of the engine to
+    #[arg(long)]
+    out: Option<PathBuf>,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let baseline = match (&args.baseline, &args.against) {
+        (Some(path), _) => Some(Trace::load(path)?),
+        (None, Some(name)) => Some(trace(name, &args, None)?),
+        (None, None) => None,
+    };
+    let name = match (&args.engine, &baseline) {
+        (Some(name), _) => name.clone(),
+        (None, Some(baseline)) if args.against.is_none() => baseline.engine.clone(),
+        _ => "rank2".to_string(),
+    };
+    let candidate = trace(&name, &args, baseline.as_ref())?;
+    if let Some(path) = &args.out {
+        candidate.save(path)?;
+    }
+    let Some(baseline) = baseline else {
+        let positions: usize = candidate.games.iter().map(|g| g.decisions.len()).sum();
+        println!(
+            "Traced {name} over {} games, {positions} positions",
+            candidate.games.len()
+        );
+        return Ok(());
+    };
+
+    let result = diff(&baseline, &candidate, args.tolerance);
+    for d in result.differences.iter().take(args.show) {
+        let mut line = format!("seed {} ply {:>3}:", d.seed, d.ply);
+        if d.move_changed() {
+            let (before, after) = describe(&baseline, d);
+            line += &format!(" move {before} -> {after}");
+        }
+        if d.eval_change() != 0.0 {
+            line += &format!(" eval {:+.2} -> {:+.2}", d.baseline.eval, d.candidate.eval);
+        }
+        println!("{line}");
+    }
+    if result.differences.len() > args.show {
+        println!("... {} more", result.differences.len() - args.show);
+    }
+    println!(
+        "{} of {} positions differ: {} moves changed, largest eval change {:.2}",
+        result.differences.len(),
+        result.positions,
+        result.moves_changed(),
+        result.largest_eval_change()
+    );
+    if !result.mismatched.is_empty() {
+        println!(
+            "Games that couldn't be compared, by seed: {:?}",
+            result.mismatched
+        );
+    }
+    Ok(())
+}
+
+/// Trace `name`, following the baseline's games if there is one
+fn trace(name: &str, args: &Args, baseline: Option<&Trace>) -> Result<Trace, Error> {
+    let depth = baseline.map_or(args.depth, |b| b.depth);
+    let seeds = match baseline {
+        Some(b) => b.games.iter().map(|g| g.seed).collect::<Vec<_>>(),
+        None => (args.seed..args.seed + args.games).collect(),
+    };
+    let mut trace = Trace::new(name, depth);
+    for (i, seed) in seeds.into_iter().enumerate() {
+        // A new player each game, so no state carries over between them
+        let mut player = engine::engine(name, args.time)?;
+        let follow = baseline.map(|b| &b.games[i]);
+        trace
+            .games
+            .push(trace_game(&mut *player, seed, depth, follow));
+    }
+    Ok(trace)
+}
+
+/// Moves with these indices at the difference's position, as printed by the other commands
+fn describe(baseline: &Trace, d: &Difference) -> (String, String) {
+    let mut gs = Gamestate::<2, 6>::new_2_player_with_seed(d.seed, 0);
+    let game = baseline.games.iter().find(|g| g.seed == d.seed).unwrap();
+    for decision in &game.decisions[..d.ply] {
+        let m = gs
+            .get_moves()
+            .into_iter()
+            .find(|m| m.to_index() == decision.m);
+        if gs.play_move(m.unwrap()) == State::RoundEnd {
+            gs.end_round();
+        }
+    }
+    let moves = gs.get_moves();
+    let describe = |index| {
+        moves
+            .iter()
+            .find(|m| m.to_index() == index)
+            .map_or(format!("[{index}]"), format_move)
+    };
+    (describe(d.baseline.m), describe(d.candidate.m))
+}
@@ -0,0 +1,125 @@
+//! `azul replay`: print a recorded game move by move
+
+use std::{io::IsTerminal, path::PathBuf};
+
+use azul_tiles_rs::{
+    analysis::{self, SearchEvaluator},
+    gamestate::{Gamestate, State},
+};
+
+use crate::{position::format_move, record::GameRecord, Error};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Game record as json, or a jsonl file of records from `azul selfplay`
+    file: PathBuf,
+    /// Which game to replay from a jsonl file
+    #[arg(long, default_value_t = 0)]
+    game: usize,
+    /// Print the board before every move
+    #[arg(long)]
+    boards: bool,
+    /// Annotate each move with an analysis to this depth
+    #[arg(long)]
+    annotate: Option<u8>,
+    /// Number of threads analysing positions, defaults to the number of cpus
+    #[arg(long)]
+    threads: Option<usize>,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let contents = std::fs::read_to_string(&args.file)
+        .map_err(|e| format!("Failed to read {:?}: {e}", args.file))?;
+    let record: GameRecord = match serde_json::from_str(&contents) {
+        Ok(record) => record,
+        Err(_) => {
+            let line = contents
+                .lines()
+                .nth(args.game)
+                .ok_or(format!("No game {} in file", args.game))?;
+            serde_json::from_str(line).map_err(|e| format!("Invalid game record: {e}"))?
+        }
+    };
+
+    println!(
+        "{} vs {}, seed {}",
+        record.players[0], record.players[1], record.seed
+    );
+    // Colour the boards unless they are going to a file
+    let colour = std::io::stdout().is_terminal();
+    let print_board = |gs: &Gamestate<2, 6>| {
+        if colour {
+            println!("{gs:#}");
+        } else {
+            println!("{gs}");
+        }
+    };
+    // Evaluate the moves of every position together before printing any
+    let annotations = match args.annotate {
+        Some(depth) => {
+            let mut positions = record.replay().positions()?;
+            // Nothing to annotate after the last move
+            positions.pop();
+            let threads = args.threads.unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+            });
+            let mut evaluator = SearchEvaluator(depth.max(1) - 1);
+            Some(analysis::evaluate_moves(
+                &positions,
+                &mut evaluator,
+                threads,
+            ))
+        }
+        None => None,
+    };
+    let mut gs = record.replay().start()?;
+    let mut round = 0;
+    for (i, &index) in record.moves.iter().enumerate() {
+        if gs.round() != round {
+            round = gs.round();
+            println!("Round {round}");
+        }
+        if args.boards {
+            print_board(&gs);
+        }
+        let m = gs
+            .get_moves()
+            .into_iter()
+            .find(|m| m.to_index() == index)
+            .ok_or(format!("Move {} ({index}) is not legal", i + 1))?;
+        let mut line = format!("{:>3}. P{} {}", i + 1, gs.current_player(), format_move(&m));
+        if let Some(evals) = annotations.as_ref().map(|a| &a[i]) {
+            // Evals are printed for player 0, as by `azul analyze`
+            let sign = if gs.current_player() == 0 { 1.0 } else { -1.0 };
+            let best = evals
+                .iter()
+                .max_by(|a, b| (sign * a.1).total_cmp(&(sign * b.1)));
+            if let (Some(best), Some(played)) = (best, evals.iter().find(|e| e.0 == m)) {
+                line += &format!(" eval {:+.1}", played.1);
+                if sign * best.1 > sign * played.1 {
+                    line += &format!(" (best {} {:+.1})", format_move(&best.0), best.1);
+                }
+            }
+        }
+        println!("{line}");
+        if gs.play_move(m) == State::RoundEnd {
+            gs.end_round();
+            println!("  scores {:?}", gs.scores());
+        }
+    }
+    if args.boards {
+        print_board(&gs);
+    }
+    println!("Final scores {:?}", gs.scores());
+    if gs.scores() != record.scores {
+        return Err(format!(
+            "Replayed scores {:?} don't match the record {:?}",
+            gs.scores(),
+            record.scores
+        )
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! `azul selfplay`: generate game records and training data
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use azul_tiles_rs::{
+    opening::{Opening, OpeningBook},
+    playerboard::Handicap,
+    variant::Variant,
+};
+
+use crate::{engine, record, Error};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The two players as comma separated engine names, eg `minimax,rank2`
+    #[arg(long, value_delimiter = ',', default_value = "rank2,rank2")]
+    players: Vec<String>,
+    /// Number of games to play
+    #[arg(long, default_value_t = 100)]
+    games: u32,
+    /// Directory to write `games.jsonl` and `samples.jsonl` to
+    #[arg(long)]
+    out: PathBuf,
+    /// Time per move for searching players
+    #[arg(long, value_parser = engine::parse_duration, default_value = "100ms")]
+    time: Duration,
+    /// Seed of the first game, following games use the next seeds
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Handicaps for the two players, eg `start=10,free=1;floor=2`.
+    /// Each is a comma separated list of `start`, `floor` and `free`
+    #[arg(long, value_delimiter = ';', default_value = ";")]
+    handicaps: Vec<Handicap>,
+    /// Rules of the games, eg `rounds=12` or `wall=BYRKW/YRKWB/RKWBY/KWBYR/WBYRK`
+    #[arg(long, default_value = "standard")]
+    variant: Variant,
+    /// Moves at the start of each game picked at random instead of by the players,
+    /// for more varied training data
+    #[arg(long, default_value_t = 0)]
+    opening_moves: u8,
+    /// Json file of move weights for the opening moves, see `OpeningBook`
+    #[arg(long)]
+    opening_book: Option<PathBuf>,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    if args.players.len() != 2 {
+        return Err("Expected two players".into());
+    }
+    let handicaps: [Handicap; 2] = args
+        .handicaps
+        .try_into()
+        .map_err(|_| "Expected two handicaps separated by ;".to_string())?;
+    let mut players = [
+        engine::engine(&args.players[0], args.time)?,
+        engine::engine(&args.players[1], args.time)?,
+    ];
+    let opening = match &args.opening_book {
+        Some(path) => Opening::from_book(args.opening_moves, OpeningBook::load(path)?),
+        None => Opening::random(args.opening_moves),
+    };
+    std::fs::create_dir_all(&args.out)?;
+    let create = |name: &str| {
+        File::create(args.out.join(name))
+            .map(BufWriter::new)
+            .map_err(|e| format!("Failed to create {name}: {e}"))
+    };
+    let mut games = create("games.jsonl")?;
+    let mut samples = create("samples.jsonl")?;
+
+    let start = Instant::now();
+    let mut n_samples = 0;
+    for game in 0..args.games {
+        // Play each seed twice with each player going first
+        let seed = args.seed + (game / 2) as u64;
+        let (record, game_samples) = record::play_game(
+            &mut players,
+            seed,
+            (game % 2) as u8,
+            args.variant,
+            handicaps,
+            &opening,
+        )?;
+        write_line(&mut games, &record)?;
+        for sample in &game_samples {
+            write_line(&mut samples, sample)?;
+        }
+        n_samples += game_samples.len();
+        eprintln!(
+            "Game {}/{}: scores {:?}, {} samples, {:.1} games/s",
+            game + 1,
+            args.games,
+            record.scores,
+            n_samples,
+            (game + 1) as f64 / start.elapsed().as_secs_f64()
+        );
+    }
+    games.flush()?;
+    samples.flush()?;
+    Ok(())
+}
+
+fn write_line(writer: &mut impl Write, value: &impl serde::Serialize) -> Result<(), Error> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writeln!(writer)?;
+    Ok(())
+}
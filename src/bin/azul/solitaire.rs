@@ -0,0 +1,73 @@
+//! `azul solitaire`: play single player score attack games against par
+
+use azul_tiles_rs::{
+    players::{FirstMovePlayer, MoveRankPlayer, MoveRankPlayer2, Player, RandomPlayer},
+    solitaire::{Solitaire, DEFAULT_PAR},
+};
+
+use crate::Error;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Engine playing the board, one of rank, rank2, first or random
+    #[arg(long, default_value = "rank2")]
+    player: String,
+    /// Number of games to play
+    #[arg(long, default_value_t = 100)]
+    games: u32,
+    /// Seed of the first game, following games use the next seeds
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Score to beat
+    #[arg(long, default_value_t = DEFAULT_PAR)]
+    par: u8,
+}
+
+/// Searching engines are left out as the scripted opponent is not part of their game tree
+fn engine(name: &str) -> Result<Box<dyn Player<1, 6>>, Error> {
+    Ok(match name {
+        "rank" => Box::new(MoveRankPlayer),
+        "rank2" => Box::new(MoveRankPlayer2),
+        "first" => Box::new(FirstMovePlayer),
+        "random" => Box::new(RandomPlayer::new()),
+        other => {
+            return Err(format!(
+                "Unknown engine {other}, expected one of rank, rank2, first, random"
+            )
+            .into())
+        }
+    })
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let mut player = engine(&args.player)?;
+    let mut total = 0u32;
+    let mut beaten = 0;
+    let mut best = 0;
+    for game in 0..args.games {
+        let mut solitaire = Solitaire::new(args.seed + game as u64, args.par);
+        let score = solitaire.play(player.as_mut());
+        println!(
+            "Game {}: score {} ({:+} against par, {} rounds)",
+            game + 1,
+            score,
+            solitaire.result(),
+            solitaire.gamestate().round()
+        );
+        total += score as u32;
+        best = best.max(score);
+        if solitaire.result() > 0 {
+            beaten += 1;
+        }
+    }
+    if args.games > 0 {
+        println!(
+            "Average {:.1}, best {}, beat par {} of {} games",
+            total as f64 / args.games as f64,
+            best,
+            beaten,
+            args.games
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,455 @@
+//! `azul tournament`: round robin between players described in a config file
+//!
+//! Example config:
+//! ```toml
+//! games = 20
+//! seed = 1
+//! out = "tournament"
+//!
+//! [[players]]
+//! name = "Minimax 10ms"
+//! engine = "minimax"
+//! time = "10ms"
+//!
+//! [[players]]
+//! name = "Rank"
+//! engine = "rank2"
+//! ```
+//!
+//! Setting `positions` to a suite from `azul openings` starts the games from its positions.
+//!
+//! An `[adjudication]` table ends games whose winner is already decided, see
+//! [Adjudication] for its fields:
+//! ```toml
+//! [adjudication]
+//! margin = 30.0
+//! plies = 4
+//! ```
+//!
+//! Results are saved after every matchup, so an interrupted tournament
+//! continues where it left off when run again with the same config.
+//! With `--spectate` every move is also written to `progress.jsonl`,
+//! which the GUI can follow while the tournament runs.
+//! The standings include each player's think time and peak memory per move,
+//! to weigh strength against cost.
+//! With the `web-dashboard` feature, `--dashboard <address>` serves the standings
+//! and recent games to a browser while the tournament runs.
+//!
+//! Each tournament writes a `manifest.json` to its results, and
+//! `--manifest <file> --out <dir>` plays the tournament it describes again
+
+use std::{
+    collections::BTreeMap,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+};
+
+#[cfg(feature = "web-dashboard")]
+use azul_tiles_rs::web::{Table, WebDashboard};
+use azul_tiles_rs::{
+    adjudication::Adjudication,
+    manifest::{Manifest, MANIFEST_FILE},
+    opening::PositionSuite,
+    players::Player,
+    registry::Registry,
+    runner::{MatchUpResult, Progress, Runner},
+};
+
+use crate::{engine, Error};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// Tournament config file
+    #[arg(long, required_unless_present = "manifest")]
+    config: Option<PathBuf>,
+    /// Manifest of a previous tournament to play again
+    #[arg(long, conflicts_with = "config", requires = "out")]
+    manifest: Option<PathBuf>,
+    /// Directory for the results, instead of the one in the config
+    #[arg(long)]
+    out: Option<PathBuf>,
+    /// Number of matchups to run at once, defaults to the number of cpus
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Write the moves of every game to progress.jsonl for spectating
+    #[arg(long)]
+    spectate: bool,
+    /// Serve a live dashboard on this address, eg `127.0.0.1:8080`
+    #[cfg(feature = "web-dashboard")]
+    #[arg(long)]
+    dashboard: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Config {
+    /// Game pairs played in each matchup
+    #[serde(default = "default_games")]
+    games: u32,
+    /// Seed for the games in every matchup
+    #[serde(default)]
+    seed: u64,
+    /// Directory for the results
+    #[serde(default = "default_out")]
+    out: PathBuf,
+    /// Suite of start positions to play from, see `azul openings`
+    positions: Option<PathBuf>,
+    /// When to end decided games early
+    #[serde(default)]
+    adjudication: Option<Adjudication>,
+    players: Vec<PlayerConfig>,
+}
+
+fn default_games() -> u32 {
+    10
+}
+
+fn default_out() -> PathBuf {
+    "tournament".into()
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct PlayerConfig {
+    name: String,
+    engine: String,
+    /// Time per move for searching players
+    #[serde(default = "default_time")]
+    time: String,
+}
+
+fn default_time() -> String {
+    "100ms".into()
+}
+
+impl PlayerConfig {
+    /// Create the player, seeding any random choices from the tournament's seed
+    fn player(&self, seed: u64) -> Result<Box<dyn Player<2, 6>>, Error> {
+        let mut config = engine::registry()?.config(&self.engine);
+        config.seed.get_or_insert(seed);
+        Ok(config.player(engine::parse_duration(&self.time)?)?)
+    }
+}
+
+/// Results saved to `results.json`, keyed by `"i-j"` player indices
+/// with the result from player i's point of view
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Results {
+    players: Vec<String>,
+    matchups: BTreeMap<String, MatchUpResult>,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let (source, mut config) = match (&args.config, &args.manifest) {
+        (Some(path), _) => {
+            let config = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {path:?}: {e}"))?;
+            let config: Config = toml::from_str(&config)?;
+            (path, config)
+        }
+        (None, Some(path)) => {
+            let manifest = Manifest::load(path)?;
+            for difference in manifest.differences() {
+                eprintln!("Warning: {difference}");
+            }
+            (path, manifest.config("tournament")?)
+        }
+        (None, None) => return Err("Missing tournament config".into()),
+    };
+    if let Some(out) = args.out {
+        config.out = out;
+    }
+    eprintln!("Tournament {source:?}, results in {:?}", config.out);
+    if config.players.len() < 2 {
+        return Err("Tournament needs at least two players".into());
+    }
+    // Check all players can be created before starting
+    for p in &config.players {
+        p.player(config.seed)?;
+    }
+    std::fs::create_dir_all(&config.out)?;
+    write_manifest(&config)?;
+    let positions = match &config.positions {
+        Some(path) => PositionSuite::load(path)?.positions,
+        None => Vec::new(),
+    };
+
+    let names = config
+        .players
+        .iter()
+        .map(|p| p.name.clone())
+        .collect::<Vec<_>>();
+    let results_path = config.out.join("results.json");
+    let mut results = load_results(&results_path)?;
+    if results.players != names {
+        if !results.matchups.is_empty() {
+            eprintln!("Players changed, starting tournament again");
+        }
+        results = Results {
+            players: names.clone(),
+            ..Default::default()
+        };
+    }
+
+    // Queue the matchups that haven't been played yet
+    let n = config.players.len();
+    let queue = (0..n)
+        .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+        .filter(|(i, j)| !results.matchups.contains_key(&key(*i, *j)))
+        .collect::<Vec<_>>();
+    eprintln!(
+        "{} matchups to play, {} already done",
+        queue.len(),
+        results.matchups.len()
+    );
+    let queue = Mutex::new(queue);
+    let results = Mutex::new(results);
+    let threads = args.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+
+    let progress = if args.spectate {
+        Some(spectate(&config.out.join("progress.jsonl"))?)
+    } else {
+        None
+    };
+    #[cfg(feature = "web-dashboard")]
+    let (progress, dashboard) = match &args.dashboard {
+        Some(addr) => {
+            let dashboard = WebDashboard::serve(addr, format!("Tournament {source:?}"))?;
+            eprintln!("Dashboard at http://{}", dashboard.addr());
+            show_standings(&dashboard, &results.lock().unwrap());
+            let progress = match progress {
+                Some(file) => tee(file, dashboard.progress_sender()),
+                None => dashboard.progress_sender(),
+            };
+            (Some(progress), Some(dashboard))
+        }
+        None => (progress, None),
+    };
+
+    std::thread::scope(|s| {
+        let handles = (0..threads)
+            .map(|_| {
+                s.spawn(|| -> Result<(), Error> {
+                    loop {
+                        let Some((i, j)) = queue.lock().unwrap().pop() else {
+                            return Ok(());
+                        };
+                        let (a, b) = (&config.players[i], &config.players[j]);
+                        let players = [a.player(config.seed)?, b.player(config.seed)?];
+                        let mut runner = Runner::new_2_player(players, Some(config.seed))
+                            .with_start_positions(positions.clone())?;
+                        if let Some(adjudication) = config.adjudication {
+                            runner = runner.with_adjudication(adjudication);
+                        }
+                        if let Some(progress) = &progress {
+                            runner = runner
+                                .with_progress(progress.clone(), [a.name.clone(), b.name.clone()]);
+                        }
+                        let result = runner.run_matchup(config.games);
+                        eprintln!(
+                            "{} vs {}: {:+.2} average score, {:?}, {} adjudicated",
+                            a.name,
+                            b.name,
+                            result.average_score(),
+                            result.winner_count,
+                            result.adjudications.total()
+                        );
+                        let mut results = results.lock().unwrap();
+                        results.matchups.insert(key(i, j), result);
+                        save_results(&results_path, &results)?;
+                        #[cfg(feature = "web-dashboard")]
+                        if let Some(dashboard) = &dashboard {
+                            show_standings(dashboard, &results);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().try_for_each(|h| {
+            h.join()
+                .map_err(|_| "Matchup thread panicked".to_string())?
+        })
+    })?;
+
+    let results = results.into_inner().unwrap();
+    if results.matchups.len() != n * (n - 1) / 2 {
+        return Err("Some matchups did not finish".into());
+    }
+    write_report(&config.out.join("report.csv"), &results)?;
+    print_standings(&results);
+    Ok(())
+}
+
+/// Record the config and the files the players and games are read from
+fn write_manifest(config: &Config) -> Result<(), Error> {
+    let registry = engine::registry()?;
+    let models = config
+        .players
+        .iter()
+        .filter_map(|p| registry.config(&p.engine).model);
+    let files = config
+        .positions
+        .clone()
+        .into_iter()
+        .chain(models)
+        .chain(Registry::default_path());
+    let mut manifest = Manifest::new("tournament", config.seed, config)?;
+    for path in files {
+        manifest = manifest.with_file(&path)?;
+    }
+    manifest.save(&config.out.join(MANIFEST_FILE))?;
+    Ok(())
+}
+
+/// Write progress from all runners to `path` on a background thread
+fn spectate(path: &Path) -> Result<mpsc::Sender<Progress>, Error> {
+    let file =
+        std::fs::File::create(path).map_err(|e| format!("Failed to create {path:?}: {e}"))?;
+    let (sender, receiver) = mpsc::channel::<Progress>();
+    std::thread::spawn(move || {
+        let mut writer = BufWriter::new(file);
+        while let Ok(progress) = receiver.recv() {
+            // Write everything that is waiting, then flush so spectators see it
+            for progress in std::iter::once(progress).chain(receiver.try_iter()) {
+                let line = serde_json::to_string(&progress).unwrap();
+                if writeln!(writer, "{line}").is_err() {
+                    return;
+                }
+            }
+            if writer.flush().is_err() {
+                return;
+            }
+        }
+    });
+    Ok(sender)
+}
+
+fn key(i: usize, j: usize) -> String {
+    format!("{i}-{j}")
+}
+
+/// Result from player i's point of view
+fn result(results: &Results, i: usize, j: usize) -> Option<MatchUpResult> {
+    if i < j {
+        results.matchups.get(&key(i, j)).copied()
+    } else {
+        results.matchups.get(&key(j, i)).map(|r| r.invert())
+    }
+}
+
+fn load_results(path: &Path) -> Result<Results, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => Ok(serde_json::from_str(&s).map_err(|e| format!("Invalid {path:?}: {e}"))?),
+        Err(_) => Ok(Results::default()),
+    }
+}
+
+fn save_results(path: &Path, results: &Results) -> Result<(), Error> {
+    // Write to a temporary file first so an interrupted save can't lose results
+    let tmp = path.with_extension("json.tmp");
+    let file = std::fs::File::create(&tmp)?;
+    serde_json::to_writer_pretty(file, results)?;
+    std::fs::rename(tmp, path)?;
+    Ok(())
+}
+
+/// Matrix of average scores as csv, row player against column player
+fn write_report(path: &Path, results: &Results) -> Result<(), Error> {
+    let mut csv = String::from(",");
+    csv += &results.players.join(",");
+    csv += "\n";
+    for (i, name) in results.players.iter().enumerate() {
+        csv += name;
+        for j in 0..results.players.len() {
+            csv += ",";
+            if let Some(r) = result(results, i, j) {
+                csv += &format!("{:.3}", r.average_score());
+            }
+        }
+        csv += "\n";
+    }
+    std::fs::write(path, csv)?;
+    Ok(())
+}
+
+/// Columns of the standings
+const STANDINGS_HEADER: [&str; 10] = [
+    "Player", "Games", "Wins", "Losses", "Draws", "Average", "Think s", "Avg ms", "Max ms",
+    "Peak KiB",
+];
+
+/// Rows of the standings, best average score first
+fn standings(results: &Results) -> Vec<[String; 10]> {
+    let n = results.players.len();
+    let mut standings = (0..n)
+        .map(|i| {
+            let mut total = MatchUpResult::default();
+            for r in (0..n).filter_map(|j| result(results, i, j)) {
+                total += r;
+            }
+            (&results.players[i], total)
+        })
+        .collect::<Vec<_>>();
+    standings.sort_by(|a, b| b.1.average_score().total_cmp(&a.1.average_score()));
+    standings
+        .into_iter()
+        .map(|(name, r)| {
+            let usage = r.usage[0];
+            [
+                name.clone(),
+                r.games.to_string(),
+                r.winner_count.player0.to_string(),
+                r.winner_count.player1.to_string(),
+                r.winner_count.draw.to_string(),
+                format!("{:+.2}", r.average_score()),
+                format!("{:.2}", usage.think_time),
+                format!("{:.3}", usage.average_move_time() * 1000.0),
+                format!("{:.1}", usage.max_move_time * 1000.0),
+                usage
+                    .peak_bytes
+                    .map_or("-".to_string(), |b| format!("{:.1}", b as f64 / 1024.0)),
+            ]
+        })
+        .collect()
+}
+
+fn print_standings(results: &Results) {
+    let rows = standings(results);
+    for row in std::iter::once(STANDINGS_HEADER.map(String::from)).chain(rows) {
+        println!(
+            "{:<24} {:>6} {:>6} {:>6} {:>6} {:>8} {:>10} {:>9} {:>9} {:>10}",
+            row[0], row[1], row[2], row[3], row[4], row[5], row[6], row[7], row[8], row[9]
+        );
+    }
+}
+
+/// Show the standings and how many matchups are done on the dashboard
+#[cfg(feature = "web-dashboard")]
+fn show_standings(dashboard: &WebDashboard, results: &Results) {
+    let n = results.players.len();
+    dashboard.set_progress(format!(
+        "{} of {} matchups played",
+        results.matchups.len(),
+        n * (n - 1) / 2
+    ));
+    dashboard.set_standings(Table {
+        header: STANDINGS_HEADER.map(String::from).to_vec(),
+        rows: standings(results).into_iter().map(Vec::from).collect(),
+    });
+}
+
+/// Send progress to both `a` and `b`
+#[cfg(feature = "web-dashboard")]
+fn tee(a: mpsc::Sender<Progress>, b: mpsc::Sender<Progress>) -> mpsc::Sender<Progress> {
+    let (sender, receiver) = mpsc::channel::<Progress>();
+    std::thread::spawn(move || {
+        for progress in receiver {
+            let _ = a.send(progress.clone());
+            let _ = b.send(progress);
+        }
+    });
+    sender
+}
@@ -0,0 +1,68 @@
+//! `azul tune`: tune the heuristic evaluator's weights with SPSA
+//!
+//! Accepted weights are saved to `--out` as they improve, and a run continues
+//! from the best weights already in the file. Play them with the `heuristic:<file>` engine
+
+use std::path::PathBuf;
+
+use azul_tiles_rs::players::tuning::{Tuner, TunerConfig, WeightSets};
+
+use crate::Error;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// File the weight sets are saved to and continued from
+    #[arg(long, default_value = "heuristic_weights.json")]
+    out: PathBuf,
+    /// SPSA steps to take
+    #[arg(long, default_value_t = 200)]
+    iterations: u32,
+    /// Game pairs played for each step
+    #[arg(long, default_value_t = 10)]
+    pairs: u32,
+    /// Steps between matches against the best weights
+    #[arg(long, default_value_t = 10)]
+    check_every: u32,
+    /// Game pairs played against the best weights
+    #[arg(long, default_value_t = 50)]
+    check_pairs: u32,
+    /// Search depth of the players
+    #[arg(long, default_value_t = 1)]
+    depth: u8,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+pub fn run(args: Args) -> Result<(), Error> {
+    let sets = if args.out.exists() {
+        WeightSets::load(&args.out)?
+    } else {
+        WeightSets::default()
+    };
+    let config = TunerConfig::new()
+        .with_iterations(args.iterations)
+        .with_pairs(args.pairs)
+        .with_check_every(args.check_every)
+        .with_check_pairs(args.check_pairs)
+        .with_depth(args.depth)
+        .with_seed(args.seed);
+    let mut tuner = Tuner::new(config, sets);
+    let start = tuner.iteration();
+    tuner.run(|tuner| {
+        let last = tuner.weight_sets().sets.last().unwrap();
+        println!(
+            "Step {}: accepted weights scoring {:+.2} against the previous best",
+            last.iteration, last.score
+        );
+        tuner.weight_sets().save(&args.out)
+    })?;
+    tuner.weight_sets().save(&args.out)?;
+    println!(
+        "Finished steps {} to {}, {} weight sets in {:?}",
+        start + 1,
+        tuner.iteration(),
+        tuner.weight_sets().sets.len(),
+        args.out
+    );
+    Ok(())
+}
@@ -0,0 +1,246 @@
+//! Live view of a PPO training run
+//!
+//! Usage: `dashboard [checkpoint dir]`, defaulting to `ppo_large`.
+//! Follows `metrics.jsonl` in the checkpoint directory, plotting losses, win rate and Elo,
+//! and pauses training or requests an evaluation through files the trainer checks between episodes
+use std::path::PathBuf;
+
+use azul_tiles_rs::{
+    players::ppo::train::{Metric, EVALUATE_FILE, PAUSE_FILE},
+    spectate::LogTail,
+};
+use eframe::egui;
+use egui::{Color32, FontId, Pos2, Sense, Stroke, Vec2};
+
+fn main() -> eframe::Result {
+    env_logger::init();
+    let dir = PathBuf::from(
+        std::env::args()
+            .nth(1)
+            .unwrap_or_else(|| "ppo_large".to_string()),
+    );
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([900.0, 900.0]),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "Training dashboard",
+        options,
+        Box::new(|_| Ok(Box::new(Dashboard::new(dir)))),
+    )
+}
+
+/// Points of a line, as (episode, value)
+type Series = Vec<[f64; 2]>;
+
+struct Dashboard {
+    dir: PathBuf,
+    log: LogTail,
+    policy_loss: Series,
+    critic_loss: Series,
+    win_rate: Series,
+    elo: Series,
+    best_elo: Series,
+    /// Description of the latest episode
+    latest: String,
+    error: Option<String>,
+}
+
+impl Dashboard {
+    fn new(dir: PathBuf) -> Self {
+        Self {
+            log: LogTail::new(dir.join("metrics.jsonl")),
+            dir,
+            policy_loss: Vec::new(),
+            critic_loss: Vec::new(),
+            win_rate: Vec::new(),
+            elo: Vec::new(),
+            best_elo: Vec::new(),
+            latest: String::new(),
+            error: None,
+        }
+    }
+
+    fn poll(&mut self) {
+        let metrics = match self.log.read::<Metric>() {
+            Ok(metrics) => metrics,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                return;
+            }
+        };
+        self.error = None;
+        for metric in metrics {
+            match metric {
+                Metric::Episode {
+                    episode,
+                    learning_rate,
+                    policy_loss,
+                    critic_loss,
+                    win_rate,
+                    opponent,
+                } => {
+                    let x = episode as f64;
+                    self.policy_loss.push([x, policy_loss as f64]);
+                    self.critic_loss.push([x, critic_loss as f64]);
+                    self.win_rate.push([x, win_rate as f64]);
+                    self.latest = format!(
+                        "Episode {episode} against {opponent:?}, learning rate {learning_rate:.2e}"
+                    );
+                }
+                Metric::Evaluation {
+                    episode,
+                    elo,
+                    best_elo,
+                    ..
+                } => {
+                    self.elo.push([episode as f64, elo]);
+                    self.best_elo.push([episode as f64, best_elo]);
+                }
+            }
+        }
+    }
+
+    fn paused(&self) -> bool {
+        self.dir.join(PAUSE_FILE).exists()
+    }
+
+    /// Create or remove a control file, showing any error
+    fn control(&mut self, file: &str, create: bool) {
+        let path = self.dir.join(file);
+        let result = if create {
+            std::fs::write(&path, "")
+        } else {
+            std::fs::remove_file(&path)
+        };
+        if let Err(e) = result {
+            self.error = Some(format!("{path:?}: {e}"));
+        }
+    }
+}
+
+impl eframe::App for Dashboard {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll();
+        ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let paused = self.paused();
+                if ui.button(if paused { "Resume" } else { "Pause" }).clicked() {
+                    self.control(PAUSE_FILE, !paused);
+                }
+                let requested = self.dir.join(EVALUATE_FILE).exists();
+                if ui
+                    .add_enabled(!requested, egui::Button::new("Evaluate now"))
+                    .clicked()
+                {
+                    self.control(EVALUATE_FILE, true);
+                }
+                if paused {
+                    ui.label("Paused after the current episode");
+                } else if requested {
+                    ui.label("Evaluation after the current episode");
+                }
+            });
+            ui.label(&self.latest);
+            if let Some(e) = &self.error {
+                ui.colored_label(Color32::RED, e);
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                plot(
+                    ui,
+                    "Loss",
+                    &[
+                        ("policy", Color32::LIGHT_BLUE, &self.policy_loss),
+                        ("critic", Color32::ORANGE, &self.critic_loss),
+                    ],
+                );
+                plot(
+                    ui,
+                    "Win rate",
+                    &[("win rate", Color32::GREEN, &self.win_rate)],
+                );
+                plot(
+                    ui,
+                    "Elo",
+                    &[
+                        ("elo", Color32::YELLOW, &self.elo),
+                        ("best", Color32::GRAY, &self.best_elo),
+                    ],
+                );
+            });
+        });
+    }
+}
+
+/// Draw lines scaled to fit a box, with the range of each axis
+fn plot(ui: &mut egui::Ui, title: &str, lines: &[(&str, Color32, &Series)]) {
+    ui.heading(title);
+    ui.horizontal(|ui| {
+        for (name, colour, _) in lines {
+            ui.colored_label(*colour, *name);
+        }
+    });
+    let (response, painter) =
+        ui.allocate_painter(Vec2::new(ui.available_width(), 200.0), Sense::hover());
+    let rect = response.rect.shrink(4.0);
+    painter.rect_stroke(
+        rect,
+        0.0,
+        Stroke::new(1.0, Color32::DARK_GRAY),
+        egui::StrokeKind::Inside,
+    );
+    let points = lines.iter().flat_map(|(_, _, s)| s.iter());
+    let (min, max) = points.fold(
+        ([f64::INFINITY; 2], [f64::NEG_INFINITY; 2]),
+        |(min, max), p| {
+            (
+                [min[0].min(p[0]), min[1].min(p[1])],
+                [max[0].max(p[0]), max[1].max(p[1])],
+            )
+        },
+    );
+    if min[0] > max[0] {
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            "No data yet",
+            FontId::default(),
+            Color32::GRAY,
+        );
+        return;
+    }
+    // Avoid dividing by zero when there is a single value
+    let span = |i: usize| (max[i] - min[i]).max(1e-9);
+    let to_screen = |p: &[f64; 2]| {
+        Pos2::new(
+            rect.left() + ((p[0] - min[0]) / span(0)) as f32 * rect.width(),
+            rect.bottom() - ((p[1] - min[1]) / span(1)) as f32 * rect.height(),
+        )
+    };
+    for (_, colour, series) in lines {
+        let points = series.iter().map(to_screen).collect::<Vec<_>>();
+        if points.len() == 1 {
+            painter.circle_filled(points[0], 2.0, *colour);
+        }
+        painter.add(egui::Shape::line(points, Stroke::new(1.5, *colour)));
+    }
+    let label = |pos: Pos2, align, text: String| {
+        painter.text(pos, align, text, FontId::monospace(10.0), Color32::GRAY);
+    };
+    label(
+        rect.left_top(),
+        egui::Align2::LEFT_TOP,
+        format!("{:.3}", max[1]),
+    );
+    label(
+        rect.left_bottom(),
+        egui::Align2::LEFT_BOTTOM,
+        format!("{:.3}", min[1]),
+    );
+    label(
+        rect.right_bottom(),
+        egui::Align2::RIGHT_BOTTOM,
+        format!("episode {}", max[0]),
+    );
+}
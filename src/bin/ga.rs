@@ -1,28 +1,125 @@
+use std::{error::Error, path::Path};
+
 use azul_tiles_rs::{
-    players::{nn::MoveSelectNN, MoveRankPlayer2, MoveWeightPlayer, SLNNPlayer},
+    manifest::Manifest,
+    opening::Opening,
+    players::{nn::MoveSelectNN, MoveRankPlayer2},
     runner::Population,
 };
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+/// Manifest written at the start of each run
+const MANIFEST: &str = "ga_manifest.json";
+
+/// Settings of a run, recorded in its manifest
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Config {
+    population: usize,
+    /// Game pairs each player plays against the opponent to rank it
+    games: u32,
+    generations: usize,
+    /// Random moves opening each ranking game
+    opening_moves: u8,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            population: 400,
+            games: 50,
+            generations: 100000,
+            // Enough that the population doesn't settle on one opening
+            opening_moves: 2,
+        }
+    }
+}
 
+/// Value following `flag` on the command line
+fn arg(flag: &str) -> Option<String> {
+    std::env::args().skip_while(|a| a != flag).nth(1)
+}
+
+/// Evolve players against MoveRankPlayer2
+///
+/// `ga --seed <n>` starts from a given seed, otherwise a random one. Every run writes
+/// `ga_manifest.json`, and `ga --manifest <file>` repeats the run it describes.
+/// With the `web-dashboard` feature, `ga --dashboard <address>` shows progress in a browser
 fn main() {
-    let players = (0..400).map(|_| MoveSelectNN::new_random()).collect();
+    if let Err(e) = try_main() {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn try_main() -> Result<(), Box<dyn Error>> {
+    let (config, seed) = match arg("--manifest") {
+        Some(path) => {
+            let manifest = Manifest::load(Path::new(&path))?;
+            for difference in manifest.differences() {
+                println!("Warning: {difference}");
+            }
+            (manifest.config("ga")?, manifest.seed)
+        }
+        None => (
+            Config::default(),
+            match arg("--seed") {
+                Some(s) => s.parse().map_err(|_| format!("Invalid seed {s}"))?,
+                None => rand::random(),
+            },
+        ),
+    };
+    Manifest::new("ga", seed, &config)?.save(Path::new(MANIFEST))?;
+    println!("Seed {seed}");
+    let mut rng = SmallRng::seed_from_u64(seed);
+
+    #[cfg(feature = "web-dashboard")]
+    let dashboard = match arg("--dashboard") {
+        Some(addr) => {
+            let dashboard = azul_tiles_rs::web::WebDashboard::serve(&addr, "Genetic algorithm")?;
+            println!("Dashboard at http://{}", dashboard.addr());
+            Some(dashboard)
+        }
+        None => None,
+    };
+    let players = (0..config.population)
+        .map(|_| MoveSelectNN::from_rng(&mut rng))
+        .collect();
     let opponent = Box::new(MoveRankPlayer2::new());
-    let mut population = Population::new(players, opponent);
+    let mut population = Population::new(players, opponent)
+        .with_opening(Opening::random(config.opening_moves))
+        .with_seed(rng.gen());
 
-    let n_games = 50;
-    let best = population.rank_players(n_games);
+    let n_games = config.games;
+    let best = population.rank_players(n_games)?;
     dbg!(&best);
-    for generation in 0..100000 {
-        population.evolve();
-        let best = population.rank_players(n_games);
+    for generation in 0..config.generations {
+        population.evolve()?;
+        let best = population.rank_players(n_games)?;
         println!(
             "Gen: {}, Score: {}, Wins: {}",
             generation,
             best.2.score / best.2.games as f64,
             best.2.winner_count.player0
         );
-        serde_json::to_writer_pretty(std::fs::File::create("move_select_nn.json").unwrap(), &best)
-            .unwrap();
+        #[cfg(feature = "web-dashboard")]
+        if let Some(dashboard) = &dashboard {
+            dashboard.set_progress(format!("Generation {generation}"));
+            dashboard.add_point(
+                "Best average score",
+                generation as f64,
+                best.2.score / best.2.games as f64,
+            );
+            dashboard.add_point(
+                "Best wins",
+                generation as f64,
+                best.2.winner_count.player0 as f64,
+            );
+        }
+        let file = std::fs::File::create("move_select_nn.json")
+            .map_err(|e| format!("Failed to create move_select_nn.json: {e}"))?;
+        serde_json::to_writer_pretty(file, &best)?;
     }
-    population.evolve();
-    dbg!(&population.rank_players(n_games));
+    population.evolve()?;
+    dbg!(&population.rank_players(n_games)?);
+    Ok(())
 }
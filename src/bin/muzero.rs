@@ -0,0 +1,33 @@
+//! Train the experimental MuZero style agent from self-play
+//!
+//! Usage: `muzero [listen address]` to train, accepting self-play workers if an address is given,
+//! or `muzero worker <address>` to generate games for a trainer
+use azul_tiles_rs::players::muzero::{
+    distributed,
+    train::{MuZeroTrainConfig, MuZeroTrainer},
+    MuZeroConfig,
+};
+use burn::backend::{Autodiff, NdArray};
+use burn::tensor::Device;
+
+type Backend = Autodiff<NdArray>;
+
+fn main() {
+    env_logger::init();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("worker") => {
+            let addr = args.get(1).expect("Expected the trainer's address");
+            let device = Device::<NdArray>::default();
+            if let Err(e) = distributed::worker::<NdArray>(addr, &MuZeroConfig::new(), &device) {
+                eprintln!("Worker stopped: {e}");
+            }
+        }
+        listen => {
+            let device = Device::<Backend>::default();
+            let config = MuZeroTrainConfig::new().with_listen(listen.map(String::from));
+            let trainer = MuZeroTrainer::<Backend>::new(MuZeroConfig::new(), config, &device);
+            trainer.train(std::path::Path::new("muzero"));
+        }
+    }
+}
@@ -1,44 +1,251 @@
-use azul_tiles_rs::players::minimax::{Minimaxer, ScoreEvaluator};
-use azul_tiles_rs::players::ppo::train::PPOTrainer;
+//! Train a PPO agent
+//!
+//! Usage: `ppo [shaped|terminal|margin] [episodes]` to train with a reward scheme,
+//! `ppo compare [episodes]` to train with each scheme and compare them in a gauntlet,
+//! or `ppo config.json` to train with a saved [TrainConfig]
+//!
+//! `--batched` trains on whole minibatches at once, `--half` uses half precision floats
+//! and `--pretrain` starts from a policy trained to copy MoveRankPlayer2
+//!
+//! Run `dashboard <checkpoint dir>` alongside to follow, pause or evaluate training,
+//! or with the `web-dashboard` feature pass `--dashboard <address>` to follow it in a browser
+//!
+//! Each run writes a `manifest.json` to its checkpoint directory,
+//! and `ppo --manifest <file>` trains again the way it describes
+use std::error::Error;
+
+use azul_tiles_rs::manifest::{Manifest, MANIFEST_FILE};
+use azul_tiles_rs::players::curriculum::Opponent;
+use azul_tiles_rs::players::ppo::pretrain::PretrainConfig;
+use azul_tiles_rs::players::ppo::train::{PPOTrainer, RewardScheme, TrainConfig};
 use azul_tiles_rs::players::ppo::{PPOMoveSelector, PolicyConfig, ValueConfig};
-use burn::optim::{Adam, AdamConfig};
-use burn::tensor::{Device, Tensor};
-use minimaxer::negamax::SearchOptions;
+use azul_tiles_rs::players::Player;
+use azul_tiles_rs::runner::gauntlet;
+use burn::backend::wgpu::WgpuDevice;
+use burn::config::Config;
+use burn::tensor::backend::AutodiffBackend;
+use burn::tensor::f16;
+
+use burn::backend::{Autodiff, Wgpu};
 
-use burn::backend::{Autodiff, NdArray, Wgpu};
+/// Opponents used to compare the trained agents
+const GAUNTLET: [Opponent; 4] = [
+    Opponent::Random,
+    Opponent::MoveRank,
+    Opponent::MoveRank2,
+    Opponent::Minimax(1),
+];
 
-type Backend = Autodiff<Wgpu>; //Wgpu; //NdArray;
+/// Settings recorded in the manifest of a training run
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Run {
+    half: bool,
+    train: TrainConfig,
+}
 
 fn main() {
-    let device = Device::<Backend>::default();
+    if let Err(e) = try_main() {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn try_main() -> Result<(), Box<dyn Error>> {
+    let device = WgpuDevice::default();
     burn::backend::wgpu::init_setup::<burn::backend::wgpu::graphics::OpenGl>(
         &device,
         Default::default(),
     );
-    // Create policy and value networks
-    let policy_config = PolicyConfig {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let mut flag = |name: &str| {
+        let found = args.iter().any(|a| a == name);
+        args.retain(|a| a != name);
+        found
+    };
+    let half = flag("--half");
+    let batched = flag("--batched");
+    let pretrain = flag("--pretrain").then(PretrainConfig::new);
+    let dashboard = match args.iter().position(|a| a == "--dashboard") {
+        Some(i) => {
+            let addr = args
+                .get(i + 1)
+                .cloned()
+                .ok_or("Missing dashboard address")?;
+            args.drain(i..=i + 1);
+            Some(dashboard::serve(&addr)?)
+        }
+        None => None,
+    };
+    if let Some(i) = args.iter().position(|a| a == "--manifest") {
+        let path = args.get(i + 1).ok_or("Missing manifest")?;
+        let manifest = Manifest::load(std::path::Path::new(path))?;
+        for difference in manifest.differences() {
+            println!("Warning: {difference}");
+        }
+        let run: Run = manifest.config("ppo")?;
+        return if run.half {
+            train_and_evaluate::<Autodiff<Wgpu<f16>>>(run.train, true, dashboard.as_ref(), &device)
+        } else {
+            train_and_evaluate::<Autodiff<Wgpu>>(run.train, false, dashboard.as_ref(), &device)
+        };
+    }
+    if half {
+        run::<Autodiff<Wgpu<f16>>>(args, half, batched, pretrain, dashboard, &device)
+    } else {
+        run::<Autodiff<Wgpu>>(args, half, batched, pretrain, dashboard, &device)
+    }
+}
+
+fn run<B: AutodiffBackend>(
+    args: Vec<String>,
+    half: bool,
+    batched: bool,
+    pretrain: Option<PretrainConfig>,
+    dashboard: Option<dashboard::Dashboard>,
+    device: &B::Device,
+) -> Result<(), Box<dyn Error>> {
+    let mut args = args.into_iter();
+    let mode = args.next().unwrap_or("shaped".into());
+    let episodes = match args.next() {
+        Some(e) => Some(
+            e.parse()
+                .map_err(|_| format!("Invalid number of episodes {e}"))?,
+        ),
+        None => None,
+    };
+
+    if mode.ends_with(".json") {
+        let config = TrainConfig::load(&mode)
+            .map_err(|e| format!("Failed to load training config {mode}: {e}"))?;
+        return train_and_evaluate::<B>(config, half, dashboard.as_ref(), device);
+    }
+
+    let schemes = match mode.as_str() {
+        "compare" => vec![
+            RewardScheme::Shaped,
+            RewardScheme::Terminal,
+            RewardScheme::Margin,
+        ],
+        scheme => vec![scheme.parse()?],
+    };
+
+    for scheme in schemes {
+        // Start against random and work up to minimax
+        let mut config = TrainConfig::new()
+            .with_reward(scheme)
+            .with_checkpoint_dir(format!("ppo_{scheme:?}").to_lowercase())
+            .with_batched(batched)
+            .with_pretrain(pretrain.clone());
+        if let Some(episodes) = episodes {
+            config = config.with_episodes(episodes);
+        }
+        train_and_evaluate::<B>(config, half, dashboard.as_ref(), device)?;
+    }
+    Ok(())
+}
+
+/// Train a new agent and print its results against the gauntlet
+fn train_and_evaluate<B: AutodiffBackend>(
+    config: TrainConfig,
+    half: bool,
+    dashboard: Option<&dashboard::Dashboard>,
+    device: &B::Device,
+) -> Result<(), Box<dyn Error>> {
+    let reward = config.reward;
+    // Keep the config next to the checkpoints
+    let dir = std::path::Path::new(&config.checkpoint_dir);
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {dir:?}: {e}"))?;
+    config
+        .save(dir.join("config.json"))
+        .map_err(|e| format!("Failed to save the training config: {e}"))?;
+    let run = Run {
+        half,
+        train: config.clone(),
+    };
+    Manifest::new("ppo", config.seed, &run)?.save(&dir.join(MANIFEST_FILE))?;
+
+    // Seed the first weights
+    B::seed(config.seed);
+    let ppo = PPOMoveSelector::<B>::new(new_policy(), new_value(), device);
+    let title = format!("PPO training in {}", config.checkpoint_dir);
+    let mut trainer = PPOTrainer::new(ppo, config, device);
+    if let Some(dashboard) = dashboard {
+        trainer = dashboard::observe(trainer, dashboard, title);
+    }
+    let ppo = trainer.train();
+
+    let opponents: Vec<Box<dyn Player<2, 6>>> = GAUNTLET.iter().map(|o| o.player()).collect();
+    println!("Gauntlet for {reward:?} rewards");
+    for (opponent, result) in GAUNTLET.iter().zip(gauntlet(&ppo, &opponents, 50, 0)) {
+        println!(
+            " vs {opponent:?}: won {}, lost {}, drew {}, average margin {:.1}",
+            result.winner_count.player0,
+            result.winner_count.player1,
+            result.winner_count.draw,
+            result.average_score()
+        );
+    }
+    Ok(())
+}
+
+fn new_policy() -> PolicyConfig {
+    PolicyConfig {
         input_size: 150,
         hidden_size: 320,
-    };
-    let value_config = ValueConfig {
+    }
+}
+
+fn new_value() -> ValueConfig {
+    ValueConfig {
         input_size: 150,
         hidden_size: 320,
-    };
-    let mut ppo = PPOMoveSelector::<Backend>::new(policy_config, value_config, &device);
+    }
+}
+
+/// Showing training in a browser
+#[cfg(feature = "web-dashboard")]
+mod dashboard {
+    use azul_tiles_rs::{players::ppo::train::PPOTrainer, web::WebDashboard};
+    use burn::tensor::backend::AutodiffBackend;
+
+    pub type Dashboard = WebDashboard;
+
+    pub fn serve(addr: &str) -> Result<Dashboard, Box<dyn std::error::Error>> {
+        let dashboard = WebDashboard::serve(addr, "PPO training")?;
+        println!("Dashboard at http://{}", dashboard.addr());
+        Ok(dashboard)
+    }
+
+    /// Show the trainer's metrics on the dashboard, clearing the previous run
+    pub fn observe<B: AutodiffBackend>(
+        trainer: PPOTrainer<B>,
+        dashboard: &Dashboard,
+        title: String,
+    ) -> PPOTrainer<B> {
+        dashboard.reset(title);
+        let dashboard = dashboard.clone();
+        trainer.with_observer(move |metric| dashboard.add_metric(metric))
+    }
+}
 
-    println!("PPO Move Selector: {:?}", ppo);
+/// Stand in for builds without the web dashboard
+#[cfg(not(feature = "web-dashboard"))]
+mod dashboard {
+    use azul_tiles_rs::players::ppo::train::PPOTrainer;
+    use burn::tensor::backend::AutodiffBackend;
 
-    // Create a basic opponent
-    let opponent = Box::new(Minimaxer::new(
-        SearchOptions {
-            max_depth: Some(1),
-            ..Default::default()
-        },
-        "Depth1",
-        ScoreEvaluator,
-    ));
+    pub struct Dashboard;
 
-    let mut trainer = PPOTrainer::new(ppo, opponent, &device);
+    pub fn serve(_addr: &str) -> Result<Dashboard, Box<dyn std::error::Error>> {
+        eprintln!("Built without the web-dashboard feature, not serving a dashboard");
+        Ok(Dashboard)
+    }
 
-    trainer.train();
+    pub fn observe<B: AutodiffBackend>(
+        trainer: PPOTrainer<B>,
+        _dashboard: &Dashboard,
+        _title: String,
+    ) -> PPOTrainer<B> {
+        trainer
+    }
 }
@@ -0,0 +1,383 @@
+//! Play a game against an AI opponent in the terminal
+//!
+//! Usage: `tui [opponent]` where opponent is a name from the player registry,
+//! see `azul players`, defaulting to `minimax` with a second a move
+//!
+//! Selection works the same as the GUI: press a number to pick a factory
+//! (0 is the centre), then a tile (colour in the centre, position in a factory),
+//! then a row 1-5 or 0 for the floor.
+//! Space advances the AI player and the end of round, Esc clears the selection
+//! and q quits.
+
+use std::time::Duration;
+
+use azul_tiles_rs::{
+    gamestate::{Destination, Gamestate, Move, Source, State},
+    playerboard::{PlayerBoard, RowIndex},
+    players,
+    registry::Registry,
+    tiles::Tile,
+};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+enum Player {
+    Ai(Box<dyn players::Player<2, 6>>),
+    Human,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Selection {
+    moves: Vec<Move>,
+    factory: Option<usize>,
+    tile: Option<Tile>,
+}
+
+struct App {
+    gs: Gamestate<2, 6>,
+    players: [Player; 2],
+    selection: Selection,
+    message: String,
+}
+
+fn main() -> std::io::Result<()> {
+    let opponent = std::env::args().nth(1).unwrap_or("minimax".into());
+    let opponent = Registry::load_default()
+        .and_then(|registry| registry.player(&opponent, Duration::from_secs(1)))
+        .unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+
+    let app = App {
+        gs: Gamestate::new_2_player_with_seed(rand::random(), 0),
+        players: [Player::Human, Player::Ai(opponent)],
+        selection: Selection::default(),
+        message: String::new(),
+    };
+
+    let terminal = ratatui::init();
+    let result = app.run(terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn run(mut self, mut terminal: DefaultTerminal) -> std::io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Char(' ') => self.advance_gamestate(),
+                    KeyCode::Esc => self.selection = Selection::default(),
+                    KeyCode::Char(c) => {
+                        if let Some(n) = c.to_digit(10) {
+                            self.select(n as usize);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Play the AI move or end the round
+    fn advance_gamestate(&mut self) {
+        match self.gs.state() {
+            State::RoundActive => {
+                if let Player::Ai(player) = &mut self.players[self.gs.current_player() as usize] {
+                    let moves = self.gs.get_moves();
+                    let m = player.pick_move(&self.gs, moves);
+                    self.message = format!("{} played {}", player.name(), describe_move(&m));
+                    self.gs.play_move(m);
+                }
+            }
+            State::RoundEnd => {
+                self.gs.end_round();
+                self.message = match self.gs.state() {
+                    State::GameEnd => format!(
+                        "Game over, {}, scores {:?}",
+                        self.gs.ending().unwrap(),
+                        self.gs.scores()
+                    ),
+                    _ => format!("Round {}", self.gs.round()),
+                };
+            }
+            State::GameEnd => (),
+        }
+    }
+
+    /// Handle a number key for the human player
+    fn select(&mut self, n: usize) {
+        if self.gs.state() != State::RoundActive {
+            return;
+        }
+        if let Player::Ai(_) = self.players[self.gs.current_player() as usize] {
+            return;
+        }
+        let moves = self.gs.get_moves();
+        match (self.selection.factory, self.selection.tile) {
+            (None, _) => {
+                // Select factory if valid move
+                if moves.iter().any(|m| m.source == Source(n as u8)) {
+                    self.selection.factory = Some(n);
+                }
+            }
+            (Some(factory), None) => {
+                let tile = if factory == 0 {
+                    // centre, select by colour
+                    Tile::try_from(n).ok().filter(|_| n < 5)
+                } else {
+                    // factory, select by tile position
+                    let tiles = self.gs.factories()[factory]
+                        .map(|f| f.tile_vec())
+                        .unwrap_or_default();
+                    n.checked_sub(1).and_then(|i| tiles.get(i).copied())
+                };
+                if let Some(tile) = tile {
+                    self.selection.moves = moves
+                        .into_iter()
+                        .filter(|m| m.tile == tile && m.source == Source(factory as u8))
+                        .collect();
+                    if !self.selection.moves.is_empty() {
+                        self.selection.tile = Some(tile);
+                    }
+                }
+            }
+            (Some(factory), Some(tile)) => {
+                let destination = match n {
+                    0 => Destination::Floor,
+                    1..=5 => match RowIndex::try_from(n - 1) {
+                        Ok(row) => Destination::Row(row),
+                        Err(_) => return,
+                    },
+                    _ => return,
+                };
+                match self
+                    .gs
+                    .explain_move(Source(factory as u8), tile, destination)
+                {
+                    Ok(m) => {
+                        self.message = format!("You played {}", describe_move(&m));
+                        self.gs.play_move(m);
+                        self.selection = Selection::default();
+                    }
+                    Err(reason) => self.message = format!("Can't play there, {reason}"),
+                }
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [opponent, factories, player, status] = Layout::vertical([
+            Constraint::Length(10),
+            Constraint::Length(8),
+            Constraint::Length(10),
+            Constraint::Min(3),
+        ])
+        .areas(frame.area());
+
+        self.draw_board(frame, opponent, 1);
+        self.draw_factories(frame, factories);
+        self.draw_board(frame, player, 0);
+
+        let help = match (self.selection.factory, self.selection.tile) {
+            _ if self.gs.state() != State::RoundActive => "Space: continue, q: quit",
+            _ if matches!(
+                self.players[self.gs.current_player() as usize],
+                Player::Ai(_)
+            ) =>
+            {
+                "Space: play AI move, q: quit"
+            }
+            (None, _) => "Pick a factory: 0 for centre, 1-5",
+            (Some(0), None) => "Pick a colour: 0 Blue, 1 Yellow, 2 Red, 3 Black, 4 White",
+            (Some(_), None) => "Pick a tile: 1-4",
+            (Some(_), Some(_)) => "Pick a row: 1-5, or 0 for the floor",
+        };
+        frame.render_widget(
+            Paragraph::new(vec![Line::from(self.message.as_str()), Line::from(help)])
+                .block(Block::bordered().title(format!(" Round {} ", self.gs.round()))),
+            status,
+        );
+    }
+
+    fn draw_board(&self, frame: &mut Frame, area: Rect, index: usize) {
+        let board = &self.gs.boards()[index];
+        let active =
+            self.gs.state() == State::RoundActive && self.gs.current_player() as usize == index;
+        let highlight = |dest: Destination| {
+            active && self.selection.moves.iter().any(|m| m.destination == dest)
+        };
+        let mut lines = Vec::new();
+        for (row_ind, row) in board.row_iter() {
+            let capacity = row_ind.capacity() as usize;
+            let mut spans = vec![Span::raw(format!("{} ", row_ind as usize + 1))];
+            spans.push(Span::raw("  ".repeat(5 - capacity)));
+            for i in (0..capacity).rev() {
+                spans.push(match row.tile() {
+                    Some(tile) if (row.count() as usize) > i => tile_span(tile),
+                    _ if highlight(row_ind.into()) => {
+                        Span::styled("[]", Style::new().fg(Color::Magenta))
+                    }
+                    _ => Span::raw(" ."),
+                });
+            }
+            spans.push(Span::raw(" | "));
+            for (cell, colour) in board
+                .wall
+                .iter()
+                .nth(row_ind as usize)
+                .unwrap()
+                .iter()
+                .zip(self.gs.wall_pattern().colours()[row_ind as usize])
+            {
+                spans.push(match cell {
+                    Some(tile) => tile_span(*tile),
+                    None => Span::styled(
+                        format!(" {}", colour.letter().to_ascii_lowercase()),
+                        Style::new()
+                            .fg(tile_colour(&colour))
+                            .add_modifier(Modifier::DIM),
+                    ),
+                });
+            }
+            lines.push(Line::from(spans));
+        }
+        lines.push(Line::from(floor_spans(
+            board,
+            highlight(Destination::Floor),
+        )));
+        lines.push(Line::from(format!(
+            "Score {} | Predicted {}",
+            board.score, board.predicted_score
+        )));
+
+        let mut block = Block::bordered().title(format!(" Player {} ", index));
+        if active {
+            block = block.border_style(Style::new().fg(Color::Magenta));
+        }
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_factories(&self, frame: &mut Frame, area: Rect) {
+        let areas = Layout::horizontal([Constraint::Ratio(1, 6); 6]).split(area);
+        for (i, factory) in self.gs.factories().iter().enumerate() {
+            let selected = self.selection.factory == Some(i);
+            let tiles = factory.unwrap_or_default();
+            let lines = if i == 0 {
+                // Centre shows a count of each colour
+                let mut lines = tiles
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, (&count, _))| count > 0)
+                    .map(|(n, (count, tile))| {
+                        Line::from(vec![
+                            Span::raw(format!("{n}:")),
+                            self.selected_tile_span(selected, tile),
+                            Span::raw(format!(" x{count}")),
+                        ])
+                    })
+                    .collect::<Vec<_>>();
+                if self.gs.first_player_tile() {
+                    lines.push(Line::from(Span::styled(
+                        "1st",
+                        Style::new().fg(Color::Magenta),
+                    )));
+                }
+                lines
+            } else {
+                let spans = tiles
+                    .tile_vec()
+                    .into_iter()
+                    .enumerate()
+                    .flat_map(|(n, tile)| {
+                        [
+                            Span::raw(format!("{}:", n + 1)),
+                            self.selected_tile_span(selected, tile),
+                            Span::raw(" "),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                spans.chunks(6).map(|s| Line::from(s.to_vec())).collect()
+            };
+            let mut block = Block::bordered().title(if i == 0 {
+                " 0 Centre ".to_string()
+            } else {
+                format!(" {i} ")
+            });
+            if selected {
+                block = block.border_style(Style::new().fg(Color::Magenta));
+            }
+            frame.render_widget(Paragraph::new(lines).block(block), areas[i]);
+        }
+    }
+
+    /// Tile span with an underline if it is the selected tile
+    fn selected_tile_span(&self, selected: bool, tile: Tile) -> Span<'static> {
+        let span = tile_span(tile);
+        if selected && self.selection.tile == Some(tile) {
+            span.patch_style(Style::new().add_modifier(Modifier::UNDERLINED))
+        } else {
+            span
+        }
+    }
+}
+
+fn floor_spans(board: &PlayerBoard, highlight: bool) -> Vec<Span<'static>> {
+    let mut spans = vec![Span::styled(
+        "Floor ",
+        if highlight {
+            Style::new().fg(Color::Magenta)
+        } else {
+            Style::new()
+        },
+    )];
+    if board.floor.has_first_player_tile() {
+        spans.push(Span::styled("1st", Style::new().fg(Color::Magenta)));
+    }
+    spans.extend(board.floor.tiles().tile_vec().into_iter().map(tile_span));
+    spans
+}
+
+fn tile_span(tile: Tile) -> Span<'static> {
+    Span::styled(
+        format!(" {}", tile.letter()),
+        Style::new()
+            .fg(tile_colour(&tile))
+            .add_modifier(Modifier::BOLD),
+    )
+}
+
+fn tile_colour(tile: &Tile) -> Color {
+    match tile {
+        Tile::Blue => Color::Blue,
+        Tile::Yellow => Color::Yellow,
+        Tile::Red => Color::Red,
+        Tile::Black => Color::Green,
+        Tile::White => Color::White,
+        Tile::Orange => Color::LightRed,
+    }
+}
+
+fn describe_move(m: &Move) -> String {
+    let dest = match m.destination {
+        Destination::Row(row) => format!("row {}", row as usize + 1),
+        Destination::Floor => "floor".into(),
+    };
+    let source = match m.source.0 {
+        0 => "centre".into(),
+        f => format!("factory {f}"),
+    };
+    format!("{} {:?} from {} to {}", m.count, m.tile, source, dest)
+}
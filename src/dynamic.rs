@@ -0,0 +1,208 @@
+//! Games whose number of players is chosen at runtime
+//!
+//! [Gamestate] fixes the players and factories as const generics, so code that lets
+//! a menu or config file choose between 2, 3 and 4 players would have to be written
+//! for each. [DynGamestate] holds any of the three and answers with slices and [Vec]s
+//! instead of arrays. Match on it to reach the [Gamestate] for anything not covered
+
+use crate::{
+    error::{AzulError, PositionError},
+    gamestate::{GameEnding, Gamestate, Move, State},
+    playerboard::PlayerBoard,
+    tiles::TileGroup,
+    variant::Variant,
+};
+
+/// Game of 2, 3 or 4 players with the standard number of factories
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum DynGamestate {
+    Two(Gamestate<2, 6>),
+    Three(Gamestate<3, 8>),
+    Four(Gamestate<4, 10>),
+}
+
+/// Call the same method whichever game is held
+macro_rules! dispatch {
+    ($self:expr, $gs:ident => $body:expr) => {
+        match $self {
+            DynGamestate::Two($gs) => $body,
+            DynGamestate::Three($gs) => $body,
+            DynGamestate::Four($gs) => $body,
+        }
+    };
+}
+
+impl DynGamestate {
+    /// New game of `players` players, see [Gamestate::new]
+    pub fn new(players: usize, seed: u64, first_player: u8) -> Result<Self, AzulError> {
+        if first_player as usize >= players {
+            return Err(PositionError::NoSuchPlayer(first_player).into());
+        }
+        match players {
+            2 => Ok(Self::Two(Gamestate::new(seed, first_player))),
+            3 => Ok(Self::Three(Gamestate::new(seed, first_player))),
+            4 => Ok(Self::Four(Gamestate::new(seed, first_player))),
+            _ => Err(AzulError::PlayerCount(players)),
+        }
+    }
+
+    /// Read a position string for `players` players, see [crate::position]
+    pub fn from_position_str(players: usize, s: &str) -> Result<Self, AzulError> {
+        match players {
+            2 => Gamestate::from_position_str(s).map(Self::Two),
+            3 => Gamestate::from_position_str(s).map(Self::Three),
+            4 => Gamestate::from_position_str(s).map(Self::Four),
+            _ => Err(AzulError::PlayerCount(players)),
+        }
+    }
+
+    /// Number of players
+    pub fn players(&self) -> usize {
+        dispatch!(self, gs => gs.boards().len())
+    }
+
+    /// Number of factories, including the centre
+    pub fn factory_count(&self) -> usize {
+        dispatch!(self, gs => gs.factories().len())
+    }
+
+    /// Play by the rules of a variant, see [Gamestate::set_variant]
+    pub fn set_variant(&mut self, variant: Variant) -> Result<(), AzulError> {
+        dispatch!(self, gs => gs.set_variant(variant))
+    }
+
+    pub fn variant(&self) -> &Variant {
+        dispatch!(self, gs => gs.variant())
+    }
+
+    pub fn state(&self) -> State {
+        dispatch!(self, gs => gs.state())
+    }
+
+    pub fn round(&self) -> u16 {
+        dispatch!(self, gs => gs.round())
+    }
+
+    pub fn current_player(&self) -> u8 {
+        dispatch!(self, gs => gs.current_player())
+    }
+
+    pub fn first_player_tile(&self) -> bool {
+        dispatch!(self, gs => gs.first_player_tile())
+    }
+
+    pub fn boards(&self) -> &[PlayerBoard] {
+        dispatch!(self, gs => gs.boards())
+    }
+
+    /// Factories with the centre first, see [Gamestate::factories]
+    pub fn factories(&self) -> &[Option<TileGroup>] {
+        dispatch!(self, gs => gs.factories())
+    }
+
+    pub fn centre(&self) -> TileGroup {
+        dispatch!(self, gs => gs.centre())
+    }
+
+    pub fn tilebag(&self) -> &TileGroup {
+        dispatch!(self, gs => gs.tilebag())
+    }
+
+    pub fn lid(&self) -> &TileGroup {
+        dispatch!(self, gs => gs.lid())
+    }
+
+    pub fn scores(&self) -> Vec<u8> {
+        dispatch!(self, gs => gs.scores().to_vec())
+    }
+
+    /// Seats from first place to last, see [Gamestate::rankings]
+    pub fn rankings(&self) -> Vec<u8> {
+        dispatch!(self, gs => gs.rankings().to_vec())
+    }
+
+    /// Seat that wins by the official rules, see [Gamestate::winner]
+    pub fn winner(&self) -> Option<u8> {
+        dispatch!(self, gs => gs.winner())
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        dispatch!(self, gs => gs.is_terminal())
+    }
+
+    /// Why the game ended, see [Gamestate::ending]
+    pub fn ending(&self) -> Option<GameEnding> {
+        dispatch!(self, gs => gs.ending())
+    }
+
+    pub fn get_moves(&self) -> Vec<Move> {
+        dispatch!(self, gs => gs.get_moves())
+    }
+
+    /// Play a move without checking it, see [Gamestate::play_move]
+    pub fn play_move(&mut self, move_: Move) -> State {
+        dispatch!(self, gs => gs.play_move(move_))
+    }
+
+    /// Play a move if it is legal, see [Gamestate::try_play_move]
+    pub fn try_play_move(&mut self, move_: Move) -> Result<State, AzulError> {
+        dispatch!(self, gs => gs.try_play_move(move_))
+    }
+
+    /// End the round, returning the state of the game after it.
+    /// The full [RoundSummary](crate::gamestate::RoundSummary) is on [Gamestate::end_round]
+    pub fn end_round(&mut self) -> State {
+        dispatch!(self, gs => gs.end_round().state)
+    }
+
+    /// End the round, returning an error if the round is not over
+    pub fn try_end_round(&mut self) -> Result<State, AzulError> {
+        dispatch!(self, gs => gs.try_end_round())
+    }
+
+    /// The position on one line, see [crate::position]
+    pub fn to_string_position(&self) -> String {
+        dispatch!(self, gs => gs.to_string_position())
+    }
+}
+
+impl std::fmt::Display for DynGamestate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        dispatch!(self, gs => std::fmt::Display::fmt(gs, f))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DynGamestate;
+    use crate::{
+        error::{AzulError, PositionError},
+        gamestate::State,
+    };
+
+    #[test]
+    fn any_player_count() {
+        for players in 2..=4 {
+            let mut gs = DynGamestate::new(players, 3, 0).unwrap();
+            assert_eq!(gs.players(), players);
+            assert_eq!(gs.factory_count(), 2 * players + 2);
+            while !gs.is_terminal() {
+                if gs.play_move(gs.get_moves()[0]) == State::RoundEnd {
+                    gs.end_round();
+                }
+            }
+            assert_eq!(gs.rankings().len(), players);
+            let loaded =
+                DynGamestate::from_position_str(players, &gs.to_string_position()).unwrap();
+            assert_eq!(loaded.scores(), gs.scores());
+        }
+        assert!(matches!(
+            DynGamestate::new(5, 0, 0),
+            Err(AzulError::PlayerCount(5))
+        ));
+        assert!(matches!(
+            DynGamestate::new(2, 0, 2),
+            Err(AzulError::Position(PositionError::NoSuchPlayer(2)))
+        ));
+    }
+}
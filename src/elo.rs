@@ -0,0 +1,99 @@
+//! Elo ratings from match results
+
+use crate::runner::MatchUpResult;
+
+/// Rating given to new players
+pub const INITIAL_RATING: f64 = 1500.0;
+
+/// Expected score (0 to 1) of a player rated `a` against a player rated `b`
+pub fn expected(a: f64, b: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((b - a) / 400.0))
+}
+
+/// Fraction of points won by player 0, counting draws as half
+pub fn points(result: &MatchUpResult) -> f64 {
+    let w = &result.winner_count;
+    let games = w.player0 + w.player1 + w.draw;
+    if games == 0 {
+        0.5
+    } else {
+        (w.player0 as f64 + 0.5 * w.draw as f64) / games as f64
+    }
+}
+
+/// Update the ratings of two players after a matchup with factor `k` per game
+///
+/// All games are treated as played at the starting ratings
+pub fn update(a: f64, b: f64, result: &MatchUpResult, k: f64) -> (f64, f64) {
+    let w = &result.winner_count;
+    let games = (w.player0 + w.player1 + w.draw) as f64;
+    let delta = k * games * (points(result) - expected(a, b));
+    (a + delta, b - delta)
+}
+
+/// Estimated rating difference of player 0 over player 1 from the results
+///
+/// Clamped to +-800 when one player won every game
+pub fn difference(result: &MatchUpResult) -> f64 {
+    let p = points(result).clamp(0.01, 0.99);
+    (-400.0 * (1.0 / p - 1.0).log10()).clamp(-800.0, 800.0)
+}
+
+/// Rating at which the expected points against the rated opponents match the results
+///
+/// Each entry is an opponent's rating and the result against them.
+/// Limited to 800 either side of the opponents when every game was won or lost
+pub fn performance(results: &[(f64, MatchUpResult)]) -> f64 {
+    let games = |r: &MatchUpResult| {
+        let w = &r.winner_count;
+        (w.player0 + w.player1 + w.draw) as f64
+    };
+    if results.is_empty() {
+        return INITIAL_RATING;
+    }
+    let target: f64 = results.iter().map(|(_, r)| points(r) * games(r)).sum();
+    let ratings = || results.iter().map(|(rating, _)| *rating);
+    let mut low = ratings().fold(f64::INFINITY, f64::min) - 800.0;
+    let mut high = ratings().fold(f64::NEG_INFINITY, f64::max) + 800.0;
+    // Expected points increase with rating so bisect
+    for _ in 0..50 {
+        let mid = (low + high) / 2.0;
+        let expected: f64 = results
+            .iter()
+            .map(|(rating, r)| expected(mid, *rating) * games(r))
+            .sum();
+        if expected < target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runner::WinnerCount;
+
+    #[test]
+    fn elo() {
+        assert_eq!(expected(1500.0, 1500.0), 0.5);
+        let result = MatchUpResult {
+            games: 4,
+            score: 10.0,
+            winner_count: WinnerCount {
+                player0: 3,
+                player1: 1,
+                draw: 0,
+            },
+            ..Default::default()
+        };
+        let (a, b) = update(1500.0, 1500.0, &result, 16.0);
+        assert_eq!((a, b), (1516.0, 1484.0));
+        assert!(difference(&result) > 0.0);
+        assert!((difference(&result.invert()) + difference(&result)).abs() < 1e-9);
+        let rating = performance(&[(1500.0, result)]);
+        assert!((rating - 1500.0 - difference(&result)).abs() < 1e-6);
+    }
+}
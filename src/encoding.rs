@@ -0,0 +1,218 @@
+//! Positions packed into a few dozen bytes, for storing large numbers of them,
+//! eg as training data
+//!
+//! Every position of a game type takes [Gamestate::ENCODED_LEN] bytes, 60 for a
+//! standard 2 player game. Counts below 16 share a byte, low nibble first:
+//! 1. Round, 2 bytes little endian, with the top bit set once the game is over
+//! 2. Player to move
+//! 3. Where the first player tile is: 0 in the centre, or 1 + the seat whose floor it's on
+//! 4. Count of each colour in each factory, in nibbles
+//! 5. A byte for each colour in the centre, the bag and the lid
+//! 6. For each board: score, the wall as a little endian bitmask of placed tiles,
+//!    a byte for each pattern line with its colour in the high nibble and count
+//!    in the low nibble, then the count of each colour on the floor in nibbles
+//!
+//! Like [position strings](crate::position), the rng, handicaps and score breakdowns
+//! are left out. The [Variant] is not stored either, the decoder is given it,
+//! so only walls with pattern placement can be encoded
+
+use rand::{Rng, SeedableRng};
+
+use crate::{
+    error::{AzulError, PositionError},
+    gamestate::{Gamestate, State},
+    playerboard::{
+        wall::{ColumnIndex, Wall, WallPlacement},
+        RowIndex,
+    },
+    tiles::{Tile, TileGroup},
+    variant::Variant,
+};
+
+/// Bit of the round set when the game is over
+const GAME_OVER: u16 = 1 << 15;
+
+impl<const P: usize, const F: usize, R: Rng + Clone, const C: usize> Gamestate<P, F, R, C> {
+    /// Bytes taken by every position from [Gamestate::encode]
+    pub const ENCODED_LEN: usize =
+        4 + (F - 1) * C.div_ceil(2) + 3 * C + P * (1 + (C * C).div_ceil(8) + C + C.div_ceil(2));
+
+    /// The position in [Gamestate::ENCODED_LEN] bytes, see [crate::encoding].
+    /// Fails for walls with free placement, as the colours of their tiles aren't stored
+    pub fn encode(&self) -> Result<Vec<u8>, AzulError> {
+        if self.variant().placement == WallPlacement::Free {
+            return Err(PositionError::FreePlacement.into());
+        }
+        let mut bytes = Vec::with_capacity(Self::ENCODED_LEN);
+        let game_over = if self.state() == State::GameEnd {
+            GAME_OVER
+        } else {
+            0
+        };
+        bytes.extend((self.round() | game_over).to_le_bytes());
+        bytes.push(self.current_player());
+        bytes.push(
+            self.boards()
+                .iter()
+                .position(|b| b.floor.has_first_player_tile())
+                .map_or(0, |seat| seat as u8 + 1),
+        );
+        for factory in &self.factories()[1..] {
+            push_nibbles(&mut bytes, &factory.unwrap_or_default());
+        }
+        for tiles in [&self.centre(), self.tilebag(), self.lid()] {
+            bytes.extend(tiles.counts());
+        }
+        for board in self.boards() {
+            bytes.push(board.score);
+            let wall = board
+                .wall
+                .iter()
+                .flatten()
+                .enumerate()
+                .filter(|(_, cell)| cell.is_some())
+                .fold(0u64, |mask, (i, _)| mask | 1 << i);
+            bytes.extend(&wall.to_le_bytes()[..(C * C).div_ceil(8)]);
+            bytes.extend(board.rows.iter().map(|row| match row.tile() {
+                Some(tile) => (tile as u8) << 4 | row.count(),
+                None => 0,
+            }));
+            push_nibbles(&mut bytes, board.floor.tiles());
+        }
+        Ok(bytes)
+    }
+}
+
+impl<const P: usize, const F: usize, R: Rng + SeedableRng + Clone, const C: usize>
+    Gamestate<P, F, R, C>
+{
+    /// Read a position from [Gamestate::encode], played with the rules of `variant`.
+    /// Future deals come from a seed of 0, see [Gamestate::reseed]
+    pub fn decode(bytes: &[u8], variant: Variant<C>) -> Result<Self, AzulError> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return Err(PositionError::EncodedLength {
+                expected: Self::ENCODED_LEN,
+                found: bytes.len(),
+            }
+            .into());
+        }
+        if variant.placement == WallPlacement::Free {
+            return Err(PositionError::FreePlacement.into());
+        }
+        let mut bytes = bytes.iter().copied();
+        let mut next = || bytes.next().expect("Length was checked");
+        let round = u16::from_le_bytes([next(), next()]);
+        let mut builder = Self::builder()
+            .with_variant(variant)
+            .with_round(round & !GAME_OVER)
+            .with_game_over(round & GAME_OVER != 0)
+            .with_current_player(next())
+            .with_first_player_tile(next().checked_sub(1));
+        for factory in 1..F {
+            builder = builder.with_factory(factory, read_nibbles(&mut next));
+        }
+        builder = builder
+            .with_centre(read_bytes(&mut next))
+            .with_bag(read_bytes(&mut next))
+            .with_lid(read_bytes(&mut next));
+        for player in 0..P {
+            builder = builder.with_score(player, next());
+            let mut mask = [0; 8];
+            for byte in &mut mask[..(C * C).div_ceil(8)] {
+                *byte = next();
+            }
+            let mask = u64::from_le_bytes(mask);
+            let mut wall = Wall::with_placement(variant.wall, variant.placement);
+            for row in RowIndex::rows(C) {
+                for (i, col) in ColumnIndex::columns(C).enumerate() {
+                    if mask & 1 << (usize::from(row) * C + i) != 0 {
+                        let tile = variant.wall.colours()[usize::from(row)][i];
+                        wall.place_tile_at(row, col, tile);
+                    }
+                }
+            }
+            builder = builder.with_wall(player, wall);
+            for row in RowIndex::rows(C) {
+                let line = next();
+                if line != 0 {
+                    let tile = (line >> 4) as usize;
+                    if tile >= C {
+                        return Err(AzulError::Tile(tile));
+                    }
+                    builder = builder.with_row(player, row, Tile::try_from(tile)?, line & 0xf);
+                }
+            }
+            builder = builder.with_floor(player, read_nibbles(&mut next));
+        }
+        builder.build()
+    }
+}
+
+/// Counts of each colour, two to a byte
+fn push_nibbles<const C: usize>(bytes: &mut Vec<u8>, tiles: &TileGroup<C>) {
+    bytes.extend(
+        tiles
+            .counts()
+            .chunks(2)
+            .map(|pair| pair[0] | pair.get(1).map_or(0, |count| count << 4)),
+    );
+}
+
+fn read_nibbles<const C: usize>(next: &mut impl FnMut() -> u8) -> TileGroup<C> {
+    let mut tiles = TileGroup::new_empty();
+    let mut byte = 0;
+    for (i, tile) in Tile::colours(C).enumerate() {
+        if i % 2 == 0 {
+            byte = next();
+            tiles.add_tiles(tile, byte & 0xf);
+        } else {
+            tiles.add_tiles(tile, byte >> 4);
+        }
+    }
+    tiles
+}
+
+fn read_bytes<const C: usize>(next: &mut impl FnMut() -> u8) -> TileGroup<C> {
+    let mut tiles = TileGroup::new_empty();
+    for tile in Tile::colours(C) {
+        tiles.add_tiles(tile, next());
+    }
+    tiles
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        gamestate::{Gamestate, State},
+        players::{MoveRankPlayer2, Player},
+        variant::Variant,
+    };
+
+    #[test]
+    fn encode_and_decode() {
+        assert_eq!(Gamestate::<2, 6>::ENCODED_LEN, 60);
+        let mut gs = Gamestate::new_2_player_with_seed(12, 1);
+        let mut player = MoveRankPlayer2;
+        loop {
+            let bytes = gs.encode().unwrap();
+            assert_eq!(bytes.len(), Gamestate::<2, 6>::ENCODED_LEN);
+            let decoded = Gamestate::<2, 6>::decode(&bytes, Variant::standard()).unwrap();
+            assert_eq!(decoded.to_string_position(), gs.to_string_position());
+            assert_eq!(decoded.state(), gs.state());
+            match gs.state() {
+                State::RoundActive => {
+                    let m = player.pick_move(&gs, gs.get_moves());
+                    gs.play_move(m);
+                }
+                State::RoundEnd => {
+                    gs.end_round();
+                }
+                State::GameEnd => break,
+            }
+        }
+
+        assert!(Gamestate::<2, 6>::decode(&[0; 10], Variant::standard()).is_err());
+        gs.set_variant("grey".parse().unwrap()).unwrap();
+        assert!(gs.encode().is_err());
+    }
+}
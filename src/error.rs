@@ -0,0 +1,258 @@
+//! Errors a library user can recover from
+//!
+//! Constructors, loaders and parsers return [AzulError], with the details in fields
+//! so callers can match on what went wrong
+
+use std::{fmt::Display, path::PathBuf};
+
+use crate::{
+    gamestate::{IllegalMove, Move, State},
+    playerboard::{wall::ColumnIndex, RowIndex},
+    registry::ENGINES,
+    tiles::Tile,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AzulError {
+    /// Pattern line index out of range, see [RowIndex]
+    #[error("no row with index {0}")]
+    RowIndex(usize),
+    /// Wall column index out of range, see [ColumnIndex]
+    #[error("no column with index {0}")]
+    ColumnIndex(usize),
+    /// Colour index out of range, see [Tile]
+    #[error("no colour with index {0}")]
+    Tile(usize),
+    /// Fewer tiles of a colour than were to be taken from a group
+    #[error("can't take {wanted} {tile:?} tiles, there are {available}")]
+    NotEnoughTiles {
+        tile: Tile,
+        wanted: u8,
+        available: u8,
+    },
+    /// Move can't be played, see [IllegalMove] for why
+    #[error(transparent)]
+    Illegal(#[from] IllegalMove),
+    /// Move is legal but the counts don't match, contains the legal move.
+    /// See [Gamestate::set_strict](crate::gamestate::Gamestate::set_strict)
+    #[error("move counts don't match, expected {0:?}")]
+    MoveMismatch(Move),
+    /// Round can't be ended from the contained state
+    #[error("round can't end in state {0:?}")]
+    RoundNotOver(State),
+    /// Text that can't be read as a value of `kind`
+    #[error("{text:?} is not a valid {kind}")]
+    Parse { kind: ParseKind, text: String },
+    /// Position string, encoding or setup that isn't a position of the game
+    #[error(transparent)]
+    Position(#[from] PositionError),
+    /// Wall pattern with a colour missing from a row or column
+    #[error("{} {} of the wall pattern has no {tile:?} tile", if *.row { "row" } else { "column" }, .index + 1)]
+    WallPattern { row: bool, index: usize, tile: Tile },
+    /// Move of a replay or start position that can't be played
+    #[error(transparent)]
+    Replay(#[from] ReplayError),
+    /// Start position that can't be played, with its index in the list
+    #[error("start position {index} can't be played: {source}")]
+    StartPosition {
+        index: usize,
+        source: Box<AzulError>,
+    },
+    /// Number of players there are no games for
+    #[error("games have 2, 3 or 4 players, not {0}")]
+    PlayerCount(usize),
+    /// Teams variant for a number of players that can't be split in two
+    #[error("teams need an even number of players, not {0}")]
+    OddTeams(usize),
+    /// Engine name that isn't registered, built in or a difficulty
+    #[error("unknown engine {0}, expected a difficulty or one of {engines}", engines = ENGINES.join(", "))]
+    UnknownEngine(String),
+    /// Engine that can't be created without a model file
+    #[error("the {0} engine needs a model")]
+    MissingModel(String),
+    /// Manifest written by a different experiment
+    #[error("manifest is for a {found} not a {expected}")]
+    WrongExperiment { expected: String, found: String },
+    /// Manifest config that doesn't match the experiment's settings
+    #[error("invalid {experiment} config: {reason}")]
+    Config { experiment: String, reason: String },
+    /// Population ranked or evolved out of turn, or too small to evolve
+    #[error(transparent)]
+    Population(#[from] PopulationError),
+    /// Address a server couldn't listen on
+    #[error("failed to listen on {addr}: {reason}")]
+    Listen { addr: String, reason: String },
+    /// File that couldn't be read or understood
+    #[error("failed to load {path:?}: {reason}")]
+    Load { path: PathBuf, reason: String },
+    /// File that couldn't be written
+    #[error("failed to save {path:?}: {reason}")]
+    Save { path: PathBuf, reason: String },
+}
+
+impl AzulError {
+    /// [AzulError::Parse] of `text`
+    pub fn parse(kind: ParseKind, text: impl Into<String>) -> Self {
+        AzulError::Parse {
+            kind,
+            text: text.into(),
+        }
+    }
+
+    /// [AzulError::Load] of `path`
+    pub fn load(path: impl Into<PathBuf>, reason: impl Display) -> Self {
+        AzulError::Load {
+            path: path.into(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// [AzulError::Save] of `path`
+    pub fn save(path: impl Into<PathBuf>, reason: impl Display) -> Self {
+        AzulError::Save {
+            path: path.into(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// What [AzulError::Parse] was reading, displayed with the values it accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseKind {
+    Tile,
+    Tiles,
+    Source,
+    Destination,
+    Column,
+    Move,
+    Round,
+    Player,
+    Score,
+    Count,
+    Handicap,
+    WallPattern,
+    Rule,
+    Rounds,
+    Difficulty,
+    RewardScheme,
+    Duration,
+}
+
+impl Display for ParseKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ParseKind::Tile => "tile, expected a colour or one of BYRKWO",
+            ParseKind::Tiles => "group of tiles, expected letters from BYRKWO or -",
+            ParseKind::Source => "source, expected C or F1, F2...",
+            ParseKind::Destination => "destination, expected Row1 to Row6 or Floor",
+            ParseKind::Column => "column, expected Col1 to Col6",
+            ParseKind::Move => "move, expected a source and tile -> destination",
+            ParseKind::Round => "round",
+            ParseKind::Player => "player",
+            ParseKind::Score => "score",
+            ParseKind::Count => "tile count",
+            ParseKind::Handicap => "handicap, expected start=N, floor=N or free=N",
+            ParseKind::WallPattern => "wall pattern, expected rows of tiles separated by /",
+            ParseKind::Rule => "rule, expected standard, teams, grey, wall=... or rounds=N|none",
+            ParseKind::Rounds => "number of rounds",
+            ParseKind::Difficulty => "difficulty, expected beginner, easy, medium, hard or expert",
+            ParseKind::RewardScheme => "reward scheme, expected shaped, terminal or margin",
+            ParseKind::Duration => "duration, eg 500ms, 5s or 2m",
+        })
+    }
+}
+
+/// Why a position can't be read or built, see [crate::position] and [crate::encoding]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PositionError {
+    /// Position string without a field for each board, and the optional variant
+    #[error("expected {expected} fields, or one more with a variant, got {found}")]
+    FieldCount { expected: usize, found: usize },
+    /// Factories field with the wrong number of factories
+    #[error("expected {expected} factories, got {found}")]
+    FactoryCount { expected: usize, found: usize },
+    /// Board field that isn't score:rows:wall:floor
+    #[error("expected score:rows:wall:floor for player {0}")]
+    BoardFields(usize),
+    /// Pattern lines, wall or pattern with the wrong number of rows
+    #[error("expected {expected} rows, got {found}")]
+    RowCount { expected: usize, found: usize },
+    /// Row of a wall or pattern with the wrong number of cells
+    #[error("expected {expected} cells in a row, got {found}")]
+    CellCount { expected: usize, found: usize },
+    /// Bag or lid without a count for each colour
+    #[error("expected {expected} colour counts, got {found}")]
+    ColourCount { expected: usize, found: usize },
+    /// Encoding of the wrong length
+    #[error("expected {expected} bytes, got {found}")]
+    EncodedLength { expected: usize, found: usize },
+    /// Pattern line holding tiles of more than one colour
+    #[error("row {} of player {player} has more than one colour", usize::from(.row) + 1)]
+    MixedRow { player: usize, row: RowIndex },
+    /// First player tile on more than one floor, or on a floor and in the centre
+    #[error("more than one first player tile")]
+    FirstPlayerTiles,
+    /// Player number past the number of players
+    #[error("there is no player {0}")]
+    NoSuchPlayer(u8),
+    /// Round 0
+    #[error("rounds are numbered from 1")]
+    RoundZero,
+    /// Wall with tiles, but a pattern or placement other than the variant's
+    #[error("wall of player {0} doesn't match the variant")]
+    WallMismatch(usize),
+    /// Pattern line with more tiles than it holds
+    #[error("row {} of player {player} is too full", usize::from(.row) + 1)]
+    RowTooFull { player: usize, row: RowIndex },
+    /// Pattern line with a colour its wall row already has
+    #[error("wall of player {player} already has {tile:?} in row {}", usize::from(.row) + 1)]
+    WallHasColour {
+        player: usize,
+        row: RowIndex,
+        tile: Tile,
+    },
+    /// Floor with more tiles than it holds
+    #[error("floor of player {0} is too full")]
+    FloorTooFull(usize),
+    /// Colour with more or fewer tiles than a full bag
+    #[error("there are {found} {tile:?} tiles, instead of {expected}")]
+    TileTotal { tile: Tile, found: u8, expected: u8 },
+    /// Wall tile the pattern or free placement rules don't allow in its cell
+    #[error("{tile:?} can't be in column {} of row {}", usize::from(.column) + 1, usize::from(.row) + 1)]
+    Misplaced {
+        tile: Tile,
+        row: RowIndex,
+        column: ColumnIndex,
+    },
+    /// Encoding of walls with free placement, whose colours aren't stored
+    #[error("walls with free placement can't be encoded")]
+    FreePlacement,
+}
+
+/// Move of a replay or start position that can't be played, with plies counted from 1
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ReplayError {
+    /// Move whose index isn't a legal move of the position
+    #[error("move {ply} ({index}) is not legal")]
+    IllegalIndex { ply: usize, index: usize },
+    /// Move after the round was over
+    #[error("round is over before move {0}")]
+    RoundOver(usize),
+    /// Position asked for past the last move
+    #[error("replay has only {0} moves")]
+    TooShort(usize),
+}
+
+/// Why a [Population](crate::runner::Population) can't rank or evolve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum PopulationError {
+    #[error("population is empty")]
+    Empty,
+    #[error("population was already ranked, evolve it first")]
+    AlreadyRanked,
+    #[error("population must be ranked before it evolves")]
+    NotRanked,
+    /// Fewer than 20 players, so the top 10% can't cross over
+    #[error("population of {0} is too small to evolve, it needs at least 20 players")]
+    TooSmall(usize),
+}
@@ -1,34 +1,187 @@
-use std::ops::AddAssign;
+use std::{collections::VecDeque, marker::PhantomData, ops::AddAssign};
 
-use rand::SeedableRng;
-use strum::IntoEnumIterator;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
 
 use crate::{
-    playerboard::{PlayerBoard, RowIndex},
-    tiles::{Tile, TileGroup},
+    error::{AzulError, ParseKind, PositionError},
+    playerboard::{
+        wall::{ColumnIndex, Wall, WallPattern},
+        BoardSummary, FloorLine, Handicap, PlacementResult, PlayerBoard, Row, RowIndex,
+        ScoreBreakdown,
+    },
+    tiles::{Tile, TileGroup, TileTracker},
+    variant::Variant,
+    view::Viewer,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Gamestate<const P: usize, const F: usize> {
+/// Full state of a game with `P` players and `F` factories (including the centre)
+///
+/// Generic over the rng `R` used to draw tiles from the bag,
+/// defaulting to [GameRng], and the number of colours `C`.
+/// The standard game has 5 colours, 6 gives the experimental variant
+/// with a 6x6 wall and 6 pattern lines
+///
+/// Saved with serde along with the state of the rng, so a loaded game deals the
+/// tiles the saved game would have dealt. The position without the rng is its [CoreState]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(bound(
+    serialize = "R: serde::Serialize",
+    deserialize = "R: serde::Deserialize<'de>"
+))]
+pub struct Gamestate<const P: usize, const F: usize, R = GameRng, const C: usize = 5> {
+    /// Everything on the table
+    #[serde(flatten)]
+    core: CoreState<P, F, C>,
+    /// rng for picking tiles from bag
+    rng: R,
+    /// Validate the full move in [Gamestate::try_play_move]
+    strict: bool,
+    /// Moves played so far, when recording with [Gamestate::record_moves]
+    #[serde(default)]
+    move_history: Option<Vec<PlayedMove>>,
+    /// Totals for each player, when recording with [Gamestate::record_stats]
+    #[serde(default)]
+    stats: Option<GameStats<P>>,
+    /// Earlier positions for [Gamestate::undo], not saved or compared
+    #[serde(skip)]
+    history: History<Self>,
+}
+
+/// Position of a [Gamestate] without the rng deciding future deals,
+/// so equal positions compare and hash the same, eg as keys of a search's table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct CoreState<const P: usize, const F: usize, const C: usize = 5> {
     /// List of boards for each player
-    boards: [PlayerBoard; P],
+    #[serde(with = "crate::serde_array")]
+    boards: [PlayerBoard<C>; P],
     /// Contains tiles that are not in play
-    tilebag: TileGroup,
+    tilebag: TileGroup<C>,
+    /// Tiles discarded from the boards, put back in the bag when it runs out
+    lid: TileGroup<C>,
     /// Factories from which tiles are chosen
-    factories: [Option<TileGroup>; F],
+    #[serde(with = "crate::serde_array")]
+    factories: [Option<TileGroup<C>>; F],
     /// First player token
     first_player_tile: bool,
-    /// rng for picking tiles from bag
-    rng: rand::prelude::SmallRng,
     /// Current player
     current_player: u8,
     /// Round number
     round: u16,
     /// State tracking
     state: State,
+    /// Rules in play, see [Gamestate::set_variant]
+    variant: Variant<C>,
+}
+
+impl<const P: usize, const F: usize, const C: usize> CoreState<P, F, C> {
+    /// Deal up to 4 tiles from the bag to each factory
+    ///
+    /// When the bag runs out, the tiles in the lid are put back in it and dealing carries on.
+    /// If both are empty, factories are filled in order as far as possible
+    /// and any left without tiles are out of play for the round.
+    /// Returns false, without starting a round, if there are no tiles to deal
+    fn deal<Q: Rng + ?Sized>(&mut self, rng: &mut Q) -> bool {
+        // Every tile is on a board, so the round doesn't start
+        if self.tilebag.is_empty() && self.lid.is_empty() {
+            return false;
+        }
+        // Deal tiles to factories
+        for factory in self.factories[1..].iter_mut() {
+            let mut f = TileGroup::new_empty();
+            for _ in 0..4 {
+                if self.tilebag.is_empty() {
+                    self.tilebag = std::mem::take(&mut self.lid);
+                }
+                match self.tilebag.random_tile(rng) {
+                    Some(tile) => f.add_tile(tile),
+                    None => break,
+                }
+            }
+            *factory = (!f.is_empty()).then_some(f);
+        }
+        self.state = State::RoundActive;
+        self.round += 1;
+        true
+    }
+}
+
+/// Positions before recent moves and round ends, each a copy of the game without its history
+#[derive(Clone)]
+struct History<S> {
+    /// Most positions kept, none are kept when 0
+    limit: usize,
+    undo: VecDeque<S>,
+    redo: Vec<S>,
+}
+
+impl<S> Default for History<S> {
+    fn default() -> Self {
+        Self {
+            limit: 0,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+impl<S> std::fmt::Debug for History<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "History({} undo, {} redo, limit {})",
+            self.undo.len(),
+            self.redo.len(),
+            self.limit
+        )
+    }
+}
+
+/// Games with different histories are the same position
+impl<S> PartialEq for History<S> {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl<S> Eq for History<S> {}
+
+/// Default rng of a [Gamestate], which can be saved with serde. Deals the same tiles
+/// for a seed as [SmallRng](rand::rngs::SmallRng) on 64 bit platforms, which games used before
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GameRng(Xoshiro256PlusPlus);
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+/// Only `from_seed` is passed on, as [SmallRng](rand::rngs::SmallRng) does,
+/// so `seed_from_u64` expands seeds the same way
+impl SeedableRng for GameRng {
+    type Seed = <Xoshiro256PlusPlus as SeedableRng>::Seed;
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self(Xoshiro256PlusPlus::from_seed(seed))
+    }
 }
 
-impl<const P: usize, const F: usize> Default for Gamestate<P, F> {
+impl<const P: usize, const F: usize, R: Rng + SeedableRng + Clone, const C: usize> Default
+    for Gamestate<P, F, R, C>
+{
     fn default() -> Self {
         Self::new(rand::random(), 0)
     }
@@ -44,7 +197,7 @@ impl Gamestate<2, 6> {
     }
 
     pub fn differential_predicted_score(&self) -> f32 {
-        self.boards[0].predicted_score as f32 - self.boards[1].predicted_score as f32
+        self.core.boards[0].predicted_score as f32 - self.core.boards[1].predicted_score as f32
     }
 }
 
@@ -58,114 +211,532 @@ impl Gamestate<4, 10> {
     pub fn new_4_player() -> Self {
         Self::default()
     }
+
+    /// Four players in two teams, seats 0 and 2 against seats 1 and 3
+    pub fn new_4_player_teams_with_seed(seed: u64, first_player: u8) -> Self {
+        let mut gs = Self::new(seed, first_player);
+        gs.set_teams(true).expect("Four players make two teams");
+        gs
+    }
 }
 
-impl<const P: usize, const F: usize> Gamestate<P, F> {
+impl<const P: usize, const F: usize, R: Rng + SeedableRng + Clone, const C: usize>
+    Gamestate<P, F, R, C>
+{
     /// Initialiser
     pub fn new(seed: u64, first_player: u8) -> Self {
-        let mut gs = Self {
-            boards: [PlayerBoard::default(); P],
-            tilebag: TileGroup::new_bag(),
-            factories: [None; F],
-            first_player_tile: true,
-            rng: rand::prelude::SmallRng::seed_from_u64(seed),
-            current_player: first_player,
-            round: 0,
-            state: State::GameEnd,
-        };
+        Self::new_with_rng(R::seed_from_u64(seed), first_player)
+    }
+
+    /// Replace the rng for future tile draws, so a copy of the game
+    /// can play out a different future than the original
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = R::seed_from_u64(seed);
+    }
+
+    /// Copy of the position with future deals drawn by a new rng seeded with `seed`,
+    /// one possible future of the bag for a search to play out.
+    /// Move history, stats and undo history are not copied
+    pub fn sample_future(&self, seed: u64) -> Self {
+        let mut gs = Self::from_core(self.core, R::seed_from_u64(seed));
+        gs.strict = self.strict;
+        gs
+    }
+
+    /// `samples` copies from [Gamestate::sample_future], each with its own seed
+    /// drawn from `seed`, so the same seed always gives the same futures
+    pub fn sample_futures(&self, seed: u64, samples: usize) -> impl Iterator<Item = Self> + '_ {
+        let mut seeds = R::seed_from_u64(seed);
+        (0..samples).map(move |_| self.sample_future(seeds.gen()))
+    }
+
+    /// Set up any position, see [GamestateBuilder]
+    pub fn builder() -> GamestateBuilder<P, F, R, C> {
+        GamestateBuilder::new()
+    }
+}
+
+impl<const P: usize, const F: usize, R: Rng + Clone, const C: usize> Gamestate<P, F, R, C> {
+    /// Number of distinct moves, the range of [Move::index_for] with `C` colours
+    pub const ACTIONS: usize = F * C * (C + 1);
+
+    /// Initialise with an existing rng, which is used for all tile draws
+    pub fn new_with_rng(rng: R, first_player: u8) -> Self {
+        let mut gs = Self::from_core(
+            CoreState {
+                boards: [PlayerBoard::default(); P],
+                tilebag: TileGroup::new_bag(),
+                lid: TileGroup::new_empty(),
+                factories: [None; F],
+                first_player_tile: true,
+                current_player: first_player,
+                round: 0,
+                state: State::GameEnd,
+                variant: Variant::standard(),
+            },
+            rng,
+        );
         gs.deal();
         gs
     }
 
+    /// Continue from a position with `rng` drawing the tiles of future deals.
+    /// Moves are not recorded and there is nothing to undo
+    pub fn from_core(core: CoreState<P, F, C>, rng: R) -> Self {
+        Self {
+            core,
+            rng,
+            strict: false,
+            move_history: None,
+            stats: None,
+            history: History::default(),
+        }
+    }
+
+    /// The position without the rng, equal for games which differ only in future deals
+    pub fn core(&self) -> &CoreState<P, F, C> {
+        &self.core
+    }
+
+    /// Enable or disable strict move validation
+    ///
+    /// In strict mode [Gamestate::try_play_move] rejects any move that does not
+    /// exactly match one from [Gamestate::get_moves], including the counts.
+    /// In lenient mode only the source, tile and destination are checked and
+    /// the counts are recalculated from the current state
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Check if strict move validation is enabled
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Give each player a handicap, resetting their score to its starting score
+    ///
+    /// Intended to be called when the game is created, before any moves
+    pub fn set_handicaps(&mut self, handicaps: [Handicap; P]) {
+        for (board, handicap) in self.core.boards.iter_mut().zip(handicaps) {
+            board.handicap = handicap;
+            board.score = handicap.start_score;
+            board.predict_score();
+        }
+    }
+
+    /// Handicap of each player
+    pub fn handicaps(&self) -> [Handicap; P] {
+        self.core.boards.map(|b| b.handicap)
+    }
+
+    /// Play by the rules of a variant
+    ///
+    /// Intended to be called when the game is created, as the walls are cleared.
+    /// Fails for teams with an odd number of players, leaving the rules unchanged
+    pub fn set_variant(&mut self, variant: Variant<C>) -> Result<(), AzulError> {
+        self.set_teams(variant.teams)?;
+        self.core.variant.placement = variant.placement;
+        self.set_wall_pattern(variant.wall);
+        self.core.variant.max_rounds = variant.max_rounds;
+        Ok(())
+    }
+
+    /// Rules in play
+    pub fn variant(&self) -> &Variant<C> {
+        &self.core.variant
+    }
+
+    /// Use a custom colour arrangement for every player's wall
+    ///
+    /// Intended to be called when the game is created, as the walls are cleared
+    pub fn set_wall_pattern(&mut self, pattern: WallPattern<C>) {
+        self.core.variant.wall = pattern;
+        for board in self.core.boards.iter_mut() {
+            board.wall = Wall::with_placement(pattern, self.core.variant.placement);
+            board.predict_score();
+        }
+    }
+
+    /// Colour arrangement of the walls
+    pub fn wall_pattern(&self) -> &WallPattern<C> {
+        &self.core.variant.wall
+    }
+
+    /// Play in two teams that share their score, with even seats against odd seats
+    ///
+    /// Fails for an odd number of players
+    pub fn set_teams(&mut self, teams: bool) -> Result<(), AzulError> {
+        Variant {
+            teams,
+            ..self.core.variant
+        }
+        .check_players(P)?;
+        self.core.variant.teams = teams;
+        Ok(())
+    }
+
+    /// Check if the game is played in teams
+    pub fn teams(&self) -> bool {
+        self.core.variant.teams
+    }
+
+    /// Team of a player, or the player itself when not playing in teams
+    pub fn team(&self, player: u8) -> u8 {
+        if self.core.variant.teams {
+            player % 2
+        } else {
+            player
+        }
+    }
+
+    /// Combined score of the even seats and of the odd seats
+    pub fn team_scores(&self) -> [u16; 2] {
+        let mut scores = [0; 2];
+        for (i, b) in self.core.boards.iter().enumerate() {
+            scores[i % 2] += b.score as u16;
+        }
+        scores
+    }
+
+    /// Complete rows on each wall
+    pub fn completed_rows(&self) -> [u8; P] {
+        self.core.boards.map(|b| b.wall.completed_rows())
+    }
+
+    /// Combined complete rows of the even seats and of the odd seats
+    pub fn team_completed_rows(&self) -> [u16; 2] {
+        let mut rows = [0; 2];
+        for (i, b) in self.core.boards.iter().enumerate() {
+            rows[i % 2] += b.wall.completed_rows() as u16;
+        }
+        rows
+    }
+
+    /// Place of each seat by the official rules, 0 for the winner.
+    /// Players are ranked by score, ties are broken by complete rows, and players
+    /// still tied share the place, with the next place skipped.
+    /// In team games both seats of a team share its place
+    pub fn final_standings(&self) -> [u8; P] {
+        let sides: [(u16, u16); P] = if self.teams() {
+            let (scores, rows) = (self.team_scores(), self.team_completed_rows());
+            std::array::from_fn(|i| (scores[i % 2], rows[i % 2]))
+        } else {
+            std::array::from_fn(|i| {
+                let board = &self.core.boards[i];
+                (board.score as u16, board.wall.completed_rows() as u16)
+            })
+        };
+        // Count the players or teams ahead
+        sides.map(|side| {
+            let mut ahead = (0..P as u8)
+                .filter(|&j| sides[j as usize] > side)
+                .map(|j| self.team(j))
+                .collect::<Vec<_>>();
+            ahead.sort();
+            ahead.dedup();
+            ahead.len() as u8
+        })
+    }
+
+    /// Seat that wins by the official rules, see [Gamestate::final_standings].
+    /// None if the lead is shared, in team games the lowest seat of the winning team
+    pub fn winner(&self) -> Option<u8> {
+        let standings = self.final_standings();
+        let mut winners = (0..P as u8).filter(|&i| standings[i as usize] == 0);
+        let first = winners.next()?;
+        match winners.next() {
+            Some(other) if self.team(other) != self.team(first) => None,
+            _ => Some(first),
+        }
+    }
+
+    /// Seats from first place to last, see [Gamestate::final_standings].
+    /// Tied seats are in seat order
+    pub fn rankings(&self) -> [u8; P] {
+        let standings = self.final_standings();
+        let mut seats = std::array::from_fn(|i| i as u8);
+        seats.sort_by_key(|&seat| standings[seat as usize]);
+        seats
+    }
+
+    /// Whether the game is over, so [Gamestate::winner] is final
+    pub fn is_terminal(&self) -> bool {
+        self.core.state == State::GameEnd
+    }
+
+    /// Difference in combined predicted score, positive when the even seats are ahead
+    pub fn team_differential_predicted_score(&self) -> f32 {
+        self.core
+            .boards
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                let score = b.predicted_score as f32;
+                if i % 2 == 0 {
+                    score
+                } else {
+                    -score
+                }
+            })
+            .sum()
+    }
+
+    /// Predicted score of every player, as if the round ended now
+    pub fn predicted_scores(&self) -> [u8; P] {
+        self.core.boards.map(|b| b.predicted_score)
+    }
+
+    /// Total of `values` for seat 0's side minus the best other side,
+    /// where sides are teams in team games and single players otherwise
+    pub fn side_margin(&self, values: [f32; P]) -> f32 {
+        self.side_margin_for(0, values)
+    }
+
+    /// [Gamestate::side_margin] from the side of seat `perspective`
+    pub fn side_margin_for(&self, perspective: u8, values: [f32; P]) -> f32 {
+        let perspective = perspective as usize;
+        if self.teams() {
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| if i % 2 == perspective % 2 { v } else { -v })
+                .sum()
+        } else {
+            let best_other = (0..P)
+                .filter(|&i| i != perspective)
+                .map(|i| values[i])
+                .reduce(f32::max)
+                .unwrap_or(0.0);
+            values[perspective] - best_other
+        }
+    }
+
+    /// Predicted score margin of seat 0's side, see [Gamestate::side_margin]
+    pub fn predicted_margin(&self) -> f32 {
+        self.side_margin(self.predicted_scores().map(f32::from))
+    }
+
+    /// Predicted score margin of the side of seat `perspective`, see [Gamestate::side_margin_for]
+    pub fn differential_predicted_score_for(&self, perspective: u8) -> f32 {
+        self.side_margin_for(perspective, self.predicted_scores().map(f32::from))
+    }
+
+    /// Copy with every seat moved `seats` places towards seat 0,
+    /// so a search can play as seat 0 whoever is to move.
+    /// Moves in the copy are the same as in the original
+    pub fn rotated(&self, seats: u8) -> Self
+    where
+        R: Clone,
+    {
+        let mut gs = self.clone();
+        let k = seats as usize % P;
+        gs.core.boards.rotate_left(k);
+        gs.core.current_player = ((self.core.current_player as usize + P - k) % P) as u8;
+        gs
+    }
+
+    /// Tiles left to pick this round, in the factories and centre
+    pub fn tiles_left(&self) -> u8 {
+        self.core
+            .factories
+            .iter()
+            .flatten()
+            .map(|f| f.total())
+            .sum()
+    }
+
+    /// Least number of rounds the game can still last, including this one.
+    /// A wall row gains at most one tile a round and the game ends after a row is full
+    pub fn rounds_left(&self) -> u8 {
+        let longest = self
+            .core
+            .boards
+            .iter()
+            .map(|b| b.wall.longest_row())
+            .max()
+            .unwrap_or(0);
+        (C as u8).saturating_sub(longest).max(1)
+    }
+
+    /// Part of the game the position is in
+    pub fn phase(&self) -> Phase {
+        if self.rounds_left() == 1 {
+            Phase::Late
+        } else if self.core.round < 2 {
+            Phase::Early
+        } else {
+            Phase::Middle
+        }
+    }
+
     /// Get current game state
     pub fn state(&self) -> State {
-        self.state
+        self.core.state
     }
 
     /// Get current round
     pub fn round(&self) -> u16 {
-        self.round
+        self.core.round
     }
 
     /// Get game scores
     pub fn scores(&self) -> [u8; P] {
         let mut scores = [0; P];
-        for (i, b) in self.boards.iter().enumerate() {
+        for (i, b) in self.core.boards.iter().enumerate() {
             scores[i] = b.score;
         }
         scores
     }
 
+    /// Where each player's points came from, bonuses included once the game is over
+    pub fn score_breakdowns(&self) -> [ScoreBreakdown; P] {
+        self.core.boards.map(|b| b.breakdown)
+    }
+
     /// Get tile bag
-    pub fn tilebag(&self) -> &TileGroup {
-        &self.tilebag
+    pub fn tilebag(&self) -> &TileGroup<C> {
+        &self.core.tilebag
+    }
+
+    /// Tiles discarded since the bag was last refilled
+    pub fn lid(&self) -> &TileGroup<C> {
+        &self.core.lid
+    }
+
+    /// Chances of seeing each colour in the next deal, from the bag and lid as they are now.
+    /// Tiles discarded at the end of this round are not counted until they reach the lid
+    pub fn tile_tracker(&self) -> TileTracker<C> {
+        TileTracker::new(self.core.tilebag, self.core.lid, 4 * (F as u8 - 1))
     }
 
     /// Get the current player index
     pub fn current_player(&self) -> u8 {
-        self.current_player
+        self.core.current_player
     }
 
     /// Get the first_player tile state
     pub fn first_player_tile(&self) -> bool {
-        self.first_player_tile
+        self.core.first_player_tile
     }
 
     /// Get access to the player boards
-    pub fn boards(&self) -> &[PlayerBoard; P] {
-        &self.boards
+    pub fn boards(&self) -> &[PlayerBoard<C>; P] {
+        &self.core.boards
     }
 
     /// Get access to factories
-    pub fn factories(&self) -> &[Option<TileGroup>; F] {
-        &self.factories
+    pub fn factories(&self) -> &[Option<TileGroup<C>>; F] {
+        &self.core.factories
     }
 
     /// Get access to centre
-    pub fn centre(&self) -> TileGroup {
-        self.factories[0].unwrap_or_default()
+    pub fn centre(&self) -> TileGroup<C> {
+        self.core.factories[0].unwrap_or_default()
     }
 
-    fn deal(&mut self) {
-        // Deal tiles to factories
-        for factory in self.factories[1..].iter_mut() {
-            let mut f = TileGroup::new_empty();
-            for _ in 0..4 {
-                if let Some(tile) = self.tilebag.random_tile(&mut self.rng) {
-                    f.add_tile(tile);
-                }
-            }
-            *factory = Some(f);
+    /// Deal the next round from the game's own rng, see [CoreState::deal]
+    fn deal(&mut self) -> bool {
+        self.core.deal(&mut self.rng)
+    }
+
+    /// Every set of `count` tiles that could be drawn next, with its probability
+    ///
+    /// Draws from the bag and then from the lid once it is empty, as dealing does,
+    /// so `enumerate_deals(4)` is the distribution of the next factory's tiles.
+    /// Lists which tiles come out, not which factory they go to, as a full deal has
+    /// far too many outcomes. Use [Gamestate::end_round_with] to sample whole deals
+    pub fn enumerate_deals(&self, count: u8) -> Vec<(TileGroup<C>, f64)> {
+        let bag = self.core.tilebag;
+        if count <= bag.total() {
+            return bag.draws(count);
         }
-        self.state = State::RoundActive;
-        self.round += 1;
+        // The whole bag comes out and the rest is drawn from the lid
+        self.core
+            .lid
+            .draws(count - bag.total())
+            .into_iter()
+            .map(|(mut tiles, p)| {
+                tiles += bag;
+                (tiles, p)
+            })
+            .collect()
+    }
+
+    /// Every legal move with what playing it would do, see [MoveDetailed]
+    pub fn get_moves_detailed(&self) -> Vec<MoveDetailed<C>> {
+        self.get_moves()
+            .into_iter()
+            .map(|m| self.detail_move(m))
+            .collect()
+    }
+
+    /// What playing a move would do, see [MoveDetailed]
+    pub fn detail_move(&self, move_: Move) -> MoveDetailed<C> {
+        let placed = self.preview_move(&move_);
+        let row = match move_.destination {
+            Destination::Floor => None,
+            Destination::Row(_) if placed.to_row < move_.count => Some(RowState::Overfull),
+            Destination::Row(_) if placed.fills_row => Some(RowState::Full),
+            Destination::Row(_) => Some(RowState::Partial),
+        };
+        let denies = self
+            .core
+            .boards
+            .iter()
+            .enumerate()
+            .filter(|&(i, board)| {
+                self.team(i as u8) != self.team(self.core.current_player)
+                    && board.row_iter().any(|(row, line)| {
+                        line.tile() == Some(move_.tile) && line.count() < row.capacity()
+                    })
+            })
+            .count() as u8;
+        MoveDetailed {
+            move_,
+            fp: self.takes_fp(&move_),
+            row,
+            placed,
+            denies,
+        }
+    }
+
+    /// Legal move with the given [ActionIndex] index, or None if there isn't one,
+    /// including for indices from [Gamestate::ACTIONS] up.
+    /// With free placement the first of its wall columns is chosen
+    pub fn move_from_index(&self, index: usize) -> Option<Move> {
+        let action = ActionIndex::from_index(index, F, C)?;
+        self.get_moves().into_iter().find(|m| m.action() == action)
     }
 
     /// get a list of possible moves to play
     pub fn get_moves(&self) -> Vec<Move> {
         let mut moves = Vec::with_capacity(64);
         for (source, factory) in self
+            .core
             .factories
             .iter()
             .enumerate()
             .filter_map(|(i, f)| f.as_ref().map(|f| (Source(i as u8), f)))
         {
             // for each tile that factory contains
-            for (&count, tile) in factory.into_iter().filter(|(&c, _)| c > 0) {
+            for (tile, count) in factory.iter().filter(|&(_, c)| c > 0) {
                 // for each row in the current player's board
                 // Check if can play how many will be played
-                for row in RowIndex::iter() {
-                    if let Some((play_count, row_count)) =
-                        self.boards[self.current_player as usize].can_play_tile(row, tile, count)
-                    {
-                        moves.push(Move::new(
-                            source,
-                            tile,
-                            count,
-                            play_count,
-                            row_count,
-                            row.into(),
-                        ));
+                let board = &self.core.boards[self.core.current_player as usize];
+                for row in RowIndex::rows(C) {
+                    if let Some((play_count, row_count)) = board.can_play_tile(row, tile, count) {
+                        let m = Move::new(source, tile, count, play_count, row_count, row.into());
+                        if board.wall.placement().is_pattern() || row_count < row.capacity() {
+                            moves.push(m);
+                        } else {
+                            // With free placement, filling a row chooses its column
+                            moves.extend(
+                                board
+                                    .wall_columns(row, tile)
+                                    .into_iter()
+                                    .map(|col| m.with_column(col)),
+                            );
+                        }
                     }
                 }
                 // add the floor as a destination
@@ -175,113 +746,450 @@ impl<const P: usize, const F: usize> Gamestate<P, F> {
         moves
     }
 
+    /// Moves that lead to different positions, for searching players
+    ///
+    /// Taking the same tiles from factories holding the same tiles leaves the same
+    /// position apart from which factory is empty, so only the first such factory is used
+    pub fn get_distinct_moves(&self) -> Vec<Move> {
+        let mut moves = self.get_moves();
+        let duplicate = |i: usize| {
+            i > 0
+                && self.core.factories[i].is_some()
+                && self.core.factories[1..i].contains(&self.core.factories[i])
+        };
+        moves.retain(|m| !duplicate(m.source.0 as usize));
+        moves
+    }
+
     pub fn play_move(&mut self, move_: Move) -> State {
+        self.record();
+        if let Some(history) = &mut self.move_history {
+            history.push(PlayedMove {
+                player: self.core.current_player,
+                round: self.core.round,
+                m: move_,
+            });
+        }
+        let player = self.core.current_player as usize;
+        // Tiles reaching the floor stay there or fall off it to the lid
+        let dropped =
+            |gs: &Self| gs.core.boards[player].floor.tiles().total() + gs.core.lid.total();
+        let dropped_before = dropped(self);
+        let drafted = self.core.factories[move_.source.0 as usize]
+            .map_or(0, |factory| factory.get_count(move_.tile));
+        let fp = self.takes_fp(&move_);
+        self.apply(move_);
+        let dropped_after = dropped(self);
+        if let Some(stats) = &mut self.stats {
+            let player = &mut stats.players[player];
+            player.tiles_drafted += drafted as u16;
+            player.tiles_dropped += (dropped_after - dropped_before) as u16;
+            player.first_player_tiles += fp as u16;
+        }
+        self.core.state
+    }
+
+    /// Play a move in place, returning what [Gamestate::unapply] needs to take it back.
+    ///
+    /// Unlike [Gamestate::play_move] nothing is added to the undo or move history,
+    /// so a search can explore a tree without cloning the game for every node.
+    /// The move isn't checked and must come from [Gamestate::get_moves], moves from
+    /// outside the engine go through [Gamestate::try_play_move]
+    pub fn apply(&mut self, move_: Move) -> Undo<C> {
+        let player = self.core.current_player;
+        let undo = Undo {
+            source: move_.source,
+            factory: self.core.factories[move_.source.0 as usize],
+            centre: self.core.factories[0],
+            board: self.core.boards[player as usize],
+            lid: self.core.lid,
+            first_player_tile: self.core.first_player_tile,
+            player,
+            state: self.core.state,
+        };
+
         // Get tiles from factory
-        let mut factory = self.factories[move_.source.0 as usize].take().unwrap();
+        let mut factory = self.core.factories[move_.source.0 as usize]
+            .take()
+            .expect("Legal moves take from a source with tiles");
         let tile = move_.tile;
         let count = factory.take_tile(tile);
-        let fp = self.first_player_tile && move_.source.is_centre();
+        let fp = self.core.first_player_tile && move_.source.is_centre();
 
         // Place on board
-        self.boards[self.current_player as usize].place_tiles(move_.destination, tile, count, fp);
+        let placed = self.core.boards[player as usize].place_move(&Move { count, ..move_ }, fp);
+        self.core.lid.add_assign(placed.to_lid);
 
         // Remove first player tile if used
         if fp {
-            self.first_player_tile = false;
+            self.core.first_player_tile = false;
         }
 
         // Move remaining tiles to centre
-        if let Some(centre) = &mut self.factories[0] {
+        if let Some(centre) = &mut self.core.factories[0] {
             centre.add_assign(factory);
         } else {
-            self.factories[0] = Some(factory);
+            self.core.factories[0] = Some(factory);
         }
 
         // Check for end of round
-        if self
-            .factories
-            .iter()
-            .all(|f| f.map_or(true, |f| f.total() == 0))
-        {
-            self.state = State::RoundEnd;
+        if self.factories_empty() {
+            self.core.state = State::RoundEnd;
         } else {
             // next players turn
-            self.current_player = (self.current_player + 1) % P as u8;
+            self.core.current_player = (player + 1) % P as u8;
+        }
+        undo
+    }
+
+    /// Take back the last move played by [Gamestate::apply]
+    pub fn unapply(&mut self, undo: Undo<C>) {
+        self.core.factories[undo.source.0 as usize] = undo.factory;
+        self.core.factories[0] = undo.centre;
+        self.core.boards[undo.player as usize] = undo.board;
+        self.core.lid = undo.lid;
+        self.core.first_player_tile = undo.first_player_tile;
+        self.core.current_player = undo.player;
+        self.core.state = undo.state;
+    }
+
+    /// Take all tiles of a colour from a source without placing them on a board
+    ///
+    /// The tiles go in the lid and the rest of a factory moves to the centre as usual.
+    /// The first player tile stays in the centre and the current player is unchanged.
+    /// Used for the scripted opponent in [crate::solitaire]
+    pub fn discard(&mut self, source: Source, tile: Tile) -> Result<State, AzulError> {
+        let count = self.explain_move(source, tile, Destination::Floor)?.count;
+        let mut factory = self.core.factories[usize::from(source)].expect("Source has tiles");
+        factory.take_tile(tile);
+        self.record();
+        self.core.factories[usize::from(source)] = None;
+        self.core.lid.add_tiles(tile, count);
+        if let Some(centre) = &mut self.core.factories[0] {
+            centre.add_assign(factory);
+        } else {
+            self.core.factories[0] = Some(factory);
+        }
+        if self.factories_empty() {
+            self.core.state = State::RoundEnd;
+        }
+        Ok(self.core.state)
+    }
+
+    /// Check if every factory and the centre have been emptied
+    fn factories_empty(&self) -> bool {
+        self.core
+            .factories
+            .iter()
+            .all(|f| f.is_none_or(|f| f.is_empty()))
+    }
+
+    /// Validate and play a move, returning an error instead of panicking
+    /// on moves that can't be played
+    ///
+    /// Intended for moves coming from outside the engine, such as client input.
+    /// See [Gamestate::set_strict] for how much of the move is validated
+    pub fn try_play_move(&mut self, move_: Move) -> Result<State, AzulError> {
+        let legal = self.validate_move(&move_)?;
+        if self.strict && legal != move_ {
+            return Err(AzulError::MoveMismatch(legal));
+        }
+        Ok(self.play_move(legal))
+    }
+
+    /// Check that a move can be played, returning the legal move
+    /// with the same source, tile and destination
+    fn validate_move(&self, move_: &Move) -> Result<Move, AzulError> {
+        let legal = self.explain_move(move_.source, move_.tile, move_.destination)?;
+        let (Destination::Row(row), Some(_)) = (move_.destination, legal.column) else {
+            return Ok(legal);
+        };
+        // Free placement needs a column for the full row, explain_move picks the first
+        let col = move_.column.ok_or(IllegalMove::MissingColumn(row))?;
+        let board = &self.core.boards[self.core.current_player as usize];
+        if !board.wall_columns(row, move_.tile).contains(&col) {
+            return Err(IllegalMove::ColumnNotFree(row, col, move_.tile).into());
+        }
+        Ok(legal.with_column(col))
+    }
+
+    /// The move taking `tile` from `source` to `destination` for the current player,
+    /// or why it can't be played, eg to tell a player why a row won't take their tiles
+    pub fn explain_move(
+        &self,
+        source: Source,
+        tile: Tile,
+        destination: Destination,
+    ) -> Result<Move, IllegalMove> {
+        if self.core.state != State::RoundActive {
+            return Err(IllegalMove::WrongState(self.core.state));
+        }
+        let factory = self
+            .core
+            .factories
+            .get(usize::from(source))
+            .ok_or(IllegalMove::NoSuchSource(source))?
+            .filter(|f| f.total() > 0)
+            .ok_or(IllegalMove::EmptySource(source))?;
+        let count = factory.get_count(tile);
+        if count == 0 {
+            return Err(IllegalMove::ColourNotInSource(tile, source));
+        }
+        let row = match destination {
+            Destination::Row(row) => row,
+            Destination::Floor => return Ok(Move::new_to_floor(source, tile, count)),
+        };
+        let board = &self.core.boards[self.core.current_player as usize];
+        board.check_row(row, tile)?;
+        let (play_count, row_count) = board
+            .can_play_tile(row, tile, count)
+            .ok_or(IllegalMove::NoFreeColumn(row, tile))?;
+        let m = Move::new(source, tile, count, play_count, row_count, destination);
+        if board.wall.placement().is_pattern() || row_count < row.capacity() {
+            return Ok(m);
+        }
+        // Free placement picks the first column, others are in get_moves
+        let col = board.wall_columns(row, tile)[0];
+        Ok(m.with_column(col))
+    }
+
+    /// Read a move in the notation of [Move]'s Display, eg `F3 Red -> Row2`,
+    /// as the legal move for the current player or why it can't be played
+    pub fn parse_move(&self, text: &str) -> Result<Move, AzulError> {
+        let parsed: Move = text.parse()?;
+        match (parsed.column, parsed.destination) {
+            (Some(col), Destination::Row(row)) => match self.validate_move(&parsed)? {
+                legal if legal.column == Some(col) => Ok(legal),
+                _ => Err(IllegalMove::ColumnNotFree(row, col, parsed.tile).into()),
+            },
+            _ => Ok(self.explain_move(parsed.source, parsed.tile, parsed.destination)?),
+        }
+    }
+
+    /// End the round, returning an error if the round is not over
+    pub fn try_end_round(&mut self) -> Result<State, AzulError> {
+        match self.core.state {
+            State::RoundEnd => Ok(self.end_round().state),
+            state => Err(AzulError::RoundNotOver(state)),
         }
-        self.state
     }
 
     /// Get the predicted score if this move were to be played
     /// Helps players evaluate each move
     /// Returns the score and the change in predicted score
     pub fn predict_score(&self, move_: Move) -> (u8, i8) {
-        // Clone the board
-        let mut board = self.boards[self.current_player as usize].clone();
-        // record previous predicted score
-        let prev_score = board.predicted_score;
-
-        // Place on board
-        board.place_tiles(
-            move_.destination,
-            move_.tile,
-            move_.count,
-            move_.source.is_centre() && self.first_player_tile,
-        );
+        self.predict_score_for(self.core.current_player, move_)
+    }
 
+    /// [Gamestate::predict_score] as if `player` played the move on their board,
+    /// eg to see what a move would be worth to an opponent
+    pub fn predict_score_for(&self, player: u8, move_: Move) -> (u8, i8) {
+        let mut board = self.core.boards[player as usize];
+        let placed = board.place_move(&move_, self.takes_fp(&move_));
         (
             board.predicted_score,
-            board.predicted_score as i8 - prev_score as i8,
+            placed.score_delta.clamp(i8::MIN.into(), i8::MAX.into()) as i8,
         )
     }
 
+    /// Where the tiles of this move would go on the current player's board,
+    /// worked out by the board itself rather than from the move's row counts
+    pub fn preview_move(&self, move_: &Move) -> PlacementResult<C> {
+        let mut board = self.core.boards[self.core.current_player as usize];
+        board.place_move(move_, self.takes_fp(move_))
+    }
+
     /// Check if this move will take the first player tile
     pub fn takes_fp(&self, move_: &Move) -> bool {
-        move_.source.is_centre() && self.first_player_tile
+        move_.source.is_centre() && self.core.first_player_tile
     }
 
     /// End the round, add up scores and check for game end conditions
-    pub fn end_round(&mut self) -> State {
+    pub fn end_round(&mut self) -> RoundSummary<P, C> {
+        self.finish_round(|core, rng| core.deal(rng))
+    }
+
+    /// End the round like [Gamestate::end_round], dealing the next round with `rng`
+    ///
+    /// The game's own rng is left alone, so a search can sample the deals that
+    /// could follow a round without changing the ones the game will really see
+    pub fn end_round_with<Q: Rng + ?Sized>(&mut self, rng: &mut Q) -> RoundSummary<P, C> {
+        self.finish_round(|core, _| core.deal(rng))
+    }
+
+    /// Play the game to the end with `policy` picking every move, returning the final scores.
+    /// Rounds are dealt with `rng` as in [Gamestate::end_round_with]
+    pub fn rollout<Q: Rng + ?Sized>(
+        &mut self,
+        rng: &mut Q,
+        mut policy: impl FnMut(&Self, &[Move]) -> Move,
+    ) -> [u8; P] {
+        loop {
+            match self.core.state {
+                State::RoundActive => {
+                    let moves = self.get_moves();
+                    let m = policy(self, &moves);
+                    self.play_move(m);
+                }
+                State::RoundEnd => {
+                    self.end_round_with(rng);
+                }
+                State::GameEnd => return self.scores(),
+            }
+        }
+    }
+
+    /// Score the round and set up the next one, using `deal` to fill the factories
+    fn finish_round(
+        &mut self,
+        deal: impl FnOnce(&mut CoreState<P, F, C>, &mut R) -> bool,
+    ) -> RoundSummary<P, C> {
+        self.record();
+        let round = self.core.round;
         // Get first player tile from boards
-        for (i, b) in self.boards.iter().enumerate() {
-            if b.first_player_tile {
-                self.current_player = i as u8;
+        for (i, b) in self.core.boards.iter().enumerate() {
+            if b.floor.has_first_player_tile() {
+                self.core.current_player = i as u8;
             }
         }
-        self.first_player_tile = true;
+        self.core.first_player_tile = true;
 
-        // Move tiles on game board, calc scores and return to bag
-        if self
-            .boards
-            .iter_mut()
-            .map(|b| b.end_round())
-            .map(|(t, g)| {
-                self.tilebag.add_assign(t);
-                g
-            })
-            .collect::<Vec<_>>()
-            .into_iter()
-            .any(|g| g)
-            || self.round == 10
-        {
+        // Move tiles on game board, calc scores and discard to the lid
+        let mut boards: [BoardSummary<C>; P] =
+            std::array::from_fn(|i| self.core.boards[i].end_round());
+        for board in &boards {
+            self.core.lid.add_assign(board.discarded);
+        }
+        let game_over = boards.iter().any(|b| b.completed_row) || self.round_limit_reached();
+        // Set up for next round, the game also ends when there is nothing left to deal
+        let dealt = !game_over && deal(&mut self.core, &mut self.rng);
+        if !dealt {
             // game over, calculate final scores
-            for b in &mut self.boards {
-                b.end_game();
+            for (b, summary) in self.core.boards.iter_mut().zip(&mut boards) {
+                summary.bonus = b.end_game().bonus();
+            }
+            self.core.state = State::GameEnd;
+        }
+        if let Some(stats) = &mut self.stats {
+            for (player, summary) in stats.players.iter_mut().zip(&boards) {
+                player.placement_points += summary.points() as u16;
+                player.bonus_points += summary.bonus as u16;
+                player.floor_penalty += summary.floor_penalty as u16;
             }
-            self.state = State::GameEnd;
+        }
+
+        RoundSummary {
+            round,
+            boards,
+            state: self.core.state,
+            ending: self.ending(),
+        }
+    }
+
+    /// Whether the round just played is the last allowed by the variant
+    fn round_limit_reached(&self) -> bool {
+        self.core
+            .variant
+            .max_rounds
+            .is_some_and(|max| self.core.round >= max)
+    }
+
+    /// Why the game ended, or `None` while it is still being played
+    pub fn ending(&self) -> Option<GameEnding> {
+        if self.core.state != State::GameEnd {
+            None
+        } else if self.core.boards.iter().any(|b| b.wall.has_full_row()) {
+            Some(GameEnding::CompletedRow)
+        } else if self.round_limit_reached() {
+            Some(GameEnding::RoundLimit)
         } else {
-            // Set up for next round
-            self.deal();
+            Some(GameEnding::OutOfTiles)
         }
+    }
 
-        self.state
+    /// Start or stop recording the moves played, see [Gamestate::move_history].
+    /// Starting clears any moves already recorded
+    pub fn record_moves(&mut self, record: bool) {
+        self.move_history = record.then(Vec::new);
+    }
+
+    /// Moves played since recording started, in order
+    pub fn move_history(&self) -> &[PlayedMove] {
+        self.move_history.as_deref().unwrap_or_default()
+    }
+
+    /// Start or stop adding up [GameStats] for moves from [Gamestate::play_move]
+    /// and round ends. Starting clears any stats already recorded
+    pub fn record_stats(&mut self, record: bool) {
+        self.stats = record.then(GameStats::default);
+    }
+
+    /// Totals since recording started, None when not recording
+    pub fn stats(&self) -> Option<&GameStats<P>> {
+        self.stats.as_ref()
+    }
+
+    /// Keep up to `limit` positions to go back to with [Gamestate::undo], 0 to keep none.
+    /// Each is a copy of the game, including the rng, so searches should leave this at 0
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.limit = limit;
+        let excess = self.history.undo.len().saturating_sub(limit);
+        self.history.undo.drain(..excess);
+        self.history.redo.truncate(limit);
+    }
+
+    /// Go back to the position before the last move or round end, returning false if
+    /// there is none. The rng is restored too, so the same tiles are dealt again
+    pub fn undo(&mut self) -> bool {
+        let mut history = std::mem::take(&mut self.history);
+        let done = match history.undo.pop_back() {
+            Some(previous) => {
+                history.redo.push(std::mem::replace(self, previous));
+                true
+            }
+            None => false,
+        };
+        self.history = history;
+        done
+    }
+
+    /// Play again the last move or round end taken back with [Gamestate::undo],
+    /// returning false if there is none. Anything played since the undo clears these
+    pub fn redo(&mut self) -> bool {
+        let mut history = std::mem::take(&mut self.history);
+        let done = match history.redo.pop() {
+            Some(next) => {
+                history.undo.push_back(std::mem::replace(self, next));
+                true
+            }
+            None => false,
+        };
+        self.history = history;
+        done
+    }
+
+    /// Save the position before it changes, if keeping a history
+    fn record(&mut self) {
+        if self.history.limit == 0 {
+            return;
+        }
+        let mut history = std::mem::take(&mut self.history);
+        history.redo.clear();
+        if history.undo.len() == history.limit {
+            history.undo.pop_front();
+        }
+        history.undo.push_back(self.clone());
+        self.history = history;
     }
 
     /// Count up the tiles in play
     /// Used for testing to validate logic
     fn tile_count(&self) -> u8 {
-        self.boards.iter().map(|b| b.tile_count()).sum::<u8>()
-            + self.tilebag.total()
+        self.core.boards.iter().map(|b| b.tile_count()).sum::<u8>()
+            + self.core.tilebag.total()
+            + self.core.lid.total()
             + self
+                .core
                 .factories
                 .iter()
                 .filter_map(|f| f.as_ref())
@@ -292,12 +1200,275 @@ impl<const P: usize, const F: usize> Gamestate<P, F> {
     /// Check number of first player tiles in play
     /// Used for testing to validate logic
     fn fp_count(&self) -> usize {
-        self.boards.iter().filter(|b| b.first_player_tile).count()
-            + if self.first_player_tile { 1 } else { 0 }
+        self.core
+            .boards
+            .iter()
+            .filter(|b| b.floor.has_first_player_tile())
+            .count()
+            + if self.core.first_player_tile { 1 } else { 0 }
+    }
+
+    /// Check that every tile and exactly one first player tile are in play,
+    /// which [Gamestate::play_move] and [Gamestate::end_round] must keep true
+    pub fn check_invariants(&self) -> Result<(), String> {
+        let tiles = self.tile_count();
+        if usize::from(tiles) != 20 * C {
+            return Err(format!("{tiles} tiles in play instead of {}", 20 * C));
+        }
+        match self.fp_count() {
+            1 => Ok(()),
+            n => Err(format!("{n} first player tiles in play")),
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+/// Position set up piece by piece, for puzzles, tests and editing boards
+///
+/// Anything not set is as in a new game before the deal: empty boards and factories,
+/// the first player tile in the centre, player 0 to move in round 1.
+/// Unless the bag is set it holds every tile not placed elsewhere.
+/// Setters panic for a player, factory or row that isn't in the game
+#[derive(Debug, Clone)]
+pub struct GamestateBuilder<const P: usize, const F: usize, R = GameRng, const C: usize = 5> {
+    seed: u64,
+    variant: Variant<C>,
+    boards: [PlayerBoard<C>; P],
+    floors: [TileGroup<C>; P],
+    factories: [TileGroup<C>; F],
+    bag: Option<TileGroup<C>>,
+    lid: TileGroup<C>,
+    first_player_tile: Option<u8>,
+    current_player: u8,
+    round: u16,
+    game_over: bool,
+    rng: PhantomData<R>,
+}
+
+impl<const P: usize, const F: usize, R: Rng + SeedableRng + Clone, const C: usize>
+    GamestateBuilder<P, F, R, C>
+{
+    fn new() -> Self {
+        Self {
+            seed: 0,
+            variant: Variant::standard(),
+            boards: [PlayerBoard::default(); P],
+            floors: [TileGroup::new_empty(); P],
+            factories: [TileGroup::new_empty(); F],
+            bag: None,
+            lid: TileGroup::new_empty(),
+            first_player_tile: None,
+            current_player: 0,
+            round: 1,
+            game_over: false,
+            rng: PhantomData,
+        }
+    }
+
+    /// Seed for the tiles drawn in later rounds
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Rules of the game. Walls that are set must use its pattern and placement
+    pub fn with_variant(mut self, variant: Variant<C>) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    /// Tiles in the centre
+    pub fn with_centre(self, tiles: TileGroup<C>) -> Self {
+        self.with_factory(0, tiles)
+    }
+
+    /// Tiles in a factory, numbered from 1 as in [Source]
+    pub fn with_factory(mut self, index: usize, tiles: TileGroup<C>) -> Self {
+        self.factories[index] = tiles;
+        self
+    }
+
+    pub fn with_bag(mut self, tiles: TileGroup<C>) -> Self {
+        self.bag = Some(tiles);
+        self
+    }
+
+    pub fn with_lid(mut self, tiles: TileGroup<C>) -> Self {
+        self.lid = tiles;
+        self
+    }
+
+    /// Player that has taken the first player tile this round,
+    /// which is in the centre when None
+    pub fn with_first_player_tile(mut self, player: Option<u8>) -> Self {
+        self.first_player_tile = player;
+        self
+    }
+
+    pub fn with_current_player(mut self, player: u8) -> Self {
+        self.current_player = player;
+        self
+    }
+
+    pub fn with_round(mut self, round: u16) -> Self {
+        self.round = round;
+        self
+    }
+
+    /// The game has ended, with bonuses already in the scores
+    pub fn with_game_over(mut self, game_over: bool) -> Self {
+        self.game_over = game_over;
+        self
+    }
+
+    /// Replace a player's whole board
+    pub fn with_board(mut self, player: usize, board: PlayerBoard<C>) -> Self {
+        self.boards[player] = board;
+        self.floors[player] = *board.floor.tiles();
+        self
+    }
+
+    /// Put `count` tiles in one of a player's pattern lines
+    pub fn with_row(mut self, player: usize, row: RowIndex, tile: Tile, count: u8) -> Self {
+        self.boards[player].rows[usize::from(row)] = Row::new(tile, count);
+        self
+    }
+
+    pub fn with_wall(mut self, player: usize, wall: Wall<C>) -> Self {
+        self.boards[player].wall = wall;
+        self
+    }
+
+    /// Tiles on a player's floor, no more than fit with the first player tile
+    pub fn with_floor(mut self, player: usize, tiles: TileGroup<C>) -> Self {
+        self.floors[player] = tiles;
+        self
+    }
+
+    pub fn with_score(mut self, player: usize, score: u8) -> Self {
+        self.boards[player].score = score;
+        self
+    }
+
+    /// Check the position and create the game. Fails if a row doesn't fit its
+    /// tiles, or the tiles of a colour don't add up to those in a full bag
+    pub fn build(self) -> Result<Gamestate<P, F, R, C>, AzulError> {
+        for player in [Some(self.current_player), self.first_player_tile]
+            .into_iter()
+            .flatten()
+        {
+            if player as usize >= P {
+                return Err(PositionError::NoSuchPlayer(player).into());
+            }
+        }
+        if self.round == 0 {
+            return Err(PositionError::RoundZero.into());
+        }
+        self.variant.check_players(P)?;
+        let mut boards = self.boards;
+        let mut placed = self.lid;
+        for (player, board) in boards.iter_mut().enumerate() {
+            let wall = &board.wall;
+            if wall.placement() != self.variant.placement
+                || wall
+                    .pattern()
+                    .is_some_and(|pattern| pattern != self.variant.wall)
+            {
+                if wall.tile_count() > 0 {
+                    return Err(PositionError::WallMismatch(player).into());
+                }
+                board.wall = Wall::with_placement(self.variant.wall, self.variant.placement);
+            }
+            for (row, line) in board.row_iter() {
+                let Some(tile) = line.tile() else {
+                    continue;
+                };
+                if line.count() > row.capacity() || (tile as usize) >= C {
+                    return Err(PositionError::RowTooFull { player, row }.into());
+                }
+                if !board.wall.cell_available(row, &tile) {
+                    return Err(PositionError::WallHasColour { player, row, tile }.into());
+                }
+                placed.add_tiles(tile, line.count());
+            }
+            for tile in board.wall.iter().flatten().flatten() {
+                placed.add_tile(tile);
+            }
+            placed += self.floors[player];
+            board.floor = FloorLine::default();
+            if self.first_player_tile == Some(player as u8) {
+                board.floor.add_first_player_tile();
+            }
+            if board.floor.set_tiles(self.floors[player]).total() > 0 {
+                return Err(PositionError::FloorTooFull(player).into());
+            }
+        }
+        for factory in &self.factories {
+            placed += *factory;
+        }
+        let full = TileGroup::<C>::new_bag();
+        let bag = match self.bag {
+            Some(bag) => {
+                placed += bag;
+                bag
+            }
+            None => {
+                let bag = full.saturating_sub(placed);
+                placed += bag;
+                bag
+            }
+        };
+        if let Some(tile) = Tile::colours(C).find(|t| placed.get_count(*t) != full.get_count(*t)) {
+            return Err(PositionError::TileTotal {
+                tile,
+                found: placed.get_count(tile),
+                expected: full.get_count(tile),
+            }
+            .into());
+        }
+
+        let mut gs = Gamestate::from_core(
+            CoreState {
+                boards,
+                tilebag: bag,
+                lid: self.lid,
+                factories: self.factories.map(|f| (!f.is_empty()).then_some(f)),
+                first_player_tile: self.first_player_tile.is_none(),
+                current_player: self.current_player,
+                round: self.round,
+                state: State::RoundActive,
+                variant: self.variant,
+            },
+            R::seed_from_u64(self.seed),
+        );
+        if self.game_over {
+            gs.core.state = State::GameEnd;
+        } else if gs.factories_empty() {
+            gs.core.state = State::RoundEnd;
+        }
+        for board in gs.core.boards.iter_mut() {
+            board.predict_score();
+        }
+        Ok(gs)
+    }
+}
+
+/// Centre and factories, then each player's board, in colour for `{:#}`:
+/// ```text
+/// Round 1, centre 1  Factories 1:BBYK 2:YRRW 3:BKKW 4:YYRW 5:BYKW
+/// Bag B15 Y15 R17 K16 W17  Lid B0 Y0 R0 K0 W0
+/// Player 0 to move: Score 0 (predicted 0)
+///       . | byrkw
+/// ...
+/// ```
+impl<const P: usize, const F: usize, R: Rng + Clone, const C: usize> std::fmt::Display
+    for Gamestate<P, F, R, C>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.view(Viewer::Spectator), f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Copy, serde::Serialize, serde::Deserialize)]
 pub struct Move {
     /// Where the tiles will be taken from
     pub source: Source,
@@ -311,6 +1482,9 @@ pub struct Move {
     pub row_count: u8,
     /// Where the tiles will be placed
     pub destination: Destination,
+    /// Wall column for the row this move fills, only when placement is free
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<ColumnIndex>,
 }
 
 impl Move {
@@ -329,6 +1503,7 @@ impl Move {
             play_count,
             row_count,
             destination,
+            column: None,
         }
     }
 
@@ -340,6 +1515,15 @@ impl Move {
             play_count: 0,
             row_count: 0,
             destination: Destination::Floor,
+            column: None,
+        }
+    }
+
+    /// The same move, placing the row's tile in `column` of the wall
+    pub fn with_column(self, column: ColumnIndex) -> Self {
+        Self {
+            column: Some(column),
+            ..self
         }
     }
 
@@ -352,7 +1536,7 @@ impl Move {
 
     pub fn no_floor_tiles(&self) -> bool {
         match self.destination {
-            Destination::Row(row) => self.count == self.play_count,
+            Destination::Row(_) => self.count == self.play_count,
             Destination::Floor => false,
         }
     }
@@ -375,35 +1559,343 @@ impl Move {
         }
     }
 
-    /// Convert the move to an index between 0 and 179
+    /// Convert the move to an index between 0 and 179, see [ActionIndex]
+    ///
+    /// The wall column is not included, so moves with free placement that differ only
+    /// by column share an index
     pub fn to_index(&self) -> usize {
-        (usize::from(self.source) * 30) + (self.tile as usize * 6) + usize::from(self.destination)
+        self.index_for(5)
+    }
+
+    /// Convert the move to an index in a game with `colours` colours, see [ActionIndex]
+    pub fn index_for(&self, colours: usize) -> usize {
+        self.action().index_for(colours)
+    }
+
+    /// The parts of the move that make up its index
+    pub fn action(&self) -> ActionIndex {
+        ActionIndex {
+            source: self.source,
+            tile: self.tile,
+            destination: self.destination,
+        }
     }
 }
 
+/// Source, colour and destination of a move, which number every move of a game
+/// from 0 up to [Gamestate::ACTIONS], eg for the outputs of a policy network
+///
+/// With `C` colours the index is `source * C * (C + 1) + tile * (C + 1) + destination`,
+/// where rows count from 0 and the floor is destination `C`.
+/// The standard game has 6 sources and 5 colours, so 180 indices.
+/// The number of tiles and the wall column are not included, the position decides them,
+/// see [Gamestate::move_from_index]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionIndex {
+    pub source: Source,
+    pub tile: Tile,
+    pub destination: Destination,
+}
+
+impl ActionIndex {
+    /// Index in a game with `colours` colours
+    pub fn index_for(&self, colours: usize) -> usize {
+        let destination = match self.destination {
+            Destination::Row(r) => r as usize,
+            Destination::Floor => colours,
+        };
+        usize::from(self.source) * colours * (colours + 1)
+            + self.tile as usize * (colours + 1)
+            + destination
+    }
+
+    /// Inverse of [ActionIndex::index_for] in a game with `sources` sources.
+    /// None for indices past the last action, or more than 6 colours
+    pub fn from_index(index: usize, sources: usize, colours: usize) -> Option<Self> {
+        let per_source = colours * (colours + 1);
+        if colours > Tile::ALL.len() || index >= sources.saturating_mul(per_source) {
+            return None;
+        }
+        let destination = index % (colours + 1);
+        Some(Self {
+            source: Source(u8::try_from(index / per_source).ok()?),
+            tile: Tile::try_from(index % per_source / (colours + 1)).ok()?,
+            destination: if destination == colours {
+                Destination::Floor
+            } else {
+                Destination::Row(RowIndex::try_from(destination).ok()?)
+            },
+        })
+    }
+}
+
+/// Move notation, the source, colour and destination, eg `F3 Red -> Row2` or
+/// `C Blue -> Floor`, with the wall column last when placement is free, eg `Row1 Col4`
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:?} -> {}", self.source, self.tile, self.destination)?;
+        if let Some(col) = self.column {
+            write!(f, " Col{}", col as u8 + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the notation from Display, case insensitive and with tiles also as letters.
+/// The counts are 0, see [Gamestate::parse_move] for the full move
+impl std::str::FromStr for Move {
+    type Err = AzulError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AzulError::parse(ParseKind::Move, s);
+        let (from, to) = s.split_once("->").ok_or_else(invalid)?;
+        let mut from = from.split_whitespace();
+        let (Some(source), Some(tile), None) = (from.next(), from.next(), from.next()) else {
+            return Err(invalid());
+        };
+        let source: Source = source.parse()?;
+        let tile = Tile::ALL
+            .into_iter()
+            .find(|t| format!("{t:?}").eq_ignore_ascii_case(tile))
+            .or(tile
+                .chars()
+                .next()
+                .filter(|_| tile.len() == 1)
+                .and_then(Tile::from_letter))
+            .ok_or_else(|| AzulError::parse(ParseKind::Tile, tile))?;
+        let mut to = to.split_whitespace();
+        let destination: Destination = to.next().ok_or_else(invalid)?.parse()?;
+        let column = match to.next() {
+            None => None,
+            Some(col) => Some(
+                numbered(col, "col")
+                    .and_then(|n| ColumnIndex::try_from(n.checked_sub(1)?).ok())
+                    .ok_or_else(|| AzulError::parse(ParseKind::Column, col))?,
+            ),
+        };
+        if to.next().is_some() {
+            return Err(invalid());
+        }
+        let m = match destination {
+            Destination::Row(_) => Move::new(source, tile, 0, 0, 0, destination),
+            Destination::Floor if column.is_none() => Move::new_to_floor(source, tile, 0),
+            Destination::Floor => return Err(invalid()),
+        };
+        Ok(Move { column, ..m })
+    }
+}
+
+/// Legal move with what playing it would do, see [Gamestate::get_moves_detailed]
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
-pub struct MoveDetailed {
-    move_: Move,
-    count: u8,
-    fp: bool,
-    row: RowState,
+pub struct MoveDetailed<const C: usize = 5> {
+    pub move_: Move,
+    /// Takes the first player tile from the centre
+    pub fp: bool,
+    /// How full the pattern line is left, None for moves to the floor
+    pub row: Option<RowState>,
+    /// Where the tiles go and the change in the mover's predicted score
+    pub placed: PlacementResult<C>,
+    /// Opponents with an unfinished pattern line of this colour, who could have used the tiles
+    pub denies: u8,
 }
 
+/// Pattern line after a move, see [MoveDetailed]
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
-enum RowState {
+pub enum RowState {
+    /// Still has space
     Partial,
+    /// Exactly full
     Full,
+    /// Full with tiles left over for the floor
     Overfull,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+/// Move recorded by [Gamestate::record_moves]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PlayedMove {
+    /// Seat of the player that played it
+    pub player: u8,
+    pub round: u16,
+    pub m: Move,
+}
+
+/// What [Gamestate::apply] changed: the source and centre, the mover's board,
+/// the lid for tiles falling off the floor and the first player tile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo<const C: usize = 5> {
+    source: Source,
+    factory: Option<TileGroup<C>>,
+    centre: Option<TileGroup<C>>,
+    board: PlayerBoard<C>,
+    lid: TileGroup<C>,
+    first_player_tile: bool,
+    player: u8,
+    state: State,
+}
+
+/// Totals for one player over a game, see [Gamestate::record_stats]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct PlayerStats {
+    /// Tiles taken from the factories and the centre
+    pub tiles_drafted: u16,
+    /// Tiles that went to the floor, including any that fell off it to the lid
+    pub tiles_dropped: u16,
+    /// Number of rounds the player took the first player tile
+    pub first_player_tiles: u16,
+    /// Points for tiles placed on the wall, from the tiles adjacent to them
+    pub placement_points: u16,
+    /// Bonuses for complete rows, columns and colours at the end of the game
+    pub bonus_points: u16,
+    /// Points lost for the floor
+    pub floor_penalty: u16,
+}
+
+/// [PlayerStats] of each seat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct GameStats<const P: usize> {
+    #[serde(with = "crate::serde_array")]
+    pub players: [PlayerStats; P],
+}
+
+impl<const P: usize> Default for GameStats<P> {
+    fn default() -> Self {
+        Self {
+            players: [PlayerStats::default(); P],
+        }
+    }
+}
+
+/// What happened at the end of a round, from [Gamestate::end_round]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundSummary<const P: usize, const C: usize = 5> {
+    /// Round that ended
+    pub round: u16,
+    /// Scoring on each board
+    pub boards: [BoardSummary<C>; P],
+    /// [State::GameEnd] if the game is over, otherwise the next round has been dealt
+    pub state: State,
+    /// Why the game ended, when it did
+    pub ending: Option<GameEnding>,
+}
+
+impl<const P: usize, const C: usize> RoundSummary<P, C> {
+    /// Whether the game ended, because a row was completed, the last round was played
+    /// or there were no tiles left to deal, see [RoundSummary::ending]
+    pub fn game_over(&self) -> bool {
+        self.state == State::GameEnd
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, serde::Serialize, serde::Deserialize)]
 pub enum State {
     RoundActive,
     RoundEnd,
     GameEnd,
 }
 
+/// Why a game ended, see [Gamestate::ending]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, serde::Serialize, serde::Deserialize)]
+pub enum GameEnding {
+    /// A player completed a row of their wall
+    CompletedRow,
+    /// The last round allowed by [Variant::max_rounds] was played
+    RoundLimit,
+    /// There were not enough tiles left to deal another round
+    OutOfTiles,
+}
+
+impl std::fmt::Display for GameEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameEnding::CompletedRow => write!(f, "row completed"),
+            GameEnding::RoundLimit => write!(f, "round limit reached"),
+            GameEnding::OutOfTiles => write!(f, "out of tiles"),
+        }
+    }
+}
+
+/// Part of the game a position is in, see [Gamestate::phase]
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub enum Phase {
+    /// First two rounds, where the bag decides most of what follows
+    Early,
+    Middle,
+    /// Round that may be the last, when someone could complete a wall row
+    Late,
+}
+
+/// Why a move can't be played, see [Gamestate::explain_move]
+#[derive(Debug, Clone, PartialEq, Eq, Copy)]
+pub enum IllegalMove {
+    /// Moves are only played while a round is active
+    WrongState(State),
+    /// There is no factory with this number
+    NoSuchSource(Source),
+    /// Factory or centre has no tiles left this round
+    EmptySource(Source),
+    /// Factory or centre has none of this colour
+    ColourNotInSource(Tile, Source),
+    /// Row is not on the board
+    NoSuchRow(RowIndex),
+    /// Row is already being filled with the contained colour
+    RowHasOtherColour(RowIndex, Tile),
+    /// Row has all the tiles it can hold
+    RowFull(RowIndex),
+    /// Wall already has this colour in the row
+    WallHasColour(RowIndex, Tile),
+    /// With free placement, every column the full row could use has this colour
+    NoFreeColumn(RowIndex, Tile),
+    /// With free placement, the move filling the row doesn't say which column to use
+    MissingColumn(RowIndex),
+    /// With free placement, the column can't take the colour from the full row
+    ColumnNotFree(RowIndex, ColumnIndex, Tile),
+}
+
+impl std::fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = |source: &Source| match source.0 {
+            0 => "the centre".to_string(),
+            n => format!("factory {n}"),
+        };
+        let row = |row: &RowIndex| *row as usize + 1;
+        match self {
+            IllegalMove::WrongState(_) => write!(f, "no round is being played"),
+            IllegalMove::NoSuchSource(s) => write!(f, "there is no factory {}", s.0),
+            IllegalMove::EmptySource(s) => write!(f, "{} is empty", source(s)),
+            IllegalMove::ColourNotInSource(tile, s) => {
+                write!(f, "{} has no {tile:?} tiles", source(s))
+            }
+            IllegalMove::NoSuchRow(r) => write!(f, "there is no row {}", row(r)),
+            IllegalMove::RowHasOtherColour(r, tile) => {
+                write!(f, "row {} is already holding {tile:?} tiles", row(r))
+            }
+            IllegalMove::RowFull(r) => write!(f, "row {} is full", row(r)),
+            IllegalMove::WallHasColour(r, tile) => {
+                write!(f, "the wall already has {tile:?} in row {}", row(r))
+            }
+            IllegalMove::NoFreeColumn(r, tile) => {
+                write!(
+                    f,
+                    "no column of the wall is free for {tile:?} from row {}",
+                    row(r)
+                )
+            }
+            IllegalMove::MissingColumn(r) => {
+                write!(f, "row {} is full, pick a wall column for it", row(r))
+            }
+            IllegalMove::ColumnNotFree(r, col, tile) => write!(
+                f,
+                "column {} is not free for {tile:?} from row {}",
+                *col as usize + 1,
+                row(r)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Copy, serde::Serialize, serde::Deserialize)]
 pub struct Source(pub u8);
 
 impl From<Source> for usize {
@@ -418,40 +1910,87 @@ impl Source {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy)]
-pub enum Destination {
-    Row(RowIndex),
-    Floor,
+/// `C` for the centre, `F1` and on for the factories
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            0 => write!(f, "C"),
+            n => write!(f, "F{n}"),
+        }
+    }
 }
 
-impl From<Destination> for usize {
-    fn from(value: Destination) -> Self {
-        match value {
-            Destination::Row(r) => r as usize,
-            Destination::Floor => 5,
+impl std::str::FromStr for Source {
+    type Err = AzulError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("c") {
+            return Ok(Source(0));
         }
+        numbered(s, "f")
+            .and_then(|n| u8::try_from(n).ok())
+            .filter(|&n| n > 0)
+            .map(Source)
+            .ok_or_else(|| AzulError::parse(ParseKind::Source, s))
     }
 }
 
+/// Number after a case insensitive prefix, eg 2 for `Row2`
+fn numbered(s: &str, prefix: &str) -> Option<usize> {
+    s.get(..prefix.len())
+        .filter(|p| p.eq_ignore_ascii_case(prefix))
+        .and_then(|_| s[prefix.len()..].parse().ok())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Copy, serde::Serialize, serde::Deserialize)]
+pub enum Destination {
+    Row(RowIndex),
+    Floor,
+}
+
 impl From<RowIndex> for Destination {
     fn from(value: RowIndex) -> Self {
         Self::Row(value)
     }
 }
 
+/// `Row1` to `Row6` or `Floor`
+impl std::fmt::Display for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Destination::Row(row) => write!(f, "Row{}", *row as u8 + 1),
+            Destination::Floor => write!(f, "Floor"),
+        }
+    }
+}
+
+impl std::str::FromStr for Destination {
+    type Err = AzulError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("floor") {
+            return Ok(Destination::Floor);
+        }
+        numbered(s, "row")
+            .and_then(|n| RowIndex::try_from(n.checked_sub(1)?).ok())
+            .map(Destination::Row)
+            .ok_or_else(|| AzulError::parse(ParseKind::Destination, s))
+    }
+}
+
 #[cfg(test)]
 mod test {
     #[test]
     fn gamestate() {
         let mut g = super::Gamestate::new_2_player();
         // sanity checks
-        assert_eq!(g.boards.len(), 2);
-        assert_eq!(g.factories.len(), 6);
-        assert!(g.first_player_tile);
-        assert_eq!(g.round, 1);
-        assert_eq!(g.tilebag.total(), 80);
-        assert_eq!(g.factories[0], None);
-        for f in &g.factories[1..] {
+        assert_eq!(g.core.boards.len(), 2);
+        assert_eq!(g.core.factories.len(), 6);
+        assert!(g.core.first_player_tile);
+        assert_eq!(g.core.round, 1);
+        assert_eq!(g.core.tilebag.total(), 80);
+        assert_eq!(g.core.factories[0], None);
+        for f in &g.core.factories[1..] {
             assert_eq!(f.as_ref().unwrap().total(), 4);
         }
         assert_eq!(g.tile_count(), 100);
@@ -459,7 +1998,7 @@ mod test {
 
         let moves = g.get_moves();
         assert_eq!(g.play_move(moves[0]), super::State::RoundActive);
-        assert_eq!(g.current_player, 1);
+        assert_eq!(g.core.current_player, 1);
         assert_eq!(g.tile_count(), 100);
         assert_eq!(g.fp_count(), 1);
 
@@ -477,7 +2016,7 @@ mod test {
             }
             assert_eq!(g.tile_count(), 100);
             assert_eq!(g.fp_count(), 1);
-            if g.end_round() == super::State::GameEnd {
+            if g.end_round().game_over() {
                 break;
             }
             // dbg!(&g);
@@ -485,4 +2024,1080 @@ mod test {
             assert_eq!(g.fp_count(), 1);
         }
     }
+
+    #[test]
+    fn teams() {
+        use crate::error::AzulError;
+
+        let mut g = super::Gamestate::new_4_player_teams_with_seed(1, 0);
+        assert!(g.teams());
+        assert_eq!(g.team(2), 0);
+        assert_eq!(g.team(3), 1);
+        while g.state() != super::State::GameEnd {
+            if g.play_move(g.get_moves()[0]) == super::State::RoundEnd {
+                g.end_round();
+            }
+        }
+        let scores = g.scores().map(u16::from);
+        assert_eq!(
+            g.team_scores(),
+            [scores[0] + scores[2], scores[1] + scores[3]]
+        );
+        assert_eq!(g.team_differential_predicted_score(), {
+            let p = g.core.boards.map(|b| b.predicted_score as f32);
+            p[0] + p[2] - p[1] - p[3]
+        });
+
+        // Three players can't split into teams
+        let mut g = super::Gamestate::<3, 8>::new(0, 0);
+        let teams = "teams".parse().unwrap();
+        assert_eq!(g.set_variant(teams), Err(AzulError::OddTeams(3)));
+        assert!(!g.teams());
+        assert_eq!(
+            super::Gamestate::<3, 8>::builder()
+                .with_variant(teams)
+                .build()
+                .map(|_| ()),
+            Err(AzulError::OddTeams(3))
+        );
+    }
+
+    #[test]
+    fn handicaps() {
+        use super::{Destination, Handicap};
+        let mut g = super::Gamestate::new_2_player_with_seed(4, 0);
+        g.set_handicaps([
+            Handicap {
+                start_score: 10,
+                free_floor_tiles: 2,
+                ..Default::default()
+            },
+            "floor=3".parse().unwrap(),
+        ]);
+        assert_eq!(g.scores(), [10, 0]);
+        assert_eq!(g.core.boards[0].predicted_score, 10);
+
+        // Two tiles on the floor are free for player 0, player 1 loses 3 extra
+        let mut board = g.core.boards[0];
+        board.place_tiles(Destination::Floor, crate::tiles::Tile::Red, 3, false);
+        assert_eq!(board.floor_penalty(), 1);
+        let mut board = g.core.boards[1];
+        board.place_tiles(Destination::Floor, crate::tiles::Tile::Red, 3, false);
+        assert_eq!(board.floor_penalty(), 7);
+        assert_eq!(g.core.boards[1].floor_penalty(), 0);
+        assert!("start=1,bad=2".parse::<Handicap>().is_err());
+
+        // Scores stop at the ends of their range rather than overflowing
+        let mut g = super::Gamestate::new_2_player_with_seed(4, 0);
+        g.set_handicaps([
+            "start=250,free=250".parse().unwrap(),
+            "start=250,floor=250".parse().unwrap(),
+        ]);
+        while !g.is_terminal() {
+            if g.play_move(g.get_moves()[0]) == super::State::RoundEnd {
+                g.end_round();
+            }
+        }
+        // Player 1 loses nearly everything to the floor
+        assert_eq!(g.scores()[0], 255);
+        assert!(g.scores()[1] < 10);
+    }
+
+    #[test]
+    fn rollout() {
+        use super::Gamestate;
+        use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+        let gs: Gamestate<3, 8> = Gamestate::new(4, 2);
+        let play = |seed| {
+            let mut g = gs.clone();
+            let mut rng = SmallRng::seed_from_u64(seed);
+            let scores = g.rollout(&mut rng.clone(), |_, moves| {
+                *moves.choose(&mut rng).unwrap()
+            });
+            assert!(g.is_terminal());
+            assert_eq!(scores, g.scores());
+            scores
+        };
+        assert_eq!(play(1), play(1));
+
+        // Finished games are left as they are
+        let mut g = gs.clone();
+        let scores = g.rollout(&mut SmallRng::seed_from_u64(0), |_, moves| moves[0]);
+        assert_eq!(
+            g.rollout(&mut SmallRng::seed_from_u64(1), |_, _| unreachable!()),
+            scores
+        );
+    }
+
+    #[test]
+    fn prediction_for_any_player() {
+        use super::{Destination, Gamestate, Move, RowIndex, Source, Tile};
+        let mut g: Gamestate<3, 8> = Gamestate::builder()
+            .with_centre("RRR".parse().unwrap())
+            .with_score(0, 10)
+            .with_score(2, 4)
+            .with_current_player(1)
+            .build()
+            .unwrap();
+        let m = Move::new(
+            Source(0),
+            Tile::Red,
+            3,
+            3,
+            3,
+            Destination::Row(RowIndex::Three),
+        );
+        assert_eq!(g.predict_score(m), g.predict_score_for(1, m));
+        // Filling the row scores 1 and the first player tile costs 1
+        assert_eq!(g.predict_score_for(0, m), (10, 0));
+        assert_eq!(g.predict_score_for(2, m), (4, 0));
+
+        assert_eq!(g.differential_predicted_score_for(0), 6.0);
+        assert_eq!(g.differential_predicted_score_for(2), -6.0);
+        assert_eq!(g.differential_predicted_score_for(0), g.predicted_margin());
+        g.core.boards[1].score = 20;
+        g.core.boards[1].predict_score();
+        assert_eq!(g.differential_predicted_score_for(1), 10.0);
+    }
+
+    #[test]
+    fn moves_detailed() {
+        use super::{Destination, Gamestate, Move, RowIndex, RowState, Source, Tile};
+        let g: Gamestate<2, 6> = Gamestate::builder()
+            .with_centre("RRRR".parse().unwrap())
+            .with_row(1, RowIndex::Two, Tile::Red, 1)
+            .build()
+            .unwrap();
+        let detailed = g.get_moves_detailed();
+        assert_eq!(detailed.len(), g.get_moves().len());
+        assert!(detailed.iter().all(|d| d.fp && d.denies == 1));
+
+        let to_row = |row| Move::new(Source(0), Tile::Red, 4, 0, 0, Destination::Row(row));
+        let full = g.detail_move(to_row(RowIndex::Four));
+        assert_eq!(full.row, Some(RowState::Full));
+        let over = g.detail_move(to_row(RowIndex::Three));
+        assert_eq!(over.row, Some(RowState::Overfull));
+        assert_eq!(over.placed.to_floor, 1);
+        assert_eq!(
+            g.detail_move(to_row(RowIndex::Five)).row,
+            Some(RowState::Partial)
+        );
+        let floor = g.detail_move(Move::new_to_floor(Source(0), Tile::Red, 4));
+        assert_eq!(floor.row, None);
+        assert!(floor.placed.score_delta <= full.placed.score_delta);
+    }
+
+    #[test]
+    fn action_index() {
+        use super::{ActionIndex, Destination, Gamestate};
+        for index in 0..Gamestate::<2, 6>::ACTIONS {
+            let action = ActionIndex::from_index(index, 6, 5).unwrap();
+            assert_eq!(action.index_for(5), index);
+        }
+        for index in 0..7 * 6 * 7 {
+            let action = ActionIndex::from_index(index, 7, 6).unwrap();
+            assert_eq!(action.index_for(6), index);
+        }
+        // Past the last source, where the source number would wrap, or too many colours
+        assert_eq!(ActionIndex::from_index(180, 6, 5), None);
+        assert_eq!(ActionIndex::from_index(256 * 30, 300, 5), None);
+        assert_eq!(ActionIndex::from_index(0, 6, 7), None);
+        let g = Gamestate::new_2_player_with_seed(5, 0);
+        for m in g.get_moves() {
+            assert_eq!(g.move_from_index(m.to_index()), Some(m));
+        }
+        let floor = ActionIndex::from_index(5, 6, 5).unwrap();
+        assert_eq!((floor.source.0, floor.destination), (0, Destination::Floor));
+        assert_eq!(g.move_from_index(Gamestate::<2, 6>::ACTIONS), None);
+        assert_eq!(g.move_from_index(256 * 30), None);
+    }
+
+    #[test]
+    fn six_colours() {
+        use rand::rngs::SmallRng;
+        type Big = super::Gamestate<2, 6, SmallRng, 6>;
+        let mut g = Big::new(2, 0);
+        assert_eq!(g.tile_count(), 120);
+        assert_eq!(Big::ACTIONS, 6 * 6 * 7);
+        while g.state() != super::State::GameEnd {
+            let moves = g.get_moves();
+            let mut indices = moves.iter().map(|m| m.index_for(6)).collect::<Vec<_>>();
+            indices.sort();
+            indices.dedup();
+            assert_eq!(indices.len(), moves.len());
+            assert!(indices.iter().all(|&i| i < Big::ACTIONS));
+            if g.play_move(moves[moves.len() / 2]) == super::State::RoundEnd {
+                g.end_round();
+            }
+            assert_eq!(g.tile_count(), 120);
+            // Encoding and tile counting cover the sixth colour too
+            let decoded = Big::decode(&g.encode().unwrap(), *g.variant()).unwrap();
+            assert_eq!(decoded.to_string_position(), g.to_string_position());
+            let orange = g.tile_tracker().distribution(super::Tile::Orange);
+            assert!((orange.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+        // Standard indices are unchanged
+        let m = super::Gamestate::new_2_player_with_seed(2, 0).get_moves()[0];
+        assert_eq!(m.to_index(), m.index_for(5));
+    }
+
+    #[test]
+    fn save_and_load() {
+        // Partway through the second round, with tiles on the walls
+        let mut g = super::Gamestate::new_2_player_with_seed(6, 0);
+        while g.core.round < 2 || g.core.boards[0].floor.tiles().total() == 0 {
+            let m = g.get_moves()[0];
+            if g.play_move(m) == super::State::RoundEnd {
+                g.end_round();
+            }
+        }
+        let json = serde_json::to_string(&g).unwrap();
+        let loaded: super::Gamestate<2, 6> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.core.boards, g.core.boards);
+        assert_eq!(loaded.core.factories, g.core.factories);
+        assert_eq!(loaded.core.tilebag, g.core.tilebag);
+        assert_eq!(loaded.to_string(), g.to_string());
+        // The floor line is saved as the fields it replaced
+        let board = serde_json::to_value(g.core.boards[0]).unwrap();
+        assert!(board["floor"].is_array() && board["first_player_tile"].is_boolean());
+        assert_eq!(loaded, g);
+        // The next round is dealt the same way
+        let mut loaded = loaded;
+        for gs in [&mut g, &mut loaded] {
+            while gs.play_move(gs.get_moves()[0]) == super::State::RoundActive {}
+            gs.end_round();
+        }
+        assert_eq!(loaded.core, g.core);
+
+        let m = g.get_moves()[0];
+        let json = serde_json::to_string(&m).unwrap();
+        assert_eq!(serde_json::from_str::<super::Move>(&json).unwrap(), m);
+
+        // Tiles on the wall must match its pattern
+        let mut wall = serde_json::to_value(g.core.boards[0].wall).unwrap();
+        let tiles = wall["tiles"].as_str().unwrap().to_string();
+        let placed = tiles.find(|c: char| c != '.' && c != '/').unwrap();
+        let other = if &tiles[placed..=placed] == "B" {
+            "Y"
+        } else {
+            "B"
+        };
+        wall["tiles"] = tiles.replacen(&tiles[placed..=placed], other, 1).into();
+        assert!(serde_json::from_value::<crate::playerboard::wall::Wall>(wall).is_err());
+    }
+
+    #[test]
+    fn custom_rng() {
+        use rand::{rngs::StdRng, SeedableRng};
+        let g1: super::Gamestate<2, 6, StdRng> =
+            super::Gamestate::new_with_rng(StdRng::seed_from_u64(7), 0);
+        let g2: super::Gamestate<2, 6, StdRng> = super::Gamestate::new(7, 0);
+        assert_eq!(g1.core.factories, g2.core.factories);
+        assert_eq!(g1.tile_count(), 100);
+
+        // The default rng deals as SmallRng did, so old seeds give the same games
+        use rand::{rngs::SmallRng, Rng};
+        let mut a = super::GameRng::seed_from_u64(7);
+        let mut b = SmallRng::seed_from_u64(7);
+        assert!((0..100).all(|_| a.gen::<u64>() == b.gen::<u64>()));
+    }
+
+    #[test]
+    fn core_state() {
+        use std::collections::HashSet;
+        let g1 = super::Gamestate::new_2_player_with_seed(3, 0);
+        let mut g2 = g1.clone();
+        g2.reseed(4);
+        // Same position, different future deals
+        assert_ne!(g1, g2);
+        assert_eq!(g1.core(), g2.core());
+        let seen = HashSet::from([*g1.core(), *g2.core()]);
+        assert_eq!(seen.len(), 1);
+
+        let mut g3 = super::Gamestate::from_core(*g1.core(), g2.rng.clone());
+        assert_eq!(g3, g2);
+        let m = g3.get_moves()[0];
+        g3.play_move(m);
+        assert!(!seen.contains(g3.core()));
+    }
+
+    #[test]
+    fn apply_and_unapply() {
+        let mut g = super::Gamestate::new_2_player_with_seed(6, 1);
+        let start = g.clone();
+        let mut undos = Vec::new();
+        while g.state() == super::State::RoundActive {
+            let moves = g.get_moves();
+            let m = moves[undos.len() * 7 % moves.len()];
+            let mut played = g.clone();
+            played.play_move(m);
+            undos.push(g.apply(m));
+            assert_eq!(g.core(), played.core());
+        }
+        while let Some(undo) = undos.pop() {
+            g.unapply(undo);
+        }
+        assert_eq!(g, start);
+    }
+
+    #[test]
+    fn floor_overflow() {
+        use super::{Gamestate, Move, Source, Tile};
+        let mut g: Gamestate<2, 6> = Gamestate::builder()
+            .with_floor(0, "KKKKKK".parse().unwrap())
+            .with_centre("RRR".parse().unwrap())
+            .build()
+            .unwrap();
+        let m = Move::new_to_floor(Source(0), Tile::Red, 3);
+        let placed = g.preview_move(&m);
+        assert_eq!((placed.to_row, placed.to_floor), (0, 0));
+        assert_eq!(placed.to_lid.get_count(Tile::Red), 3);
+        let undo = g.apply(m);
+        // The first player tile takes the last space and the reds go to the lid
+        assert_eq!(g.core.boards[0].floor.len(), 7);
+        assert_eq!(g.core.lid.get_count(Tile::Red), 3);
+        assert_eq!(g.tile_count(), 100);
+        g.unapply(undo);
+        assert_eq!(g.core.lid.total(), 0);
+
+        assert!(Gamestate::<2, 6>::builder()
+            .with_floor(1, "KKKKKKKK".parse().unwrap())
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn placement_result() {
+        use super::{Destination, Gamestate, Move, RowIndex, Source, Tile};
+        let mut g: Gamestate<2, 6> = Gamestate::builder()
+            .with_centre("RRRRR".parse().unwrap())
+            .build()
+            .unwrap();
+        g.core.first_player_tile = false;
+        // Stale row counts on the move don't change where the tiles go
+        let m = Move::new(
+            Source(0),
+            Tile::Red,
+            5,
+            5,
+            5,
+            Destination::Row(RowIndex::Three),
+        );
+        let placed = g.preview_move(&m);
+        assert_eq!((placed.to_row, placed.to_floor), (3, 2));
+        assert!(placed.fills_row);
+        // The point for the wall tile is lost to the floor, and scores stop at 0
+        assert_eq!(placed.score_delta, 0);
+        assert_eq!(g.predict_score(m).1 as i16, placed.score_delta);
+    }
+
+    #[test]
+    fn legal_rows() {
+        use super::{Destination, PlayerBoard, RowIndex, Tile};
+        let mut board = PlayerBoard::<5>::default();
+        board.place_tiles(Destination::Row(RowIndex::One), Tile::Red, 1, false);
+        board.place_tiles(Destination::Row(RowIndex::Two), Tile::Blue, 1, false);
+        board.wall.place_tile(RowIndex::Three, Tile::Yellow);
+
+        let rows = |tile| board.legal_rows_for(tile).collect::<Vec<_>>();
+        assert_eq!(
+            rows(Tile::Blue),
+            [
+                (RowIndex::Two, 1),
+                (RowIndex::Three, 3),
+                (RowIndex::Four, 4),
+                (RowIndex::Five, 5)
+            ]
+        );
+        assert_eq!(
+            rows(Tile::Yellow),
+            [(RowIndex::Four, 4), (RowIndex::Five, 5)]
+        );
+        for tile in Tile::colours(5) {
+            let checked = RowIndex::rows(5).filter(|&row| board.check_row(row, tile).is_ok());
+            assert!(checked.eq(board.legal_rows_for(tile).map(|(row, _)| row)));
+        }
+        // A sixth row or colour is turned away rather than indexed
+        assert_eq!(rows(Tile::Orange), []);
+        assert_eq!(board.row(RowIndex::Six), None);
+        assert_eq!(board.can_play_tile(RowIndex::Six, Tile::Blue, 1), None);
+        let placed = board.place_tiles_in_row(RowIndex::Six, Tile::Blue, 2);
+        assert_eq!((placed.to_row, placed.to_floor), (0, 2));
+    }
+
+    #[test]
+    fn round_limit() {
+        use super::{GameEnding, Gamestate, State};
+        let play = |variant: &str| {
+            let mut g = Gamestate::<2, 6>::new_2_player_with_seed(4, 0);
+            g.set_variant(variant.parse().unwrap()).unwrap();
+            loop {
+                assert_eq!(g.ending(), None);
+                let m = g.get_moves()[0];
+                if g.play_move(m) == State::RoundEnd {
+                    let summary = g.end_round();
+                    if summary.game_over() {
+                        assert_eq!(summary.ending, g.ending());
+                        return g;
+                    }
+                }
+            }
+        };
+        let short = play("rounds=2");
+        assert_eq!(short.round(), 2);
+        assert_eq!(short.ending(), Some(GameEnding::RoundLimit));
+        let endless = play("rounds=none");
+        assert_ne!(endless.ending(), Some(GameEnding::RoundLimit));
+        assert!(endless.ending().is_some());
+    }
+
+    #[test]
+    fn stats() {
+        use super::{Destination, State};
+        let mut g = super::Gamestate::new_2_player_with_seed(8, 0);
+        assert!(g.stats().is_none());
+        g.record_stats(true);
+        let (mut drafted, mut to_floor) = (0, 0);
+        while g.state() != State::GameEnd {
+            let moves = g.get_moves();
+            let m = moves[moves.len() / 3];
+            drafted += m.count as u16;
+            if m.destination == Destination::Floor {
+                to_floor += m.count as u16;
+            }
+            if g.play_move(m) == State::RoundEnd {
+                g.end_round();
+            }
+        }
+        let stats = g.stats().unwrap().players;
+        assert_eq!(stats.iter().map(|p| p.tiles_drafted).sum::<u16>(), drafted);
+        assert!(stats.iter().map(|p| p.tiles_dropped).sum::<u16>() >= to_floor);
+        // One player took the first player tile each round
+        assert_eq!(
+            stats.iter().map(|p| p.first_player_tiles).sum::<u16>(),
+            g.round()
+        );
+        for (p, score) in stats.iter().zip(g.scores()) {
+            // Scores can't drop below zero, so they may have lost less than the penalty
+            assert!(score as u16 + p.floor_penalty >= p.placement_points + p.bonus_points);
+            assert!(score as u16 <= p.placement_points + p.bonus_points);
+        }
+    }
+
+    #[test]
+    fn short_deal() {
+        let mut g = super::Gamestate::new_2_player();
+        // Put everything back in the bag except 6 tiles
+        for f in g.core.factories.iter_mut() {
+            if let Some(f) = f.take() {
+                g.core.tilebag += f;
+            }
+        }
+        let mut short = g.core.tilebag;
+        while short.total() > 6 {
+            short.random_tile(&mut g.rng);
+        }
+        g.core.tilebag = short;
+
+        // First factory full, second gets the rest, others out of play
+        assert!(g.deal());
+        assert_eq!(g.core.factories[1].unwrap().total(), 4);
+        assert_eq!(g.core.factories[2].unwrap().total(), 2);
+        assert!(g.core.factories[3..].iter().all(|f| f.is_none()));
+        assert_eq!(g.core.tilebag.total(), 0);
+        assert!(g.get_moves().iter().all(|m| m.source.0 <= 2));
+
+        // Nothing left to deal ends the game at the end of the round
+        while g.play_move(g.get_moves()[0]) == super::State::RoundActive {}
+        g.core.boards = Default::default();
+        let round = g.round();
+        let rng = g.rng.clone();
+        assert!(g.end_round().game_over());
+        assert!(g.core.factories[1..].iter().all(|f| f.is_none()));
+        // The round that couldn't be dealt is never started
+        assert_eq!(g.round(), round);
+        assert_eq!(g.rng, rng);
+        assert_eq!(g.state(), super::State::GameEnd);
+        assert_eq!(g.ending(), Some(super::GameEnding::OutOfTiles));
+    }
+
+    #[test]
+    fn lid() {
+        let mut g = super::Gamestate::new_2_player_with_seed(3, 0);
+        // Tiles go to the lid at the end of the round, not the bag
+        while g.play_move(g.get_moves()[0]) == super::State::RoundActive {}
+        let bag = g.core.tilebag;
+        g.end_round();
+        assert!(g.core.lid.total() > 0);
+        assert_eq!(g.core.tilebag.total(), bag.total() - 20);
+        assert_eq!(g.tile_count(), 100);
+
+        // An empty bag is refilled from the lid part way through the deal
+        for f in g.core.factories.iter_mut() {
+            if let Some(f) = f.take() {
+                g.core.lid += f;
+            }
+        }
+        while g.core.tilebag.total() > 6 {
+            let tile = g.core.tilebag.random_tile(&mut g.rng).unwrap();
+            g.core.lid.add_tile(tile);
+        }
+        let tiles = g.core.tilebag.total() + g.core.lid.total();
+        assert!(g.deal());
+        assert!(g.core.factories[1..]
+            .iter()
+            .all(|f| f.unwrap().total() == 4));
+        assert_eq!(g.core.lid.total(), 0);
+        assert_eq!(g.core.tilebag.total(), tiles - 20);
+    }
+
+    #[test]
+    fn chance_deals() {
+        use rand::{rngs::SmallRng, SeedableRng};
+        let mut g = super::Gamestate::new_2_player_with_seed(3, 0);
+        while g.play_move(g.get_moves()[0]) == super::State::RoundActive {}
+
+        // Sampled deals come from the given rng and leave the game's own alone
+        let mut a = g.clone();
+        let mut b = g.clone();
+        a.end_round_with(&mut SmallRng::seed_from_u64(1));
+        b.end_round_with(&mut SmallRng::seed_from_u64(1));
+        assert_eq!(a, b);
+        assert_eq!(a.tile_count(), 100);
+        assert_eq!(a.rng, g.rng);
+        g.end_round();
+        assert_ne!(a.core.factories, g.core.factories);
+
+        // The bag runs out part way through, so the lid is drawn from too
+        g.core.lid += std::mem::take(&mut g.core.tilebag);
+        g.core.tilebag = "RB".parse().unwrap();
+        let deals = g.enumerate_deals(4);
+        assert!(deals.iter().all(|(tiles, _)| tiles.total() == 4
+            && tiles.get_count(super::Tile::Red) >= 1
+            && tiles.get_count(super::Tile::Blue) >= 1));
+        assert!((deals.iter().map(|d| d.1).sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sample_future() {
+        let g = super::Gamestate::new_2_player_with_seed(3, 0);
+        let samples = g.sample_futures(7, 4).collect::<Vec<_>>();
+        assert_eq!(samples.len(), 4);
+        assert!(samples.iter().all(|s| s.core() == g.core()));
+        assert_ne!(samples[0].rng, samples[1].rng);
+        assert_eq!(samples, g.sample_futures(7, 4).collect::<Vec<_>>());
+
+        // Different futures from the same position
+        let ends = samples.into_iter().map(|mut s| {
+            while s.play_move(s.get_moves()[0]) == super::State::RoundActive {}
+            s.end_round();
+            s.core.factories
+        });
+        assert!(ends.collect::<std::collections::HashSet<_>>().len() > 1);
+    }
+
+    #[test]
+    fn free_placement() {
+        use super::{Destination, IllegalMove};
+        use crate::error::AzulError;
+        use crate::playerboard::{
+            wall::{ColumnIndex, Wall},
+            RowIndex,
+        };
+
+        let mut g = super::Gamestate::new_2_player_with_seed(4, 0);
+        g.set_variant("grey".parse().unwrap()).unwrap();
+        // Filling the top row can use any column of the empty wall
+        let fills = g
+            .get_moves()
+            .into_iter()
+            .filter(|m| m.destination == Destination::Row(RowIndex::One))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fills
+                .iter()
+                .filter(|m| (m.source, m.tile) == (fills[0].source, fills[0].tile))
+                .count(),
+            5
+        );
+        let m = *fills
+            .iter()
+            .find(|m| m.column == Some(ColumnIndex::Four))
+            .unwrap();
+        let no_column = super::Move { column: None, ..m };
+        assert_eq!(
+            g.try_play_move(no_column),
+            Err(AzulError::Illegal(IllegalMove::MissingColumn(
+                RowIndex::One
+            )))
+        );
+        let off_wall = m.with_column(ColumnIndex::Six);
+        assert_eq!(
+            g.try_play_move(off_wall),
+            Err(AzulError::Illegal(IllegalMove::ColumnNotFree(
+                RowIndex::One,
+                ColumnIndex::Six,
+                m.tile
+            )))
+        );
+        g.play_move(m);
+        while g.play_move(g.get_moves()[0]) == super::State::RoundActive {}
+        g.end_round();
+        assert_eq!(
+            g.core.boards[0].wall[(RowIndex::One, ColumnIndex::Four)],
+            Some(m.tile)
+        );
+
+        // Colours are never repeated in a row or column, which loading the wall checks
+        loop {
+            while g.play_move(g.get_moves()[0]) == super::State::RoundActive {}
+            assert_eq!(g.tile_count(), 100);
+            if g.end_round().game_over() {
+                break;
+            }
+        }
+        for board in g.core.boards {
+            let json = serde_json::to_string(&board.wall).unwrap();
+            assert_eq!(serde_json::from_str::<Wall>(&json).unwrap(), board.wall);
+        }
+    }
+
+    #[test]
+    fn round_summary() {
+        let mut g = super::Gamestate::new_2_player_with_seed(8, 0);
+        let mut placed = [0; 2];
+        loop {
+            while g.play_move(g.get_moves()[0]) == super::State::RoundActive {}
+            let before = g.scores();
+            let round = g.core.round;
+            let summary = g.end_round();
+            assert_eq!(summary.round, round);
+            for (i, board) in summary.boards.iter().enumerate() {
+                let score = (before[i] + board.points()).saturating_sub(board.floor_penalty);
+                assert_eq!(g.core.boards[i].score, score + board.bonus);
+                placed[i] += board.points() as u16;
+            }
+            if summary.game_over() {
+                assert_eq!(g.state(), super::State::GameEnd);
+                break;
+            }
+            assert!(summary.boards.iter().all(|b| b.bonus == 0));
+        }
+        for (i, breakdown) in g.score_breakdowns().iter().enumerate() {
+            let wall = &g.core.boards[i].wall;
+            assert_eq!(breakdown.bonus(), wall.score());
+            assert_eq!(breakdown.columns, 7 * wall.completed_columns());
+            assert_eq!(breakdown.adjacency, placed[i]);
+        }
+    }
+
+    #[test]
+    fn move_notation() {
+        use super::{Destination, Move, Source};
+        use crate::{playerboard::RowIndex, tiles::Tile};
+
+        let g = super::Gamestate::new_2_player_with_seed(6, 0);
+        for m in g.get_moves() {
+            assert_eq!(g.parse_move(&m.to_string()), Ok(m));
+        }
+        let m: Move = "f3 red -> row2".parse().unwrap();
+        assert_eq!(
+            (m.source, m.tile, m.destination),
+            (Source(3), Tile::Red, Destination::Row(RowIndex::Two))
+        );
+        assert_eq!(m.to_string(), "F3 Red -> Row2");
+        let m: Move = "C K -> Floor".parse().unwrap();
+        assert_eq!(m.to_string(), "C Black -> Floor");
+        for bad in [
+            "F3 Red Row2",
+            "F0 Red -> Row2",
+            "F3 Pink -> Row2",
+            "C Red -> Row7",
+        ] {
+            assert!(bad.parse::<Move>().is_err(), "{bad}");
+        }
+        // Parses, but the centre is empty at the start of a round
+        assert!(g.parse_move("C Red -> Floor").is_err());
+    }
+
+    #[test]
+    fn builder() {
+        use crate::{
+            playerboard::{wall::Wall, RowIndex},
+            tiles::{Tile, TileGroup},
+        };
+
+        let tiles = |tile, count| {
+            let mut group = TileGroup::new_empty();
+            group.add_tiles(tile, count);
+            group
+        };
+        let mut wall = Wall::default();
+        wall.place_tile(RowIndex::One, Tile::Blue);
+        let g = super::Gamestate::<2, 6>::builder()
+            .with_factory(2, tiles(Tile::Red, 4))
+            .with_centre(tiles(Tile::Blue, 3))
+            .with_first_player_tile(Some(0))
+            .with_wall(0, wall)
+            .with_row(0, RowIndex::Two, Tile::Red, 1)
+            .with_floor(0, tiles(Tile::Black, 2))
+            .with_score(1, 12)
+            .with_current_player(1)
+            .with_round(4)
+            .build()
+            .unwrap();
+        assert_eq!(g.state(), super::State::RoundActive);
+        assert_eq!(g.current_player(), 1);
+        assert_eq!(g.round(), 4);
+        assert_eq!(g.scores(), [0, 12]);
+        assert!(!g.core.first_player_tile);
+        assert_eq!(g.tile_count(), 100);
+        assert_eq!(g.core.tilebag.get_count(Tile::Red), 15);
+        assert_eq!(g.get_moves().len(), 2 * 6);
+
+        // Too many tiles of a colour
+        let g = super::Gamestate::<2, 6>::builder()
+            .with_factory(1, tiles(Tile::Red, 4))
+            .with_bag(TileGroup::new_bag());
+        assert!(g.build().is_err());
+        // Row over capacity, and a colour already on the wall
+        let g = super::Gamestate::<2, 6>::builder().with_wall(1, wall);
+        assert!(g
+            .clone()
+            .with_row(1, RowIndex::One, Tile::Red, 2)
+            .build()
+            .is_err());
+        assert!(g.with_row(1, RowIndex::One, Tile::Blue, 1).build().is_err());
+        let g = super::Gamestate::<2, 6>::builder().with_current_player(2);
+        assert!(g.build().is_err());
+    }
+
+    #[test]
+    fn standings() {
+        use crate::{playerboard::RowIndex, tiles::Tile};
+        let mut g = super::Gamestate::new_2_player_with_seed(1, 0);
+        g.core.boards[0].score = 20;
+        g.core.boards[1].score = 20;
+        assert_eq!(g.final_standings(), [0, 0]);
+        assert_eq!(g.winner(), None);
+        // Complete rows break the tie
+        for tile in Tile::colours(5) {
+            g.core.boards[1].wall.place_tile(RowIndex::Two, tile);
+        }
+        assert_eq!(g.completed_rows(), [0, 1]);
+        assert_eq!(g.final_standings(), [1, 0]);
+        assert_eq!(g.winner(), Some(1));
+        assert_eq!(g.rankings(), [1, 0]);
+        assert!(!g.is_terminal());
+        g.core.boards[0].score = 21;
+        assert_eq!(g.winner(), Some(0));
+
+        let mut g = super::Gamestate::new_4_player_teams_with_seed(1, 0);
+        for (i, b) in g.core.boards.iter_mut().enumerate() {
+            b.score = [10, 15, 5, 1][i];
+        }
+        assert_eq!(g.final_standings(), [1, 0, 1, 0]);
+        assert_eq!(g.winner(), Some(1));
+        assert_eq!(g.rankings(), [1, 3, 0, 2]);
+        g.core.boards[3].score = 0;
+        assert_eq!(g.final_standings(), [0, 0, 0, 0]);
+        assert_eq!(g.winner(), None);
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut g = super::Gamestate::new_2_player_with_seed(5, 0);
+        assert!(!g.undo());
+        g.set_history_limit(100);
+        let start = g.clone();
+        while g.play_move(g.get_moves()[0]) == super::State::RoundActive {}
+        let round_end = g.clone();
+        g.end_round();
+        let dealt = g.clone();
+
+        // The same tiles are dealt again after undoing the round end
+        assert!(g.undo());
+        assert_eq!(g, round_end);
+        g.end_round();
+        assert_eq!(g, dealt);
+        assert!(g.undo());
+        assert!(g.redo());
+        assert_eq!(g, dealt);
+        assert!(!g.redo());
+
+        while g.undo() {}
+        assert_eq!(g, start);
+        assert!(g.redo());
+        // Playing a different move clears the redo stack
+        g.play_move(*g.get_moves().last().unwrap());
+        assert!(!g.redo());
+
+        // Only the most recent positions are kept
+        g.set_history_limit(1);
+        assert!(g.undo());
+        assert!(!g.undo());
+    }
+
+    #[test]
+    fn move_history() {
+        let mut g = super::Gamestate::new_2_player_with_seed(2, 1);
+        g.play_move(g.get_moves()[0]);
+        assert!(g.move_history().is_empty());
+        g.record_moves(true);
+        let mut played = Vec::new();
+        while g.state() != super::State::GameEnd {
+            let m = g.get_moves()[0];
+            played.push((g.current_player(), g.round(), m));
+            if g.play_move(m) == super::State::RoundEnd {
+                g.end_round();
+            }
+        }
+        let history = g
+            .move_history()
+            .iter()
+            .map(|p| (p.player, p.round, p.m))
+            .collect::<Vec<_>>();
+        assert_eq!(history, played);
+        g.record_moves(false);
+        assert!(g.move_history().is_empty());
+    }
+
+    #[test]
+    fn try_play_move() {
+        use super::{Destination, IllegalMove, Move, Source, State};
+        use crate::error::AzulError;
+        use crate::{playerboard::RowIndex, tiles::Tile};
+
+        let mut g = super::Gamestate::new_2_player_with_seed(3, 0);
+        let m = g.get_moves()[0];
+        // Bad input is rejected without changing the state
+        let before = g.clone();
+        assert_eq!(
+            g.try_play_move(Move::new_to_floor(Source(9), m.tile, 1)),
+            Err(AzulError::Illegal(IllegalMove::NoSuchSource(Source(9))))
+        );
+        assert_eq!(
+            g.try_play_move(Move::new_to_floor(Source(0), m.tile, 1)),
+            Err(AzulError::Illegal(IllegalMove::EmptySource(Source(0))))
+        );
+        let missing = Tile::colours(5)
+            .find(|&t| g.core.factories[1].unwrap().get_count(t) == 0)
+            .unwrap();
+        assert_eq!(
+            g.try_play_move(Move::new_to_floor(Source(1), missing, 1)),
+            Err(AzulError::Illegal(IllegalMove::ColourNotInSource(
+                missing,
+                Source(1)
+            )))
+        );
+        assert_eq!(
+            g.try_end_round(),
+            Err(AzulError::RoundNotOver(State::RoundActive))
+        );
+        assert_eq!(g, before);
+
+        // Lenient mode fixes up the counts, strict mode rejects them
+        let sloppy = Move::new(m.source, m.tile, 0, 0, 0, m.destination);
+        let mut strict = g.clone();
+        strict.set_strict(true);
+        assert_eq!(
+            strict.try_play_move(sloppy),
+            Err(AzulError::MoveMismatch(m))
+        );
+        assert_eq!(g.try_play_move(sloppy), Ok(State::RoundActive));
+        assert_eq!(g, {
+            let mut b = before.clone();
+            b.play_move(m);
+            b
+        });
+
+        // The reason a row won't take the tiles
+        let mut g = before;
+        g.core.boards[0].place_tiles(Destination::Row(RowIndex::One), missing, 1, false);
+        let to_row = |row| Move {
+            destination: Destination::Row(row),
+            ..m
+        };
+        assert_eq!(
+            g.try_play_move(to_row(RowIndex::One)),
+            Err(AzulError::Illegal(IllegalMove::RowHasOtherColour(
+                RowIndex::One,
+                missing
+            )))
+        );
+        assert_eq!(
+            g.try_play_move(to_row(RowIndex::Six)),
+            Err(AzulError::Illegal(IllegalMove::NoSuchRow(RowIndex::Six)))
+        );
+        assert_eq!(
+            g.try_play_move(Move::new_to_floor(m.source, Tile::Orange, 1)),
+            Err(AzulError::Illegal(IllegalMove::ColourNotInSource(
+                Tile::Orange,
+                m.source
+            )))
+        );
+    }
+
+    #[test]
+    fn discard() {
+        use super::{IllegalMove, Source, State};
+        use crate::error::AzulError;
+        use crate::tiles::Tile;
+
+        let mut g = super::Gamestate::new_2_player_with_seed(3, 0);
+        let before = g.clone();
+        // A colour the game doesn't have is an error, not an index out of range
+        assert_eq!(
+            g.discard(Source(1), Tile::Orange),
+            Err(AzulError::Illegal(IllegalMove::ColourNotInSource(
+                Tile::Orange,
+                Source(1)
+            )))
+        );
+        assert_eq!(g, before);
+
+        let tile = g.core.factories[1].unwrap().most_common().unwrap().0;
+        assert_eq!(g.discard(Source(1), tile), Ok(State::RoundActive));
+        assert_eq!(g.core.factories[1], None);
+        assert!(g.core.lid.get_count(tile) > 0);
+        assert_eq!(g.current_player(), before.current_player());
+    }
+
+    #[test]
+    fn distinct_moves() {
+        use super::{Move, Source};
+
+        let mut g = super::Gamestate::new_2_player_with_seed(1, 0);
+        assert_eq!(g.get_distinct_moves(), g.get_moves());
+
+        g.core.factories[3] = g.core.factories[1];
+        let moves = g.get_moves();
+        let distinct = g.get_distinct_moves();
+        assert!(distinct.len() < moves.len());
+        assert!(distinct.iter().all(|m| m.source != Source(3)));
+        // Every move from the copy leads to the same position as one from the original
+        for m in moves.iter().filter(|m| m.source == Source(3)) {
+            let original = Move {
+                source: Source(1),
+                ..*m
+            };
+            assert!(distinct.contains(&original));
+            let (mut a, mut b) = (g.clone(), g.clone());
+            a.play_move(*m);
+            b.play_move(original);
+            a.core.factories.swap(1, 3);
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn phases() {
+        use super::Phase;
+        use crate::{playerboard::RowIndex, tiles::Tile};
+
+        let mut g = super::Gamestate::new_2_player_with_seed(1, 0);
+        assert_eq!(g.tiles_left(), 20);
+        assert_eq!(g.rounds_left(), 5);
+        assert_eq!(g.phase(), Phase::Early);
+
+        g.core.round = 2;
+        assert_eq!(g.phase(), Phase::Middle);
+        for tile in [Tile::Blue, Tile::Yellow, Tile::Red, Tile::Black] {
+            g.core.boards[1].wall.place_tile(RowIndex::One, tile);
+        }
+        assert_eq!(g.rounds_left(), 1);
+        assert_eq!(g.phase(), Phase::Late);
+    }
+
+    #[test]
+    fn display() {
+        let mut g = super::Gamestate::new_2_player_with_seed(4, 0);
+        let m = g.get_moves()[0];
+        g.play_move(m);
+        let text = g.to_string();
+        let lines = text.lines().collect::<Vec<_>>();
+        // Header and bag, then a score, pattern lines and a floor for each player
+        assert_eq!(lines.len(), 16);
+        assert!(lines[0].starts_with("Round 1, centre 1"));
+        assert!(lines[1].starts_with("Bag B"));
+        assert!(lines[1].ends_with("  Lid B0 Y0 R0 K0 W0"));
+        assert!(lines[2].starts_with("Player 0: Score 0"));
+        assert!(lines[9].starts_with("Player 1 to move: Score 0"));
+        assert_eq!(lines[10], "      . | byrkw");
+        assert_eq!(lines[11], "     .. | wbyrk");
+        assert_eq!(lines[15], "  Floor: -");
+
+        // Colour only adds escape codes
+        let coloured = format!("{g:#}");
+        assert!(coloured.contains("\x1b["));
+        let mut plain = String::new();
+        let mut chars = coloured.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        assert_eq!(plain, text);
+    }
+
+    #[test]
+    fn explain_move() {
+        use super::{Destination, IllegalMove, Source};
+        use crate::{playerboard::RowIndex, tiles::Tile};
+
+        let g = super::Gamestate::new_2_player_with_seed(3, 0);
+        let two = Destination::Row(RowIndex::Two);
+        let m = g
+            .get_moves()
+            .into_iter()
+            .find(|m| m.source == Source(1) && m.destination == two)
+            .unwrap();
+        assert_eq!(g.explain_move(Source(1), m.tile, two), Ok(m));
+        assert_eq!(
+            g.explain_move(Source(9), m.tile, two),
+            Err(IllegalMove::NoSuchSource(Source(9)))
+        );
+        assert_eq!(
+            g.explain_move(Source(0), m.tile, two),
+            Err(IllegalMove::EmptySource(Source(0)))
+        );
+        let missing = Tile::colours(5)
+            .find(|&t| g.core.factories[1].unwrap().get_count(t) == 0)
+            .unwrap();
+        assert_eq!(
+            g.explain_move(Source(1), missing, two),
+            Err(IllegalMove::ColourNotInSource(missing, Source(1)))
+        );
+
+        let mut b = g.clone();
+        b.core.boards[0].place_tiles(two, missing, 1, false);
+        b.core.boards[0].place_tiles(Destination::Row(RowIndex::One), m.tile, 1, false);
+        b.core.boards[0].wall.place_tile(RowIndex::Three, m.tile);
+        assert_eq!(
+            b.explain_move(Source(1), m.tile, two),
+            Err(IllegalMove::RowHasOtherColour(RowIndex::Two, missing))
+        );
+        assert_eq!(
+            b.explain_move(Source(1), m.tile, Destination::Row(RowIndex::One)),
+            Err(IllegalMove::RowFull(RowIndex::One))
+        );
+        let three = b.explain_move(Source(1), m.tile, Destination::Row(RowIndex::Three));
+        assert_eq!(
+            three,
+            Err(IllegalMove::WallHasColour(RowIndex::Three, m.tile))
+        );
+        assert_eq!(
+            three.unwrap_err().to_string(),
+            format!("the wall already has {:?} in row 3", m.tile)
+        );
+        assert_eq!(
+            b.explain_move(Source(1), m.tile, Destination::Row(RowIndex::Six)),
+            Err(IllegalMove::NoSuchRow(RowIndex::Six))
+        );
+        assert!(b
+            .explain_move(Source(1), m.tile, Destination::Floor)
+            .is_ok());
+    }
 }
@@ -1,5 +1,32 @@
+pub mod adjudication;
+pub mod analysis;
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
+pub mod dynamic;
+pub mod elo;
+pub mod encoding;
+pub mod error;
 pub mod gamestate;
+pub mod logging;
+pub mod manifest;
+pub mod memory;
+pub mod observer;
+pub mod online;
+pub mod opening;
 pub mod playerboard;
 pub mod players;
+pub mod position;
+pub mod presets;
+pub mod ranking;
+pub mod record;
+pub mod registry;
+pub mod regression;
 pub mod runner;
+mod serde_array;
+pub mod solitaire;
+pub mod spectate;
 pub mod tiles;
+pub mod variant;
+pub mod view;
+#[cfg(feature = "web-dashboard")]
+pub mod web;
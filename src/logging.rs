@@ -0,0 +1,227 @@
+//! Every move of every game as a line of JSON, for mining self-play for statistics
+//!
+//! [MoveLogger] is a [GameObserver], so it is opted into with
+//! [Runner::with_observer](crate::runner::Runner::with_observer) or called from any other
+//! game loop. Each game's lines are written together once it ends, as the outcome
+//! isn't known before then:
+//! ```json
+//! {"game":0,"ply":0,"round":1,"player":0,"state":"5b0e2c9a1f3d7e64","move":"F2 Yellow -> Row3","index":68,"legal":47,"tags":["loss","completed_row"]}
+//! ```
+
+use std::io::Write;
+
+use crate::{
+    gamestate::{GameEnding, Gamestate, Move, State},
+    observer::GameObserver,
+};
+
+/// One move, as written by [MoveLogger]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MoveLine {
+    /// Games are numbered from 0 by each logger
+    pub game: u64,
+    /// Moves before this one in the game
+    pub ply: u32,
+    pub round: u16,
+    pub player: u8,
+    /// Position the move was played from, see [state_hash]
+    pub state: String,
+    /// Move in the notation of its Display, see [Gamestate::parse_move]
+    #[serde(rename = "move")]
+    pub move_: String,
+    /// See [Move::to_index]
+    pub index: usize,
+    /// Number of legal moves in the position
+    pub legal: usize,
+    pub tags: Vec<Tag>,
+}
+
+/// Outcome of the game for the player that moved, and how it ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tag {
+    Win,
+    Loss,
+    Draw,
+    /// Game was ended early, so has no winner
+    Adjudicated,
+    CompletedRow,
+    RoundLimit,
+    OutOfTiles,
+    /// Move took the first player tile
+    FirstPlayer,
+}
+
+/// Hash of everything about a position except the rng, as 16 hex digits.
+/// Equal positions have equal hashes across games and runs
+pub fn state_hash<const P: usize, const F: usize>(gs: &Gamestate<P, F>) -> String {
+    format!("{:016x}", fxhash::hash64(gs.core()))
+}
+
+/// Writes a [MoveLine] for every move of every game it observes
+pub struct MoveLogger<W: Write> {
+    writer: W,
+    game: u64,
+    /// Lines of the game being played
+    lines: Vec<MoveLine>,
+    /// Hash, legal move count and whether the first player tile is in the centre,
+    /// for the position to be moved from
+    next: Option<(String, usize, bool)>,
+    /// First failure to write, after which nothing more is written
+    error: Option<std::io::Error>,
+}
+
+impl<W: Write> MoveLogger<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            game: 0,
+            lines: Vec::new(),
+            next: None,
+            error: None,
+        }
+    }
+
+    /// Why writing stopped, if it did
+    pub fn error(&self) -> Option<&std::io::Error> {
+        self.error.as_ref()
+    }
+
+    /// Note the position the next move will be played from
+    fn prepare<const P: usize, const F: usize>(&mut self, gs: &Gamestate<P, F>) {
+        self.next = (gs.state() == State::RoundActive)
+            .then(|| (state_hash(gs), gs.get_moves().len(), gs.first_player_tile()));
+    }
+
+    fn write_game(&mut self) -> std::io::Result<()> {
+        for line in &self.lines {
+            serde_json::to_writer(&mut self.writer, line)?;
+            self.writer.write_all(b"\n")?;
+        }
+        self.writer.flush()
+    }
+}
+
+impl<const P: usize, const F: usize, W: Write> GameObserver<P, F> for MoveLogger<W> {
+    fn on_deal(&mut self, gs: &Gamestate<P, F>) {
+        self.prepare(gs);
+    }
+
+    fn on_move(&mut self, gs: &Gamestate<P, F>, player: u8, m: &Move) {
+        let (state, legal, first_player_tile) =
+            self.next.take().expect("Position before the move was seen");
+        let mut tags = Vec::new();
+        if first_player_tile && usize::from(m.source) == 0 {
+            tags.push(Tag::FirstPlayer);
+        }
+        self.lines.push(MoveLine {
+            game: self.game,
+            ply: self.lines.len() as u32,
+            round: gs.round(),
+            player,
+            state,
+            move_: m.to_string(),
+            index: m.to_index(),
+            legal,
+            tags,
+        });
+        self.prepare(gs);
+    }
+
+    fn on_game_end(&mut self, gs: &Gamestate<P, F>) {
+        let winner = gs.winner();
+        let ending = match gs.ending() {
+            Some(GameEnding::CompletedRow) => Tag::CompletedRow,
+            Some(GameEnding::RoundLimit) => Tag::RoundLimit,
+            Some(GameEnding::OutOfTiles) => Tag::OutOfTiles,
+            None => Tag::Adjudicated,
+        };
+        for line in &mut self.lines {
+            if ending != Tag::Adjudicated {
+                line.tags.insert(
+                    0,
+                    match winner {
+                        None => Tag::Draw,
+                        Some(w) if gs.team(w) == gs.team(line.player) => Tag::Win,
+                        Some(_) => Tag::Loss,
+                    },
+                );
+            }
+            line.tags.push(ending);
+        }
+        if self.error.is_none() {
+            if let Err(e) = self.write_game() {
+                log::error!("Failed to write moves of game {}: {e}", self.game);
+                self.error = Some(e);
+            }
+        }
+        self.lines.clear();
+        self.next = None;
+        self.game += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, io::Write, rc::Rc};
+
+    use super::{MoveLine, MoveLogger, Tag};
+    use crate::{
+        players::{MoveRankPlayer2, Player, RandomPlayer},
+        runner::Runner,
+    };
+
+    /// Writer whose output can be read while the logger owns it
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn log_moves() {
+        let out = Shared::default();
+        let players: [Box<dyn Player<2, 6>>; 2] =
+            [Box::new(MoveRankPlayer2), Box::new(RandomPlayer::new())];
+        let mut runner = Runner::new_2_player(players, Some(2))
+            .with_observer(Box::new(MoveLogger::new(out.clone())));
+        runner.run_matchup(1);
+
+        let text = String::from_utf8(out.0.take()).unwrap();
+        let lines = text
+            .lines()
+            .map(|l| serde_json::from_str::<MoveLine>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.iter().filter(|l| l.ply == 0).count(), 2);
+        assert_eq!(lines.last().unwrap().game, 1);
+        assert!(lines.iter().all(|l| l.legal > 0 && l.state.len() == 16));
+        // One first player tile taken each round
+        let rounds = lines
+            .iter()
+            .map(|l| (l.game, l.round))
+            .collect::<std::collections::HashSet<_>>();
+        let taken = lines.iter().filter(|l| l.tags.contains(&Tag::FirstPlayer));
+        assert_eq!(taken.count(), rounds.len());
+        // Both players of a game get opposite results, or both draw
+        for game in 0..2 {
+            let result = |player| {
+                lines
+                    .iter()
+                    .find(|l| l.game == game && l.player == player)
+                    .unwrap()
+                    .tags[0]
+            };
+            assert!(matches!(
+                (result(0), result(1)),
+                (Tag::Win, Tag::Loss) | (Tag::Loss, Tag::Win) | (Tag::Draw, Tag::Draw)
+            ));
+        }
+    }
+}
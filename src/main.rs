@@ -1,24 +1,29 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
-use std::{fs::File, path::PathBuf};
+use std::{path::PathBuf, time::Duration};
 
 use azul_tiles_rs::{
+    analysis::Limit,
+    error::AzulError,
     gamestate::{Destination, Gamestate, Move, Source},
-    playerboard::{wall::WALL_COLOURS, RowIndex},
+    online::{self, ClientMessage, Event, LobbyGame, ServerMessage},
+    playerboard::{PlayerBoard, RowIndex},
     players::{
         self,
-        minimax::Minimaxer,
-        nn::MoveSelectNN,
         ppo::{PPOMoveSelector, PolicyConfig, ValueConfig},
     },
-    runner::MatchUpResult,
+    presets::Difficulty,
+    ranking::{rank_moves, RankedMove, RankingStrategy},
+    record::Replay,
+    registry::Registry,
+    runner::Progress,
+    solitaire::{Solitaire, DEFAULT_PAR},
+    spectate::{LogTail, Spectator},
     tiles::{Tile, TileGroup},
+    view::{PlayerView, Viewer},
 };
-use burn::{
-    backend::{NdArray, Wgpu},
-    tensor::Device,
-};
+use burn::{backend::NdArray, tensor::Device};
 use eframe::egui;
 use egui::{Color32, FontId, Key, PointerButton, Pos2, Rect, Stroke, Vec2};
 
@@ -32,7 +37,7 @@ fn main() -> eframe::Result {
     eframe::run_native(
         "My egui App",
         options,
-        Box::new(|cc| {
+        Box::new(|_cc| {
             // This gives us image support:
             // egui_extras::install_image_loaders(&cc.egui_ctx);
 
@@ -44,10 +49,14 @@ fn main() -> eframe::Result {
 enum Player {
     Ai(Box<dyn players::Player<2, 6>>),
     Human,
+    /// Opponent in an online game, moves arrive from the server
+    Remote,
 }
 
 struct MyApp {
     gs: Gamestate<2, 6>,
+    /// Seed and rules the local game was started from, for saving it
+    start: Replay,
 
     players: [Player; 2],
 
@@ -55,13 +64,86 @@ struct MyApp {
     config: UIConfig,
     /// Track selection of move for human player
     selection: Selection,
+    /// Connection details entered in the online window
+    join: JoinForm,
+    /// Set while connected to a server
+    online: Option<Online>,
+    /// Following a tournament instead of playing
+    spectate: Spectate,
+    /// Single player game shown instead of the main game
+    solitaire: Option<Solitaire>,
+    /// Difficulty or registered player the computer opponents are created from
+    opponent: String,
+    registry: Registry,
+    hints: Hints,
+    keys: Keybindings,
+    keybinding_ui: KeybindingUI,
 }
 
-impl MyApp {
-    fn new() -> Self {
-        Self::default()
+struct Spectate {
+    open: bool,
+    /// Progress file written by `azul tournament --spectate`
+    path: String,
+    log: Option<LogTail>,
+    spectator: Spectator,
+    /// Game shown on the board
+    selected: Option<u64>,
+    error: Option<String>,
+}
+
+impl Default for Spectate {
+    fn default() -> Self {
+        Self {
+            open: false,
+            path: "tournament/progress.jsonl".to_string(),
+            log: None,
+            spectator: Spectator::default(),
+            selected: None,
+            error: None,
+        }
+    }
+}
+
+/// Search depth used to rank hinted moves
+const HINT_DEPTH: u8 = 2;
+/// Moves listed in the hints window
+const HINTS: usize = 5;
+
+/// Moves suggested to the human player
+#[derive(Default)]
+struct Hints {
+    open: bool,
+    /// Ranking of the last position hinted, kept until the position changes
+    ranked: Option<(Gamestate<2, 6>, Vec<RankedMove>)>,
+}
+
+struct JoinForm {
+    open: bool,
+    address: String,
+    name: String,
+}
+
+impl Default for JoinForm {
+    fn default() -> Self {
+        Self {
+            open: false,
+            address: "ws://127.0.0.1:7878".to_string(),
+            name: "Player".to_string(),
+        }
     }
+}
+
+/// State of the connection to a game server
+struct Online {
+    client: online::Client,
+    lobby: Vec<LobbyGame>,
+    /// Seat and player names while in a game
+    game: Option<(u8, [String; 2])>,
+    /// Shown above the board
+    status: String,
+}
 
+impl MyApp {
     fn advance_gamestate(&mut self) {
         match self.gs.state() {
             azul_tiles_rs::gamestate::State::RoundActive => {
@@ -79,255 +161,835 @@ impl MyApp {
             azul_tiles_rs::gamestate::State::GameEnd => (),
         }
     }
+
+    /// Continue the game saved in [GAME_FILE]
+    fn load_game(&mut self) -> Result<(), AzulError> {
+        let replay = Replay::load(GAME_FILE.as_ref())?;
+        self.gs = replay.position(replay.len())?;
+        self.start = replay;
+        Ok(())
+    }
+
+    /// Play a move chosen by the human, sending it to the server in online games
+    fn play_human_move(&mut self, m: Move) {
+        self.gs.play_move(m);
+        self.selection = Selection::default();
+        if let Some(online) = &mut self.online {
+            if let Err(e) = online.client.send(&ClientMessage::Move {
+                index: m.to_index(),
+            }) {
+                online.status = format!("Move not sent: {e}");
+            }
+        }
+    }
+
+    /// Handle messages from the server
+    fn poll_online(&mut self) {
+        let Some(online) = &mut self.online else {
+            return;
+        };
+        for event in online.client.poll() {
+            match event {
+                Event::Connected => online.status = "Connected".to_string(),
+                Event::Disconnected(e) => online.status = format!("Reconnecting: {e}"),
+                Event::Message(ServerMessage::Lobby { games }) => online.lobby = games,
+                Event::Message(ServerMessage::Start {
+                    seat,
+                    seed,
+                    first_player,
+                    players,
+                    moves,
+                    variant,
+                    ..
+                }) => match online::rebuild(seed, first_player, variant, &moves) {
+                    Some(gs) => {
+                        self.gs = gs;
+                        self.selection = Selection::default();
+                        self.players = [Player::Remote, Player::Remote];
+                        self.players[seat as usize] = Player::Human;
+                        online.status = format!("{} vs {}", players[0], players[1]);
+                        if !variant.is_standard() {
+                            online.status += &format!(" ({variant})");
+                        }
+                        online.game = Some((seat, players));
+                    }
+                    None => online.status = "Server sent an invalid game".to_string(),
+                },
+                Event::Message(ServerMessage::Move { index }) => {
+                    if online::apply_index(&mut self.gs, index).is_none() {
+                        online.status = format!("Server sent an illegal move {index}");
+                    }
+                }
+                Event::Message(ServerMessage::OpponentDisconnected) => {
+                    online.status = "Opponent disconnected, waiting for them to return".to_string()
+                }
+                Event::Message(ServerMessage::OpponentReconnected) => {
+                    online.status = "Opponent reconnected".to_string()
+                }
+                Event::Message(ServerMessage::OpponentLeft) => {
+                    online.status = "Opponent left the game".to_string();
+                    online.game = None;
+                }
+                Event::Message(ServerMessage::Error { message }) => online.status = message,
+            }
+        }
+    }
+
+    /// Read new progress and keep a running game selected
+    fn poll_spectate(&mut self) {
+        let spectate = &mut self.spectate;
+        let Some(log) = &mut spectate.log else {
+            return;
+        };
+        match log.read::<Progress>() {
+            Ok(progress) => {
+                spectate.error = None;
+                for p in progress {
+                    spectate.spectator.update(p);
+                }
+            }
+            Err(e) => spectate.error = Some(e.to_string()),
+        }
+        let games = &spectate.spectator.games;
+        if !spectate.selected.is_some_and(|id| games.contains_key(&id)) {
+            spectate.selected = games.keys().next().copied();
+        }
+    }
+
+    /// Standings, running games and recent results of the spectated tournament
+    fn spectate_panel(&mut self, ctx: &egui::Context) {
+        if self.spectate.open {
+            egui::Window::new("Spectate tournament")
+                .open(&mut self.spectate.open)
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Progress file");
+                        ui.text_edit_singleline(&mut self.spectate.path);
+                    });
+                    if ui.button("Watch").clicked() {
+                        self.spectate.log = Some(LogTail::new(&self.spectate.path));
+                        self.spectate.spectator = Spectator::default();
+                        self.spectate.selected = None;
+                    }
+                });
+        }
+        if self.spectate.log.is_none() {
+            return;
+        }
+        let spectate = &mut self.spectate;
+        let mut stop = false;
+        egui::SidePanel::right("spectate").show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                if let Some(e) = &spectate.error {
+                    ui.colored_label(Color32::RED, e);
+                }
+                if ui.button("Stop watching").clicked() {
+                    stop = true;
+                }
+                ui.heading("Standings");
+                egui::Grid::new("standings").striped(true).show(ui, |ui| {
+                    for header in ["Player", "W", "D", "L", "Margin"] {
+                        ui.label(header);
+                    }
+                    ui.end_row();
+                    for (name, s) in spectate.spectator.table() {
+                        ui.label(name);
+                        ui.label(s.wins.to_string());
+                        ui.label(s.draws.to_string());
+                        ui.label(s.losses.to_string());
+                        ui.label(format!("{:+}", s.margin));
+                        ui.end_row();
+                    }
+                });
+                ui.heading("Running");
+                for (id, game) in &spectate.spectator.games {
+                    let text = format!(
+                        "{} {} - {} {}",
+                        game.players[0],
+                        game.view().scores()[0].unwrap_or_default(),
+                        game.view().scores()[1].unwrap_or_default(),
+                        game.players[1]
+                    );
+                    if ui
+                        .selectable_label(spectate.selected == Some(*id), text)
+                        .clicked()
+                    {
+                        spectate.selected = Some(*id);
+                    }
+                }
+                ui.heading("Recent");
+                for game in &spectate.spectator.recent {
+                    ui.label(format!(
+                        "{} {} - {} {}",
+                        game.players[0], game.scores[0], game.scores[1], game.players[1]
+                    ));
+                }
+            });
+        });
+        if stop {
+            *spectate = Spectate::default();
+        }
+    }
+
+    /// Window for remapping keys
+    fn keybinding_window(&mut self, ctx: &egui::Context) {
+        let ui_state = &mut self.keybinding_ui;
+        let keys = &mut self.keys;
+        egui::Window::new("Keybindings")
+            .open(&mut ui_state.open)
+            .show(ctx, |ui| {
+                egui::Grid::new("keybindings").show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.label(action.label());
+                        let text = if ui_state.rebinding == Some(action) {
+                            "Press a key".to_string()
+                        } else {
+                            keys.key_mut(action).name().to_string()
+                        };
+                        if ui.button(text).clicked() {
+                            ui_state.rebinding = Some(action);
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Number row").clicked() {
+                        *keys = Keybindings::number_row();
+                    }
+                    if ui.button("Home row").clicked() {
+                        *keys = Keybindings::home_row();
+                    }
+                    if ui.button("Save").clicked() {
+                        ui_state.status = match keys.save() {
+                            Ok(()) => format!("Saved to {KEYBINDINGS_FILE}"),
+                            Err(e) => format!("Failed to save: {e}"),
+                        };
+                    }
+                });
+                ui.label(&ui_state.status);
+            });
+        if !ui_state.open {
+            ui_state.rebinding = None;
+        }
+    }
+
+    /// Seat the board is shown to: the online seat, else the first human player
+    fn viewer(&self) -> Viewer {
+        if let Some((seat, _)) = self.online.as_ref().and_then(|o| o.game.as_ref()) {
+            return Viewer::Seat(*seat);
+        }
+        self.players
+            .iter()
+            .position(|p| matches!(p, Player::Human))
+            .map_or(Viewer::Spectator, |seat| Viewer::Seat(seat as u8))
+    }
+
+    /// Best moves for the human player to move, from a shallow search
+    fn hints_window(&mut self, ctx: &egui::Context) {
+        let gs = &self.gs;
+        let human_to_move = matches!(self.players[gs.current_player() as usize], Player::Human)
+            && self.solitaire.is_none()
+            && self.spectate.selected.is_none();
+        let hints = &mut self.hints;
+        egui::Window::new("Hints")
+            .open(&mut hints.open)
+            .show(ctx, |ui| {
+                if !human_to_move || gs.state() != azul_tiles_rs::gamestate::State::RoundActive {
+                    ui.label("Hints are shown on your turn");
+                    return;
+                }
+                if hints.ranked.as_ref().is_none_or(|(hinted, _)| hinted != gs) {
+                    let ranked = rank_moves(gs, RankingStrategy::Search(Limit::Depth(HINT_DEPTH)));
+                    hints.ranked = Some((gs.clone(), ranked));
+                }
+                let (_, ranked) = hints.ranked.as_ref().unwrap();
+                egui::Grid::new("hints").striped(true).show(ui, |ui| {
+                    for r in ranked.iter().take(HINTS) {
+                        ui.label(hint_text(&r.m));
+                        ui.label(format!("{:+.1}", r.score));
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    /// Window for connecting to a server, the lobby and leaving games
+    fn online_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.join.open;
+        let mut send = None;
+        let mut disconnect = false;
+        egui::Window::new("Online game")
+            .open(&mut open)
+            .show(ctx, |ui| match &self.online {
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.label("Server");
+                        ui.text_edit_singleline(&mut self.join.address);
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Name");
+                        ui.text_edit_singleline(&mut self.join.name);
+                    });
+                    if ui.button("Connect").clicked() {
+                        self.online = Some(Online {
+                            client: online::Client::connect(&self.join.address, &self.join.name),
+                            lobby: Vec::new(),
+                            game: None,
+                            status: "Connecting".to_string(),
+                        });
+                    }
+                }
+                Some(online) => {
+                    ui.label(&online.status);
+                    if online.game.is_some() {
+                        if ui.button("Leave game").clicked() {
+                            send = Some(ClientMessage::Leave);
+                        }
+                    } else if online.client.connected() {
+                        if ui.button("Create game").clicked() {
+                            send = Some(ClientMessage::Create);
+                        }
+                        ui.label("Waiting games");
+                        for game in &online.lobby {
+                            ui.horizontal(|ui| {
+                                ui.label(&game.host);
+                                if ui.button("Join").clicked() {
+                                    send = Some(ClientMessage::Join { game: game.id });
+                                }
+                            });
+                        }
+                    }
+                    if ui.button("Disconnect").clicked() {
+                        disconnect = true;
+                    }
+                }
+            });
+        self.join.open = open;
+        if let Some(online) = &mut self.online {
+            if let Some(message) = send {
+                if message == ClientMessage::Leave {
+                    online.game = None;
+                }
+                if let Err(e) = online.client.send(&message) {
+                    online.status = e.to_string();
+                }
+            }
+        }
+        if disconnect {
+            *self = Self {
+                join: std::mem::take(&mut self.join),
+                ..Self::default()
+            };
+        }
+    }
 }
 
 impl MyApp {}
 
-fn key_to_number(key: &Key) -> Option<usize> {
-    match key {
-        Key::Num0 => Some(0),
-        Key::Num1 => Some(1),
-        Key::Num2 => Some(2),
-        Key::Num3 => Some(3),
-        Key::Num4 => Some(4),
-        Key::Num5 => Some(5),
-        _ => None,
+/// File the local game is saved to and loaded from, as a [Replay]
+const GAME_FILE: &str = "game.json";
+
+/// File the keybindings are saved to
+const KEYBINDINGS_FILE: &str = "keybindings.json";
+
+/// Keys for each action, saved by key name
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(into = "KeyNames", try_from = "KeyNames")]
+struct Keybindings {
+    /// Play the AI's move or end the round
+    advance: Key,
+    /// Clear the current selection
+    cancel: Key,
+    /// Keys for 0 to 5, used to pick the factory (0 is the centre),
+    /// the tile and the row (0 is the floor)
+    numbers: [Key; 6],
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct KeyNames {
+    advance: String,
+    cancel: String,
+    numbers: [String; 6],
+}
+
+impl From<Keybindings> for KeyNames {
+    fn from(keys: Keybindings) -> Self {
+        Self {
+            advance: keys.advance.name().to_string(),
+            cancel: keys.cancel.name().to_string(),
+            numbers: keys.numbers.map(|k| k.name().to_string()),
+        }
+    }
+}
+
+impl TryFrom<KeyNames> for Keybindings {
+    type Error = String;
+
+    fn try_from(names: KeyNames) -> Result<Self, Self::Error> {
+        let key = |name: &str| Key::from_name(name).ok_or(format!("Unknown key {name}"));
+        let mut numbers = [Key::Num0; 6];
+        for (key_, name) in numbers.iter_mut().zip(&names.numbers) {
+            *key_ = key(name)?;
+        }
+        Ok(Self {
+            advance: key(&names.advance)?,
+            cancel: key(&names.cancel)?,
+            numbers,
+        })
+    }
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self::number_row()
     }
 }
 
+impl Keybindings {
+    /// Number keys with space and escape
+    fn number_row() -> Self {
+        Self {
+            advance: Key::Space,
+            cancel: Key::Escape,
+            numbers: [
+                Key::Num0,
+                Key::Num1,
+                Key::Num2,
+                Key::Num3,
+                Key::Num4,
+                Key::Num5,
+            ],
+        }
+    }
+
+    /// Home row letters, for keyboards where numbers need a modifier
+    fn home_row() -> Self {
+        Self {
+            advance: Key::Space,
+            cancel: Key::Backspace,
+            numbers: [Key::A, Key::S, Key::D, Key::F, Key::G, Key::H],
+        }
+    }
+
+    /// Load the saved keybindings, or the defaults if there are none
+    fn load() -> Self {
+        std::fs::read_to_string(KEYBINDINGS_FILE)
+            .ok()
+            .and_then(|s| match serde_json::from_str(&s) {
+                Ok(keys) => Some(keys),
+                Err(e) => {
+                    log::warn!("Ignoring {KEYBINDINGS_FILE}: {e}");
+                    None
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(KEYBINDINGS_FILE, json)?;
+        Ok(())
+    }
+
+    fn number(&self, key: &Key) -> Option<usize> {
+        self.numbers.iter().position(|k| k == key)
+    }
+
+    fn key_mut(&mut self, action: Action) -> &mut Key {
+        match action {
+            Action::Advance => &mut self.advance,
+            Action::Cancel => &mut self.cancel,
+            Action::Number(n) => &mut self.numbers[n],
+        }
+    }
+}
+
+/// Something a key can be bound to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Advance,
+    Cancel,
+    Number(usize),
+}
+
+impl Action {
+    const ALL: [Action; 8] = [
+        Action::Advance,
+        Action::Cancel,
+        Action::Number(0),
+        Action::Number(1),
+        Action::Number(2),
+        Action::Number(3),
+        Action::Number(4),
+        Action::Number(5),
+    ];
+
+    fn label(&self) -> String {
+        match self {
+            Action::Advance => "AI move / end round".to_string(),
+            Action::Cancel => "Cancel selection".to_string(),
+            Action::Number(0) => "0 (centre / floor)".to_string(),
+            Action::Number(n) => n.to_string(),
+        }
+    }
+}
+
+/// State of the keybinding window
+#[derive(Default)]
+struct KeybindingUI {
+    open: bool,
+    /// Action waiting for a key press
+    rebinding: Option<Action>,
+    status: String,
+}
+
 type Backend = NdArray;
 impl Default for MyApp {
     fn default() -> Self {
         // let (player, _, _): (MoveSelectNN, f64, MatchUpResult) =
         //     serde_json::from_reader(File::open("move_select_nn.json").unwrap()).unwrap();
-        let player = Minimaxer::new(
-            minimaxer::negamax::SearchOptions {
-                alpha_beta: true,
-                max_time: Some(std::time::Duration::from_millis(1000)),
-                iterative: true,
-                ..Default::default()
-            },
-            "Minimaxer",
-            players::minimax::ScoreEvaluator,
-        );
+        let difficulty = Difficulty::default();
+        let registry = Registry::load_default().unwrap_or_else(|e| {
+            eprintln!("{e}");
+            Registry::default()
+        });
         let device = Device::<Backend>::default();
-        let ppo = PPOMoveSelector::<Backend>::from_file(
+        // Not played by default, so a missing checkpoint is only reported
+        let _ppo = PPOMoveSelector::<Backend>::from_file(
             PolicyConfig::new(150, 240),
             ValueConfig::new(150, 240),
             &PathBuf::from("ppo/checkpoint_200"),
             &device,
-        );
+        )
+        .map_err(|e| eprintln!("{e}"))
+        .ok();
+        let start = Replay::new(rand::random(), 0);
         Self {
-            gs: Gamestate::new_2_player_with_seed(rand::random(), 0),
+            gs: start.start().expect("Standard rules"),
+            start,
             config: UIConfig::default(),
             players: [
                 Player::Human,
                 // Player::Ai(Box::new(azul_tiles_rs::players::MoveRankPlayer)),
                 // Player::Ai(Box::new(azul_tiles_rs::players::MoveRankPlayer2)),
-                Player::Ai(Box::new(player)),
-                // Player::Ai(Box::new(ppo)),
+                Player::Ai(difficulty.player()),
+                // Player::Ai(Box::new(_ppo.unwrap())),
             ],
             selection: Selection::default(),
+            join: JoinForm::default(),
+            online: None,
+            spectate: Spectate::default(),
+            solitaire: None,
+            opponent: difficulty.name().to_string(),
+            registry,
+            hints: Hints::default(),
+            keys: Keybindings::load(),
+            keybinding_ui: KeybindingUI::default(),
         }
     }
 }
 
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.online.is_some() {
+            self.poll_online();
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        egui::TopBottomPanel::top("menu").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Join online game").clicked() {
+                    self.join.open = true;
+                }
+                if ui.button("Spectate tournament").clicked() {
+                    self.spectate.open = true;
+                }
+                if ui.button("Keybindings").clicked() {
+                    self.keybinding_ui.open = true;
+                }
+                if ui.button("Hints").clicked() {
+                    self.hints.open = true;
+                }
+                let previous = self.opponent.clone();
+                egui::ComboBox::from_label("Opponent")
+                    .selected_text(&self.opponent)
+                    .show_ui(ui, |ui| {
+                        let names = <Difficulty as strum::IntoEnumIterator>::iter()
+                            .map(|d| d.name().to_string())
+                            .chain(self.registry.names().cloned());
+                        for name in names {
+                            let label = name.clone();
+                            ui.selectable_value(&mut self.opponent, name, label);
+                        }
+                    });
+                if self.opponent != previous {
+                    for player in &mut self.players {
+                        if let Player::Ai(ai) = player {
+                            match self.registry.player(&self.opponent, Duration::from_secs(1)) {
+                                Ok(opponent) => *ai = opponent,
+                                Err(e) => eprintln!("{e}"),
+                            }
+                        }
+                    }
+                }
+                if self.online.is_none() && self.solitaire.is_none() {
+                    if ui.button("Save game").clicked() {
+                        let replay =
+                            Replay::from_game(self.start.seed, self.start.first_player, &self.gs);
+                        if let Err(e) = replay.save(GAME_FILE.as_ref()) {
+                            eprintln!("{e}");
+                        }
+                    }
+                    if ui.button("Load game").clicked() {
+                        match self.load_game() {
+                            Ok(()) => self.selection = Selection::default(),
+                            Err(e) => eprintln!("{e}"),
+                        }
+                    }
+                }
+                if ui.button("Solitaire").clicked() {
+                    self.solitaire = Some(Solitaire::new(rand::random(), DEFAULT_PAR));
+                    self.selection = Selection::default();
+                }
+                if let Some(solitaire) = &self.solitaire {
+                    ui.label(solitaire_status(solitaire));
+                    if ui.button("Back to game").clicked() {
+                        self.solitaire = None;
+                        self.selection = Selection::default();
+                    }
+                }
+                if let Some(online) = &self.online {
+                    ui.label(&online.status);
+                }
+            });
+        });
+        self.online_window(ctx);
+        self.keybinding_window(ctx);
+        self.hints_window(ctx);
+        if self.spectate.log.is_some() {
+            self.poll_spectate();
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+        self.spectate_panel(ctx);
         egui::CentralPanel::default().show(ctx, |ui| {
             let window_size = ui.available_size();
             self.config.update(&window_size);
+            // Scroll when the window is too small for the smallest tiles
+            egui::ScrollArea::both().show(ui, |ui| {
+                let (rect, _) =
+                    ui.allocate_exact_size(self.config.content_size, egui::Sense::hover());
+                self.config.set_origin(rect.min);
+
+                // Show the selected tournament game instead of the local one
+                if let Some(game) = self
+                    .spectate
+                    .selected
+                    .and_then(|id| self.spectate.spectator.games.get(&id))
+                {
+                    draw_game(ui, &self.config, &game.view(), Highlight::default(), None);
+                    return;
+                }
 
-            let key = ctx.input(|input| {
-                for event in &input.events {
-                    if let egui::Event::Key {
-                        key,
-                        physical_key: _,
-                        pressed: true,
-                        repeat: _,
-                        modifiers: _,
-                    } = event
-                    {
-                        return Some(*key);
+                let key = ctx.input(|input| {
+                    for event in &input.events {
+                        if let egui::Event::Key {
+                            key,
+                            physical_key: _,
+                            pressed: true,
+                            repeat: _,
+                            modifiers: _,
+                        } = event
+                        {
+                            return Some(*key);
+                        }
                     }
-                }
-                None
-            });
+                    None
+                });
+
+                // Next key press goes to the action being rebound
+                let key = match (self.keybinding_ui.rebinding, key) {
+                    (Some(action), Some(key)) => {
+                        *self.keys.key_mut(action) = key;
+                        self.keybinding_ui.rebinding = None;
+                        None
+                    }
+                    _ => key,
+                };
+
+                let click = ctx.input(|input| {
+                    for event in &input.events {
+                        if let egui::Event::PointerButton {
+                            pos,
+                            button: PointerButton::Primary,
+                            pressed: true,
+                            modifiers: _,
+                        } = event
+                        {
+                            return Some(*pos);
+                        }
+                    }
+                    None
+                });
 
-            let click = ctx.input(|input| {
-                for event in &input.events {
-                    if let egui::Event::PointerButton {
-                        pos,
-                        button: PointerButton::Primary,
-                        pressed: true,
-                        modifiers: _,
-                    } = event
+                if let Some(solitaire) = &mut self.solitaire {
+                    if key == Some(self.keys.cancel) {
+                        self.selection = Selection::default();
+                    }
+                    let highlight = self
+                        .selection
+                        .highlight((!solitaire.is_over()).then_some(0));
+                    let clicked = draw_game(
+                        ui,
+                        &self.config,
+                        &solitaire.gamestate().view(Viewer::Seat(0)),
+                        highlight,
+                        click,
+                    );
+                    if let Some(m) =
+                        clicked.and_then(|c| self.selection.click(&solitaire.get_moves(), c))
                     {
-                        return Some(*pos);
+                        solitaire.play_move(m);
+                        self.selection = Selection::default();
                     }
+                    return;
                 }
-                None
-            });
 
-            // Perform actions from space button
-            if let Some(Key::Space) = key {
-                self.advance_gamestate();
-            } else if key == Some(Key::Escape) {
-                self.selection = Selection::default();
-            } else if let Some(key) = key {
-                // If current player is human
-                if let Player::Human = self.players[self.gs.current_player() as usize] {
-                    // get list of available moves
-                    let moves = self.gs.get_moves();
-                    // Check if factory selected
-                    if let Some(factory) = self.selection.factory {
-                        // Check if tile selected
-                        if let Some(tile) = self.selection.tile {
-                            // Select row
-                            if let Some(row) = key_to_number(&key) {
-                                let m = if row == 0 {
-                                    // Floor
-                                    moves.iter().find(|m| {
-                                        m.source == Source(factory as u8)
-                                            && m.tile == tile
-                                            && m.destination == Destination::Floor
-                                    })
-                                } else {
-                                    // Row move
-                                    let row = RowIndex::from(row as u8 - 1);
-                                    moves.iter().find(|m| {
-                                        m.source == Source(factory as u8)
-                                            && m.tile == tile
-                                            && m.destination == Destination::Row(row)
-                                    })
-                                };
-                                if let Some(m) = m {
-                                    self.gs.play_move(*m);
-                                    self.selection = Selection::default();
-                                } else {
-                                    self.selection.row = None;
-                                }
-                            }
-                        } else {
-                            // Select tile if valid move
-                            if let Some(tile) = key_to_number(&key) {
-                                if tile < 5 {
-                                    if factory == 0 {
-                                        // centre, select by colour
-                                        let centre = self.gs.centre();
-                                        let tile = Tile::from(tile);
-                                        let count = centre.get_count(tile);
-                                        if count > 0 {
-                                            self.selection.tile = Some(tile);
-                                        }
+                if key == Some(self.keys.advance) {
+                    self.advance_gamestate();
+                } else if key == Some(self.keys.cancel) {
+                    self.selection = Selection::default();
+                } else if let Some(key) = key {
+                    // If current player is human
+                    if let Player::Human = self.players[self.gs.current_player() as usize] {
+                        // get list of available moves
+                        let moves = self.gs.get_moves();
+                        // Check if factory selected
+                        if let Some(factory) = self.selection.factory {
+                            // Check if tile selected
+                            if let Some(tile) = self.selection.tile {
+                                // Select row
+                                if let Some(row) = self.keys.number(&key) {
+                                    let m = if row == 0 {
+                                        // Floor
+                                        moves.iter().find(|m| {
+                                            m.source == Source(factory as u8)
+                                                && m.tile == tile
+                                                && m.destination == Destination::Floor
+                                        })
                                     } else {
-                                        // factory, select by tile
-                                        let tiles =
-                                            self.gs.factories()[factory].unwrap().tile_vec();
-
-                                        if tile > 0 && tile < 5 {
-                                            let tile = tiles[tile - 1];
-                                            if tiles.iter().any(|t| t == &tile) {
+                                        // Row move
+                                        let row = RowIndex::try_from(row - 1).ok();
+                                        moves.iter().find(|m| {
+                                            m.source == Source(factory as u8)
+                                                && m.tile == tile
+                                                && Some(m.destination) == row.map(Destination::Row)
+                                        })
+                                    };
+                                    if let Some(m) = m {
+                                        self.play_human_move(*m);
+                                    } else {
+                                        self.selection.row = None;
+                                    }
+                                }
+                            } else {
+                                // Select tile if valid move
+                                if let Some(tile) = self.keys.number(&key) {
+                                    if tile < 5 {
+                                        if factory == 0 {
+                                            // centre, select by colour
+                                            let centre = self.gs.centre();
+                                            let tile =
+                                                Tile::try_from(tile).expect("Tile is below 5");
+                                            let count = centre.get_count(tile);
+                                            if count > 0 {
                                                 self.selection.tile = Some(tile);
                                             }
+                                        } else {
+                                            // factory, select by tile
+                                            let tiles =
+                                                self.gs.factories()[factory].unwrap().tile_vec();
+
+                                            if tile > 0 && tile < 5 {
+                                                let tile = tiles[tile - 1];
+                                                if tiles.iter().any(|t| t == &tile) {
+                                                    self.selection.tile = Some(tile);
+                                                }
+                                            }
                                         }
                                     }
                                 }
+                                // If a tile has been set, store list of valid moves for highlighting on board
+                                if let Some(tile) = self.selection.tile {
+                                    self.selection.moves = moves
+                                        .iter()
+                                        .filter(|m| {
+                                            m.tile == tile && m.source == Source(factory as u8)
+                                        })
+                                        .cloned()
+                                        .collect();
+                                }
                             }
-                            // If a tile has been set, store list of valid moves for highlighting on board
-                            if let Some(tile) = self.selection.tile {
-                                self.selection.moves = moves
-                                    .iter()
-                                    .filter(|m| m.tile == tile && m.source == Source(factory as u8))
-                                    .cloned()
-                                    .collect();
-                            }
-                        }
-                    } else {
-                        // Select factory if valid move
-                        if let Some(factory) = key_to_number(&key) {
-                            if moves.iter().any(|m| m.source == Source(factory as u8)) {
-                                self.selection.factory = Some(factory);
+                        } else {
+                            // Select factory if valid move
+                            if let Some(factory) = self.keys.number(&key) {
+                                if moves.iter().any(|m| m.source == Source(factory as u8)) {
+                                    self.selection.factory = Some(factory);
+                                }
                             }
                         }
                     }
                 }
-            }
 
-            let mut highlight = Highlight::default();
-            if self.gs.state() == azul_tiles_rs::gamestate::State::RoundActive {
-                highlight.board = Some(self.gs.current_player() as usize);
-            }
-            highlight.factory = self.selection.factory;
-            highlight.tile = self.selection.tile;
-            highlight.rows = self.selection.moves.iter().fold([false; 5], |mut acc, m| {
-                if let Destination::Row(ind) = m.destination {
-                    acc[ind as usize] = true;
-                }
-                acc
-            });
-            highlight.floor = self
-                .selection
-                .moves
-                .iter()
-                .any(|m| m.destination == Destination::Floor);
+                let highlight = self.selection.highlight(
+                    (self.gs.state() == azul_tiles_rs::gamestate::State::RoundActive)
+                        .then_some(self.gs.current_player() as usize),
+                );
 
-            if let Some(click) = draw_game(ui, &self.config, &self.gs, highlight, click) {
-                // if human turn, update selection
+                let view = self.gs.view(self.viewer());
+                let clicked = draw_game(ui, &self.config, &view, highlight, click);
+
+                // Preview the selected move under the mouse before it is played
                 if let Player::Human = self.players[self.gs.current_player() as usize] {
-                    let moves = self.gs.get_moves();
-                    let m = match click {
-                        Click::Factory(factory, tile) => {
-                            self.selection.factory = Some(factory as usize);
-                            self.selection.tile = Some(tile);
-                            self.selection.moves = moves
-                                .iter()
-                                .filter(|m| m.tile == tile && m.source == Source(factory))
-                                .cloned()
-                                .collect();
-                            None
-                        }
-                        Click::Row(row) => {
-                            if let Some(factory) = self.selection.factory {
-                                if let Some(tile) = self.selection.tile {
-                                    // find move
-                                    moves.iter().find(|m| {
-                                        m.source == Source(factory as u8)
-                                            && m.tile == tile
-                                            && m.destination == Destination::Row(row)
-                                    })
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
+                    let board = self.gs.current_player() as usize;
+                    let hovered = ctx
+                        .input(|input| input.pointer.hover_pos())
+                        .and_then(|pos| destination_at(&self.config, board, pos));
+                    let selected = self.selection.factory.zip(self.selection.tile);
+                    if let (Some(dest), Some((factory, tile))) = (hovered, selected) {
+                        match self.gs.explain_move(Source(factory as u8), tile, dest) {
+                            Ok(m) => {
+                                let preview = preview_board(&self.gs, &m);
+                                draw_preview(
+                                    ui,
+                                    &self.config,
+                                    board,
+                                    &self.gs.boards()[board],
+                                    &preview,
+                                );
                             }
-                        }
-                        Click::Floor => {
-                            if let Some(factory) = self.selection.factory {
-                                if let Some(tile) = self.selection.tile {
-                                    // find move
-                                    moves.iter().find(|m| {
-                                        m.source == Source(factory as u8)
-                                            && m.tile == tile
-                                            && m.destination == Destination::Floor
-                                    })
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
+                            Err(reason) => {
+                                egui::show_tooltip_at_pointer(
+                                    ctx,
+                                    ui.layer_id(),
+                                    egui::Id::new("illegal_move"),
+                                    |ui| ui.label(format!("Can't play there, {reason}")),
+                                );
                             }
                         }
-                    };
-                    if let Some(m) = m {
-                        self.gs.play_move(*m);
-                        self.selection = Selection::default();
                     }
                 }
-            } else if let Some(click) = click {
-                self.advance_gamestate();
-            }
+
+                if let Some(click) = clicked {
+                    // if human turn, update selection
+                    if let Player::Human = self.players[self.gs.current_player() as usize] {
+                        let moves = self.gs.get_moves();
+                        if let Some(m) = self.selection.click(&moves, click) {
+                            self.play_human_move(m);
+                        }
+                    }
+                } else if click.is_some() {
+                    self.advance_gamestate();
+                }
+            });
         });
     }
 }
@@ -339,9 +1001,40 @@ enum Click {
     Floor,
 }
 
+/// Smallest tile size before the game scrolls instead of shrinking
+const MIN_TILE_SIZE: f32 = 16.0;
+/// Largest tile size, so the game doesn't become huge on large screens
+const MAX_TILE_SIZE: f32 = 72.0;
+/// Gap between tiles as a fraction of the tile size
+const TILE_SPACING: f32 = 0.2;
+/// Space around and between the boards and factories, in tile sizes
+const MARGIN: f32 = 0.5;
+
+/// Sizes of the parts of the game in tile sizes, with their spacing
+mod units {
+    use super::TILE_SPACING;
+
+    /// Tile and the gap after it
+    const U: f32 = 1.0 + TILE_SPACING;
+    /// Board extends further left than right because the bag sits beside it
+    pub const BOARD_LEFT: f32 = 7.0 * U + (U + TILE_SPACING) / 2.0;
+    pub const BOARD_RIGHT: f32 = (11.0 * U + TILE_SPACING) / 2.0;
+    pub const BOARD_HEIGHT: f32 = 6.0 * U + 3.0 * TILE_SPACING;
+    /// Distance between the centres of neighbouring factories
+    pub const FACTORY_GAP: f32 = 2.0 * U + TILE_SPACING + 1.0 / 3.0;
+    /// Height of a row of factories, set by the centre which is the tallest
+    pub const FACTORY_ROW_HEIGHT: f32 = 3.0 * U + TILE_SPACING;
+}
+
 #[derive(Debug, Default)]
 struct UIConfig {
     window_size: Vec2,
+    /// Top left of the game on screen
+    origin: Pos2,
+    /// Size needed to draw the game, at least the window size
+    content_size: Vec2,
+    /// Number of rows the centre and factories are split over
+    factory_rows: usize,
     pub tile_size: f32,
     pub tile_spacing: f32,
     pub tile_rounding: f32,
@@ -352,58 +1045,89 @@ struct UIConfig {
 }
 
 impl UIConfig {
-    fn new(window_size: &Vec2) -> Self {
-        let mut conf = Self::default();
-        conf.update(window_size);
-        conf
+    /// Size of the game in tile sizes with the factories over `rows` rows
+    fn size_in_tiles(rows: usize) -> Vec2 {
+        let per_row = 6usize.div_ceil(rows);
+        let width = (units::BOARD_LEFT + units::BOARD_RIGHT)
+            .max(per_row as f32 * units::FACTORY_GAP)
+            + 2.0 * MARGIN;
+        let height = 2.0 * units::BOARD_HEIGHT
+            + rows as f32 * units::FACTORY_ROW_HEIGHT
+            + (rows + 3) as f32 * MARGIN;
+        Vec2::new(width, height)
     }
 
+    /// Choose the tile size and factory rows that fit the window best
     fn update(&mut self, window_size: &Vec2) {
         if *window_size == self.window_size {
             return;
         }
         self.window_size = *window_size;
-        let height = window_size.y;
-        let width = window_size.x;
-        self.tile_size = (0.04 * height).clamp(20.0, 50.0);
-        self.tile_spacing = self.tile_size * 0.2;
+        // Wrap the factories if it allows bigger tiles, preferring a single row
+        let (tile_size, rows) = [1, 2]
+            .into_iter()
+            .map(|rows| {
+                let size = Self::size_in_tiles(rows);
+                ((window_size.x / size.x).min(window_size.y / size.y), rows)
+            })
+            .fold(
+                (0.0, 1),
+                |best, next| if next.0 > best.0 { next } else { best },
+            );
+        self.tile_size = tile_size.clamp(MIN_TILE_SIZE, MAX_TILE_SIZE);
+        self.factory_rows = rows;
+        self.tile_spacing = self.tile_size * TILE_SPACING;
         self.tile_rounding = 0.1 * self.tile_size;
-        let board_y_0 = 0.8 * height;
-        let board_y_1 = 0.2 * height;
-        self.boards[0] = BoardUI::new(
-            Pos2::new(0.5 * width, board_y_0),
-            self.tile_size,
-            self.tile_spacing,
-        );
-        self.boards[1] = BoardUI::new(
-            Pos2::new(0.5 * width, board_y_1),
-            self.tile_size,
-            self.tile_spacing,
-        );
-        let factory_space = self.tile_size / 3.0;
-        let factory_gap =
-            2.0 * (self.tile_size + self.tile_spacing) + self.tile_spacing + factory_space;
-
-        let factory_left = Pos2::new(0.5 * width - 2.5 * factory_gap, 0.5 * height);
+        self.content_size = (Self::size_in_tiles(rows) * self.tile_size).max(*window_size);
+        self.layout();
+    }
 
-        for i in 1..6 {
-            self.factories[i - 1] = FactoryUI::new(
-                factory_left + Vec2::new(i as f32 * factory_gap, 0.0),
-                self.tile_size,
-                self.tile_spacing,
-            );
+    /// Move the game to start at `origin`, which changes when scrolling
+    fn set_origin(&mut self, origin: Pos2) {
+        if origin != self.origin {
+            self.origin = origin;
+            self.layout();
         }
+    }
 
-        self.bag = BagUI::new(
+    /// Position every part of the game, stacked top to bottom and centred horizontally
+    fn layout(&mut self) {
+        let (ts, sp) = (self.tile_size, self.tile_spacing);
+        let margin = MARGIN * ts;
+        // Shift the boards right so that they and the bag are centred together
+        let centre_x = self.origin.x + 0.5 * self.content_size.x;
+        let board_x = centre_x + 0.5 * (units::BOARD_LEFT - units::BOARD_RIGHT) * ts;
+        let board_height = units::BOARD_HEIGHT * ts;
+        let row_height = units::FACTORY_ROW_HEIGHT * ts;
+        let factories_height =
+            self.factory_rows as f32 * row_height + (self.factory_rows - 1) as f32 * margin;
+        // Spread any spare height evenly between the parts
+        let used = 2.0 * board_height + factories_height;
+        let gap = ((self.content_size.y - used) / 4.0).max(margin);
+
+        let board_y_1 = self.origin.y + gap + 0.5 * board_height;
+        let factories_top = board_y_1 + 0.5 * board_height + gap;
+        let board_y_0 = factories_top + factories_height + gap + 0.5 * board_height;
+        self.boards[0] = BoardUI::new(Pos2::new(board_x, board_y_0), ts, sp);
+        self.boards[1] = BoardUI::new(Pos2::new(board_x, board_y_1), ts, sp);
+
+        // Centre first, then the factories, filling each row in turn
+        let per_row = 6usize.div_ceil(self.factory_rows);
+        let factory_gap = units::FACTORY_GAP * ts;
+        let slot = |i: usize| {
+            let (row, col) = (i / per_row, i % per_row);
+            let in_row = per_row.min(6 - row * per_row);
             Pos2::new(
-                0.5 * width - 7.0 * (self.tile_size + self.tile_spacing),
-                board_y_1,
-            ),
-            self.tile_size,
-            self.tile_spacing,
-        );
+                centre_x + (col as f32 - 0.5 * (in_row - 1) as f32) * factory_gap,
+                factories_top + (row as f32 + 0.5) * row_height + row as f32 * margin,
+            )
+        };
+        self.centre = CentreUI::new(slot(0), ts, sp);
+        for i in 1..6 {
+            self.factories[i - 1] = FactoryUI::new(slot(i), ts, sp);
+        }
 
-        self.centre = CentreUI::new(factory_left, self.tile_size, self.tile_spacing);
+        self.bag = BagUI::new(Pos2::new(board_x - 7.0 * (ts + sp), board_y_1), ts, sp);
     }
 }
 
@@ -422,9 +1146,9 @@ impl BoardUI {
     fn new(centre: Pos2, tile_size: f32, tile_space: f32) -> Self {
         let wall_tl = centre + Vec2::new(tile_size + tile_space, -(2.5 * (tile_size + tile_space)));
         let mut wall: [[Pos2; 5]; 5] = Default::default();
-        for i in 0..5 {
-            for j in 0..5 {
-                wall[i][j] = wall_tl
+        for (i, row) in wall.iter_mut().enumerate() {
+            for (j, pos) in row.iter_mut().enumerate() {
+                *pos = wall_tl
                     + Vec2::new(
                         j as f32 * (tile_size + tile_space),
                         i as f32 * (tile_size + tile_space),
@@ -437,16 +1161,16 @@ impl BoardUI {
                 2.5 * (tile_size + tile_space) + tile_space,
             );
         let mut floor: [Pos2; 7] = Default::default();
-        for i in 0..7 {
-            floor[i] = floor_first + Vec2::new(i as f32 * (tile_size + tile_space), 0.0);
+        for (i, pos) in floor.iter_mut().enumerate() {
+            *pos = floor_first + Vec2::new(i as f32 * (tile_size + tile_space), 0.0);
         }
 
         // rows
         let row_tr = wall_tl + Vec2::new(-2.0 * (tile_size + tile_space), 0.0);
         let mut rows: [[Pos2; 5]; 5] = Default::default();
-        for i in 0..5 {
-            for j in 0..5 {
-                rows[i][j] = row_tr
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, pos) in row.iter_mut().enumerate() {
+                *pos = row_tr
                     + Vec2::new(
                         j as f32 * -(tile_size + tile_space),
                         i as f32 * (tile_size + tile_space),
@@ -480,7 +1204,7 @@ struct FactoryUI {
 impl FactoryUI {
     fn new(centre: Pos2, tile_size: f32, tile_space: f32) -> Self {
         let mut tiles: [Pos2; 4] = Default::default();
-        for i in 0..4 {
+        for (i, pos) in tiles.iter_mut().enumerate() {
             let (x, y) = match i {
                 0 => (-1.0, -1.0),
                 1 => (1.0, -1.0),
@@ -488,7 +1212,7 @@ impl FactoryUI {
                 3 => (1.0, 1.0),
                 _ => unreachable!(),
             };
-            tiles[i] = centre
+            *pos = centre
                 + Vec2::new(
                     x * 0.5 * (tile_size + tile_space),
                     y * 0.5 * (tile_size + tile_space),
@@ -514,8 +1238,10 @@ struct CentreUI {
 
 impl CentreUI {
     fn new(centre: Pos2, tile_size: f32, tile_space: f32) -> Self {
-        let mut c = CentreUI::default();
-        c.centre = centre;
+        let mut c = CentreUI {
+            centre,
+            ..Default::default()
+        };
         for i in 0..6 {
             let (y, x) = match i {
                 0 => (-1.0, -1.0),
@@ -542,7 +1268,6 @@ impl CentreUI {
 
 #[derive(Debug, Default)]
 struct BagUI {
-    centre: Pos2,
     border: Vec2,
     tiles: [Pos2; 5],
 }
@@ -550,7 +1275,6 @@ struct BagUI {
 impl BagUI {
     fn new(centre: Pos2, tile_size: f32, tile_space: f32) -> Self {
         let mut b = BagUI::default();
-        b.centre = centre;
         let top = centre - Vec2::new(0.0, 2.0 * (tile_size + tile_space));
         for i in 0..5 {
             b.tiles[i] = top + Vec2::new(0.0, i as f32 * (tile_size + tile_space))
@@ -582,16 +1306,62 @@ struct Selection {
     row: Option<RowIndex>,
 }
 
-fn draw_game(
+impl Selection {
+    /// Highlight the selection on `board`, the board of the player to move
+    fn highlight(&self, board: Option<usize>) -> Highlight {
+        Highlight {
+            board,
+            tile: self.tile,
+            factory: self.factory,
+            rows: self.moves.iter().fold([false; 5], |mut acc, m| {
+                if let Destination::Row(ind) = m.destination {
+                    acc[ind as usize] = true;
+                }
+                acc
+            }),
+            floor: self
+                .moves
+                .iter()
+                .any(|m| m.destination == Destination::Floor),
+        }
+    }
+
+    /// Select tiles from a factory or return the move to a clicked row or floor
+    fn click(&mut self, moves: &[Move], click: Click) -> Option<Move> {
+        let destination = match click {
+            Click::Factory(factory, tile) => {
+                self.factory = Some(factory as usize);
+                self.tile = Some(tile);
+                self.moves = moves
+                    .iter()
+                    .filter(|m| m.tile == tile && m.source == Source(factory))
+                    .cloned()
+                    .collect();
+                return None;
+            }
+            Click::Row(row) => Destination::Row(row),
+            Click::Floor => Destination::Floor,
+        };
+        let (factory, tile) = (self.factory?, self.tile?);
+        moves
+            .iter()
+            .find(|m| {
+                m.source == Source(factory as u8) && m.tile == tile && m.destination == destination
+            })
+            .copied()
+    }
+}
+
+fn draw_game<const P: usize>(
     ui: &mut egui::Ui,
     config: &UIConfig,
-    gs: &Gamestate<2, 6>,
+    gs: &PlayerView<P, 6>,
     highlight: Highlight,
     click: Option<Pos2>,
 ) -> Option<Click> {
     let mut clicked = None;
     // Draw player boards
-    for i in 0..2 {
+    for i in 0..P {
         clicked = clicked.or(draw_board(ui, config, gs, i, &highlight, click));
     }
 
@@ -603,7 +1373,7 @@ fn draw_game(
     }
 
     // Draw bag
-    draw_bag(ui, config, gs.tilebag());
+    draw_bag(ui, config, gs.bag());
     clicked
 }
 // Draw bag of tiles
@@ -632,10 +1402,10 @@ fn draw_bag(ui: &mut egui::Ui, config: &UIConfig, bag: &TileGroup) {
     }
 }
 
-fn draw_centre(
+fn draw_centre<const P: usize>(
     ui: &mut egui::Ui,
     config: &UIConfig,
-    gs: &Gamestate<2, 6>,
+    gs: &PlayerView<P, 6>,
     highlight: &Highlight,
     click: Option<Pos2>,
 ) -> Option<Click> {
@@ -697,10 +1467,10 @@ fn draw_centre(
 }
 
 /// Draw factory to screen
-fn draw_factory(
+fn draw_factory<const P: usize>(
     ui: &mut egui::Ui,
     config: &UIConfig,
-    gs: &Gamestate<2, 6>,
+    gs: &PlayerView<P, 6>,
     factory: usize,
     highlight: &Highlight,
     click: Option<Pos2>,
@@ -739,14 +1509,16 @@ fn draw_factory(
 }
 
 /// Draw player board to screen
-fn draw_board(
+fn draw_board<const P: usize>(
     ui: &mut egui::Ui,
     config: &UIConfig,
-    gs: &Gamestate<2, 6>,
+    gs: &PlayerView<P, 6>,
     board: usize,
     highlight: &Highlight,
     click: Option<Pos2>,
 ) -> Option<Click> {
+    // Boards hidden from the viewer aren't drawn
+    let player_board = gs.board(board)?;
     let selected = highlight.board == Some(board);
     // Draw border
     ui.painter().rect_stroke(
@@ -760,9 +1532,8 @@ fn draw_board(
         egui::StrokeKind::Inside,
     );
     // Draw wall
-    for i in 0usize..5 {
-        for j in 0usize..5 {
-            let tile = gs.boards()[board].wall[(i.into(), j.into())];
+    for (i, cells) in player_board.wall.iter().enumerate() {
+        for (j, tile) in cells.into_iter().enumerate() {
             if let Some(tile) = tile {
                 draw_tile(
                     ui,
@@ -775,7 +1546,7 @@ fn draw_board(
                 draw_tile_border(
                     ui,
                     config,
-                    tile_to_colour(&WALL_COLOURS[i][j]),
+                    tile_to_colour(&gs.wall_pattern().colours()[i][j]),
                     config.boards[board].wall[i][j],
                     1.0,
                     None,
@@ -787,16 +1558,16 @@ fn draw_board(
     let mut clicked = None;
 
     // Draw rows
-    for i in 0usize..5 {
+    for (i, row) in RowIndex::rows(5).enumerate() {
         let colour = if selected && highlight.rows[i] {
             Color32::PURPLE
         } else {
             Color32::WHITE
         };
         for j in 0..(i + 1) {
-            let tile = gs.boards()[board].rows[i].tile();
+            let tile = player_board.rows[i].tile();
 
-            if gs.boards()[board].rows[i].count() as usize > j {
+            if player_board.rows[i].count() as usize > j {
                 if let Some(tile) = tile {
                     if draw_tile(
                         ui,
@@ -805,7 +1576,7 @@ fn draw_board(
                         config.boards[board].rows[i][j],
                         click,
                     ) {
-                        clicked = Some(Click::Row(RowIndex::from(i as u8)));
+                        clicked = Some(Click::Row(row));
                     }
                 }
             } else if draw_tile_border(
@@ -816,7 +1587,7 @@ fn draw_board(
                 1.0,
                 click,
             ) {
-                clicked = Some(Click::Row(RowIndex::from(i as u8)));
+                clicked = Some(Click::Row(row));
             }
         }
     }
@@ -842,7 +1613,7 @@ fn draw_board(
         }
     }
     // Check if player has first player token
-    let offset = if gs.boards()[board].first_player_tile {
+    let offset = if player_board.floor.has_first_player_tile() {
         draw_tile(
             ui,
             config,
@@ -855,8 +1626,9 @@ fn draw_board(
         0
     };
     // Draw floor
-    for (i, tile) in gs.boards()[board]
+    for (i, tile) in player_board
         .floor
+        .tiles()
         .tile_vec()
         .iter()
         .take(7 - offset)
@@ -865,29 +1637,144 @@ fn draw_board(
         draw_tile(
             ui,
             config,
-            tile_to_colour(&tile),
+            tile_to_colour(tile),
             config.boards[board].floor[i + offset],
             click,
         );
     }
 
     // Score
-    let mut font = FontId {
+    let font = FontId {
         size: config.tile_size,
         ..Default::default()
     };
     ui.painter().text(
         config.boards[board].score,
         egui::Align2::CENTER_CENTER,
-        gs.boards()[board].score.to_string()
-            + "|"
-            + &gs.boards()[board].predicted_score.to_string(),
+        player_board.score.to_string() + "|" + &player_board.predicted_score.to_string(),
         font,
         Color32::WHITE,
     );
     clicked
 }
 
+/// Score against par and the scripted opponent's last move
+fn solitaire_status(solitaire: &Solitaire) -> String {
+    let mut status = format!(
+        "Solitaire round {}: score {}, par {}",
+        solitaire.gamestate().round(),
+        solitaire.score(),
+        solitaire.par()
+    );
+    if solitaire.is_over() {
+        status += &format!(", finished {:+}", solitaire.result());
+    } else if let Some(take) = solitaire.last_take() {
+        let from = match take.source.0 {
+            0 => "the centre".to_string(),
+            f => format!("factory {f}"),
+        };
+        status += &format!(", opponent took {} {:?} from {from}", take.count, take.tile);
+    }
+    status
+}
+
+/// Row or floor of a board under `pos`
+fn destination_at(config: &UIConfig, board: usize, pos: Pos2) -> Option<Destination> {
+    let contains = |centre: &Pos2| {
+        Rect::from_center_size(*centre, Vec2::new(config.tile_size, config.tile_size)).contains(pos)
+    };
+    let layout = &config.boards[board];
+    if let Some(row) = RowIndex::rows(5).find(|&row| {
+        let i = usize::from(row);
+        layout.rows[i][..=i].iter().any(contains)
+    }) {
+        return Some(Destination::Row(row));
+    }
+    layout
+        .floor
+        .iter()
+        .any(contains)
+        .then_some(Destination::Floor)
+}
+
+/// Move as it is described in the hints window
+fn hint_text(m: &Move) -> String {
+    let source = match m.source.0 {
+        0 => "centre".to_string(),
+        i => format!("factory {i}"),
+    };
+    let destination = match m.destination {
+        Destination::Row(row) => format!("row {}", row as usize + 1),
+        Destination::Floor => "floor".to_string(),
+    };
+    format!("{} {:?} from {source} to {destination}", m.count, m.tile)
+}
+
+/// Copy of the current player's board with the move applied
+fn preview_board<const P: usize>(gs: &Gamestate<P, 6>, m: &Move) -> PlayerBoard {
+    let mut board = gs.boards()[gs.current_player() as usize];
+    let fp = gs.first_player_tile() && m.source == Source(0);
+    // Also updates the predicted score
+    board.place_move(m, fp);
+    board
+}
+
+/// Draw the tiles a move would add as faded ghost tiles, with the resulting score
+fn draw_preview(
+    ui: &mut egui::Ui,
+    config: &UIConfig,
+    board: usize,
+    current: &PlayerBoard,
+    preview: &PlayerBoard,
+) {
+    let layout = &config.boards[board];
+    let ghost = |tile: &Tile| tile_to_colour(tile).gamma_multiply(0.4);
+
+    for i in 0..5 {
+        if let Some(tile) = preview.rows[i].tile() {
+            for j in current.rows[i].count()..preview.rows[i].count() {
+                draw_tile(ui, config, ghost(&tile), layout.rows[i][j as usize], None);
+            }
+        }
+    }
+
+    // New floor tiles go after the existing ones
+    let mut slot = current.floor.len() as usize;
+    if preview.floor.has_first_player_tile() && !current.floor.has_first_player_tile() && slot < 7 {
+        draw_tile(
+            ui,
+            config,
+            Color32::PURPLE.gamma_multiply(0.4),
+            layout.floor[slot],
+            None,
+        );
+        slot += 1;
+    }
+    for ((&before, _), (&after, tile)) in
+        current.floor.tiles().into_iter().zip(preview.floor.tiles())
+    {
+        for _ in before..after {
+            if slot < 7 {
+                draw_tile(ui, config, ghost(&tile), layout.floor[slot], None);
+            }
+            slot += 1;
+        }
+    }
+
+    let change = preview.predicted_score as i16 - current.predicted_score as i16;
+    ui.painter().text(
+        layout.score + Vec2::new(0.0, config.tile_size + config.tile_spacing),
+        egui::Align2::CENTER_CENTER,
+        format!(
+            "{} ({change:+}) floor -{}",
+            preview.predicted_score,
+            preview.floor_penalty()
+        ),
+        FontId::default(),
+        Color32::LIGHT_GRAY,
+    );
+}
+
 /// Draw a tile to the screen
 fn draw_tile(
     ui: &mut egui::Ui,
@@ -982,6 +1869,7 @@ fn tile_to_colour(tile: &Tile) -> egui::Color32 {
         Tile::Red => egui::Color32::RED,
         Tile::Black => egui::Color32::GREEN,
         Tile::White => egui::Color32::WHITE,
+        Tile::Orange => egui::Color32::ORANGE,
     }
 }
 
@@ -992,5 +1880,6 @@ fn tile_to_text_colour(tile: &Tile) -> egui::Color32 {
         Tile::Red => egui::Color32::BLACK,
         Tile::Black => egui::Color32::BLACK,
         Tile::White => egui::Color32::BLACK,
+        Tile::Orange => egui::Color32::BLACK,
     }
 }
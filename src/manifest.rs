@@ -0,0 +1,161 @@
+//! Manifests for reproducing experiments
+//!
+//! Tournaments, genetic algorithm runs and PPO training write a [Manifest] alongside their
+//! results with what is needed to run them again: the config, the master seed, the version
+//! and a hash of the program, and hashes of the models and other files read.
+//! Running again from a manifest reports anything that no longer matches, so a changed
+//! result can be put down to a changed engine rather than a different setup.
+//!
+//! Searches limited by time rather than depth depend on the machine, so only repeat
+//! exactly when every player searches to a fixed depth
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::AzulError;
+
+/// File name of the manifest in an output directory
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+    /// What was run, eg `tournament`
+    pub experiment: String,
+    /// Crate version of the program that ran it
+    pub version: String,
+    /// Hash of the program's executable, if it could be read
+    pub program: Option<String>,
+    /// Command line the program was run with
+    pub command: Vec<String>,
+    /// Seed every other random choice is drawn from
+    pub seed: u64,
+    /// Config of the run, in the experiment's own format
+    pub config: serde_json::Value,
+    /// Hashes of the files read, by path
+    pub files: BTreeMap<PathBuf, String>,
+}
+
+impl Manifest {
+    /// Manifest for the running program
+    pub fn new(experiment: &str, seed: u64, config: &impl Serialize) -> Result<Self, AzulError> {
+        Ok(Self {
+            experiment: experiment.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            program: program_hash(),
+            command: std::env::args().collect(),
+            seed,
+            config: serde_json::to_value(config).map_err(|e| AzulError::Config {
+                experiment: experiment.to_string(),
+                reason: e.to_string(),
+            })?,
+            files: BTreeMap::new(),
+        })
+    }
+
+    /// Record the hash of a file read by the run
+    pub fn with_file(mut self, path: &Path) -> Result<Self, AzulError> {
+        self.files.insert(path.to_path_buf(), hash_file(path)?);
+        Ok(self)
+    }
+
+    /// Config of the run, for `experiment`
+    pub fn config<T: DeserializeOwned>(&self, experiment: &str) -> Result<T, AzulError> {
+        if self.experiment != experiment {
+            return Err(AzulError::WrongExperiment {
+                expected: experiment.to_string(),
+                found: self.experiment.clone(),
+            });
+        }
+        serde_json::from_value(self.config.clone()).map_err(|e| AzulError::Config {
+            experiment: experiment.to_string(),
+            reason: e.to_string(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, AzulError> {
+        let file = std::fs::File::open(path).map_err(|e| AzulError::load(path, e))?;
+        serde_json::from_reader(file).map_err(|e| AzulError::load(path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), AzulError> {
+        let file = std::fs::File::create(path).map_err(|e| AzulError::save(path, e))?;
+        serde_json::to_writer_pretty(file, self).map_err(|e| AzulError::save(path, e))
+    }
+
+    /// Differences between the running program and files and those recorded,
+    /// empty if the run can be repeated as it was
+    pub fn differences(&self) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.version != env!("CARGO_PKG_VERSION") {
+            differences.push(format!(
+                "Version {} was {}",
+                env!("CARGO_PKG_VERSION"),
+                self.version
+            ));
+        }
+        if self.program.is_some() && program_hash() != self.program {
+            differences.push("The program has changed".into());
+        }
+        for (path, hash) in &self.files {
+            match hash_file(path) {
+                Ok(h) if h == *hash => (),
+                Ok(_) => differences.push(format!("{path:?} has changed")),
+                Err(e) => differences.push(e.to_string()),
+            }
+        }
+        differences
+    }
+}
+
+/// Hash of the running executable
+fn program_hash() -> Option<String> {
+    hash_file(&std::env::current_exe().ok()?).ok()
+}
+
+/// 64 bit FNV-1a hash of a file's contents, in hex
+pub fn hash_file(path: &Path) -> Result<String, AzulError> {
+    let bytes = std::fs::read(path).map_err(|e| AzulError::load(path, e))?;
+    Ok(format!("{:016x}", hash(&bytes)))
+}
+
+/// FNV-1a, which is stable between builds unlike the std hasher
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{hash, Manifest};
+
+    #[test]
+    fn manifest() {
+        // Known FNV-1a values
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+
+        let dir = std::env::temp_dir().join("azul_manifest_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let model = dir.join("model.json");
+        std::fs::write(&model, "[1, 2, 3]").unwrap();
+        let manifest = Manifest::new("test", 7, &[1, 2])
+            .unwrap()
+            .with_file(&model)
+            .unwrap();
+        assert!(manifest.differences().is_empty());
+        assert_eq!(manifest.config::<[u8; 2]>("test").unwrap(), [1, 2]);
+        assert!(manifest.config::<[u8; 2]>("tournament").is_err());
+
+        let path = dir.join("manifest.json");
+        manifest.save(&path).unwrap();
+        assert_eq!(Manifest::load(&path).unwrap(), manifest);
+
+        std::fs::write(&model, "[1, 2, 4]").unwrap();
+        assert_eq!(manifest.differences().len(), 1);
+    }
+}
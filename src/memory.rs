@@ -0,0 +1,86 @@
+//! Optional allocation tracking for resource reports
+//!
+//! A binary opts in by installing [TrackingAllocator] as its global allocator:
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: azul_tiles_rs::memory::TrackingAllocator = azul_tiles_rs::memory::TrackingAllocator;
+//! ```
+//! Counts are kept per thread, so runners on different threads don't see each other's allocations.
+//! Memory freed on another thread than it was allocated on is not tracked exactly
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Set once the first allocation goes through [TrackingAllocator]
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Bytes allocated by this thread and not yet freed
+    static CURRENT: Cell<isize> = const { Cell::new(0) };
+    /// Highest value of [CURRENT] since the last [measure] started
+    static PEAK: Cell<isize> = const { Cell::new(0) };
+}
+
+/// System allocator that counts the bytes in use by each thread
+pub struct TrackingAllocator;
+
+fn track(change: isize) {
+    // Fails while the thread is being torn down, when nothing is being measured
+    let _ = CURRENT.try_with(|current| {
+        let now = current.get() + change;
+        current.set(now);
+        let _ = PEAK.try_with(|peak| peak.set(peak.get().max(now)));
+    });
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            INSTALLED.store(true, Ordering::Relaxed);
+            track(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            INSTALLED.store(true, Ordering::Relaxed);
+            track(layout.size() as isize);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        track(-(layout.size() as isize));
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            track(new_size as isize - layout.size() as isize);
+        }
+        new
+    }
+}
+
+/// Check if [TrackingAllocator] is the global allocator
+pub fn is_tracking() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+/// Run `f`, also returning the most bytes it had allocated at once on this thread,
+/// or `None` without a [TrackingAllocator]
+pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Option<u64>) {
+    let start = CURRENT.with(Cell::get);
+    let outer = PEAK.with(|peak| peak.replace(start));
+    let result = f();
+    let peak = PEAK.with(|peak| peak.replace(outer.max(peak.get())));
+    let bytes = is_tracking().then(|| (peak - start).max(0) as u64);
+    (result, bytes)
+}
@@ -0,0 +1,24 @@
+//! Watching games as they are played
+//!
+//! A [GameObserver] registered with [Runner::with_observer](crate::runner::Runner::with_observer)
+//! is told about every deal, move and round end of the runner's games, so loggers and
+//! collectors of training data don't need their own game loop
+
+use crate::gamestate::{Gamestate, Move, RoundSummary};
+
+/// Callbacks for the events of a game, each given the game as it is after the event.
+/// All do nothing unless implemented
+pub trait GameObserver<const P: usize, const F: usize> {
+    /// Tiles were dealt to the factories for a new round, including the first
+    fn on_deal(&mut self, _gs: &Gamestate<P, F>) {}
+
+    /// `player` played `m`
+    fn on_move(&mut self, _gs: &Gamestate<P, F>, _player: u8, _m: &Move) {}
+
+    /// Round was scored. Unless the game is over the next round has been dealt,
+    /// and [GameObserver::on_deal] follows
+    fn on_round_end(&mut self, _gs: &Gamestate<P, F>, _summary: &RoundSummary<P>) {}
+
+    /// Game is over, or was ended early by adjudication
+    fn on_game_end(&mut self, _gs: &Gamestate<P, F>) {}
+}
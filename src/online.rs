@@ -0,0 +1,395 @@
+//! Client for playing games against remote opponents
+//!
+//! Messages are JSON in WebSocket text frames. A [Client] talks to the server on a
+//! background thread and reconnects when the connection drops, rejoining the current game
+//! so the server can resend it. Games are sent as a seed and the moves played so far,
+//! which [rebuild] replays to recreate the gamestate
+
+use std::{
+    io,
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use crate::{
+    gamestate::{Gamestate, Move, State},
+    variant::Variant,
+};
+
+/// Longest wait between reconnection attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Longest wait for a message before sending any queued by the client
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Introduce the player, the server replies with the lobby
+    Hello { name: String },
+    /// Open a new game and wait for an opponent
+    Create,
+    /// Join a waiting game from the lobby
+    Join { game: u64 },
+    /// Resume a game after reconnecting
+    Rejoin { game: u64, token: String },
+    /// Play a move, as from [Move::to_index]
+    Move { index: usize },
+    /// Give up the current game and return to the lobby
+    Leave,
+}
+
+/// Game waiting for an opponent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LobbyGame {
+    pub id: u64,
+    pub host: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Lobby {
+        games: Vec<LobbyGame>,
+    },
+    /// A game has started or been resumed
+    Start {
+        game: u64,
+        /// Secret used to rejoin the game
+        token: String,
+        /// Seat of this client
+        seat: u8,
+        seed: u64,
+        first_player: u8,
+        players: [String; 2],
+        /// Moves played so far
+        moves: Vec<usize>,
+        /// Rules of the game, standard if not sent
+        #[serde(default)]
+        variant: Variant,
+    },
+    /// Opponent played a move
+    Move {
+        index: usize,
+    },
+    /// Opponent lost connection, the game is paused until they return
+    OpponentDisconnected,
+    OpponentReconnected,
+    /// Opponent left or timed out, the game is over
+    OpponentLeft,
+    Error {
+        message: String,
+    },
+}
+
+/// Received by the application from [Client::poll]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Connected,
+    Message(ServerMessage),
+    /// Connection lost, will try to reconnect
+    Disconnected(String),
+}
+
+/// Connection to a game server
+pub struct Client {
+    /// Messages for the background thread to send, while connected
+    outgoing: Arc<Mutex<Option<Sender<ClientMessage>>>>,
+    /// Game to rejoin after reconnecting
+    current: Arc<Mutex<Option<(u64, String)>>>,
+    closed: Arc<AtomicBool>,
+    events: Receiver<Event>,
+}
+
+impl Client {
+    /// Start connecting to `addr` in the background, a `ws://` url or `host:port`
+    pub fn connect(addr: &str, name: &str) -> Self {
+        let outgoing = Arc::new(Mutex::new(None));
+        let current = Arc::new(Mutex::new(None));
+        let closed = Arc::new(AtomicBool::new(false));
+        let (sender, events) = mpsc::channel();
+        let url = if addr.contains("://") {
+            addr.to_string()
+        } else {
+            format!("ws://{addr}")
+        };
+        let connection = Connection {
+            url,
+            name: name.to_string(),
+            outgoing: outgoing.clone(),
+            current: current.clone(),
+            closed: closed.clone(),
+            sender,
+        };
+        thread::spawn(move || connection.run());
+        Self {
+            outgoing,
+            current,
+            closed,
+            events,
+        }
+    }
+
+    /// Send a message, failing if currently disconnected
+    pub fn send(&self, message: &ClientMessage) -> io::Result<()> {
+        if *message == ClientMessage::Leave {
+            *self.current.lock().unwrap() = None;
+        }
+        let outgoing = self.outgoing.lock().unwrap();
+        let outgoing = outgoing.as_ref().ok_or(io::ErrorKind::NotConnected)?;
+        outgoing
+            .send(message.clone())
+            .map_err(|_| io::ErrorKind::NotConnected.into())
+    }
+
+    /// Events received since the last call
+    pub fn poll(&self) -> Vec<Event> {
+        self.events.try_iter().collect()
+    }
+
+    pub fn connected(&self) -> bool {
+        self.outgoing.lock().unwrap().is_some()
+    }
+}
+
+/// The background thread closes the connection once it sees the client is gone
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Background side of a [Client]
+struct Connection {
+    url: String,
+    name: String,
+    outgoing: Arc<Mutex<Option<Sender<ClientMessage>>>>,
+    current: Arc<Mutex<Option<(u64, String)>>>,
+    closed: Arc<AtomicBool>,
+    sender: Sender<Event>,
+}
+
+impl Connection {
+    /// Connect, read until the connection drops and repeat until the client is dropped
+    fn run(self) {
+        let mut backoff = Duration::from_millis(250);
+        while !self.closed.load(Ordering::Relaxed) {
+            let error = match self.session() {
+                Ok(()) => "Server closed the connection".to_string(),
+                Err(e) => e.to_string(),
+            };
+            if self.outgoing.lock().unwrap().take().is_some() {
+                // Was connected, so retry quickly
+                backoff = Duration::from_millis(250);
+            }
+            if self.closed.load(Ordering::Relaxed)
+                || self.sender.send(Event::Disconnected(error)).is_err()
+            {
+                return;
+            }
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    /// Pass messages both ways until the connection drops or the client is dropped
+    fn session(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let (mut socket, _) = tungstenite::connect(&self.url)?;
+        if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+            stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        }
+        write_message(
+            &mut socket,
+            &ClientMessage::Hello {
+                name: self.name.clone(),
+            },
+        )?;
+        if let Some((game, token)) = self.current.lock().unwrap().clone() {
+            write_message(&mut socket, &ClientMessage::Rejoin { game, token })?;
+        }
+        let (sender, outgoing) = mpsc::channel();
+        *self.outgoing.lock().unwrap() = Some(sender);
+        if self.sender.send(Event::Connected).is_err() {
+            return Ok(());
+        }
+        while !self.closed.load(Ordering::Relaxed) {
+            for message in outgoing.try_iter() {
+                write_message(&mut socket, &message)?;
+            }
+            let message: ServerMessage = match socket.read() {
+                Ok(Message::Text(text)) => serde_json::from_str(&text)?,
+                Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+                // Pings are answered by the socket
+                Ok(_) => continue,
+                Err(tungstenite::Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(e.into()),
+            };
+            match &message {
+                ServerMessage::Start { game, token, .. } => {
+                    *self.current.lock().unwrap() = Some((*game, token.clone()));
+                }
+                ServerMessage::OpponentLeft => *self.current.lock().unwrap() = None,
+                _ => (),
+            }
+            if self.sender.send(Event::Message(message)).is_err() {
+                break;
+            }
+        }
+        let _ = socket.close(None);
+        let _ = socket.flush();
+        Ok(())
+    }
+}
+
+fn write_message(
+    socket: &mut WebSocket<MaybeTlsStream<TcpStream>>,
+    message: &ClientMessage,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let text = serde_json::to_string(message)?;
+    Ok(socket.send(Message::text(text))?)
+}
+
+/// Find the legal move with the given index, see [Gamestate::move_from_index]
+pub fn move_from_index(gs: &Gamestate<2, 6>, index: usize) -> Option<Move> {
+    gs.move_from_index(index)
+}
+
+/// Play a move sent by the server, ending the round first if needed.
+/// None if the move is not legal
+pub fn apply_index(gs: &mut Gamestate<2, 6>, index: usize) -> Option<State> {
+    if gs.state() == State::RoundEnd {
+        gs.end_round();
+    }
+    if gs.state() != State::RoundActive {
+        return None;
+    }
+    let m = move_from_index(gs, index)?;
+    Some(gs.play_move(m))
+}
+
+/// Recreate a game from its seed, rules and the moves played
+pub fn rebuild(
+    seed: u64,
+    first_player: u8,
+    variant: Variant,
+    moves: &[usize],
+) -> Option<Gamestate<2, 6>> {
+    let mut gs = Gamestate::new_2_player_with_seed(seed, first_player);
+    gs.set_variant(variant).ok()?;
+    for &index in moves {
+        apply_index(&mut gs, index)?;
+    }
+    Some(gs)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::TcpListener,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use tungstenite::Message;
+
+    use super::{rebuild, Client, ClientMessage, Event, ServerMessage};
+
+    #[test]
+    fn reconnects_and_rejoins() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let mut received = Vec::new();
+            for attempt in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                let mut socket = tungstenite::accept(stream).unwrap();
+                let read = |socket: &mut tungstenite::WebSocket<_>| {
+                    let text = socket.read().unwrap().into_text().unwrap();
+                    serde_json::from_str::<ClientMessage>(&text).unwrap()
+                };
+                received.push(read(&mut socket));
+                if attempt == 0 {
+                    let start = ServerMessage::Start {
+                        game: 7,
+                        token: "secret".into(),
+                        seat: 1,
+                        seed: 3,
+                        first_player: 0,
+                        players: ["a".into(), "b".into()],
+                        moves: vec![],
+                        variant: Default::default(),
+                    };
+                    let text = serde_json::to_string(&start).unwrap();
+                    socket.send(Message::text(text)).unwrap();
+                    // Drop the connection without closing it
+                } else {
+                    received.push(read(&mut socket));
+                }
+            }
+            received
+        });
+
+        let client = Client::connect(&addr, "b");
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut events = Vec::new();
+        while !events.iter().any(|e| matches!(e, Event::Disconnected(_))) {
+            assert!(Instant::now() < deadline);
+            events.extend(client.poll());
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, Event::Message(ServerMessage::Start { game: 7, .. }))));
+
+        let received = server.join().unwrap();
+        let hello = ClientMessage::Hello { name: "b".into() };
+        assert_eq!(
+            received,
+            [
+                hello.clone(),
+                hello,
+                ClientMessage::Rejoin {
+                    game: 7,
+                    token: "secret".into()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn rebuild_game() {
+        let mut gs = super::Gamestate::new_2_player_with_seed(5, 1);
+        let mut moves = Vec::new();
+        for _ in 0..12 {
+            if gs.state() == super::State::RoundEnd {
+                gs.end_round();
+            }
+            let m = gs.get_moves()[0];
+            moves.push(m.to_index());
+            gs.play_move(m);
+        }
+        assert_eq!(rebuild(5, 1, Default::default(), &moves).unwrap(), gs);
+        // An index from the server past the last action is turned away
+        let before = gs.clone();
+        assert_eq!(super::apply_index(&mut gs, 256 * 30), None);
+        assert_eq!(gs, before);
+        moves.push(256 * 30);
+        assert_eq!(rebuild(5, 1, Default::default(), &moves), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! Varied openings for self-play
+//!
+//! Players that always pick their best move start every game the same way, so self-play
+//! for training only ever sees a few openings. An [Opening] plays the first moves of each
+//! game instead, uniformly from the legal moves or weighted by an [OpeningBook].
+//! The moves are drawn from the game seed, so both games of a pair open the same way.
+//!
+//! A [PositionSuite] instead fixes the start of each game to positions chosen ahead of time,
+//! eg ones a strong engine finds balanced, to take the luck of the deal out of comparisons
+
+use std::{collections::BTreeMap, path::Path};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rand_distr::{Distribution, WeightedIndex};
+
+use crate::{
+    error::{AzulError, ReplayError},
+    gamestate::{Gamestate, Move, State},
+};
+
+/// Weights of moves by [Move::to_index], eg to favour taking from a factory over the centre
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct OpeningBook {
+    pub weights: BTreeMap<usize, f32>,
+}
+
+impl OpeningBook {
+    pub fn load(path: &Path) -> Result<Self, AzulError> {
+        let file = std::fs::File::open(path).map_err(|e| AzulError::load(path, e))?;
+        serde_json::from_reader(file).map_err(|e| AzulError::load(path, e))
+    }
+}
+
+/// How the first moves of a game are chosen
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Opening {
+    /// Moves at the start of the game played by the opening rather than the players
+    pub moves: u8,
+    /// Weights for the moves, every legal move is equally likely without one
+    pub book: Option<OpeningBook>,
+}
+
+impl Opening {
+    /// `moves` uniformly random moves
+    pub fn random(moves: u8) -> Self {
+        Self { moves, book: None }
+    }
+
+    /// `moves` moves weighted by `book`
+    pub fn from_book(moves: u8, book: OpeningBook) -> Self {
+        Self {
+            moves,
+            book: Some(book),
+        }
+    }
+
+    /// Play the opening moves on a new game, drawing them from `seed`,
+    /// and return them in order
+    pub fn play<const P: usize, const F: usize>(
+        &self,
+        gs: &mut Gamestate<P, F>,
+        seed: u64,
+    ) -> Vec<Move> {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut played = Vec::with_capacity(self.moves as usize);
+        while played.len() < self.moves as usize && gs.state() == State::RoundActive {
+            let moves = gs.get_moves();
+            let index = match self.weights(&moves) {
+                Some(weights) => weights.sample(&mut rng),
+                None => rng.gen_range(0..moves.len()),
+            };
+            gs.play_move(moves[index]);
+            played.push(moves[index]);
+        }
+        played
+    }
+
+    /// Book weights of `moves`, if any of them are in the book
+    fn weights(&self, moves: &[Move]) -> Option<WeightedIndex<f32>> {
+        let book = self.book.as_ref()?;
+        let weights = moves.iter().map(|m| {
+            book.weights
+                .get(&m.to_index())
+                .copied()
+                .unwrap_or(0.0)
+                .max(0.0)
+        });
+        WeightedIndex::new(weights).ok()
+    }
+}
+
+/// Position reached by playing `moves` on the game dealt from `seed`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StartPosition {
+    pub seed: u64,
+    /// Moves played from the start of the game, as from [Move::to_index]
+    pub moves: Vec<usize>,
+    /// Evaluation for the player that moved first, when the position was chosen
+    pub eval: f32,
+}
+
+impl StartPosition {
+    /// Play the moves on a game started from [StartPosition::seed].
+    /// Either player can have started, so both get to play each side of the position
+    pub fn play<const P: usize, const F: usize>(
+        &self,
+        gs: &mut Gamestate<P, F>,
+    ) -> Result<Vec<Move>, AzulError> {
+        self.moves
+            .iter()
+            .enumerate()
+            .map(|(i, &index)| {
+                if gs.state() != State::RoundActive {
+                    return Err(ReplayError::RoundOver(i + 1).into());
+                }
+                let m = gs
+                    .get_moves()
+                    .into_iter()
+                    .find(|m| m.to_index() == index)
+                    .ok_or(ReplayError::IllegalIndex { ply: i + 1, index })?;
+                gs.play_move(m);
+                Ok(m)
+            })
+            .collect()
+    }
+}
+
+/// Start positions for engine comparisons, saved as json
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PositionSuite {
+    /// How the positions were evaluated, eg `depth 4`
+    pub evaluated_by: String,
+    pub positions: Vec<StartPosition>,
+}
+
+impl PositionSuite {
+    /// Load a suite, checking every position can be played
+    pub fn load(path: &Path) -> Result<Self, AzulError> {
+        let file = std::fs::File::open(path).map_err(|e| AzulError::load(path, e))?;
+        let suite: Self = serde_json::from_reader(file).map_err(|e| AzulError::load(path, e))?;
+        for (i, position) in suite.positions.iter().enumerate() {
+            let mut gs = Gamestate::<2, 6>::new_2_player_with_seed(position.seed, 0);
+            position
+                .play(&mut gs)
+                .map_err(|e| AzulError::load(path, format!("position {}: {e}", i + 1)))?;
+        }
+        Ok(suite)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), AzulError> {
+        let file = std::fs::File::create(path).map_err(|e| AzulError::save(path, e))?;
+        serde_json::to_writer_pretty(file, self).map_err(|e| AzulError::save(path, e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Opening, OpeningBook};
+    use crate::gamestate::Gamestate;
+
+    #[test]
+    fn openings() {
+        let start = Gamestate::new_2_player_with_seed(3, 0);
+
+        let mut a = start.clone();
+        let mut b = start.clone();
+        let moves = Opening::random(2).play(&mut a, 7);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(Opening::random(2).play(&mut b, 7), moves);
+        assert_eq!(a, b);
+
+        // Only the book move can be picked
+        let first = start.get_moves()[5];
+        let book = OpeningBook {
+            weights: [(first.to_index(), 1.0)].into(),
+        };
+        for seed in 0..5 {
+            let mut gs = start.clone();
+            assert_eq!(
+                Opening::from_book(1, book.clone()).play(&mut gs, seed),
+                [first]
+            );
+        }
+    }
+}
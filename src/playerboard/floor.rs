@@ -0,0 +1,129 @@
+//! Floor line of a player board
+//! Has 7 spaces, one of them taken by the first player tile when it is there.
+//! Tiles that don't fit fall straight to the lid
+
+use crate::tiles::{Tile, TileGroup};
+
+/// Number of spaces on the floor line
+pub const FLOOR_SPACES: u8 = 7;
+
+/// Tiles on the floor and the first player tile
+///
+/// Saved as the `floor` and `first_player_tile` fields of its [PlayerBoard](super::PlayerBoard)
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize,
+)]
+pub struct FloorLine<const C: usize = 5> {
+    #[serde(rename = "floor")]
+    tiles: TileGroup<C>,
+    first_player_tile: bool,
+}
+
+impl<const C: usize> FloorLine<C> {
+    /// Tiles on the floor, not including the first player tile
+    pub fn tiles(&self) -> &TileGroup<C> {
+        &self.tiles
+    }
+
+    /// Whether the first player tile is on the floor
+    pub fn has_first_player_tile(&self) -> bool {
+        self.first_player_tile
+    }
+
+    /// Number of spaces taken, including the first player tile
+    pub fn len(&self) -> u8 {
+        self.tiles.total() + self.first_player_tile as u8
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of spaces left
+    pub fn free_spaces(&self) -> u8 {
+        FLOOR_SPACES.saturating_sub(self.len())
+    }
+
+    /// Add tiles to the free spaces, returning those that don't fit
+    pub fn add_tiles(&mut self, tile: Tile, count: u8) -> TileGroup<C> {
+        let placed = count.min(self.free_spaces());
+        self.tiles.add_tiles(tile, placed);
+        let mut overflow = TileGroup::new_empty();
+        overflow.add_tiles(tile, count - placed);
+        overflow
+    }
+
+    /// Put the first player tile on the floor. It always takes a space,
+    /// so on a full floor the last tile in [Tile] order is returned to go to the lid
+    pub fn add_first_player_tile(&mut self) -> TileGroup<C> {
+        let mut overflow = TileGroup::new_empty();
+        if self.first_player_tile {
+            return overflow;
+        }
+        if self.free_spaces() == 0 {
+            if let Some(&tile) = self.tiles.tile_vec().last() {
+                self.tiles.saturating_take_tiles(tile, 1);
+                overflow.add_tile(tile);
+            }
+        }
+        self.first_player_tile = true;
+        overflow
+    }
+
+    /// Replace the tiles on the floor, returning those that don't fit
+    pub fn set_tiles(&mut self, tiles: TileGroup<C>) -> TileGroup<C> {
+        self.tiles = TileGroup::new_empty();
+        let mut overflow = TileGroup::new_empty();
+        for (tile, count) in tiles.iter() {
+            overflow += self.add_tiles(tile, count);
+        }
+        overflow
+    }
+
+    /// Clear the floor for the next round, returning its tiles.
+    /// The first player tile goes back to the centre
+    pub fn empty(&mut self) -> TileGroup<C> {
+        self.first_player_tile = false;
+        self.tiles.empty()
+    }
+}
+
+/// Tiles, with a leading `1` for the first player tile
+impl<const C: usize> std::fmt::Display for FloorLine<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.first_player_tile {
+            write!(f, "1")?;
+            if self.tiles.is_empty() {
+                return Ok(());
+            }
+        }
+        std::fmt::Display::fmt(&self.tiles, f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FloorLine, FLOOR_SPACES};
+    use crate::tiles::Tile;
+
+    #[test]
+    fn capped() {
+        let mut floor: FloorLine = FloorLine::default();
+        assert!(floor.is_empty());
+        assert_eq!(floor.add_tiles(Tile::Red, 4).total(), 0);
+        assert_eq!(floor.add_first_player_tile().total(), 0);
+        let overflow = floor.add_tiles(Tile::Blue, 4);
+        assert_eq!(overflow.get_count(Tile::Blue), 2);
+        assert_eq!(floor.len(), FLOOR_SPACES);
+        assert_eq!(floor.to_string(), "1BBRRRR");
+
+        // The marker bumps a tile when the floor is full
+        let mut full: FloorLine = FloorLine::default();
+        full.add_tiles(Tile::Black, 7);
+        assert_eq!(full.add_first_player_tile().get_count(Tile::Black), 1);
+        assert_eq!(full.len(), FLOOR_SPACES);
+
+        assert_eq!(floor.empty().total(), 6);
+        assert!(floor.is_empty());
+    }
+}
@@ -1,23 +1,70 @@
+pub mod floor;
 pub mod wall;
 
+pub use floor::FloorLine;
 pub use wall::RowIndex;
 
-use core::panic;
-use std::{iter::Zip, mem};
+use std::iter::Zip;
 
 use strum::IntoEnumIterator;
-use wall::{RowIndexIter, Wall};
+use wall::{ColumnIndex, RowIndexIter, Wall, WallPlacement};
 
 use crate::{
-    gamestate::Destination,
+    error::{AzulError, ParseKind},
+    gamestate::{Destination, IllegalMove, Move},
     tiles::{Tile, TileGroup},
 };
 
+/// Adjustments to even out games between players of different strength
+///
+/// Written as comma separated `start=N`, `floor=N` and `free=N`, eg `start=10,free=1`
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize,
+)]
+pub struct Handicap {
+    /// Score the player starts with, for the weaker side
+    #[serde(default)]
+    pub start_score: u8,
+    /// Extra points lost in each round with tiles on the floor, for the stronger side
+    #[serde(default)]
+    pub floor_penalty: u8,
+    /// Number of floor tiles each round that cost nothing, for the weaker side
+    #[serde(default)]
+    pub free_floor_tiles: u8,
+}
+
+impl std::str::FromStr for Handicap {
+    type Err = AzulError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut handicap = Self::default();
+        for part in s.split(',').filter(|p| !p.is_empty()) {
+            let invalid = || AzulError::parse(ParseKind::Handicap, part);
+            let (name, value) = part.split_once('=').ok_or_else(invalid)?;
+            let value = value.parse().map_err(|_| invalid())?;
+            match name {
+                "start" => handicap.start_score = value,
+                "floor" => handicap.floor_penalty = value,
+                "free" => handicap.free_floor_tiles = value,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(handicap)
+    }
+}
+
 /// Line of tiles on board
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize,
+)]
 pub struct Row(Option<(Tile, u8)>);
 
 impl Row {
+    /// Row holding `count` tiles of a colour, or empty for 0
+    pub fn new(tile: Tile, count: u8) -> Self {
+        Self((count > 0).then_some((tile, count)))
+    }
+
     pub fn is_empty(&self) -> bool {
         self.0.is_none()
     }
@@ -39,23 +86,179 @@ impl Row {
     }
 }
 
+/// Tile moved from a pattern line to the wall at the end of a round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub row: RowIndex,
+    pub tile: Tile,
+    /// Points scored for placing it
+    pub points: u8,
+}
+
+/// What happened when tiles were placed on a board, see [PlayerBoard::place_tiles]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub struct PlayerBoard {
+pub struct PlacementResult<const C: usize = 5> {
+    /// Tiles added to the pattern line
+    pub to_row: u8,
+    /// Tiles that missed the pattern line and went on the floor
+    pub to_floor: u8,
+    /// Tiles that didn't fit on the floor, which go to the lid.
+    /// Can include a tile bumped off a full floor by the first player tile
+    pub to_lid: TileGroup<C>,
+    /// The pattern line is now full and will move to the wall at the end of the round
+    pub fills_row: bool,
+    /// Change in the predicted score
+    pub score_delta: i16,
+}
+
+/// What happened on a board at the end of a round, see [PlayerBoard::end_round]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BoardSummary<const C: usize = 5> {
+    /// Tiles placed on the wall, in row order
+    pub placements: Vec<Placement>,
+    /// Points lost for the floor and the first player tile
+    pub floor_penalty: u8,
+    /// Bonuses for completed rows, columns and colours, only when the game ended
+    pub bonus: u8,
+    /// Tiles left over from full pattern lines and the floor, which go to the lid
+    pub discarded: TileGroup<C>,
+    /// The wall has a complete row, so the game ends this round
+    pub completed_row: bool,
+}
+
+impl<const C: usize> BoardSummary<C> {
+    /// Points scored by the placements
+    pub fn points(&self) -> u8 {
+        self.placements.iter().map(|p| p.points).sum()
+    }
+}
+
+/// Where a player's points came from. Start scores from a [Handicap] are left out,
+/// and a score that would have gone below zero lost less than `floor_penalties`
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize,
+)]
+pub struct ScoreBreakdown {
+    /// End of game bonus for complete rows, 2 each
+    pub rows: u8,
+    /// End of game bonus for complete columns, 7 each
+    pub columns: u8,
+    /// End of game bonus for colours with a tile in every row, 10 each
+    pub colours: u8,
+    /// Points for placing tiles on the wall, from the tiles adjacent to them
+    pub adjacency: u16,
+    /// Points lost for the floor and the first player tile
+    pub floor_penalties: u16,
+}
+
+impl ScoreBreakdown {
+    /// Total of the end of game bonuses
+    pub fn bonus(&self) -> u8 {
+        self.rows + self.columns + self.colours
+    }
+}
+
+/// Board of a player in a game with `C` colours, 5 in the standard game
+///
+/// Boards are compared and hashed as positions, so the [ScoreBreakdown] is left out
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct PlayerBoard<const C: usize = 5> {
     /// Wall of tiles
-    pub wall: Wall,
-    /// Floor of tiles
-    pub floor: TileGroup,
-    /// First player tile
-    pub first_player_tile: bool,
+    pub wall: Wall<C>,
+    /// Floor line, with the first player tile once taken
+    #[serde(flatten)]
+    pub floor: FloorLine<C>,
     /// Pattern lines
-    pub rows: [Row; 5],
-    /// Score
+    #[serde(with = "crate::serde_array")]
+    pub rows: [Row; C],
+    /// Wall column chosen for each full pattern line when placement is free
+    #[serde(with = "crate::serde_array", default = "no_columns")]
+    pub columns: [Option<ColumnIndex>; C],
+    /// Score, which stops at 255 rather than overflowing with a large start score
     pub score: u8,
     /// Predicted score if rows were moved to wall
     pub predicted_score: u8,
+    /// Changes to scoring for this player
+    pub handicap: Handicap,
+    /// Points scored so far, by where they came from
+    #[serde(default)]
+    pub breakdown: ScoreBreakdown,
+}
+
+impl<const C: usize> Default for PlayerBoard<C> {
+    fn default() -> Self {
+        Self {
+            wall: Wall::default(),
+            floor: FloorLine::default(),
+            rows: [Row::default(); C],
+            columns: [None; C],
+            score: 0,
+            predicted_score: 0,
+            handicap: Handicap::default(),
+            breakdown: ScoreBreakdown::default(),
+        }
+    }
+}
+
+/// Boards reached by different scoring are the same position
+impl<const C: usize> PartialEq for PlayerBoard<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.position() == other.position()
+    }
+}
+
+impl<const C: usize> Eq for PlayerBoard<C> {}
+
+impl<const C: usize> std::hash::Hash for PlayerBoard<C> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.position().hash(state);
+    }
 }
 
-impl PlayerBoard {
+fn no_columns<const C: usize>() -> [Option<ColumnIndex>; C] {
+    [None; C]
+}
+
+impl<const C: usize> PlayerBoard<C> {
+    /// Every field but the breakdown, destructured so new fields aren't missed
+    #[allow(clippy::type_complexity)]
+    fn position(
+        &self,
+    ) -> (
+        &Wall<C>,
+        &FloorLine<C>,
+        &[Row; C],
+        &[Option<ColumnIndex>; C],
+        u8,
+        u8,
+        &Handicap,
+    ) {
+        let Self {
+            wall,
+            floor,
+            rows,
+            columns,
+            score,
+            predicted_score,
+            handicap,
+            breakdown: _,
+        } = self;
+        (
+            wall,
+            floor,
+            rows,
+            columns,
+            *score,
+            *predicted_score,
+            handicap,
+        )
+    }
+
+    /// Pattern line of a row, None beyond the `C` rows of the board
+    pub fn row(&self, row: RowIndex) -> Option<&Row> {
+        self.rows.get(usize::from(row))
+    }
+
     /// Iterate over the rows of the board with their indices
     pub fn row_iter(&self) -> Zip<RowIndexIter, core::slice::Iter<'_, Row>> {
         RowIndex::iter().zip(self.rows.iter())
@@ -65,11 +268,14 @@ impl PlayerBoard {
     /// Returns the number of tiles that can be played
     /// and how many tiles will be on the row after
     pub fn can_play_tile(&self, row: RowIndex, tile: Tile, count: u8) -> Option<(u8, u8)> {
-        if let Some((row_tile, row_count)) = self.rows[usize::from(row)].0 {
+        if let Some((row_tile, row_count)) = self.row(row)?.0 {
             if row_tile == tile {
                 // Check if row is full
                 if row_count < row.row_capacity() {
                     let total = (row_count + count).min(row.row_capacity());
+                    if total == row.row_capacity() && !self.column_available(row, tile) {
+                        return None;
+                    }
                     Some((total - row_count, total))
                 } else {
                     None
@@ -79,7 +285,9 @@ impl PlayerBoard {
             }
         } else {
             // Check the wall
-            if self.wall.cell_available(row, &tile) {
+            if count >= row.row_capacity() && !self.column_available(row, tile) {
+                None
+            } else if self.wall.cell_available(row, &tile) {
                 Some((count, count))
             } else {
                 None
@@ -87,48 +295,151 @@ impl PlayerBoard {
         }
     }
 
+    /// Whether a free column is left for filling `row` with `tile`, always true for
+    /// pattern placement as the cell was checked when the row was started
+    fn column_available(&self, row: RowIndex, tile: Tile) -> bool {
+        self.wall.placement().is_pattern() || !self.wall_columns(row, tile).is_empty()
+    }
+
+    /// Columns of the wall that `tile` could go to from a full `row`, leaving out
+    /// those chosen for the same colour by other rows
+    pub fn wall_columns(&self, row: RowIndex, tile: Tile) -> Vec<ColumnIndex> {
+        let mut columns = self.wall.columns(row, &tile);
+        columns.retain(|col| {
+            !self.row_iter().any(|(r, line)| {
+                r != row && line.tile() == Some(tile) && self.columns[usize::from(r)] == Some(*col)
+            })
+        });
+        columns
+    }
+
+    /// Column the tile from a full `row` will be placed in, the chosen one
+    /// or the first that is free when placement is free
+    fn column_for(&self, row: RowIndex, tile: Tile) -> Option<ColumnIndex> {
+        match self.wall.placement() {
+            WallPlacement::Pattern => self.wall.pattern_column(row, tile),
+            WallPlacement::Free => self
+                .columns
+                .get(usize::from(row))?
+                .or_else(|| self.wall_columns(row, tile).first().copied()),
+        }
+    }
+
+    /// Check that tiles of `tile` can go in `row`, or say why not
+    pub fn check_row(&self, row: RowIndex, tile: Tile) -> Result<(), IllegalMove> {
+        let current = self.row(row).ok_or(IllegalMove::NoSuchRow(row))?;
+        match current.0 {
+            Some((colour, _)) if colour != tile => Err(IllegalMove::RowHasOtherColour(row, colour)),
+            Some((_, count)) if count >= row.row_capacity() => Err(IllegalMove::RowFull(row)),
+            None if !self.wall.cell_available(row, &tile) => {
+                Err(IllegalMove::WallHasColour(row, tile))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Rows that tiles of `tile` can go to, with the space left in each.
+    /// The same rows as pass [PlayerBoard::check_row], without building the errors
+    pub fn legal_rows_for(&self, tile: Tile) -> impl Iterator<Item = (RowIndex, u8)> + '_ {
+        self.row_iter().filter_map(move |(row, line)| {
+            let left = row.capacity() - line.count();
+            match line.tile() {
+                Some(colour) if colour != tile || left == 0 => None,
+                Some(_) => Some((row, left)),
+                None => self.wall.cell_available(row, &tile).then_some((row, left)),
+            }
+        })
+    }
+
+    /// Place the tiles of a move, choosing its wall column if it has one
+    /// Does not check that the move is valid
+    /// Only the move's destination, tile and count are used, not its row counts
+    pub fn place_move(&mut self, m: &Move, first_player_tile: bool) -> PlacementResult<C> {
+        if let (Destination::Row(row), Some(col)) = (m.destination, m.column) {
+            if let Some(chosen) = self.columns.get_mut(usize::from(row)) {
+                *chosen = Some(col);
+            }
+        }
+        self.place_tiles(m.destination, m.tile, m.count, first_player_tile)
+    }
+
     /// Place tiles in a row or on the floor
     /// Does not check that the move is valid
     /// Updates predicted score
+    /// Returns where the tiles went, and the tiles for the lid
     pub fn place_tiles(
         &mut self,
         dest: Destination,
         tile: Tile,
         count: u8,
         first_player_tile: bool,
-    ) {
-        if first_player_tile {
-            self.first_player_tile = true;
-        }
-        match dest {
+    ) -> PlacementResult<C> {
+        let prev_score = self.predicted_score;
+        // The first player tile goes on the floor before the tiles taken with it
+        let bumped = if first_player_tile {
+            self.floor.add_first_player_tile()
+        } else {
+            TileGroup::new_empty()
+        };
+        let mut result = match dest {
             Destination::Row(row) => self.place_tiles_in_row(row, tile, count),
-            Destination::Floor => self.floor.add_tiles(tile, count),
-        }
+            Destination::Floor => {
+                let to_lid = self.floor.add_tiles(tile, count);
+                PlacementResult {
+                    to_floor: count - to_lid.total(),
+                    to_lid,
+                    ..Default::default()
+                }
+            }
+        };
+        result.to_lid += bumped;
         // update predicted score
-        self.predict_score();
+        result.score_delta = self.predict_score() as i16 - prev_score as i16;
+        result
     }
 
     /// Place tiles in a row
-    /// Does not check that the move is valid
-    pub fn place_tiles_in_row(&mut self, row_ind: RowIndex, tile: Tile, count: u8) {
+    /// Does not check that the move is valid, but tiles for a row the board
+    /// doesn't have go on the floor
+    /// Does not update the predicted score, so the result's score delta is 0
+    pub fn place_tiles_in_row(
+        &mut self,
+        row_ind: RowIndex,
+        tile: Tile,
+        count: u8,
+    ) -> PlacementResult<C> {
         // Get access to row
-        let row = &mut self.rows[row_ind as usize];
+        let Some(row) = self.rows.get_mut(usize::from(row_ind)) else {
+            let to_lid = self.floor.add_tiles(tile, count);
+            return PlacementResult {
+                to_floor: count - to_lid.total(),
+                to_lid,
+                ..Default::default()
+            };
+        };
         // Get row capacity
         let capacity = row_ind.row_capacity();
 
-        let leftover = if let Some((row_tile, row_count)) = &mut row.0 {
+        let (to_row, row_count) = if let Some((_, row_count)) = &mut row.0 {
             // If row is empty or matches factory tile, is valid move
-
-            let total = *row_count + count;
-            *row_count = if total > capacity { capacity } else { total };
-            total - *row_count
+            let to_row = count.min(capacity.saturating_sub(*row_count));
+            *row_count += to_row;
+            (to_row, *row_count)
         } else {
-            let total = if count > capacity { capacity } else { count };
-            row.0 = Some((tile, total));
-            count - total
+            let to_row = count.min(capacity);
+            row.0 = Some((tile, to_row));
+            (to_row, to_row)
         };
         // If there are leftover tiles, add them to the floor
-        self.floor.add_tiles(tile, leftover);
+        let leftover = count - to_row;
+        let to_lid = self.floor.add_tiles(tile, leftover);
+        PlacementResult {
+            to_row,
+            to_floor: leftover - to_lid.total(),
+            to_lid,
+            fills_row: row_count == capacity,
+            score_delta: 0,
+        }
     }
 
     /// Fake move the full rows to the wall to calculate score
@@ -138,16 +449,19 @@ impl PlayerBoard {
         // Copy the wall
         let mut wall = self.wall;
         let mut score = 0;
-        for row_ind in RowIndex::iter() {
+        for row_ind in RowIndex::rows(C) {
             if let Some((tile, count)) = self.rows[usize::from(row_ind)].0 {
                 if count == row_ind.row_capacity() {
-                    score += wall.place_and_score_tile(row_ind, tile);
+                    if let Some(col) = self.column_for(row_ind, tile) {
+                        score += wall.score_tile_at(row_ind, col);
+                        wall.place_tile_at(row_ind, col, tile);
+                    }
                 }
             }
         }
-        self.predicted_score = self.score + score + wall.score();
+        self.predicted_score = self.score.saturating_add(score + wall.score());
         // cap the score depending on floor
-        let floor_score = floor_score(&self.floor, self.first_player_tile);
+        let floor_score = self.floor_penalty();
         if self.predicted_score < floor_score {
             self.predicted_score = 0;
         } else {
@@ -156,14 +470,31 @@ impl PlayerBoard {
         self.predicted_score
     }
 
+    /// Points that will be lost for the tiles on the floor
+    pub fn floor_penalty(&self) -> u8 {
+        self.penalty_for(&self.floor)
+    }
+
+    /// Floor score for `floor`, adjusted by the handicap
+    fn penalty_for(&self, floor: &FloorLine<C>) -> u8 {
+        let count = floor.len();
+        if count == 0 {
+            return 0;
+        }
+        floor_score(count.saturating_sub(self.handicap.free_floor_tiles))
+            .saturating_add(self.handicap.floor_penalty)
+    }
+
     /// Return a copy of the wall with all tiles moved to where they will be at the end
     /// of the round
-    pub fn simulate_wall(&self) -> Wall {
-        let mut wall = self.wall.clone();
-        for row_ind in RowIndex::iter() {
+    pub fn simulate_wall(&self) -> Wall<C> {
+        let mut wall = self.wall;
+        for row_ind in RowIndex::rows(C) {
             if let Some((tile, count)) = self.rows[usize::from(row_ind)].0 {
                 if count == row_ind.row_capacity() {
-                    wall.place_tile(row_ind, tile);
+                    if let Some(col) = self.column_for(row_ind, tile) {
+                        wall.place_tile_at(row_ind, col, tile);
+                    }
                 }
             }
         }
@@ -172,57 +503,61 @@ impl PlayerBoard {
 
     /// Move tiles from rows to wall
     /// Score as it goes
-    /// Return tiles that are to be returned
     /// Calculate floor score and empty
     /// Set things up for next round
-    /// returns true if the game is over
-    pub fn end_round(&mut self) -> (TileGroup, bool) {
-        // Store tiles that are to be returned
-        let mut tile_return = TileGroup::new_empty();
-        // Count score as it goes
-        let mut score = 0;
+    /// Returns what happened, including the tiles to discard
+    pub fn end_round(&mut self) -> BoardSummary<C> {
+        let mut summary = BoardSummary::default();
         // Go through rows in order
-        for row_ind in RowIndex::iter() {
+        for row_ind in RowIndex::rows(C) {
             // if row contains any tiles
             if let Some((tile, count)) = self.rows[usize::from(row_ind)].0 {
                 // if row is at capacity, move single tile to wall
                 // otherwise leave tiles as they are
                 if count == row_ind.row_capacity() {
-                    // Get score from placing this tile
-                    score += self.wall.score_tile(row_ind, tile);
                     // Assume that wall is empty in this cell
                     // Tile will disappear otherwise and is previous logic error
                     // in move generation
-                    self.wall.place_tile(row_ind, tile);
-                    // add remaining tiles to return
-                    tile_return.add_tiles(tile, count - 1);
+                    let col = self
+                        .column_for(row_ind, tile)
+                        .expect("Full row has a free column");
+                    // Get score from placing this tile
+                    let points = self.wall.score_tile_at(row_ind, col);
+                    self.wall.place_tile_at(row_ind, col, tile);
+                    summary.placements.push(Placement {
+                        row: row_ind,
+                        tile,
+                        points,
+                    });
+                    // add remaining tiles to discard
+                    summary.discarded.add_tiles(tile, count - 1);
                     // clear the row
                     self.rows[usize::from(row_ind)] = Row::default();
+                    self.columns[usize::from(row_ind)] = None;
                 }
             }
         }
-        // Empty the floor
-        let floor = self.floor.empty();
         // Calculate floor score
-        let floor_score = floor_score(&floor, self.first_player_tile);
-        let total = self.score + score;
+        summary.floor_penalty = self.floor_penalty();
+        let total = self.score.saturating_add(summary.points());
         // Add up scores, can't go below zero
-        if total < floor_score {
-            self.score = 0;
-        } else {
-            self.score = total - floor_score;
-        }
-        // remove first player tile
-        self.first_player_tile = false;
-
-        // Return tiles that are to be put back in bag
-        tile_return += floor;
-        (tile_return, self.wall.has_full_row())
+        self.score = total.saturating_sub(summary.floor_penalty);
+        self.breakdown.adjacency += summary.points() as u16;
+        self.breakdown.floor_penalties += summary.floor_penalty as u16;
+        // Empty the floor, removing the first player tile
+        summary.discarded += self.floor.empty();
+        summary.completed_row = self.wall.has_full_row();
+        summary
     }
 
-    pub fn end_game(&mut self) {
-        // row score
-        self.score += self.wall.score();
+    /// Add the end of game bonuses to the score and return where all of
+    /// the player's points came from
+    pub fn end_game(&mut self) -> ScoreBreakdown {
+        self.breakdown.rows = 2 * self.wall.completed_rows();
+        self.breakdown.columns = 7 * self.wall.completed_columns();
+        self.breakdown.colours = 10 * self.wall.completed_colours();
+        self.score = self.score.saturating_add(self.breakdown.bonus());
+        self.breakdown
     }
 
     /// Count tiles on the board for testing
@@ -233,14 +568,53 @@ impl PlayerBoard {
                 count += c;
             }
         }
-        count += self.floor.total() + self.wall.tile_count();
+        count += self.floor.tiles().total() + self.wall.tile_count();
         count
     }
 }
 
-fn floor_score(tiles: &TileGroup, fp: bool) -> u8 {
-    let total = tiles.total() + if fp { 1 } else { 0 };
-    match total {
+/// Score, then each pattern line beside its row of the wall, then the floor,
+/// in colour for `{:#}`:
+/// ```text
+/// Score 3 (predicted 5)
+///       . | byrkw
+///      YY | wbYrk
+///     ... | kwbyr
+///    .... | rkwby
+///   ..... | yrkwb
+///   Floor: 1R
+/// ```
+impl<const C: usize> std::fmt::Display for PlayerBoard<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Score {} (predicted {})",
+            self.score, self.predicted_score
+        )?;
+        for (i, row) in self.rows.iter().enumerate() {
+            let filled = row.count() as usize;
+            write!(
+                f,
+                "\n  {}{}",
+                " ".repeat(C - 1 - i),
+                ".".repeat(i + 1 - filled)
+            )?;
+            if let Some(tile) = row.tile() {
+                for _ in 0..filled {
+                    std::fmt::Display::fmt(&tile, f)?;
+                }
+            }
+            write!(f, " | ")?;
+            self.wall.fmt_row(f, i)?;
+        }
+        write!(f, "\n  Floor: ")?;
+        std::fmt::Display::fmt(&self.floor, f)
+    }
+}
+
+/// Points lost for a number of tiles on the floor
+fn floor_score(count: u8) -> u8 {
+    match count {
         0 => 0,
         1 => 1,
         2 => 2,
@@ -260,6 +634,7 @@ impl RowIndex {
             RowIndex::Three => 3,
             RowIndex::Four => 4,
             RowIndex::Five => 5,
+            RowIndex::Six => 6,
         }
     }
 }
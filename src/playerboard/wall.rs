@@ -2,11 +2,14 @@
 //! Responsible for tracking correct placement of tiles in wall
 //! and counting points at end of round and end of game
 
-use std::ops::{Index, IndexMut};
+use std::ops::Index;
 
 use strum::IntoEnumIterator;
 
-use crate::tiles::Tile;
+use crate::{
+    error::{AzulError, ParseKind, PositionError},
+    tiles::Tile,
+};
 
 pub const WALL_COLOURS: [[Tile; 5]; 5] = [
     [
@@ -46,147 +49,663 @@ pub const WALL_COLOURS: [[Tile; 5]; 5] = [
     ],
 ];
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub struct Wall([[Option<Tile>; 5]; 5]);
+/// Arrangement of `C` colours on a `C`x`C` wall
+///
+/// Must be a Latin square, with every colour once in each row and column.
+/// Parsed from rows of `B`, `Y`, `R`, `K`, `W` (and `O`) separated by `/`,
+/// e.g. `BYRKW/WBYRK/KWBYR/RKWBY/YRKWB` for [WALL_COLOURS]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WallPattern<const C: usize = 5> {
+    colours: [[Tile; C]; C],
+    /// Column of each tile in each row
+    columns: [[u8; C]; C],
+}
+
+impl WallPattern {
+    /// The pattern printed on the standard board
+    pub const STANDARD: Self = Self::from_latin_square(WALL_COLOURS);
+}
+
+impl<const C: usize> WallPattern<C> {
+    /// Each row shifted one to the right of the row above, as on the standard board
+    pub const fn diagonal() -> Self {
+        let mut colours = [[Tile::Blue; C]; C];
+        let mut row = 0;
+        while row < C {
+            let mut col = 0;
+            while col < C {
+                colours[row][col] = Tile::ALL[(col + C - row) % C];
+                col += 1;
+            }
+            row += 1;
+        }
+        Self::from_latin_square(colours)
+    }
+
+    /// Checks that each colour appears once in every row and column
+    pub fn new(colours: [[Tile; C]; C]) -> Result<Self, AzulError> {
+        for (i, row) in colours.iter().enumerate() {
+            for tile in Tile::colours(C) {
+                if !row.contains(&tile) {
+                    return Err(AzulError::WallPattern {
+                        row: true,
+                        index: i,
+                        tile,
+                    });
+                }
+                if !colours.iter().any(|r| r[i] == tile) {
+                    return Err(AzulError::WallPattern {
+                        row: false,
+                        index: i,
+                        tile,
+                    });
+                }
+            }
+        }
+        Ok(Self::from_latin_square(colours))
+    }
+
+    const fn from_latin_square(colours: [[Tile; C]; C]) -> Self {
+        let mut columns = [[0; C]; C];
+        let mut row = 0;
+        while row < C {
+            let mut col = 0;
+            while col < C {
+                columns[row][colours[row][col] as usize] = col as u8;
+                col += 1;
+            }
+            row += 1;
+        }
+        Self { colours, columns }
+    }
+
+    /// Colour of each cell
+    pub fn colours(&self) -> &[[Tile; C]; C] {
+        &self.colours
+    }
+
+    /// Column where the tile is placed in the row, `None` for rows or colours
+    /// past the `C` of the pattern
+    pub fn column(&self, row: RowIndex, tile: &Tile) -> Option<ColumnIndex> {
+        let column = *self
+            .columns
+            .get(usize::from(row))?
+            .get(usize::from(*tile))?;
+        column.try_into().ok()
+    }
+}
+
+impl<const C: usize> Default for WallPattern<C> {
+    fn default() -> Self {
+        Self::diagonal()
+    }
+}
+
+impl<const C: usize> std::str::FromStr for WallPattern<C> {
+    type Err = AzulError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows = s.trim().split('/').collect::<Vec<_>>();
+        if rows.len() != C {
+            return Err(PositionError::RowCount {
+                expected: C,
+                found: rows.len(),
+            }
+            .into());
+        }
+        let mut colours = Self::diagonal().colours;
+        for (row, text) in colours.iter_mut().zip(rows) {
+            let tiles = text
+                .trim()
+                .chars()
+                .map(|c| Tile::from_letter(c).ok_or_else(|| AzulError::parse(ParseKind::Tile, c)))
+                .collect::<Result<Vec<_>, _>>()?;
+            *row = tiles
+                .try_into()
+                .map_err(|tiles: Vec<_>| PositionError::CellCount {
+                    expected: C,
+                    found: tiles.len(),
+                })?;
+        }
+        Self::new(colours)
+    }
+}
+
+impl<const C: usize> std::fmt::Display for WallPattern<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rows = self
+            .colours
+            .iter()
+            .map(|row| row.iter().map(Tile::letter).collect::<String>())
+            .collect::<Vec<_>>();
+        write!(f, "{}", rows.join("/"))
+    }
+}
+
+impl<const C: usize> serde::Serialize for WallPattern<C> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, const C: usize> serde::Deserialize<'de> for WallPattern<C> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// How tiles are moved to the wall at the end of a round
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Default, serde::Serialize, serde::Deserialize,
+)]
+pub enum WallPlacement {
+    /// In the column of its colour in the [WallPattern], the coloured side of the board
+    #[default]
+    Pattern,
+    /// In a column chosen by the player, the grey side of the board.
+    /// A colour can still only be once in each row and column
+    Free,
+}
+
+impl WallPlacement {
+    pub fn is_pattern(&self) -> bool {
+        *self == Self::Pattern
+    }
+}
+
+/// Wall of `C` rows and columns, 5 in the standard game
+///
+/// Placed tiles are kept as a bitmask, and the colour of each cell as three more,
+/// so scoring and finding a colour's cells are done with bit operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(into = "WallText<C>", try_from = "WallText<C>")]
+pub struct Wall<const C: usize = 5> {
+    /// Bit `row * C + column` is set for each cell with a tile
+    occupied: u64,
+    /// Bit `i` of each cell's colour index is in plane `i`. Every cell of the pattern,
+    /// or only those with a tile when placement is free, as the pattern doesn't say
+    colours: [u64; 3],
+    placement: WallPlacement,
+}
+
+/// Cell contents for [Wall]'s [Index], by [Tile] then empty
+static CELLS: [Option<Tile>; 7] = [
+    Some(Tile::Blue),
+    Some(Tile::Yellow),
+    Some(Tile::Red),
+    Some(Tile::Black),
+    Some(Tile::White),
+    Some(Tile::Orange),
+    None,
+];
 
-impl Index<(RowIndex, ColumnIndex)> for Wall {
+/// [Wall] as saved, with the placed tiles written in rows like the [WallPattern]
+/// and `.` for empty cells, eg `B..../.B.../...../...../.....`
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WallText<const C: usize> {
+    /// Not written when placement is free, which doesn't use it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pattern: Option<WallPattern<C>>,
+    #[serde(default, skip_serializing_if = "WallPlacement::is_pattern")]
+    placement: WallPlacement,
+    tiles: String,
+}
+
+impl<const C: usize> From<Wall<C>> for WallText<C> {
+    fn from(wall: Wall<C>) -> Self {
+        Self {
+            pattern: wall.pattern(),
+            placement: wall.placement,
+            tiles: wall.tiles_text(),
+        }
+    }
+}
+
+impl<const C: usize> TryFrom<WallText<C>> for Wall<C> {
+    type Error = AzulError;
+
+    fn try_from(text: WallText<C>) -> Result<Self, Self::Error> {
+        let pattern = text.pattern.unwrap_or_default();
+        let mut wall = Self::with_placement(pattern, text.placement);
+        wall.place_tiles_text(&text.tiles)?;
+        Ok(wall)
+    }
+}
+
+impl<const C: usize> Default for Wall<C> {
+    fn default() -> Self {
+        Self::with_pattern(WallPattern::default())
+    }
+}
+
+/// One line per row, placed tiles as upper case letters and spaces as lower case,
+/// in colour for `{:#}`
+impl<const C: usize> std::fmt::Display for Wall<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in 0..C {
+            if row > 0 {
+                writeln!(f)?;
+            }
+            self.fmt_row(f, row)?;
+        }
+        Ok(())
+    }
+}
+
+impl<const C: usize> Index<(RowIndex, ColumnIndex)> for Wall<C> {
     type Output = Option<Tile>;
 
     fn index(&self, index: (RowIndex, ColumnIndex)) -> &Self::Output {
-        &self.0[usize::from(&index.0)][usize::from(&index.1)]
+        match self.cell(usize::from(&index.0), usize::from(&index.1)) {
+            Some(tile) => &CELLS[tile as usize],
+            None => &CELLS[CELLS.len() - 1],
+        }
     }
 }
 
-impl IndexMut<(RowIndex, ColumnIndex)> for Wall {
-    fn index_mut(&mut self, index: (RowIndex, ColumnIndex)) -> &mut Self::Output {
-        &mut self.0[usize::from(&index.0)][usize::from(&index.1)]
+impl<const C: usize> Wall<C> {
+    /// Cells of the first row
+    const FIRST_ROW: u64 = (1 << C) - 1;
+    /// Cells of the first column
+    const FIRST_COLUMN: u64 = {
+        let mut mask = 0;
+        let mut row = 0;
+        while row < C {
+            mask |= 1 << (row * C);
+            row += 1;
+        }
+        mask
+    };
+
+    /// Every cell of the wall
+    const ALL_CELLS: u64 = (1 << (C * C)) - 1;
+
+    fn bit(row: usize, col: usize) -> u64 {
+        1 << (row * C + col)
     }
-}
 
-impl Wall {
-    /// Read access to inner array
-    pub fn iter(&self) -> impl Iterator<Item = &[Option<Tile>; 5]> {
-        self.0.iter()
+    fn row_mask(row: usize) -> u64 {
+        Self::FIRST_ROW << (row * C)
     }
-    /// Checks if a tile can be placed in this row
-    /// Used for move generation
-    pub fn cell_available(&self, row: RowIndex, tile: &Tile) -> bool {
-        self[(row, row.tile_column(tile))].is_none()
+
+    fn column_mask(col: usize) -> u64 {
+        Self::FIRST_COLUMN << col
     }
 
-    /// Place tile on the wall and return the score
-    pub fn place_and_score_tile(&mut self, row: RowIndex, tile: Tile) -> u8 {
-        let score = self.score_tile(row, tile);
-        self.place_tile(row, tile);
-        score
+    /// Tile in a cell
+    fn cell(&self, row: usize, col: usize) -> Option<Tile> {
+        (self.occupied & Self::bit(row, col) != 0).then(|| self.colour(row, col))
     }
 
-    /// Place a tile in the wall
-    /// Does not check if the move is valid
-    /// Should have been previously checked with cell_available
-    pub fn place_tile(&mut self, row: RowIndex, tile: Tile) {
-        self[(row, row.tile_column(&tile))] = Some(tile);
+    /// Colour of a cell from the planes, blue for empty cells when placement is free
+    fn colour(&self, row: usize, col: usize) -> Tile {
+        let shift = row * C + col;
+        let index = self
+            .colours
+            .iter()
+            .enumerate()
+            .map(|(i, plane)| ((plane >> shift & 1) as usize) << i)
+            .sum::<usize>();
+        Tile::ALL[index]
     }
 
-    /// Calculate score of placing tile
-    pub fn score_tile(&self, row: RowIndex, tile: Tile) -> u8 {
-        let col: usize = (&row.tile_column(&tile)).into();
-        let row: usize = (&row).into();
+    /// Cells whose colour in the planes is `tile`, including empty ones
+    fn colour_cells(&self, tile: Tile) -> u64 {
+        let plane = |i: usize| {
+            if (tile as usize >> i) & 1 == 1 {
+                self.colours[i]
+            } else {
+                !self.colours[i]
+            }
+        };
+        plane(0) & plane(1) & plane(2) & Self::ALL_CELLS
+    }
+
+    /// Cells holding a colour
+    fn colour_mask(&self, tile: Tile) -> u64 {
+        self.colour_cells(tile) & self.occupied
+    }
+
+    /// Column of a colour in a row of the pattern, `None` when placement is free
+    /// or the colour isn't one of the `C` on the wall
+    pub fn pattern_column(&self, row: RowIndex, tile: Tile) -> Option<ColumnIndex> {
+        if !self.placement.is_pattern() || tile as usize >= C {
+            return None;
+        }
+        let row = usize::from(row);
+        let cells = self.colour_cells(tile) & Self::row_mask(row);
+        ColumnIndex::try_from(cells.trailing_zeros() as usize - row * C).ok()
+    }
+
+    /// Empty wall with a custom colour arrangement
+    pub fn with_pattern(pattern: WallPattern<C>) -> Self {
+        Self::with_placement(pattern, WallPlacement::Pattern)
+    }
+
+    /// Empty wall with the colours of each cell, which must be a Latin square,
+    /// see [WallPattern::new]
+    pub fn with_colours(colours: [[Tile; C]; C]) -> Result<Self, AzulError> {
+        WallPattern::new(colours).map(Self::with_pattern)
+    }
 
-        let mut col_score = 0;
-        // Check up
-        for i in (0..row).rev() {
-            if self.0[i][col].is_none() {
-                break;
+    /// Empty wall, where tiles are placed by `placement`.
+    /// The pattern is only kept when placement is by the pattern
+    pub fn with_placement(pattern: WallPattern<C>, placement: WallPlacement) -> Self {
+        let mut wall = Self {
+            occupied: 0,
+            colours: [0; 3],
+            placement,
+        };
+        if placement.is_pattern() {
+            for (row, colours) in pattern.colours.iter().enumerate() {
+                for (col, &tile) in colours.iter().enumerate() {
+                    wall.set_colour(row, col, tile);
+                }
             }
-            col_score += 1;
         }
-        // Check down
-        for i in row + 1..5 {
-            if self.0[i][col].is_none() {
-                break;
+        wall
+    }
+
+    pub fn placement(&self) -> WallPlacement {
+        self.placement
+    }
+
+    /// Colour arrangement of the wall, `None` when placement is free
+    pub fn pattern(&self) -> Option<WallPattern<C>> {
+        self.placement.is_pattern().then(|| {
+            WallPattern::from_latin_square(std::array::from_fn(|row| {
+                std::array::from_fn(|col| self.colour(row, col))
+            }))
+        })
+    }
+
+    fn set_colour(&mut self, row: usize, col: usize, tile: Tile) {
+        for (i, plane) in self.colours.iter_mut().enumerate() {
+            if (tile as usize >> i) & 1 == 1 {
+                *plane |= Self::bit(row, col);
+            }
+        }
+    }
+
+    /// Placed tiles in rows separated by `/`, with `.` for empty cells,
+    /// eg `B..../.B.../...../...../.....`
+    pub fn tiles_text(&self) -> String {
+        let rows = self
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| cell.map_or('.', |tile| tile.letter()))
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        rows.join("/")
+    }
+
+    /// Place the tiles from [Wall::tiles_text], checking each is allowed where it is
+    pub fn place_tiles_text(&mut self, text: &str) -> Result<(), AzulError> {
+        let rows = text.split('/').collect::<Vec<_>>();
+        if rows.len() != C {
+            return Err(PositionError::RowCount {
+                expected: C,
+                found: rows.len(),
             }
-            col_score += 1;
+            .into());
         }
-        if col_score > 0 {
-            col_score += 1;
+        for (row, line) in rows.into_iter().enumerate() {
+            if line.chars().count() != C {
+                return Err(PositionError::CellCount {
+                    expected: C,
+                    found: line.chars().count(),
+                }
+                .into());
+            }
+            for (column, c) in line.chars().enumerate() {
+                if c == '.' {
+                    continue;
+                }
+                let tile =
+                    Tile::from_letter(c).ok_or_else(|| AzulError::parse(ParseKind::Tile, c))?;
+                let allowed = (tile as usize) < C
+                    && match self.placement {
+                        WallPlacement::Pattern => tile == self.colour(row, column),
+                        WallPlacement::Free => {
+                            self.colour_mask(tile)
+                                & (Self::row_mask(row) | Self::column_mask(column))
+                                == 0
+                        }
+                    };
+                let (row, column) = (RowIndex::try_from(row)?, ColumnIndex::try_from(column)?);
+                if !allowed {
+                    return Err(PositionError::Misplaced { tile, row, column }.into());
+                }
+                self.place_tile_at(row, column, tile);
+            }
         }
-        let mut row_score = 0;
-        // Check left
-        for i in (0..col).rev() {
-            if self.0[row][i].is_none() {
-                break;
+        Ok(())
+    }
+
+    /// Tiles in each row, `None` for empty cells
+    pub fn iter(&self) -> impl Iterator<Item = [Option<Tile>; C]> + '_ {
+        (0..C).map(|row| std::array::from_fn(|col| self.cell(row, col)))
+    }
+
+    /// Write one row as in the [std::fmt::Display] of the wall
+    pub(crate) fn fmt_row(&self, f: &mut std::fmt::Formatter<'_>, row: usize) -> std::fmt::Result {
+        for col in 0..C {
+            match self.cell(row, col) {
+                Some(tile) => std::fmt::Display::fmt(&tile, f)?,
+                None if self.placement == WallPlacement::Free => write!(f, ".")?,
+                None => self.colour(row, col).fmt_space(f)?,
             }
-            row_score += 1;
         }
-        // Check right
-        for i in col + 1..5 {
-            if self.0[row][i].is_none() {
-                break;
+        Ok(())
+    }
+    /// Checks if a tile can be placed in this row
+    /// Used for move generation
+    pub fn cell_available(&self, row: RowIndex, tile: &Tile) -> bool {
+        match self.placement {
+            WallPlacement::Pattern => self.pattern_column(row, *tile).is_some_and(|col| {
+                self.occupied & Self::bit(usize::from(row), usize::from(&col)) == 0
+            }),
+            WallPlacement::Free => self.free_columns(row, *tile) != 0,
+        }
+    }
+
+    /// Columns where a tile could be placed in this row, only the pattern's column
+    /// unless placement is free
+    pub fn columns(&self, row: RowIndex, tile: &Tile) -> Vec<ColumnIndex> {
+        match self.placement {
+            WallPlacement::Pattern => self
+                .pattern_column(row, *tile)
+                .filter(|col| self.occupied & Self::bit(usize::from(row), usize::from(col)) == 0)
+                .into_iter()
+                .collect(),
+            WallPlacement::Free => {
+                let free = self.free_columns(row, *tile);
+                ColumnIndex::columns(C)
+                    .filter(|col| free & (1 << usize::from(col)) != 0)
+                    .collect()
             }
-            row_score += 1;
         }
-        if row_score > 0 {
-            row_score += 1;
+    }
+
+    /// Bit for each column of `row` that can take `tile` when placement is free
+    fn free_columns(&self, row: RowIndex, tile: Tile) -> u64 {
+        let row = usize::from(row);
+        let colour = self.colour_mask(tile);
+        if colour & Self::row_mask(row) != 0 {
+            return 0;
         }
-        let mut score = col_score + row_score;
-        if score == 0 {
-            score = 1;
+        // Fold the columns holding the colour into the first row
+        let mut taken = (self.occupied >> (row * C)) & Self::FIRST_ROW;
+        for r in 0..C {
+            taken |= (colour >> (r * C)) & Self::FIRST_ROW;
         }
+        !taken & Self::FIRST_ROW
+    }
+
+    /// Place tile on the wall and return the score
+    pub fn place_and_score_tile(&mut self, row: RowIndex, tile: Tile) -> u8 {
+        let score = self.score_tile(row, tile);
+        self.place_tile(row, tile);
         score
     }
 
+    /// Place a tile in the wall, in the pattern's column
+    /// Does not check if the move is valid
+    /// Should have been previously checked with cell_available,
+    /// as nothing is placed without a column, see [Wall::pattern_column]
+    pub fn place_tile(&mut self, row: RowIndex, tile: Tile) {
+        if let Some(col) = self.pattern_column(row, tile) {
+            self.place_tile_at(row, col, tile);
+        }
+    }
+
+    /// Place a tile in a chosen column, for free placement
+    /// Does not check if the move is valid, see [Wall::columns]
+    pub fn place_tile_at(&mut self, row: RowIndex, col: ColumnIndex, tile: Tile) {
+        let (row, col) = (usize::from(row), usize::from(&col));
+        self.occupied |= Self::bit(row, col);
+        if self.placement == WallPlacement::Free {
+            self.set_colour(row, col, tile);
+        }
+    }
+
+    /// Calculate score of placing tile in the pattern's column,
+    /// 0 without a column, see [Wall::pattern_column]
+    pub fn score_tile(&self, row: RowIndex, tile: Tile) -> u8 {
+        self.pattern_column(row, tile)
+            .map_or(0, |col| self.score_tile_at(row, col))
+    }
+
+    /// Calculate score of placing a tile in a cell
+    pub fn score_tile_at(&self, row: RowIndex, col: ColumnIndex) -> u8 {
+        let col: usize = (&col).into();
+        let row: usize = (&row).into();
+        let filled = |r: usize, c: usize| self.occupied & Self::bit(r, c) != 0;
+
+        // Count the tiles joined above, below, left and right
+        let up = (0..row).rev().take_while(|&r| filled(r, col)).count();
+        let down = (row + 1..C).take_while(|&r| filled(r, col)).count();
+        let left = (0..col).rev().take_while(|&c| filled(row, c)).count();
+        let right = (col + 1..C).take_while(|&c| filled(row, c)).count();
+        let col_score = match up + down {
+            0 => 0,
+            n => n + 1,
+        };
+        let row_score = match left + right {
+            0 => 0,
+            n => n + 1,
+        };
+        (col_score + row_score).max(1) as u8
+    }
+
     /// Calculate the score of the wall
     /// Includes row, column and colours
     pub fn score(&self) -> u8 {
         let mut score = 0;
         // Row
-        score += 2 * self
-            .0
-            .iter()
-            .filter(|row| row.iter().all(|t| t.is_some()))
-            .count() as u8;
+        score += 2 * self.completed_rows();
         // Column
-        score += 7 * ColumnIndex::iter()
-            .filter(|col| RowIndex::iter().all(|row| self[(row, *col)].is_some()))
-            .count() as u8;
+        score += 7 * self.completed_columns();
         // Colours
-        score += 10
-            * Tile::iter()
-                .filter(|tile| {
-                    RowIndex::iter().all(|row| {
-                        let col = row.tile_column(tile);
-                        self[(row, col)].is_some()
-                    })
-                })
-                .count() as u8;
+        score += 10 * self.completed_colours();
         score
     }
 
+    /// Number of complete columns
+    pub fn completed_columns(&self) -> u8 {
+        (0..C)
+            .filter(|&col| self.occupied & Self::column_mask(col) == Self::column_mask(col))
+            .count() as u8
+    }
+
+    /// Number of colours with a tile in every row
+    pub fn completed_colours(&self) -> u8 {
+        Tile::colours(C)
+            .filter(|&tile| self.progress_for_colour(tile) == C as u8)
+            .count() as u8
+    }
+
+    /// Number of complete rows, which breaks ties at the end of the game
+    pub fn completed_rows(&self) -> u8 {
+        (0..C)
+            .filter(|&row| self.occupied & Self::row_mask(row) == Self::row_mask(row))
+            .count() as u8
+    }
+
+    /// Tiles placed in a row, out of `C`
+    pub fn progress_for_row(&self, row: RowIndex) -> u8 {
+        (self.occupied & Self::row_mask(usize::from(row))).count_ones() as u8
+    }
+
+    /// Tiles placed in a column, out of `C`
+    pub fn progress_for_column(&self, col: ColumnIndex) -> u8 {
+        (self.occupied & Self::column_mask(usize::from(&col))).count_ones() as u8
+    }
+
+    /// Tiles of a colour placed, out of `C` as each can only be once in a row
+    pub fn progress_for_colour(&self, tile: Tile) -> u8 {
+        self.colour_mask(tile).count_ones() as u8
+    }
+
     /// Check for full row as game ending condition
     pub fn has_full_row(&self) -> bool {
-        self.0.iter().any(|row| row.iter().all(|t| t.is_some()))
+        (0..C).any(|row| self.occupied & Self::row_mask(row) == Self::row_mask(row))
+    }
+
+    /// Most tiles placed in any one row
+    pub fn longest_row(&self) -> u8 {
+        (0..C)
+            .map(|row| (self.occupied & Self::row_mask(row)).count_ones() as u8)
+            .max()
+            .unwrap_or(0)
     }
 
     pub(crate) fn tile_count(&self) -> u8 {
-        self.0.iter().flatten().filter(|t| t.is_some()).count() as u8
+        self.occupied.count_ones() as u8
     }
 }
 
 /// For indexing into wall
-#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumIter, PartialOrd, Ord)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    strum::EnumIter,
+    PartialOrd,
+    Ord,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub enum RowIndex {
     One,
     Two,
     Three,
     Four,
     Five,
+    /// Only used by the 6 colour variant
+    Six,
 }
 
 impl RowIndex {
-    /// Returns column index of tile in row
-    fn tile_column(&self, tile: &Tile) -> ColumnIndex {
-        ((u8::from(self) + u8::from(tile)) % 5).into()
+    /// The first `count` rows, those on a board for `count` colours
+    pub fn rows(count: usize) -> std::iter::Take<RowIndexIter> {
+        RowIndex::iter().take(count)
+    }
+
+    /// Returns column index of tile in row of the wall pattern,
+    /// see [WallPattern::column]
+    pub fn tile_column<const C: usize>(
+        &self,
+        tile: &Tile,
+        pattern: &WallPattern<C>,
+    ) -> Option<ColumnIndex> {
+        pattern.column(*self, tile)
     }
 
     /// Returns how many tiles can fit in this row
@@ -197,6 +716,7 @@ impl RowIndex {
             RowIndex::Three => 3,
             RowIndex::Four => 4,
             RowIndex::Five => 5,
+            RowIndex::Six => 6,
         }
     }
 }
@@ -219,33 +739,48 @@ impl From<RowIndex> for usize {
     }
 }
 
-impl From<u8> for RowIndex {
-    fn from(value: u8) -> Self {
-        (value as usize).into()
+impl TryFrom<u8> for RowIndex {
+    type Error = AzulError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        usize::from(value).try_into()
     }
 }
 
-impl From<usize> for RowIndex {
-    fn from(value: usize) -> Self {
+impl TryFrom<usize> for RowIndex {
+    type Error = AzulError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
         match value {
-            0 => RowIndex::One,
-            1 => RowIndex::Two,
-            2 => RowIndex::Three,
-            3 => RowIndex::Four,
-            4 => RowIndex::Five,
-            _ => panic!("Invalid row index"),
+            0 => Ok(RowIndex::One),
+            1 => Ok(RowIndex::Two),
+            2 => Ok(RowIndex::Three),
+            3 => Ok(RowIndex::Four),
+            4 => Ok(RowIndex::Five),
+            5 => Ok(RowIndex::Six),
+            _ => Err(AzulError::RowIndex(value)),
         }
     }
 }
 
 /// For indexing into wall
-#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumIter)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, strum::EnumIter, serde::Serialize, serde::Deserialize,
+)]
 pub enum ColumnIndex {
     One,
     Two,
     Three,
     Four,
     Five,
+    Six,
+}
+
+impl ColumnIndex {
+    /// The first `count` columns, those on a wall for `count` colours
+    pub fn columns(count: usize) -> std::iter::Take<ColumnIndexIter> {
+        ColumnIndex::iter().take(count)
+    }
 }
 
 impl From<&ColumnIndex> for u8 {
@@ -260,47 +795,108 @@ impl From<&ColumnIndex> for usize {
     }
 }
 
-impl From<usize> for ColumnIndex {
-    fn from(value: usize) -> Self {
+impl TryFrom<usize> for ColumnIndex {
+    type Error = AzulError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
         match value {
-            0 => ColumnIndex::One,
-            1 => ColumnIndex::Two,
-            2 => ColumnIndex::Three,
-            3 => ColumnIndex::Four,
-            4 => ColumnIndex::Five,
-            _ => panic!("Invalid column index"),
+            0 => Ok(ColumnIndex::One),
+            1 => Ok(ColumnIndex::Two),
+            2 => Ok(ColumnIndex::Three),
+            3 => Ok(ColumnIndex::Four),
+            4 => Ok(ColumnIndex::Five),
+            5 => Ok(ColumnIndex::Six),
+            _ => Err(AzulError::ColumnIndex(value)),
         }
     }
 }
 
-impl From<u8> for ColumnIndex {
-    fn from(value: u8) -> Self {
-        (value as usize).into()
+impl TryFrom<u8> for ColumnIndex {
+    type Error = AzulError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        usize::from(value).try_into()
     }
 }
+
 #[cfg(test)]
 mod test {
+    use crate::{playerboard::wall::WALL_COLOURS, tiles::Tile};
+
     use strum::IntoEnumIterator;
 
-    use crate::{playerboard::wall::WALL_COLOURS, tiles::Tile};
+    use super::{ColumnIndex, RowIndex, Wall, WallPattern, WallPlacement};
 
-    use super::{RowIndex, Wall};
+    #[test]
+    fn index_conversions() {
+        use crate::error::AzulError;
+        assert_eq!(RowIndex::try_from(2u8), Ok(RowIndex::Three));
+        assert_eq!(RowIndex::try_from(6usize), Err(AzulError::RowIndex(6)));
+        assert_eq!(ColumnIndex::try_from(5u8), Ok(ColumnIndex::Six));
+        assert_eq!(
+            ColumnIndex::try_from(9usize),
+            Err(AzulError::ColumnIndex(9))
+        );
+        assert_eq!(Tile::try_from(4), Ok(Tile::White));
+        assert_eq!(Tile::try_from(6), Err(AzulError::Tile(6)));
+        for row in RowIndex::iter() {
+            assert_eq!(RowIndex::try_from(usize::from(row)), Ok(row));
+        }
+    }
 
     #[test]
     fn tile_column() {
-        for row in RowIndex::iter() {
-            for tile in Tile::iter() {
-                let col = row.tile_column(&tile);
+        for row in RowIndex::rows(5) {
+            for tile in Tile::colours(5) {
+                let col = row.tile_column(&tile, &WallPattern::STANDARD).unwrap();
                 assert_eq!(tile, WALL_COLOURS[row as usize][col as usize]);
+                // Matches the diagonal layout of the standard board
+                assert_eq!(col as u8, (row as u8 + tile as u8) % 5);
             }
         }
     }
 
+    #[test]
+    fn custom_pattern() {
+        assert_eq!(WallPattern::<5>::diagonal(), WallPattern::STANDARD);
+        assert_eq!(
+            "BYRKW/WBYRK/KWBYR/RKWBY/YRKWB".parse::<WallPattern>(),
+            Ok(WallPattern::STANDARD)
+        );
+        // Repeated colour in a column
+        assert!("BYRKW/BYRKW/KWBYR/RKWBY/YRKWB"
+            .parse::<WallPattern>()
+            .is_err());
+        assert!("BYRKW/WBYRK".parse::<WallPattern>().is_err());
+
+        let pattern: WallPattern = "BYRKW/YRKWB/RKWBY/KWBYR/WBYRK".parse().unwrap();
+        assert_eq!(
+            Wall::with_colours(*pattern.colours()),
+            Ok(Wall::with_pattern(pattern))
+        );
+        let mut colours = *pattern.colours();
+        colours[0].swap(0, 1);
+        assert!(Wall::with_colours(colours).is_err());
+        let mut wall = Wall::with_pattern(pattern);
+        assert_eq!(wall.pattern(), Some(pattern));
+        // No column for a colour the wall doesn't have
+        assert_eq!(pattern.column(RowIndex::One, &Tile::Orange), None);
+        assert_eq!(wall.pattern_column(RowIndex::One, Tile::Orange), None);
+        assert!(!wall.cell_available(RowIndex::One, &Tile::Orange));
+        wall.place_tile(RowIndex::Two, Tile::Blue);
+        assert_eq!(wall.iter().nth(1).unwrap()[4], Some(Tile::Blue));
+        assert!(!wall.cell_available(RowIndex::Two, &Tile::Blue));
+        // Yellow in row 3 is below the blue and white tiles in column 5
+        wall.place_tile(RowIndex::One, Tile::White);
+        assert_eq!(wall.score_tile(RowIndex::Three, Tile::Yellow), 3);
+        assert_eq!(wall.score_tile(RowIndex::Three, Tile::Black), 1);
+    }
+
     #[test]
     fn single_tile_score() {
-        let wal = Wall::default();
-        for row in RowIndex::iter() {
-            for tile in Tile::iter() {
+        let wal: Wall = Wall::default();
+        for row in RowIndex::rows(5) {
+            for tile in Tile::colours(5) {
                 let score = wal.score_tile(row, tile);
                 assert_eq!(score, 1);
             }
@@ -309,8 +905,8 @@ mod test {
 
     #[test]
     fn wall_colours() {
-        let mut wall = Wall::default();
-        dbg!(RowIndex::Two.tile_column(&Tile::Black));
+        let mut wall: Wall = Wall::default();
+        dbg!(RowIndex::Two.tile_column(&Tile::Black, &WallPattern::STANDARD));
         wall.place_tile(RowIndex::One, Tile::Black);
         // dbg!(wall);
     }
@@ -318,10 +914,10 @@ mod test {
     #[test]
     fn tile_scores() {
         // put tile in top left
-        let mut wall = Wall::default();
+        let mut wall: Wall = Wall::default();
         wall.place_tile(RowIndex::One, Tile::Blue);
-        for row in RowIndex::iter() {
-            for tile in Tile::iter() {
+        for row in RowIndex::rows(5) {
+            for tile in Tile::colours(5) {
                 let expected = match (row, tile) {
                     (RowIndex::One, Tile::Yellow) => 2,
                     (RowIndex::Two, Tile::White) => 2,
@@ -333,10 +929,10 @@ mod test {
         }
 
         // put tile in centre
-        let mut wall = Wall::default();
+        let mut wall: Wall = Wall::default();
         wall.place_tile(RowIndex::Three, Tile::Blue);
-        for row in RowIndex::iter() {
-            for tile in Tile::iter() {
+        for row in RowIndex::rows(5) {
+            for tile in Tile::colours(5) {
                 let expected = match (row, tile) {
                     (RowIndex::Two, Tile::Yellow) => 2,
                     (RowIndex::Three, Tile::White) => 2,
@@ -351,8 +947,8 @@ mod test {
 
         // Add a tile to top right
         wall.place_tile(RowIndex::Two, Tile::Red);
-        for row in RowIndex::iter() {
-            for tile in Tile::iter() {
+        for row in RowIndex::rows(5) {
+            for tile in Tile::colours(5) {
                 let expected = match (row, tile) {
                     (RowIndex::Two, Tile::Yellow) => 4,
                     (RowIndex::Three, Tile::White) => 2,
@@ -370,8 +966,8 @@ mod test {
         // Add more for complexity
         wall.place_tile(RowIndex::Three, Tile::Red);
         wall.place_tile(RowIndex::Four, Tile::Blue);
-        for row in RowIndex::iter() {
-            for tile in Tile::iter() {
+        for row in RowIndex::rows(5) {
+            for tile in Tile::colours(5) {
                 let expected = match (row, tile) {
                     (RowIndex::Two, Tile::Yellow) => 4,
                     (RowIndex::Three, Tile::White) => 2,
@@ -389,9 +985,30 @@ mod test {
         }
     }
 
+    #[test]
+    fn free_columns() {
+        let mut wall: Wall = Wall::with_placement(WallPattern::default(), WallPlacement::Free);
+        assert_eq!(wall.columns(RowIndex::One, &Tile::Red).len(), 5);
+        wall.place_tile_at(RowIndex::One, ColumnIndex::Three, Tile::Red);
+        assert_eq!(wall[(RowIndex::One, ColumnIndex::Three)], Some(Tile::Red));
+        // Once per row
+        assert!(!wall.cell_available(RowIndex::One, &Tile::Red));
+        // and once per column, in a free cell
+        wall.place_tile_at(RowIndex::Two, ColumnIndex::One, Tile::Blue);
+        assert_eq!(
+            wall.columns(RowIndex::Two, &Tile::Red),
+            [ColumnIndex::Two, ColumnIndex::Four, ColumnIndex::Five]
+        );
+        assert_eq!(wall.progress_for_colour(Tile::Red), 1);
+        assert_eq!(wall.tiles_text(), "..R../B..../...../...../.....");
+        assert_eq!(wall.pattern(), None);
+        let json = serde_json::to_string(&wall).unwrap();
+        assert_eq!(serde_json::from_str::<Wall>(&json).unwrap(), wall);
+    }
+
     #[test]
     fn wall_scores() {
-        let mut wall = Wall::default();
+        let mut wall: Wall = Wall::default();
         wall.place_tile(RowIndex::Five, Tile::Blue);
         assert_eq!(wall.score(), 0);
         wall.place_tile(RowIndex::Four, Tile::Yellow);
@@ -428,5 +1045,16 @@ mod test {
         assert_eq!(wall.score(), 28);
         wall.place_tile(RowIndex::Five, Tile::Yellow);
         assert_eq!(wall.score(), 38);
+        assert_eq!(wall.completed_rows(), 2);
+        assert_eq!(wall.completed_columns(), 2);
+        assert_eq!(wall.completed_colours(), 2);
+        assert_eq!(wall.progress_for_colour(Tile::Red), 3);
+        assert_eq!(wall.progress_for_row(RowIndex::Three), 3);
+        assert_eq!(
+            ColumnIndex::columns(5)
+                .map(|col| wall.progress_for_column(col))
+                .sum::<u8>(),
+            wall.tile_count()
+        );
     }
 }
@@ -0,0 +1,174 @@
+//! Beam search to the end of the round
+//!
+//! Instead of searching every move like alpha-beta, each of the picking side's plies keeps
+//! only the best `width` positions, and opponents are assumed to reply with their best move
+//! by the evaluator. The search reaches the end of the round even in early positions with
+//! over a hundred moves, and the root move leading to the best final position is played
+
+use crate::gamestate::{Gamestate, Move, State};
+
+use super::{evaluate::Evaluate, Player};
+
+#[derive(Debug, Clone)]
+pub struct BeamPlayer<E> {
+    /// Positions kept at each ply
+    width: usize,
+    /// Plies to search, or the rest of the round
+    max_depth: Option<u8>,
+    evaluator: E,
+}
+
+impl<E> BeamPlayer<E> {
+    pub fn new(width: usize, evaluator: E) -> Self {
+        Self {
+            width: width.max(1),
+            max_depth: None,
+            evaluator,
+        }
+    }
+
+    /// Stop after `depth` plies rather than at the end of the round
+    pub fn with_max_depth(mut self, depth: u8) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Positions after each of `moves`, scored for the player making the move,
+    /// along with the root move of the line, `first` or the move itself at the root.
+    /// Opponents of `side` only play their best move
+    fn expand<const P: usize, const F: usize>(
+        &self,
+        first: Option<Move>,
+        state: &Gamestate<P, F>,
+        moves: Vec<Move>,
+        side: f32,
+    ) -> Vec<(f32, Move, Gamestate<P, F>)>
+    where
+        E: Evaluate<P, F> + Clone,
+    {
+        let mover = sign(state);
+        let children = moves
+            .into_iter()
+            .map(|m| {
+                let mut child = state.clone();
+                child.play_move(m);
+                (first.unwrap_or(m), child)
+            })
+            .collect::<Vec<_>>();
+        let values = self
+            .evaluator
+            .clone()
+            .evaluate_batch(&children.iter().map(|(_, child)| child).collect::<Vec<_>>());
+        let children = values
+            .into_iter()
+            .zip(children)
+            .map(|(value, (m, child))| (mover * value, m, child));
+        if mover == side {
+            children.collect()
+        } else {
+            children
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .into_iter()
+                .collect()
+        }
+    }
+
+    /// Keep the best `width` lines, skipping repeats of the same position
+    fn prune<const P: usize, const F: usize>(
+        &self,
+        mut lines: Vec<(f32, Move, Gamestate<P, F>)>,
+    ) -> Vec<(Move, Gamestate<P, F>)> {
+        lines.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut kept: Vec<(Move, Gamestate<P, F>)> = Vec::with_capacity(self.width);
+        for (_, first, state) in lines {
+            if kept.len() == self.width {
+                break;
+            }
+            if !kept.iter().any(|(_, s)| *s == state) {
+                kept.push((first, state));
+            }
+        }
+        kept
+    }
+}
+
+/// +1 when the player to move is on seat 0's side, -1 otherwise
+fn sign<const P: usize, const F: usize>(gs: &Gamestate<P, F>) -> f32 {
+    if gs.team(gs.current_player()) == gs.team(0) {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+impl<const P: usize, const F: usize, E> Player<P, F> for BeamPlayer<E>
+where
+    E: Evaluate<P, F> + Clone,
+{
+    fn pick_move(&mut self, gs: &Gamestate<P, F>, moves: Vec<Move>) -> Move {
+        // Search as seat 0, which the evaluators score for
+        let gs = &gs.rotated(gs.current_player());
+        let side = sign(gs);
+        // Root move and the position it has led to
+        let mut beam = self.prune(self.expand(None, gs, moves.clone(), side));
+        let mut finished = Vec::new();
+        let mut depth = 1;
+        loop {
+            let (done, active): (Vec<_>, Vec<_>) = beam
+                .into_iter()
+                .partition(|(_, s)| s.state() != State::RoundActive);
+            finished.extend(done);
+            if active.is_empty() || self.max_depth.is_some_and(|d| depth >= d) {
+                beam = active;
+                break;
+            }
+            beam = self.prune(
+                active
+                    .into_iter()
+                    .flat_map(|(first, s)| {
+                        self.expand(Some(first), &s, s.get_distinct_moves(), side)
+                    })
+                    .collect(),
+            );
+            depth += 1;
+        }
+        let lines = finished.iter().chain(&beam);
+        let values = self
+            .evaluator
+            .clone()
+            .evaluate_batch(&lines.clone().map(|(_, s)| s).collect::<Vec<_>>());
+        values
+            .into_iter()
+            .zip(lines)
+            .map(|(value, (m, _))| (side * value, *m))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map_or(moves[0], |(_, m)| m)
+    }
+
+    fn name(&self) -> String {
+        format!("Beam{}", self.width)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BeamPlayer;
+    use crate::{
+        players::{minimax::ScoreEvaluator, MoveRankPlayer},
+        runner::Runner,
+    };
+
+    #[test]
+    fn beats_move_rank() {
+        let result = Runner::new_2_player(
+            [
+                Box::new(BeamPlayer::new(4, ScoreEvaluator)),
+                Box::new(MoveRankPlayer),
+            ],
+            Some(5),
+        )
+        .run_matchup(2);
+        assert_eq!(result.games, 4);
+        assert!(result.average_score() > 0.0);
+    }
+}
@@ -0,0 +1,155 @@
+//! Curriculum of opponents for training RL agents
+//!
+//! The learner starts against the first stage's opponent and is promoted to
+//! the next stage once its win rate over the recent games crosses the stage threshold
+
+use std::collections::VecDeque;
+
+use burn::config::Config;
+
+use crate::players::{
+    minimax::{Minimaxer, ScoreEvaluator},
+    MoveRankPlayer, MoveRankPlayer2, Player, RandomPlayer,
+};
+
+/// Opponent played during a stage of the curriculum
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Opponent {
+    Random,
+    MoveRank,
+    MoveRank2,
+    /// Alpha-beta search to a fixed depth using the predicted score
+    Minimax(u8),
+}
+
+impl Opponent {
+    pub fn player(&self) -> Box<dyn Player<2, 6>> {
+        match *self {
+            // Seeded so training runs can be repeated
+            Opponent::Random => Box::new(RandomPlayer::with_seed(0)),
+            Opponent::MoveRank => Box::new(MoveRankPlayer::new()),
+            Opponent::MoveRank2 => Box::new(MoveRankPlayer2::new()),
+            Opponent::Minimax(depth) => Box::new(Minimaxer::new(
+                minimaxer::negamax::SearchOptions {
+                    max_depth: Some(depth),
+                    alpha_beta: true,
+                    ..Default::default()
+                },
+                format!("Depth{depth}"),
+                ScoreEvaluator,
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Stage {
+    pub opponent: Opponent,
+    /// Win rate required to move on to the next stage
+    pub promote_at: f32,
+}
+
+#[derive(Config, Debug)]
+pub struct CurriculumConfig {
+    #[config(default = "default_stages()")]
+    pub stages: Vec<Stage>,
+    /// Number of recent games the win rate is measured over
+    #[config(default = 200)]
+    pub window: usize,
+}
+
+/// Random, then the move ranking players, then minimax at increasing depth
+fn default_stages() -> Vec<Stage> {
+    let stage = |opponent, promote_at| Stage {
+        opponent,
+        promote_at,
+    };
+    vec![
+        stage(Opponent::Random, 0.9),
+        stage(Opponent::MoveRank, 0.7),
+        stage(Opponent::MoveRank2, 0.6),
+        stage(Opponent::Minimax(1), 0.55),
+        stage(Opponent::Minimax(2), 0.55),
+        stage(Opponent::Minimax(3), 1.0),
+    ]
+}
+
+/// Tracks the learner's results and the current stage
+#[derive(Debug, Clone)]
+pub struct Curriculum {
+    config: CurriculumConfig,
+    stage: usize,
+    results: VecDeque<bool>,
+}
+
+impl Curriculum {
+    pub fn new(config: CurriculumConfig) -> Self {
+        assert!(
+            !config.stages.is_empty(),
+            "Curriculum needs at least one stage"
+        );
+        Self {
+            config,
+            stage: 0,
+            results: VecDeque::new(),
+        }
+    }
+
+    pub fn stage(&self) -> &Stage {
+        &self.config.stages[self.stage]
+    }
+
+    /// Create the opponent for the current stage
+    pub fn opponent(&self) -> Box<dyn Player<2, 6>> {
+        self.stage().opponent.player()
+    }
+
+    /// Win rate over the recent games of the current stage
+    pub fn win_rate(&self) -> f32 {
+        if self.results.is_empty() {
+            0.0
+        } else {
+            self.results.iter().filter(|&&w| w).count() as f32 / self.results.len() as f32
+        }
+    }
+
+    /// Record whether the learner won each game,
+    /// returning true if it was promoted to the next stage
+    pub fn record(&mut self, wins: impl IntoIterator<Item = bool>) -> bool {
+        for win in wins {
+            if self.results.len() == self.config.window {
+                self.results.pop_front();
+            }
+            self.results.push_back(win);
+        }
+        let last = self.stage + 1 == self.config.stages.len();
+        if last
+            || self.results.len() < self.config.window
+            || self.win_rate() < self.stage().promote_at
+        {
+            return false;
+        }
+        self.stage += 1;
+        self.results.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Curriculum, CurriculumConfig, Opponent};
+
+    #[test]
+    fn promotion() {
+        let mut curriculum = Curriculum::new(CurriculumConfig::new().with_window(10));
+        assert_eq!(curriculum.stage().opponent, Opponent::Random);
+        // Not enough games yet
+        assert!(!curriculum.record([true; 5]));
+        // Win rate below threshold
+        assert!(!curriculum.record([false; 5]));
+        assert!(curriculum.record([true; 10]));
+        assert_eq!(curriculum.stage().opponent, Opponent::MoveRank);
+        assert_eq!(curriculum.win_rate(), 0.0);
+    }
+}
@@ -0,0 +1,104 @@
+//! Position evaluation shared by the search players
+//!
+//! Evaluators implement [Evaluate] from this crate rather than the minimaxer crate's trait,
+//! so beam search, Monte Carlo and any later search can use them too.
+//! [MinimaxerEvaluator] adapts one for the minimaxer crate
+
+use dyn_clone::DynClone;
+
+use crate::gamestate::Gamestate;
+
+/// Positions evaluated at once by [evaluate_positions]
+pub const BATCH: usize = 256;
+
+/// Scores a position for seat 0's side, higher is better for seat 0,
+/// see [Gamestate::side_margin]
+pub trait Evaluate<const P: usize, const F: usize>: DynClone {
+    fn evaluate(&mut self, gs: &Gamestate<P, F>) -> f32;
+
+    /// Score several positions at once, which evaluators such as neural networks
+    /// can do faster than one at a time
+    fn evaluate_batch(&mut self, states: &[&Gamestate<P, F>]) -> Vec<f32> {
+        states.iter().map(|gs| self.evaluate(gs)).collect()
+    }
+}
+
+/// Score every position, in order, passing them to the evaluator [BATCH] at a time.
+/// With more than one thread, the positions are split between copies of the evaluator
+pub fn evaluate_positions<const P: usize, const F: usize>(
+    positions: &[Gamestate<P, F>],
+    evaluator: &mut (dyn Evaluate<P, F> + Send),
+    threads: usize,
+) -> Vec<f32> {
+    let threads = threads.clamp(1, positions.len().div_ceil(BATCH).max(1));
+    if threads == 1 {
+        return evaluate_in_batches(positions, evaluator);
+    }
+    let per_thread = positions.len().div_ceil(threads);
+    std::thread::scope(|s| {
+        let handles = positions
+            .chunks(per_thread)
+            .map(|chunk| {
+                let mut evaluator = dyn_clone::clone_box(&*evaluator);
+                s.spawn(move || evaluate_in_batches(chunk, &mut *evaluator))
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("Evaluation thread panicked"))
+            .collect()
+    })
+}
+
+fn evaluate_in_batches<const P: usize, const F: usize>(
+    positions: &[Gamestate<P, F>],
+    evaluator: &mut (dyn Evaluate<P, F> + Send),
+) -> Vec<f32> {
+    positions
+        .chunks(BATCH)
+        .flat_map(|batch| evaluator.evaluate_batch(&batch.iter().collect::<Vec<_>>()))
+        .collect()
+}
+
+/// Use an [Evaluate] with the minimaxer crate's search
+#[derive(Debug, Clone)]
+pub struct MinimaxerEvaluator<E>(pub E);
+
+impl<const P: usize, const F: usize, E: Evaluate<P, F> + Clone> minimaxer::Evaluate<Gamestate<P, F>>
+    for MinimaxerEvaluator<E>
+{
+    fn evaluate(&mut self, gs: &Gamestate<P, F>) -> f32 {
+        self.0.evaluate(gs)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{evaluate_positions, Evaluate, BATCH};
+    use crate::{gamestate::Gamestate, players::minimax::ScoreEvaluator};
+
+    #[test]
+    fn batches() {
+        let gs = Gamestate::new_2_player_with_seed(2, 0);
+        // Enough positions for several batches, some cut short
+        let positions = (0..BATCH * 2 + 10)
+            .map(|i| {
+                let mut child = gs.clone();
+                let moves = child.get_moves();
+                child.play_move(moves[i % moves.len()]);
+                child
+            })
+            .collect::<Vec<_>>();
+        let single = positions
+            .iter()
+            .map(|p| ScoreEvaluator.evaluate(p))
+            .collect::<Vec<_>>();
+        for threads in [1, 2, 8] {
+            assert_eq!(
+                evaluate_positions(&positions, &mut ScoreEvaluator, threads),
+                single
+            );
+        }
+        assert!(evaluate_positions(&positions[..0], &mut ScoreEvaluator, 4).is_empty());
+    }
+}
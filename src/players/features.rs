@@ -0,0 +1,186 @@
+//! Positions and moves as vectors of numbers, the inputs of the learned players
+//!
+//! A network trained on one layout plays nonsense given another, so each encoder has a
+//! [StateEncoder::VERSION] that changes whenever its layout does. New layouts are added
+//! as new encoders rather than by editing old ones, so saved models keep working.
+//! All values are roughly between 0 and 1, except where noted
+
+use crate::{
+    gamestate::{Gamestate, Move},
+    playerboard::{wall::Wall, PlayerBoard},
+    tiles::TileGroup,
+};
+
+/// Encodes a whole position
+pub trait StateEncoder<const P: usize, const F: usize> {
+    /// Changes whenever the layout does
+    const VERSION: u32;
+    /// Length of every encoding
+    const LEN: usize;
+
+    /// Write the encoding of `gs` into `out`, which is [StateEncoder::LEN] long
+    fn encode_into(&self, gs: &Gamestate<P, F>, out: &mut [f32]);
+
+    fn encode(&self, gs: &Gamestate<P, F>) -> Vec<f32> {
+        let mut out = vec![0.0; Self::LEN];
+        self.encode_into(gs, &mut out);
+        out
+    }
+}
+
+/// Encodes one of the moves from a position
+pub trait MoveEncoder<const P: usize, const F: usize> {
+    /// Changes whenever the layout does
+    const VERSION: u32;
+    /// Length of every encoding
+    const LEN: usize;
+
+    /// Write the encoding of `move_` played from `gs` into `out`, which is [MoveEncoder::LEN] long
+    fn encode_into(&self, gs: &Gamestate<P, F>, move_: Move, out: &mut [f32]);
+
+    fn encode(&self, gs: &Gamestate<P, F>, move_: Move) -> Vec<f32> {
+        let mut out = vec![0.0; Self::LEN];
+        self.encode_into(gs, move_, &mut out);
+        out
+    }
+}
+
+/// Values for each board in [StateFeaturesV1]
+const BOARD_LEN: usize = 59;
+
+/// The position as seen by the NN, MuZero and PPO players, 150 values for 2 players:
+/// 1. Each board in seat order, see [board_features]
+/// 2. Count of each colour in each factory, centre first, divided by 5
+/// 3. Whether the first player tile is in the centre
+/// 4. Round divided by 5, which goes above 1 in long games
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StateFeaturesV1;
+
+impl<const P: usize, const F: usize> StateEncoder<P, F> for StateFeaturesV1 {
+    const VERSION: u32 = 1;
+    const LEN: usize = P * BOARD_LEN + F * 5 + 2;
+
+    fn encode_into(&self, gs: &Gamestate<P, F>, out: &mut [f32]) {
+        let values = gs
+            .boards()
+            .iter()
+            .flat_map(board_features)
+            .chain(gs.factories().iter().flat_map(|f| match f {
+                Some(f) => factory_features(f),
+                None => [0.0; 5],
+            }))
+            .chain([gs.first_player_tile() as u8 as f32, gs.round() as f32 / 5.0]);
+        for (v, value) in out.iter_mut().zip(values) {
+            *v = value;
+        }
+    }
+}
+
+fn factory_features(factory: &TileGroup) -> [f32; 5] {
+    factory.counts().map(|v| f32::from(v) / 5.0)
+}
+
+/// The 59 values for a board in [StateFeaturesV1]:
+/// 1. For each pattern line, which colour it holds one-hot, then how full it is
+/// 2. Whether each wall space is filled, row by row
+/// 3. The floor, the first player tile, score / 100 and predicted score / 100
+///
+/// The floor value is always 1, a mistake kept so the trained networks still work
+pub fn board_features(pb: &PlayerBoard) -> [f32; BOARD_LEN] {
+    let mut arr = [0.0; BOARD_LEN];
+    for (v, value) in arr.iter_mut().zip(
+        pb.row_iter()
+            .flat_map(|(ind, row)| {
+                let mut arr = [0.0; 6];
+                if let Some(tile) = row.tile() {
+                    arr[tile as usize] = 1.0;
+                    arr[5] = row.count() as f32 / (ind.capacity() as f32);
+                }
+                arr
+            })
+            .chain(wall_features(&pb.wall))
+            .chain([
+                pb.floor.tiles().total().max(7) as f32 / 7.0,
+                pb.floor.has_first_player_tile() as u8 as f32,
+                pb.score as f32 / 100.0,
+                pb.predicted_score as f32 / 100.0,
+            ]),
+    ) {
+        *v = value;
+    }
+    arr
+}
+
+/// 1 for each filled wall space, row by row
+pub fn wall_features(wall: &Wall) -> [f32; 25] {
+    let mut arr = [0.0; 25];
+    for (i, row) in wall.iter().enumerate() {
+        for (j, tile) in row.iter().enumerate() {
+            arr[i * 5 + j] = if tile.is_some() { 1.0 } else { 0.0 };
+        }
+    }
+    arr
+}
+
+/// The 8 values the GA move players weigh, for the player to move:
+/// 1. Tiles taken
+/// 2. Tiles that miss the pattern line, landing on the floor or in the lid
+/// 3. Capacity of the destination row, 0 for the floor
+/// 4. Whether the row is filled
+/// 5. Change in predicted score
+/// 6. Whether the row is filled exactly
+/// 7. Whether the first player tile is taken
+/// 8. Whether every tile goes in the row
+///
+/// Counts and scores are not scaled
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MoveFeaturesV1;
+
+impl<const P: usize, const F: usize> MoveEncoder<P, F> for MoveFeaturesV1 {
+    const VERSION: u32 = 1;
+    const LEN: usize = 8;
+
+    fn encode_into(&self, gs: &Gamestate<P, F>, move_: Move, out: &mut [f32]) {
+        let detail = gs.detail_move(move_);
+        let placed = detail.placed;
+        let missed = move_.count - placed.to_row;
+        let values = [
+            move_.count as f32,
+            missed as f32,
+            move_.row_capacity() as f32,
+            placed.fills_row as u8 as f32,
+            placed.score_delta as f32,
+            (placed.fills_row && missed == 0) as u8 as f32,
+            detail.fp as u8 as f32,
+            (placed.to_row > 0 && missed == 0) as u8 as f32,
+        ];
+        out.copy_from_slice(&values);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MoveEncoder, MoveFeaturesV1, StateEncoder, StateFeaturesV1};
+    use crate::gamestate::Gamestate;
+
+    #[test]
+    fn lengths() {
+        let gs = Gamestate::new_2_player_with_seed(5, 0);
+        let state = StateFeaturesV1.encode(&gs);
+        assert_eq!(state.len(), 150);
+        // First player tile in the centre, round 1
+        assert_eq!(state[148..], [1.0, 0.2]);
+        // 4 tiles in every factory
+        let factories = &state[118..148];
+        assert!((factories[5..].iter().sum::<f32>() - 4.0).abs() < 1e-5);
+
+        let gs: Gamestate<3, 8> = Gamestate::new(5, 0);
+        assert_eq!(StateFeaturesV1.encode(&gs).len(), 3 * 59 + 8 * 5 + 2);
+
+        for m in gs.get_moves() {
+            let features = MoveFeaturesV1.encode(&gs, m);
+            assert_eq!(features.len(), 8);
+            assert_eq!(features[0], m.count as f32);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Flat Monte Carlo player
+//!
+//! Every legal move is followed by rollouts to the end of the game with
+//! [MoveRankPlayer2] playing all seats, and the move with the best average margin is picked.
+//! Each rollout reseeds the bag, and every move is played out with the same seeds
+//! so they are compared on the same futures
+
+use rand::{rngs::SmallRng, RngCore, SeedableRng};
+
+use crate::gamestate::{Gamestate, Move};
+
+use super::{evaluate::Evaluate, MoveRankPlayer2, Player};
+
+#[derive(Debug, Clone)]
+pub struct FlatMcPlayer {
+    /// Rollouts played after each move
+    rollouts: u32,
+    /// Threads the moves are shared between
+    threads: usize,
+    rng: SmallRng,
+}
+
+impl FlatMcPlayer {
+    pub fn new(rollouts: u32) -> Self {
+        Self {
+            rollouts,
+            threads: 1,
+            rng: SmallRng::from_entropy(),
+        }
+    }
+
+    /// Seed the rollouts, for repeatable games
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SmallRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Share the rollouts between `threads` threads
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+}
+
+impl<const P: usize, const F: usize> Player<P, F> for FlatMcPlayer {
+    fn pick_move(&mut self, gs: &Gamestate<P, F>, moves: Vec<Move>) -> Move {
+        let seeds = (0..self.rollouts)
+            .map(|_| self.rng.next_u64())
+            .collect::<Vec<_>>();
+        let player = gs.current_player() as usize;
+        let value = |m: &Move| -> f64 {
+            seeds
+                .iter()
+                .map(|&seed| rollout(gs, *m, seed, player))
+                .sum()
+        };
+        let values = if self.threads == 1 {
+            moves.iter().map(value).collect::<Vec<_>>()
+        } else {
+            let chunk = moves.len().div_ceil(self.threads);
+            std::thread::scope(|s| {
+                moves
+                    .chunks(chunk)
+                    .map(|chunk| s.spawn(|| chunk.iter().map(value).collect::<Vec<_>>()))
+                    .collect::<Vec<_>>()
+                    .into_iter()
+                    .flat_map(|h| h.join().expect("Rollout thread panicked"))
+                    .collect()
+            })
+        };
+        let best = values
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map_or(0, |(i, _)| i);
+        moves[best]
+    }
+
+    fn name(&self) -> String {
+        format!("FlatMc{}", self.rollouts)
+    }
+}
+
+/// Play `m` then the rest of the game, returning `player`'s score minus the best other score
+fn rollout<const P: usize, const F: usize>(
+    gs: &Gamestate<P, F>,
+    m: Move,
+    seed: u64,
+    player: usize,
+) -> f64 {
+    let mut gs = gs.sample_future(seed);
+    gs.play_move(m);
+    let scores = play_out(&mut gs, seed);
+    let best_other = (0..P)
+        .filter(|&p| p != player)
+        .map(|p| scores[p])
+        .max()
+        .unwrap_or(0);
+    scores[player] as f64 - best_other as f64
+}
+
+/// Play the rest of the game with [MoveRankPlayer2] in every seat, dealing from `seed`
+fn play_out<const P: usize, const F: usize>(gs: &mut Gamestate<P, F>, seed: u64) -> [u8; P] {
+    let mut policy = MoveRankPlayer2;
+    gs.rollout(&mut SmallRng::seed_from_u64(seed), |gs, moves| {
+        policy.pick_move(gs, moves.to_vec())
+    })
+}
+
+/// Values a position by the average final margin of rollouts from it, see [Gamestate::side_margin]
+#[derive(Debug, Clone)]
+pub struct RolloutEvaluator {
+    /// Rollouts played for each position
+    rollouts: u32,
+    rng: SmallRng,
+}
+
+impl RolloutEvaluator {
+    pub fn new(rollouts: u32) -> Self {
+        Self {
+            rollouts: rollouts.max(1),
+            rng: SmallRng::from_entropy(),
+        }
+    }
+
+    /// Seed the rollouts, for repeatable games
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SmallRng::seed_from_u64(seed);
+        self
+    }
+}
+
+impl<const P: usize, const F: usize> Evaluate<P, F> for RolloutEvaluator {
+    fn evaluate(&mut self, gs: &Gamestate<P, F>) -> f32 {
+        let total: f32 = (0..self.rollouts)
+            .map(|_| {
+                let mut gs = gs.sample_future(self.rng.next_u64());
+                let scores = play_out(&mut gs, self.rng.next_u64());
+                gs.side_margin(scores.map(|s| s as f32))
+            })
+            .sum();
+        total / self.rollouts as f32
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FlatMcPlayer;
+    use crate::{players::MoveRankPlayer, runner::Runner};
+
+    #[test]
+    fn beats_move_rank() {
+        let result = Runner::new_2_player(
+            [
+                Box::new(FlatMcPlayer::new(4).with_seed(1).with_threads(2)),
+                Box::new(MoveRankPlayer),
+            ],
+            Some(3),
+        )
+        .run_matchup(1);
+        assert_eq!(result.games, 2);
+        assert!(result.average_score() > 0.0);
+    }
+}
@@ -1,12 +1,25 @@
-use crate::gamestate;
+use crate::{
+    gamestate::{self, Phase},
+    playerboard::{wall::ColumnIndex, PlayerBoard, RowIndex},
+    tiles::{Tile, TileTracker},
+};
+use burn::config::Config;
 use log::debug;
-use minimaxer::{self, negamax::SearchOptions, node::Node, Evaluate};
+use minimaxer::{self, negamax::SearchOptions, node::Node};
 
-use super::Player;
+use super::{
+    evaluate::{Evaluate, MinimaxerEvaluator},
+    Player,
+};
 
-impl minimaxer::Gamestate<gamestate::Move> for gamestate::Gamestate<2, 6> {
+/// Seat 0's side maximises and everyone else minimises.
+/// In games without teams that makes the search paranoid, assuming every
+/// opponent plays against seat 0, so [Minimaxer] rotates the seats to search as seat 0
+impl<const P: usize, const F: usize> minimaxer::Gamestate<gamestate::Move>
+    for gamestate::Gamestate<P, F>
+{
     fn get_moves(&mut self) -> Vec<gamestate::Move> {
-        gamestate::Gamestate::get_moves(self)
+        self.get_distinct_moves()
     }
 
     fn play_move(&mut self, m: &gamestate::Move) {
@@ -14,39 +27,141 @@ impl minimaxer::Gamestate<gamestate::Move> for gamestate::Gamestate<2, 6> {
     }
 
     fn player_aim(&self) -> minimaxer::NodeAim {
-        match self.current_player() {
-            0 => minimaxer::NodeAim::Maximise,
-            1 => minimaxer::NodeAim::Minimise,
-            _ => panic!("Invalid player"),
+        if self.team(self.current_player()) == self.team(0) {
+            minimaxer::NodeAim::Maximise
+        } else {
+            minimaxer::NodeAim::Minimise
         }
     }
 }
 
 impl minimaxer::Move for gamestate::Move {}
 
+/// Predicted score margin of seat 0's side
 #[derive(Debug, Clone)]
 pub struct ScoreEvaluator;
 
-impl minimaxer::Evaluate<gamestate::Gamestate<2, 6>> for ScoreEvaluator {
-    fn evaluate(&mut self, g: &gamestate::Gamestate<2, 6>) -> f32 {
-        g.differential_predicted_score()
+impl<const P: usize, const F: usize> Evaluate<P, F> for ScoreEvaluator {
+    fn evaluate(&mut self, g: &gamestate::Gamestate<P, F>) -> f32 {
+        g.predicted_margin()
     }
 }
 
-// Evaluate based on score and other heuristics
+/// Difference in combined predicted score between the teams
 #[derive(Debug, Clone)]
+pub struct TeamScoreEvaluator;
+
+impl Evaluate<4, 10> for TeamScoreEvaluator {
+    fn evaluate(&mut self, g: &gamestate::Gamestate<4, 10>) -> f32 {
+        g.team_differential_predicted_score()
+    }
+}
+
+/// Evaluate based on score and other heuristics, from player 0's point of view
+///
+/// Weights are public so they can be tuned, see [crate::players::tuning]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct HeuristicEvaluator {
-    fp_weight: f32,
-    wall_weight: [[f32; 5]; 5], // Weight for each position on the wall
+    /// Value of holding the first player tile
+    pub fp_weight: f32,
+    /// Weight for each position on the wall
+    pub wall_weight: [[f32; 5]; 5],
+    /// Weight of progress towards completing rows, columns and colours,
+    /// each measured as the sum of the squared fraction filled
+    #[serde(default)]
+    pub bonus_weight: [f32; 3],
+    /// Weight of the chance that the next deal has enough tiles to finish each
+    /// pattern line left unfinished, counted with a [TileTracker]
+    #[serde(default)]
+    pub supply_weight: f32,
 }
 
+/// Number of weights in a [HeuristicEvaluator]
+pub const HEURISTIC_WEIGHTS: usize = 1 + 25 + 3 + 1;
+
 impl HeuristicEvaluator {
     pub fn new_no_wall_weight(fp_weight: f32) -> Self {
         Self {
             fp_weight,
             wall_weight: [[0.0; 5]; 5],
+            bonus_weight: [0.0; 3],
+            supply_weight: 0.0,
         }
     }
+
+    /// All weights in a flat list, first player tile then wall then bonuses then supply
+    pub fn weights(&self) -> [f32; HEURISTIC_WEIGHTS] {
+        let mut weights = [0.0; HEURISTIC_WEIGHTS];
+        weights[0] = self.fp_weight;
+        for (w, &v) in weights[1..26]
+            .iter_mut()
+            .zip(self.wall_weight.iter().flatten())
+        {
+            *w = v;
+        }
+        weights[26..29].copy_from_slice(&self.bonus_weight);
+        weights[29] = self.supply_weight;
+        weights
+    }
+
+    /// Inverse of [HeuristicEvaluator::weights]
+    pub fn from_weights(weights: &[f32; HEURISTIC_WEIGHTS]) -> Self {
+        let mut wall_weight = [[0.0; 5]; 5];
+        for (w, &v) in wall_weight.iter_mut().flatten().zip(&weights[1..26]) {
+            *w = v;
+        }
+        Self {
+            fp_weight: weights[0],
+            wall_weight,
+            bonus_weight: [weights[26], weights[27], weights[28]],
+            supply_weight: weights[29],
+        }
+    }
+
+    /// Heuristic value of one player's board, apart from the score.
+    /// Only the first 5 rows and columns of a larger wall are weighted
+    fn board_value<const C: usize>(
+        &self,
+        board: &PlayerBoard<C>,
+        tracker: Option<&TileTracker<C>>,
+    ) -> f32 {
+        let wall = board.simulate_wall();
+        let mut value = 0.0;
+        for (row, weight) in wall.iter().zip(self.wall_weight.iter()) {
+            for (tile, &w) in row.iter().zip(weight.iter()) {
+                if tile.is_some() {
+                    value += w;
+                }
+            }
+        }
+        let square = |n: u8| (n as f32 / C as f32).powi(2);
+        let progress: [f32; 3] = [
+            RowIndex::rows(C)
+                .map(|row| square(wall.progress_for_row(row)))
+                .sum(),
+            ColumnIndex::columns(C)
+                .map(|col| square(wall.progress_for_column(col)))
+                .sum(),
+            Tile::colours(C)
+                .map(|tile| square(wall.progress_for_colour(tile)))
+                .sum(),
+        ];
+        for (progress, &weight) in progress.iter().zip(&self.bonus_weight) {
+            value += weight * progress;
+        }
+        if let Some(tracker) = tracker {
+            let supply: f64 = board
+                .row_iter()
+                .filter_map(|(row, line)| {
+                    let missing = row.capacity() - line.count();
+                    let tile = line.tile().filter(|_| missing > 0)?;
+                    Some(tracker.prob_at_least(tile, missing))
+                })
+                .sum();
+            value += self.supply_weight * supply as f32;
+        }
+        value
+    }
 }
 
 impl Default for HeuristicEvaluator {
@@ -60,31 +175,63 @@ impl Default for HeuristicEvaluator {
                 [0.85, 0.9, 0.95, 0.9, 0.85],
                 [0.8, 0.85, 0.9, 0.85, 0.8],
             ],
+            bonus_weight: [0.0; 3],
+            supply_weight: 0.0,
         }
     }
 }
 
-impl minimaxer::Evaluate<gamestate::Gamestate<2, 6>> for HeuristicEvaluator {
-    fn evaluate(&mut self, g: &gamestate::Gamestate<2, 6>) -> f32 {
-        // Combine various heuristics to evaluate the game state
-        let mut score = g.differential_predicted_score();
-        // Check who has the first tile marker
-        score += if g.boards()[0].first_player_tile {
-            self.fp_weight
-        } else if g.boards()[1].first_player_tile {
-            -self.fp_weight
-        } else {
-            0.0
-        };
-        let wall = g.boards()[0].simulate_wall();
-        for (row, weight) in wall.iter().zip(self.wall_weight.iter()) {
-            for (tile, &w) in row.iter().zip(weight.iter()) {
-                if tile.is_some() {
-                    score += w;
-                }
-            }
+impl<const P: usize, const F: usize> Evaluate<P, F> for HeuristicEvaluator {
+    fn evaluate(&mut self, g: &gamestate::Gamestate<P, F>) -> f32 {
+        // Counting the tiles is only worth it when they are weighted
+        let tracker = (self.supply_weight != 0.0).then(|| g.tile_tracker());
+        // Combine various heuristics for each player, then compare the sides
+        let values = std::array::from_fn(|i| {
+            let board = &g.boards()[i];
+            let fp = if board.floor.has_first_player_tile() {
+                self.fp_weight
+            } else {
+                0.0
+            };
+            board.predicted_score as f32 + fp + self.board_value(board, tracker.as_ref())
+        });
+        g.side_margin(values)
+    }
+}
+
+/// How much the search time and depth are scaled in each [Phase] of the game
+#[derive(Config, Debug, PartialEq)]
+pub struct SearchConfig {
+    /// Early rounds, where the bag makes searching far ahead less useful
+    #[config(default = 0.5)]
+    pub early: f32,
+    #[config(default = 1.0)]
+    pub middle: f32,
+    /// Possible final round, with no bag draws left to hide the outcome
+    #[config(default = 2.0)]
+    pub late: f32,
+}
+
+impl SearchConfig {
+    /// Scale for positions in `phase`
+    pub fn scale(&self, phase: Phase) -> f32 {
+        match phase {
+            Phase::Early => self.early,
+            Phase::Middle => self.middle,
+            Phase::Late => self.late,
+        }
+    }
+
+    /// `opts` with the time limit and depth scaled for `phase`, keeping at least one ply
+    pub fn options(&self, opts: SearchOptions, phase: Phase) -> SearchOptions {
+        let scale = self.scale(phase).max(0.0);
+        SearchOptions {
+            max_time: opts.max_time.map(|t| t.mul_f32(scale)),
+            max_depth: opts
+                .max_depth
+                .map(|d| (d as f32 * scale).round().clamp(1.0, u8::MAX as f32) as u8),
+            ..opts
         }
-        score
     }
 }
 
@@ -93,6 +240,8 @@ pub struct Minimaxer<E> {
     pub opts: minimaxer::negamax::SearchOptions,
     pub name: String,
     pub evaluator: E,
+    /// Scales [Minimaxer::opts] by the phase of the game when set
+    pub phases: Option<SearchConfig>,
 }
 
 impl<E> Minimaxer<E> {
@@ -105,20 +254,35 @@ impl<E> Minimaxer<E> {
             opts,
             name: name.into(),
             evaluator,
+            phases: None,
         }
     }
+
+    /// Scale the search time and depth by the phase of the game
+    pub fn with_phases(mut self, config: SearchConfig) -> Self {
+        self.phases = Some(config);
+        self
+    }
 }
 
-impl<E: Evaluate<gamestate::Gamestate<2, 6>>> Player<2, 6> for Minimaxer<E> {
+impl<const P: usize, const F: usize, E> Player<P, F> for Minimaxer<E>
+where
+    E: Evaluate<P, F> + Clone,
+{
     fn pick_move(
         &mut self,
-        gamestate: &gamestate::Gamestate<2, 6>,
-        moves: Vec<gamestate::Move>,
+        gamestate: &gamestate::Gamestate<P, F>,
+        _moves: Vec<gamestate::Move>,
     ) -> gamestate::Move {
+        let opts = match &self.phases {
+            Some(config) => config.options(self.opts, gamestate.phase()),
+            None => self.opts,
+        };
+        // Search as seat 0, which the evaluators score for
         let mut n = minimaxer::negamax::Negamax::new(
-            Node::new(gamestate.clone()),
-            self.evaluator.clone(),
-            self.opts,
+            Node::new(gamestate.rotated(gamestate.current_player())),
+            MinimaxerEvaluator(self.evaluator.clone()),
+            opts,
         );
         let result = n.search();
         debug!("Minimax search result: {:?}", result);
@@ -129,3 +293,81 @@ impl<E: Evaluate<gamestate::Gamestate<2, 6>>> Player<2, 6> for Minimaxer<E> {
         self.name.clone()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{HeuristicEvaluator, Minimaxer, ScoreEvaluator, HEURISTIC_WEIGHTS};
+    use crate::{
+        gamestate::{Gamestate, State},
+        players::{evaluate::Evaluate, Player, RandomPlayer},
+    };
+
+    /// Play one game and return the scores
+    fn play<const P: usize, const F: usize>(
+        mut gs: Gamestate<P, F>,
+        players: &mut [Box<dyn Player<P, F>>; P],
+    ) -> [u8; P] {
+        loop {
+            let moves = gs.get_moves();
+            let m = players[gs.current_player() as usize].pick_move(&gs, moves);
+            if gs.play_move(m) == State::RoundEnd && gs.end_round().game_over() {
+                return gs.scores();
+            }
+        }
+    }
+
+    fn searcher<E: Clone>(evaluator: E) -> Minimaxer<E> {
+        Minimaxer::new(
+            minimaxer::negamax::SearchOptions {
+                max_depth: Some(1),
+                alpha_beta: true,
+                ..Default::default()
+            },
+            "Minimaxer",
+            evaluator,
+        )
+    }
+
+    #[test]
+    fn more_players() {
+        // Searching from a seat other than 0 in a game without teams
+        let mut players: [Box<dyn Player<3, 8>>; 3] = [
+            Box::new(RandomPlayer::new()),
+            Box::new(searcher(ScoreEvaluator)),
+            Box::new(RandomPlayer::new()),
+        ];
+        let scores = play(Gamestate::new(1, 0), &mut players);
+        assert!(scores[1] > scores[0] && scores[1] > scores[2]);
+
+        let mut players: [Box<dyn Player<4, 10>>; 4] = [
+            Box::new(RandomPlayer::new()),
+            Box::new(RandomPlayer::new()),
+            Box::new(RandomPlayer::new()),
+            Box::new(searcher(HeuristicEvaluator::default())),
+        ];
+        let scores = play(Gamestate::new(2, 0), &mut players);
+        assert_eq!(scores.iter().max(), Some(&scores[3]));
+    }
+
+    #[test]
+    fn supply_weight() {
+        let mut weights = [0.0; HEURISTIC_WEIGHTS];
+        weights[29] = 1.0;
+        let mut evaluator = HeuristicEvaluator::from_weights(&weights);
+        assert_eq!(evaluator.weights(), weights);
+
+        // Seat 0 starts a pattern line that the next deal may finish
+        let mut gs = Gamestate::new_2_player_with_seed(1, 0);
+        let m = *gs
+            .get_moves()
+            .iter()
+            .find(|m| {
+                let placed = gs.preview_move(m);
+                placed.to_row == m.count && !placed.fills_row
+            })
+            .unwrap();
+        gs.play_move(m);
+        let value = evaluator.evaluate(&gs);
+        assert!(value > 0.0 && value <= 1.0);
+    }
+}
@@ -1,13 +1,25 @@
 use dyn_clone::DynClone;
-use nalgebra::{SMatrix, Vector6};
+use nalgebra::SMatrix;
 use rand::{Rng, SeedableRng};
 use rand_distr::{Bernoulli, Distribution, StandardNormal};
 
-use crate::gamestate::{Destination, Gamestate, Move};
-
+use crate::{
+    gamestate::{Destination, Gamestate, Move},
+    players::features::{MoveEncoder, MoveFeaturesV1},
+    ranking::heuristic_score,
+};
+
+pub mod beam;
+pub mod curriculum;
+pub mod evaluate;
+pub mod features;
+pub mod flat_mc;
 pub mod minimax;
+pub mod muzero;
 pub mod nn;
 pub mod ppo;
+pub mod replay;
+pub mod tuning;
 
 /// Required implementation for a player
 /// Main function is [Player::pick_move]
@@ -26,6 +38,11 @@ impl RandomPlayer {
     pub fn new() -> Self {
         Self(rand::prelude::SmallRng::from_entropy())
     }
+
+    /// Play the same moves every run
+    pub fn with_seed(seed: u64) -> Self {
+        Self(rand::prelude::SmallRng::seed_from_u64(seed))
+    }
 }
 
 impl Default for RandomPlayer {
@@ -71,14 +88,12 @@ impl MoveRankPlayer {
             (Destination::Row(_), Destination::Floor) => a,
             (Destination::Floor, Destination::Row(_)) => b,
             (Destination::Floor, Destination::Floor) => b,
-            (Destination::Row(a_r), Destination::Row(b_r)) => {
-                match (a.fills_row(), b.fills_row()) {
-                    (true, false) => a,
-                    (false, true) => b,
-                    (true, true) => a,
-                    (false, false) => a,
-                }
-            }
+            (Destination::Row(_), Destination::Row(_)) => match (a.fills_row(), b.fills_row()) {
+                (true, false) => a,
+                (false, true) => b,
+                (true, true) => a,
+                (false, false) => a,
+            },
         }
     }
 }
@@ -100,42 +115,17 @@ impl MoveRankPlayer2 {
     pub fn new() -> Self {
         Self
     }
-
-    fn compare_move<'a>(
-        &self,
-        a: &'a (i8, bool, Move),
-        b: &'a (i8, bool, Move),
-    ) -> &'a (i8, bool, Move) {
-        if a.0 > b.0 {
-            return a;
-        } else if a.0 < b.0 {
-            return b;
-        }
-        if a.1 && !b.1 {
-            return a;
-        } else if !a.1 && b.1 {
-            return b;
-        }
-
-        match (a.2.destination, b.2.destination) {
-            (Destination::Row(_), Destination::Floor) => a,
-            (Destination::Floor, Destination::Row(_)) => b,
-            _ => a,
-        }
-    }
 }
 
 impl<const P: usize, const F: usize> Player<P, F> for MoveRankPlayer2 {
     fn pick_move(&mut self, gs: &Gamestate<P, F>, moves: Vec<Move>) -> Move {
-        let moves = moves
-            .into_iter()
-            .map(|m| (gs.predict_score(m).1, gs.takes_fp(&m), m))
-            .collect::<Vec<_>>();
+        // First of the best moves, as ranked by `RankingStrategy::Heuristic`
         moves
-            .iter()
-            .reduce(|a, b| self.compare_move(a, b))
+            .into_iter()
+            .map(|m| (heuristic_score(gs, &m), m))
+            .reduce(|a, b| if b.0 > a.0 { b } else { a })
             .unwrap()
-            .2
+            .1
     }
 
     fn name(&self) -> String {
@@ -143,6 +133,40 @@ impl<const P: usize, const F: usize> Player<P, F> for MoveRankPlayer2 {
     }
 }
 
+/// Plays a random move some of the time and the inner player's move otherwise,
+/// for opponents weaker than any heuristic
+#[derive(Clone)]
+pub struct ThrottledPlayer<T> {
+    inner: T,
+    random: Bernoulli,
+    rng: rand::prelude::SmallRng,
+}
+
+impl<T> ThrottledPlayer<T> {
+    /// `random_rate` is the chance of each move being random, between 0 and 1
+    pub fn new(inner: T, random_rate: f64) -> Self {
+        Self {
+            inner,
+            random: Bernoulli::new(random_rate).expect("Random rate between 0 and 1"),
+            rng: rand::prelude::SmallRng::from_entropy(),
+        }
+    }
+}
+
+impl<const P: usize, const F: usize, T: Player<P, F> + Clone> Player<P, F> for ThrottledPlayer<T> {
+    fn pick_move(&mut self, gs: &Gamestate<P, F>, moves: Vec<Move>) -> Move {
+        if self.random.sample(&mut self.rng) {
+            moves[self.rng.gen_range(0..moves.len())]
+        } else {
+            self.inner.pick_move(gs, moves)
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("Throttled{}", self.inner.name())
+    }
+}
+
 pub trait EvolvingPlayer {
     /// Create a new random player
     fn birth() -> Self;
@@ -151,8 +175,8 @@ pub trait EvolvingPlayer {
     fn mutate(&self, prob: Bernoulli, rng: &mut rand::rngs::SmallRng) -> Self;
     /// Crossover with another player
     ///
-    /// Select each player feature with a coin flip
-    fn crossover(&self, other: &Self, prob: Bernoulli) -> Self;
+    /// Select each player feature with a coin flip from `rng`
+    fn crossover(&self, other: &Self, prob: Bernoulli, rng: &mut rand::rngs::SmallRng) -> Self;
 }
 
 #[derive(Debug, Clone)]
@@ -177,21 +201,9 @@ impl MoveWeightPlayer {
     }
 
     fn score_move(&self, move_: &Move, gs: &Gamestate<2, 6>) -> f32 {
-        let (score, delta) = gs.predict_score(*move_);
-        [
-            move_.count as f32,
-            move_.floor_tiles() as f32,
-            move_.row_capacity() as f32,
-            move_.fills_row() as u8 as f32,
-            delta as f32,
-            move_.perfect_move() as u8 as f32,
-            gs.takes_fp(move_) as u8 as f32,
-            move_.no_floor_tiles() as u8 as f32,
-        ]
-        .iter()
-        .zip(self.weights.iter())
-        .map(|(a, b)| a * b)
-        .sum()
+        let mut input = SMatrix::<f32, 8, 1>::zeros();
+        MoveFeaturesV1.encode_into(gs, *move_, input.as_mut_slice());
+        input.dot(&self.weights)
     }
 }
 
@@ -227,11 +239,11 @@ impl EvolvingPlayer for MoveWeightPlayer {
         Self { weights }
     }
 
-    fn crossover(&self, other: &Self, prob: Bernoulli) -> Self {
+    fn crossover(&self, other: &Self, prob: Bernoulli, rng: &mut rand::rngs::SmallRng) -> Self {
         let weights = self
             .weights
             .map_with_location(|r, c, a| {
-                if prob.sample(&mut rand::thread_rng()) {
+                if prob.sample(rng) {
                     a
                 } else {
                     other.weights[(r, c)]
@@ -267,18 +279,8 @@ impl SLNNPlayer {
     }
 
     fn score_move(&self, move_: &Move, gs: &Gamestate<2, 6>) -> f32 {
-        let (score, delta) = gs.predict_score(*move_);
-        let input: SMatrix<f32, 8, 1> = [
-            move_.count as f32,
-            move_.floor_tiles() as f32,
-            move_.row_capacity() as f32,
-            move_.fills_row() as u8 as f32,
-            delta as f32,
-            move_.perfect_move() as u8 as f32,
-            gs.takes_fp(move_) as u8 as f32,
-            move_.no_floor_tiles() as u8 as f32,
-        ]
-        .into();
+        let mut input = SMatrix::<f32, 8, 1>::zeros();
+        MoveFeaturesV1.encode_into(gs, *move_, input.as_mut_slice());
         let hidden = self.weights1 * input;
         let output = self.weights2 * hidden.map(|x| x.tanh());
         output[0]
@@ -322,16 +324,16 @@ impl EvolvingPlayer for SLNNPlayer {
         Self { weights1, weights2 }
     }
 
-    fn crossover(&self, other: &Self, prob: Bernoulli) -> Self {
+    fn crossover(&self, other: &Self, prob: Bernoulli, rng: &mut rand::rngs::SmallRng) -> Self {
         let weights1 = self.weights1.map_with_location(|r, c, a| {
-            if prob.sample(&mut rand::thread_rng()) {
+            if prob.sample(rng) {
                 a
             } else {
                 other.weights1[(r, c)]
             }
         });
         let weights2 = self.weights2.map_with_location(|r, c, a| {
-            if prob.sample(&mut rand::thread_rng()) {
+            if prob.sample(rng) {
                 a
             } else {
                 other.weights2[(r, c)]
@@ -0,0 +1,221 @@
+//! Self-play spread across processes and machines
+//!
+//! The trainer runs a [Coordinator] that serves the latest network over TCP.
+//! [worker] processes fetch the network, play self-play games with it and
+//! send the trajectories back to be added to the trainer's replay buffer.
+//!
+//! Messages are framed as a kind byte, a little endian u32 length and the payload
+
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use burn::{
+    module::Module,
+    prelude::Backend,
+    record::{BinBytesRecorder, FullPrecisionSettings, Recorder},
+};
+use log::{info, warn};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use super::{
+    train::{self_play, Trajectory},
+    MuZeroConfig, MuZeroNet, MuZeroPlayer,
+};
+
+/// Worker asks for the network, with the version it already has
+const GET_MODEL: u8 = 0;
+/// Coordinator replies with the latest version, followed by the network if it changed
+const MODEL: u8 = 1;
+/// Worker sends a finished game
+const TRAJECTORY: u8 = 2;
+
+type NetRecorder = BinBytesRecorder<FullPrecisionSettings>;
+
+/// Latest network as bytes, version 0 until the first is published
+#[derive(Default)]
+struct Model {
+    version: u64,
+    bytes: Arc<Vec<u8>>,
+}
+
+/// Serves the network to workers and collects their games
+pub struct Coordinator {
+    addr: SocketAddr,
+    model: Arc<RwLock<Model>>,
+    trajectories: Receiver<Trajectory>,
+}
+
+impl Coordinator {
+    /// Start accepting workers in the background
+    pub fn listen(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        info!("Coordinator listening on {addr}");
+        let model = Arc::new(RwLock::new(Model::default()));
+        let (sender, trajectories) = mpsc::channel();
+        let shared = model.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (model, sender) = (shared.clone(), sender.clone());
+                thread::spawn(move || {
+                    let peer = stream.peer_addr();
+                    if let Err(e) = serve(stream, &model, &sender) {
+                        warn!("Worker {peer:?} disconnected: {e}");
+                    }
+                });
+            }
+        });
+        Ok(Self {
+            addr,
+            model,
+            trajectories,
+        })
+    }
+
+    /// Address workers connect to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Make a new version of the network available to workers
+    pub fn publish<B: Backend>(&self, net: &MuZeroNet<B>) {
+        let bytes = NetRecorder::default()
+            .record(net.clone().into_record(), ())
+            .unwrap();
+        let mut model = self.model.write().unwrap();
+        model.version += 1;
+        model.bytes = Arc::new(bytes);
+    }
+
+    /// Games received from workers since the last call
+    pub fn receive(&self) -> Vec<Trajectory> {
+        self.trajectories.try_iter().collect()
+    }
+}
+
+/// Handle requests from a single worker until it disconnects
+fn serve(
+    mut stream: TcpStream,
+    model: &RwLock<Model>,
+    sender: &Sender<Trajectory>,
+) -> io::Result<()> {
+    loop {
+        let (kind, payload) = read_frame(&mut stream)?;
+        match kind {
+            GET_MODEL => {
+                let have = version(&payload)?;
+                let (latest, bytes) = {
+                    let model = model.read().unwrap();
+                    (model.version, model.bytes.clone())
+                };
+                let mut reply = latest.to_le_bytes().to_vec();
+                if have != latest {
+                    reply.extend_from_slice(&bytes);
+                }
+                write_frame(&mut stream, MODEL, &reply)?;
+            }
+            TRAJECTORY => {
+                let game = serde_json::from_slice(&payload).map_err(io::Error::other)?;
+                if sender.send(game).is_err() {
+                    // Trainer has finished
+                    return Ok(());
+                }
+            }
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unknown message kind {other}"),
+                ))
+            }
+        }
+    }
+}
+
+/// Play self-play games with the coordinator's latest network until disconnected
+pub fn worker<B: Backend>(
+    addr: impl ToSocketAddrs,
+    config: &MuZeroConfig,
+    device: &B::Device,
+) -> io::Result<()> {
+    let mut stream = TcpStream::connect(addr)?;
+    let mut player = MuZeroPlayer::<B>::new(config, device);
+    let mut have = 0u64;
+    let mut rng = SmallRng::from_entropy();
+    loop {
+        write_frame(&mut stream, GET_MODEL, &have.to_le_bytes())?;
+        let (_, reply) = read_frame(&mut stream)?;
+        let latest = version(&reply)?;
+        if latest != have {
+            let record = NetRecorder::default()
+                .load(reply[8..].to_vec(), device)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            player.net = player.net.load_record(record);
+            have = latest;
+            info!("Loaded network version {latest}");
+        }
+        if have == 0 {
+            // Nothing published yet
+            thread::sleep(Duration::from_secs(1));
+            continue;
+        }
+        let game = self_play(&player, rng.gen(), &mut rng);
+        let payload = serde_json::to_vec(&game).map_err(io::Error::other)?;
+        write_frame(&mut stream, TRAJECTORY, &payload)?;
+    }
+}
+
+fn version(payload: &[u8]) -> io::Result<u64> {
+    payload
+        .get(..8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing model version"))
+}
+
+fn write_frame(stream: &mut impl Write, kind: u8, payload: &[u8]) -> io::Result<()> {
+    stream.write_all(&[kind])?;
+    stream.write_all(&(payload.len() as u32).to_le_bytes())?;
+    stream.write_all(payload)?;
+    stream.flush()
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 5];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header[1..].try_into().unwrap()) as usize;
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload)?;
+    Ok((header[0], payload))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread, time::Duration};
+
+    use burn::backend::NdArray;
+
+    use super::{worker, Coordinator};
+    use crate::players::muzero::MuZeroConfig;
+
+    #[test]
+    fn worker_sends_games() {
+        let config = MuZeroConfig::new().with_hidden_size(8).with_simulations(2);
+        let coordinator = Coordinator::listen("127.0.0.1:0").unwrap();
+        let addr = coordinator.local_addr();
+        coordinator.publish(&config.init::<NdArray>(&Default::default()));
+        let worker_config = config.clone();
+        thread::spawn(move || worker::<NdArray>(addr, &worker_config, &Default::default()));
+        let game = coordinator
+            .trajectories
+            .recv_timeout(Duration::from_secs(60))
+            .unwrap();
+        assert!(!game.is_empty());
+    }
+}
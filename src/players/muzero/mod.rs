@@ -0,0 +1,313 @@
+//! Experimental MuZero style agent
+//!
+//! Learns a model of the game in a latent space with three networks:
+//! - representation: encodes a gamestate into a latent state
+//! - dynamics: predicts the next latent state and reward after an action
+//! - prediction: predicts the policy and value of a latent state
+//!
+//! Moves are chosen by running MCTS over the latent states, so the real
+//! game rules are only used to mask illegal moves at the root.
+//! The latent model assumes players alternate, which is not true at the end
+//! of a round when the first player token decides who goes next
+
+use burn::{
+    config::Config,
+    nn::{Linear, LinearConfig, Relu},
+    prelude::{Backend, Module},
+    tensor::{activation, cast::ToElement, Tensor},
+};
+
+use crate::{
+    gamestate::{Gamestate, Move},
+    players::{
+        features::{StateEncoder, StateFeaturesV1},
+        Player,
+    },
+};
+
+pub mod distributed;
+pub mod train;
+
+/// Number of possible actions, see [Move::to_index]
+pub const ACTIONS: usize = 180;
+
+#[derive(Config, Debug)]
+pub struct MuZeroConfig {
+    #[config(default = 150)]
+    pub input_size: usize,
+    #[config(default = 64)]
+    pub latent_size: usize,
+    #[config(default = 256)]
+    pub hidden_size: usize,
+    /// MCTS simulations per move
+    #[config(default = 50)]
+    pub simulations: usize,
+    /// Exploration constant used in the PUCT formula
+    #[config(default = 1.25)]
+    pub c_puct: f32,
+}
+
+impl MuZeroConfig {
+    pub fn init<B: Backend>(&self, device: &B::Device) -> MuZeroNet<B> {
+        MuZeroNet {
+            representation: Mlp::new(self.input_size, self.hidden_size, self.latent_size, device),
+            dynamics: Mlp::new(
+                self.latent_size + ACTIONS,
+                self.hidden_size,
+                self.latent_size + 1,
+                device,
+            ),
+            prediction: Mlp::new(self.latent_size, self.hidden_size, ACTIONS + 1, device),
+        }
+    }
+}
+
+/// Two layer perceptron used for each of the networks
+#[derive(Module, Debug)]
+pub struct Mlp<B: Backend> {
+    input: Linear<B>,
+    output: Linear<B>,
+    activation: Relu,
+}
+
+impl<B: Backend> Mlp<B> {
+    fn new(input: usize, hidden: usize, output: usize, device: &B::Device) -> Self {
+        Self {
+            input: LinearConfig::new(input, hidden).init(device),
+            output: LinearConfig::new(hidden, output).init(device),
+            activation: Relu::new(),
+        }
+    }
+
+    fn forward(&self, x: Tensor<B, 1>) -> Tensor<B, 1> {
+        let x = self.input.forward(x);
+        let x = self.activation.forward(x);
+        self.output.forward(x)
+    }
+}
+
+/// The representation, dynamics and prediction networks
+#[derive(Module, Debug)]
+pub struct MuZeroNet<B: Backend> {
+    representation: Mlp<B>,
+    dynamics: Mlp<B>,
+    prediction: Mlp<B>,
+}
+
+impl<B: Backend> MuZeroNet<B> {
+    /// Encode a gamestate tensor into a latent state
+    pub fn represent(&self, state: Tensor<B, 1>) -> Tensor<B, 1> {
+        normalise(self.representation.forward(state))
+    }
+
+    /// Next latent state and the reward for the player making the action
+    pub fn dynamics(&self, latent: Tensor<B, 1>, action: usize) -> (Tensor<B, 1>, Tensor<B, 1>) {
+        let device = latent.device();
+        let latent_size = latent.dims()[0];
+        let mut one_hot = [0f32; ACTIONS];
+        one_hot[action] = 1.0;
+        let x = Tensor::cat(
+            vec![latent, Tensor::from_data(one_hot.as_slice(), &device)],
+            0,
+        );
+        let x = self.dynamics.forward(x);
+        let next = normalise(x.clone().slice(0..latent_size));
+        let reward = x.slice(latent_size..latent_size + 1);
+        (next, reward)
+    }
+
+    /// Policy logits and value for the player to move in the latent state
+    pub fn predict(&self, latent: Tensor<B, 1>) -> (Tensor<B, 1>, Tensor<B, 1>) {
+        let x = self.prediction.forward(latent);
+        let policy = x.clone().slice(0..ACTIONS);
+        let value = x.slice(ACTIONS..ACTIONS + 1).tanh();
+        (policy, value)
+    }
+}
+
+/// Scale a latent state to the range [0, 1]
+fn normalise<B: Backend>(x: Tensor<B, 1>) -> Tensor<B, 1> {
+    let min = x.clone().min();
+    let max = x.clone().max();
+    (x - min.clone()) / (max - min + 1e-6)
+}
+
+/// Player that plans with MCTS using the learned model
+#[derive(Debug, Clone)]
+pub struct MuZeroPlayer<B: Backend> {
+    device: B::Device,
+    net: MuZeroNet<B>,
+    simulations: usize,
+    c_puct: f32,
+}
+
+impl<B: Backend> MuZeroPlayer<B> {
+    pub fn new(config: &MuZeroConfig, device: &B::Device) -> Self {
+        Self::from_net(config, config.init(device), device)
+    }
+
+    pub fn from_net(config: &MuZeroConfig, net: MuZeroNet<B>, device: &B::Device) -> Self {
+        Self {
+            device: device.clone(),
+            net,
+            simulations: config.simulations,
+            c_puct: config.c_puct,
+        }
+    }
+
+    pub fn net(&self) -> &MuZeroNet<B> {
+        &self.net
+    }
+
+    pub fn state_tensor(&self, gamestate: &Gamestate<2, 6>) -> Tensor<B, 1> {
+        Tensor::from_data(StateFeaturesV1.encode(gamestate).as_slice(), &self.device)
+    }
+
+    /// Run MCTS from the gamestate and return the visit count of each action
+    ///
+    /// Only actions in `moves` are expanded at the root
+    pub fn search(&self, gamestate: &Gamestate<2, 6>, moves: &[Move]) -> [u32; ACTIONS] {
+        let latent = self.net.represent(self.state_tensor(gamestate));
+        let mut root = Node::new(1.0);
+        let legal = moves.iter().map(|m| m.to_index()).collect::<Vec<_>>();
+        root.expand(&self.net, latent, Some(&legal));
+
+        for _ in 0..self.simulations {
+            let mut path = vec![0];
+            let mut node = &root;
+            // Walk down the tree to a leaf, remembering the path taken
+            while !node.children.is_empty() {
+                let child = node.select(self.c_puct);
+                path.push(child);
+                node = &node.children[child].1;
+            }
+            // Expand the leaf from its parent's latent state
+            let parent = walk(&mut root, &path[..path.len() - 1]);
+            let action = parent.children[path[path.len() - 1]].0;
+            let (latent, reward) = self.net.dynamics(parent.latent.clone().unwrap(), action);
+            let leaf = walk(&mut root, &path);
+            leaf.reward = reward.into_scalar().to_f32();
+            let value = leaf.expand(&self.net, latent, None);
+            backup(&mut root, &path, value);
+        }
+
+        let mut visits = [0; ACTIONS];
+        for (action, child) in &root.children {
+            visits[*action] = child.visits;
+        }
+        visits
+    }
+}
+
+impl<B: Backend> Player<2, 6> for MuZeroPlayer<B> {
+    fn pick_move(&mut self, gamestate: &Gamestate<2, 6>, moves: Vec<Move>) -> Move {
+        let visits = self.search(gamestate, &moves);
+        *moves.iter().max_by_key(|m| visits[m.to_index()]).unwrap()
+    }
+
+    fn name(&self) -> String {
+        "MuZeroPlayer".into()
+    }
+}
+
+/// Node in the latent search tree
+struct Node<B: Backend> {
+    prior: f32,
+    visits: u32,
+    /// Sum of values for the player to move at the parent
+    value_sum: f32,
+    /// Predicted reward for the action leading to this node
+    reward: f32,
+    latent: Option<Tensor<B, 1>>,
+    children: Vec<(usize, Node<B>)>,
+}
+
+impl<B: Backend> Node<B> {
+    fn new(prior: f32) -> Self {
+        Self {
+            prior,
+            visits: 0,
+            value_sum: 0.0,
+            reward: 0.0,
+            latent: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Add children for each action, returning the predicted value
+    fn expand(&mut self, net: &MuZeroNet<B>, latent: Tensor<B, 1>, legal: Option<&[usize]>) -> f32 {
+        let (policy, value) = net.predict(latent.clone());
+        let policy = activation::softmax(policy, 0)
+            .to_data()
+            .to_vec::<f32>()
+            .unwrap();
+        self.children = match legal {
+            Some(legal) => legal.iter().map(|&a| (a, Node::new(policy[a]))).collect(),
+            None => policy
+                .iter()
+                .enumerate()
+                .map(|(a, &p)| (a, Node::new(p)))
+                .collect(),
+        };
+        self.latent = Some(latent);
+        value.into_scalar().to_f32()
+    }
+
+    /// Index of the child with the highest PUCT score
+    fn select(&self, c_puct: f32) -> usize {
+        let sqrt_visits = (self.visits as f32).sqrt().max(1.0);
+        let score = |child: &Node<B>| {
+            let q = if child.visits == 0 {
+                0.0
+            } else {
+                child.value_sum / child.visits as f32
+            };
+            q + c_puct * child.prior * sqrt_visits / (1.0 + child.visits as f32)
+        };
+        (0..self.children.len())
+            .max_by(|&a, &b| score(&self.children[a].1).total_cmp(&score(&self.children[b].1)))
+            .unwrap()
+    }
+}
+
+/// Follow the child indices from the root, the first index is the root itself
+fn walk<'a, B: Backend>(root: &'a mut Node<B>, path: &[usize]) -> &'a mut Node<B> {
+    path[1..]
+        .iter()
+        .fold(root, |node, &child| &mut node.children[child].1)
+}
+
+/// Propagate the leaf value back to the root, switching perspective at each level
+fn backup<B: Backend>(root: &mut Node<B>, path: &[usize], value: f32) {
+    let mut value = value;
+    for depth in (0..path.len()).rev() {
+        let node = walk(root, &path[..=depth]);
+        // Value of the move into this node for the player who made it
+        value = node.reward - value;
+        node.value_sum += value;
+        node.visits += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use burn::backend::NdArray;
+
+    use super::{MuZeroConfig, MuZeroPlayer};
+    use crate::{gamestate::Gamestate, players::Player};
+
+    #[test]
+    fn picks_legal_move() {
+        let device = Default::default();
+        let config = MuZeroConfig::new()
+            .with_hidden_size(32)
+            .with_simulations(20);
+        let mut player = MuZeroPlayer::<NdArray>::new(&config, &device);
+        let gs = Gamestate::new_2_player_with_seed(1, 0);
+        let moves = gs.get_moves();
+        let visits = player.search(&gs, &moves);
+        assert_eq!(visits.iter().sum::<u32>(), 20);
+        let m = player.pick_move(&gs, moves.clone());
+        assert!(moves.contains(&m));
+    }
+}
@@ -0,0 +1,287 @@
+//! Self-play training for [MuZeroPlayer]
+//!
+//! Games are played by the current network against itself and stored in a
+//! replay buffer. Training samples a position from the buffer and unrolls the
+//! dynamics network along the actions that were played, matching the search
+//! policy, the final outcome and the rewards at each step
+
+use std::collections::VecDeque;
+
+use burn::{
+    config::Config,
+    module::Module,
+    optim::{AdamConfig, GradientsParams, Optimizer},
+    prelude::Backend,
+    record::{DefaultFileRecorder, FullPrecisionSettings},
+    tensor::{activation::log_softmax, backend::AutodiffBackend, cast::ToElement as _, Tensor},
+};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rand_distr::{Distribution, WeightedIndex};
+
+use super::{distributed::Coordinator, MuZeroConfig, MuZeroNet, MuZeroPlayer};
+use crate::{
+    gamestate::{Gamestate, State},
+    players::{
+        features::{StateEncoder, StateFeaturesV1},
+        RandomPlayer,
+    },
+    runner::Runner,
+};
+
+#[derive(Config, Debug)]
+pub struct MuZeroTrainConfig {
+    #[config(default = 100)]
+    pub iterations: usize,
+    /// Self-play games added to the replay buffer each iteration
+    #[config(default = 20)]
+    pub games_per_iteration: usize,
+    /// Maximum number of games kept in the replay buffer
+    #[config(default = 500)]
+    pub buffer_size: usize,
+    /// Training steps each iteration
+    #[config(default = 50)]
+    pub steps_per_iteration: usize,
+    #[config(default = 32)]
+    pub batch_size: usize,
+    /// Number of dynamics steps to unroll from each sampled position
+    #[config(default = 3)]
+    pub unroll_steps: usize,
+    #[config(default = 1e-3)]
+    pub learning_rate: f64,
+    /// Game pairs played against [RandomPlayer] after each iteration
+    #[config(default = 10)]
+    pub arena_games: u32,
+    /// Address to accept self-play workers on, see [super::distributed]
+    pub listen: Option<String>,
+}
+
+/// Record of a self-play game
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Trajectory {
+    /// Gamestate arrays before each move
+    states: Vec<Vec<f32>>,
+    /// Index of the action played
+    actions: Vec<usize>,
+    /// Normalised search visit counts
+    policies: Vec<Vec<f32>>,
+    /// Change in predicted score for the player making the move
+    rewards: Vec<f32>,
+    /// Final outcome for the player making the move, 1 for a win, -1 for a loss
+    outcomes: Vec<f32>,
+}
+
+impl Trajectory {
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+/// Fixed size store of the most recent self-play games
+#[derive(Debug, Clone)]
+pub struct ReplayBuffer {
+    capacity: usize,
+    games: VecDeque<Trajectory>,
+}
+
+impl ReplayBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            games: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, game: Trajectory) {
+        if self.games.len() == self.capacity {
+            self.games.pop_front();
+        }
+        self.games.push_back(game);
+    }
+
+    /// Pick a random game and position in that game
+    pub fn sample(&self, rng: &mut impl Rng) -> (&Trajectory, usize) {
+        let game = &self.games[rng.gen_range(0..self.games.len())];
+        (game, rng.gen_range(0..game.len()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+}
+
+/// Trains a [MuZeroPlayer] from self-play
+pub struct MuZeroTrainer<B: AutodiffBackend> {
+    model: MuZeroConfig,
+    config: MuZeroTrainConfig,
+    player: MuZeroPlayer<B>,
+    buffer: ReplayBuffer,
+    device: B::Device,
+}
+
+impl<B: AutodiffBackend> MuZeroTrainer<B> {
+    pub fn new(model: MuZeroConfig, config: MuZeroTrainConfig, device: &B::Device) -> Self {
+        Self {
+            player: MuZeroPlayer::new(&model, device),
+            buffer: ReplayBuffer::new(config.buffer_size),
+            model,
+            config,
+            device: device.clone(),
+        }
+    }
+
+    /// Train, saving a checkpoint of the networks to `dir` after each iteration
+    pub fn train(mut self, dir: &std::path::Path) -> MuZeroPlayer<B> {
+        std::fs::create_dir_all(dir).unwrap();
+        let recorder = DefaultFileRecorder::<FullPrecisionSettings>::default();
+        let mut optimiser = AdamConfig::new().init();
+        let mut rng = SmallRng::from_entropy();
+        let mut seed = 0;
+        let coordinator = self
+            .config
+            .listen
+            .as_ref()
+            .map(|addr| Coordinator::listen(addr).unwrap());
+        if let Some(coordinator) = &coordinator {
+            coordinator.publish(&self.player.net);
+        }
+
+        for iteration in 0..self.config.iterations {
+            for _ in 0..self.config.games_per_iteration {
+                self.buffer.push(self_play(&self.player, seed, &mut rng));
+                seed += 1;
+            }
+            if let Some(coordinator) = &coordinator {
+                // Wait for workers if there are no local games to train on
+                while self.buffer.is_empty() {
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                    coordinator
+                        .receive()
+                        .into_iter()
+                        .for_each(|g| self.buffer.push(g));
+                }
+                coordinator
+                    .receive()
+                    .into_iter()
+                    .for_each(|g| self.buffer.push(g));
+            }
+
+            let mut total_loss = 0.0;
+            for _ in 0..self.config.steps_per_iteration {
+                let net = self.player.net.clone();
+                let loss = (0..self.config.batch_size)
+                    .map(|_| {
+                        let (game, t) = self.buffer.sample(&mut rng);
+                        loss(&net, game, t, self.config.unroll_steps, &self.device)
+                    })
+                    .reduce(|a, b| a + b)
+                    .unwrap()
+                    / self.config.batch_size as f32;
+                total_loss += loss.clone().into_scalar().to_f32();
+                let grads = GradientsParams::from_grads(loss.backward(), &net);
+                let net = optimiser.step(self.config.learning_rate, net, grads);
+                self.player = MuZeroPlayer::from_net(&self.model, net, &self.device);
+            }
+
+            if let Some(coordinator) = &coordinator {
+                coordinator.publish(&self.player.net);
+            }
+
+            let mut runner = Runner::new_2_player(
+                [Box::new(self.player.clone()), Box::new(RandomPlayer::new())],
+                Some(iteration as u64),
+            );
+            let result = runner.run_matchup(self.config.arena_games);
+            println!(
+                "Iteration {iteration}: loss {:.4}, buffer {} games, vs random {:?}, average margin {:.1}",
+                total_loss / self.config.steps_per_iteration as f32,
+                self.buffer.len(),
+                result.winner_count,
+                result.average_score(),
+            );
+
+            self.player
+                .net
+                .clone()
+                .save_file(dir.join(format!("checkpoint_{iteration}")), &recorder)
+                .unwrap();
+        }
+        self.player
+    }
+}
+
+/// Play a game against itself, choosing moves in proportion to search visits
+pub(super) fn self_play<B: Backend>(
+    player: &MuZeroPlayer<B>,
+    seed: u64,
+    rng: &mut impl Rng,
+) -> Trajectory {
+    let mut gs = Gamestate::new_2_player_with_seed(seed, (seed % 2) as u8);
+    let mut game = Trajectory::default();
+    let mut movers = Vec::new();
+    loop {
+        let moves = gs.get_moves();
+        let visits = player.search(&gs, &moves);
+        let total = visits.iter().sum::<u32>().max(1) as f32;
+        let index = WeightedIndex::new(moves.iter().map(|m| visits[m.to_index()] + 1))
+            .unwrap()
+            .sample(rng);
+        let m = moves[index];
+
+        let mover = gs.current_player() as usize;
+        let prev_score = gs.boards()[mover].predicted_score as f32;
+        game.states.push(StateFeaturesV1.encode(&gs));
+        game.actions.push(m.to_index());
+        game.policies
+            .push(visits.iter().map(|&v| v as f32 / total).collect());
+        movers.push(mover);
+
+        let state = gs.play_move(m);
+        game.rewards
+            .push((gs.boards()[mover].predicted_score as f32 - prev_score) / 10.0);
+        if state == State::RoundEnd && gs.end_round().game_over() {
+            break;
+        }
+    }
+    let scores = gs.scores();
+    let outcome = (scores[0] as f32 - scores[1] as f32).signum();
+    game.outcomes = movers
+        .iter()
+        .map(|&p| if p == 0 { outcome } else { -outcome })
+        .collect();
+    game
+}
+
+/// Loss from unrolling the model from position `t` of the game
+fn loss<B: AutodiffBackend>(
+    net: &MuZeroNet<B>,
+    game: &Trajectory,
+    t: usize,
+    unroll_steps: usize,
+    device: &B::Device,
+) -> Tensor<B, 1> {
+    let scalar = |x: f32| Tensor::<B, 1>::from_data([x].as_slice(), device);
+    let mut latent = net.represent(Tensor::from_data(game.states[t].as_slice(), device));
+    let mut loss = scalar(0.0);
+    let end = (t + unroll_steps).min(game.len() - 1);
+    for k in t..=end {
+        let (policy, value) = net.predict(latent.clone());
+        let target = Tensor::<B, 1>::from_data(game.policies[k].as_slice(), device);
+        loss = loss - (target * log_softmax(policy, 0)).sum();
+        loss = loss + (value - scalar(game.outcomes[k])).powf_scalar(2.0);
+        if k < end {
+            let (next, reward) = net.dynamics(latent, game.actions[k]);
+            loss = loss + (reward - scalar(game.rewards[k])).powf_scalar(2.0);
+            // Scale the gradient through the dynamics as in the MuZero paper
+            latent = next.clone() * 0.5 + next.detach() * 0.5;
+        }
+    }
+    loss
+}
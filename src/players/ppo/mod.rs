@@ -2,19 +2,24 @@ use burn::{
     config::Config,
     nn::{Linear, LinearConfig, Relu},
     prelude::{Backend, Module},
-    record::{self, DefaultFileRecorder, FullPrecisionSettings},
+    record::{DefaultFileRecorder, FullPrecisionSettings},
     tensor::{activation, cast::ToElement, Tensor},
 };
+use rand::{rngs::SmallRng, SeedableRng};
 use rand_distr::{Distribution, WeightedIndex};
 
 use crate::{
+    error::AzulError,
     gamestate::{Gamestate, Move},
     players::{
-        nn::{gs_to_array, index_to_move},
+        evaluate::Evaluate,
+        features::{StateEncoder, StateFeaturesV1},
         Player,
     },
+    ranking::MovePolicy,
 };
 
+pub mod pretrain;
 pub mod train;
 
 pub struct PickReturn<B: Backend> {
@@ -38,6 +43,8 @@ pub struct PPOMoveSelector<B: Backend> {
     device: B::Device,
     policy: Policy<B>,
     value: Value<B>,
+    /// Samples moves from the policy
+    rng: SmallRng,
 }
 
 impl<B: Backend> PPOMoveSelector<B> {
@@ -46,25 +53,36 @@ impl<B: Backend> PPOMoveSelector<B> {
             device: device.clone(),
             policy: policy.init(device),
             value: value.init(device),
+            rng: SmallRng::from_entropy(),
         }
     }
 
+    /// Sample the same moves every run
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SmallRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Load the policy network from `path`, the value network is left untrained
     pub fn from_file(
         policy: PolicyConfig,
         value: ValueConfig,
         path: &std::path::Path,
         device: &B::Device,
-    ) -> Self {
+    ) -> Result<Self, AzulError> {
         let policy = policy.init(device);
         let value = value.init(device);
 
-        let mut recorder = DefaultFileRecorder::<FullPrecisionSettings>::default();
-        let policy = policy.load_file(path, &recorder, device).unwrap();
-        Self {
+        let recorder = DefaultFileRecorder::<FullPrecisionSettings>::default();
+        let policy = policy
+            .load_file(path, &recorder, device)
+            .map_err(|e| AzulError::load(path, e))?;
+        Ok(Self {
             device: device.clone(),
             policy,
             value,
-        }
+            rng: SmallRng::from_entropy(),
+        })
     }
 
     pub fn action(&self, state: Tensor<B, 1>) -> Tensor<B, 1> {
@@ -75,6 +93,31 @@ impl<B: Backend> PPOMoveSelector<B> {
         self.value.value(state)
     }
 
+    /// Mask of the legal `moves`, as 0 for legal and a large negative number otherwise,
+    /// and the policy's probability of each action with the mask applied
+    fn masked_probabilities(
+        &self,
+        state: Tensor<B, 1>,
+        moves: &[Move],
+    ) -> (Tensor<B, 1>, Tensor<B, 1>) {
+        let action = self.policy.action(state);
+        let mut mask = [-1e8f32; 180];
+        for m in moves {
+            mask[m.to_index()] = 0.0;
+        }
+        let mask = Tensor::from_data(mask.as_slice(), &self.device);
+        let probs = activation::softmax(action + mask.clone(), 0);
+        (mask, probs)
+    }
+
+    /// Probability the policy gives to each of `moves`
+    pub fn move_probabilities(&self, gamestate: &Gamestate<2, 6>, moves: &[Move]) -> Vec<f32> {
+        let state = Tensor::from_data(StateFeaturesV1.encode(gamestate).as_slice(), &self.device);
+        let (_, probs) = self.masked_probabilities(state, moves);
+        let probs = probs.to_data().convert::<f32>().to_vec::<f32>().unwrap();
+        moves.iter().map(|m| probs[m.to_index()]).collect()
+    }
+
     /// Pick a move and return all the other useful info that is required for training
     pub fn pick_move_train(
         &mut self,
@@ -82,42 +125,29 @@ impl<B: Backend> PPOMoveSelector<B> {
         moves: Vec<Move>,
     ) -> PickReturn<B> {
         // Convert the gamestate into a tensor
-        let state = Tensor::from_data(gs_to_array(gamestate).as_slice(), &self.device);
-        // Get action vector and value
-        let action = self.policy.action(state.clone());
+        let state = Tensor::from_data(StateFeaturesV1.encode(gamestate).as_slice(), &self.device);
         let value = self.value.value(state.clone());
-
-        // Convert the moves into a vec of booleans to mask invalid
-        let indices = moves.iter().map(|m| m.to_index()).collect::<Vec<_>>();
-        let mut mask = [-1e8f32; 180];
-        for &i in &indices {
-            mask[i] = 0.0;
-        }
-        let masked_action = action.clone() + Tensor::from_data(mask.as_slice(), &self.device);
-
-        let action_probs = activation::softmax(masked_action, 0);
-        let action_probs_vec = action_probs.to_data().to_vec::<f32>().unwrap();
+        let (mask, action_probs) = self.masked_probabilities(state.clone(), &moves);
+        let action_probs_vec = action_probs
+            .to_data()
+            .convert::<f32>()
+            .to_vec::<f32>()
+            .unwrap();
 
         // Choose from the actions
         let dist = WeightedIndex::new(action_probs_vec).unwrap();
-        let choice = dist.sample(&mut rand::thread_rng());
+        let choice = dist.sample(&mut self.rng);
         // Find the move with the corresponding value
-        let (source, tile, destination) = index_to_move(choice);
-        // println!("Moves: {:?}", moves);
         let m = moves
             .iter()
-            .find(|m| {
-                usize::from(m.source) == source
-                    && usize::from(m.tile) == tile
-                    && usize::from(m.destination) == destination
-            })
+            .find(|m| m.to_index() == choice)
             .cloned()
             .unwrap();
         PickReturn {
             state,
             action: choice,
             action_probs,
-            action_mask: Tensor::from_data(mask.as_slice(), &self.device),
+            action_mask: mask,
             value,
             picked_move: m,
         }
@@ -139,6 +169,74 @@ impl<B: Backend> Player<2, 6> for PPOMoveSelector<B> {
     }
 }
 
+impl<B: Backend> MovePolicy for PPOMoveSelector<B> {
+    fn move_probabilities(&self, gs: &Gamestate<2, 6>, moves: &[Move]) -> Vec<f32> {
+        PPOMoveSelector::move_probabilities(self, gs, moves)
+    }
+}
+
+/// Scores positions with the PPO value network, so search players can use learned evaluations.
+///
+/// The network is trained on positions with seat 0 to move, and values from its side
+#[derive(Debug, Clone)]
+pub struct ValueEvaluator<B: Backend> {
+    device: B::Device,
+    value: Value<B>,
+}
+
+impl<B: Backend> ValueEvaluator<B> {
+    /// Load a value network saved during training
+    pub fn from_file(
+        config: ValueConfig,
+        path: &std::path::Path,
+        device: &B::Device,
+    ) -> Result<Self, AzulError> {
+        let recorder = DefaultFileRecorder::<FullPrecisionSettings>::default();
+        let value = config
+            .init(device)
+            .load_file(path, &recorder, device)
+            .map_err(|e| AzulError::load(path, e))?;
+        Ok(Self {
+            device: device.clone(),
+            value,
+        })
+    }
+
+    /// Use the value network of a [PPOMoveSelector]
+    pub fn from_selector(ppo: &PPOMoveSelector<B>) -> Self {
+        Self {
+            device: ppo.device.clone(),
+            value: ppo.value.clone(),
+        }
+    }
+}
+
+impl<B: Backend> Evaluate<2, 6> for ValueEvaluator<B> {
+    fn evaluate(&mut self, gs: &Gamestate<2, 6>) -> f32 {
+        let state = Tensor::<B, 1>::from_data(StateFeaturesV1.encode(gs).as_slice(), &self.device);
+        self.value.value(state).into_scalar().to_f32()
+    }
+
+    /// Runs the network once for all the positions
+    fn evaluate_batch(&mut self, states: &[&Gamestate<2, 6>]) -> Vec<f32> {
+        let states = states
+            .iter()
+            .map(|gs| {
+                Tensor::<B, 1>::from_data(StateFeaturesV1.encode(gs).as_slice(), &self.device)
+            })
+            .collect::<Vec<_>>();
+        if states.is_empty() {
+            return Vec::new();
+        }
+        self.value
+            .value(Tensor::stack::<2>(states, 0))
+            .into_data()
+            .convert::<f32>()
+            .to_vec::<f32>()
+            .unwrap()
+    }
+}
+
 #[derive(Config, Debug)]
 pub struct PolicyConfig {
     pub input_size: usize,
@@ -170,7 +268,9 @@ pub struct Policy<B: Backend> {
 
 impl<B: Backend> Policy<B> {
     /// Run the policy network without normalising the result
-    fn action(&self, state: Tensor<B, 1>) -> Tensor<B, 1> {
+    ///
+    /// Accepts a single state or a batch of states stacked along the first dimension
+    fn action<const D: usize>(&self, state: Tensor<B, D>) -> Tensor<B, D> {
         let x = self.input.forward(state);
         let x = self.activation.forward(x);
         let x = self.hidden.forward(x);
@@ -209,7 +309,7 @@ struct Value<B: Backend> {
 }
 
 impl<B: Backend> Value<B> {
-    fn value(&self, state: Tensor<B, 1>) -> Tensor<B, 1> {
+    fn value<const D: usize>(&self, state: Tensor<B, D>) -> Tensor<B, D> {
         let x = self.input.forward(state);
         let x = self.activation.forward(x);
         let x = self.hidden.forward(x);
@@ -217,3 +317,44 @@ impl<B: Backend> Value<B> {
         self.output.forward(x)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use burn::backend::NdArray;
+
+    use super::{PPOMoveSelector, PolicyConfig, ValueConfig, ValueEvaluator};
+    use crate::{
+        gamestate::Gamestate,
+        players::{beam::BeamPlayer, evaluate::Evaluate, Player},
+    };
+
+    #[test]
+    fn batched_values_match() {
+        let device = Default::default();
+        let ppo = PPOMoveSelector::<NdArray>::new(
+            PolicyConfig::new(150, 16),
+            ValueConfig::new(150, 16),
+            &device,
+        );
+        let mut evaluator = ValueEvaluator::from_selector(&ppo);
+        let gs = Gamestate::new_2_player_with_seed(0, 0);
+        let children = gs
+            .get_moves()
+            .into_iter()
+            .map(|m| {
+                let mut child = gs.clone();
+                child.play_move(m);
+                child
+            })
+            .collect::<Vec<_>>();
+        let batched = evaluator.evaluate_batch(&children.iter().collect::<Vec<_>>());
+        assert_eq!(batched.len(), children.len());
+        for (child, value) in children.iter().zip(batched) {
+            assert!((evaluator.evaluate(child) - value).abs() < 1e-4);
+        }
+
+        let mut beam = BeamPlayer::new(2, evaluator).with_max_depth(2);
+        let moves = gs.get_moves();
+        assert!(moves.contains(&beam.pick_move(&gs, moves.clone())));
+    }
+}
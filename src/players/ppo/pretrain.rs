@@ -0,0 +1,216 @@
+//! Supervised pre-training of the policy network
+//!
+//! Positions are generated by playing a random number of random moves from the start of a game,
+//! then labelled with the move a heuristic or search player would choose.
+//! The policy is trained to predict these moves with a cross entropy loss,
+//! giving RL a starting point that already plays sensible moves
+
+use burn::{
+    config::Config,
+    optim::{AdamConfig, GradientsParams, Optimizer},
+    prelude::Backend,
+    tensor::{
+        activation::log_softmax, backend::AutodiffBackend, cast::ToElement as _, Int, Tensor,
+    },
+};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+
+use super::PPOMoveSelector;
+use crate::{
+    gamestate::{Gamestate, State},
+    players::{
+        curriculum::Opponent,
+        features::{StateEncoder, StateFeaturesV1},
+    },
+};
+
+#[derive(Config, Debug)]
+pub struct PretrainConfig {
+    /// Number of labelled positions to generate
+    #[config(default = 100_000)]
+    pub positions: usize,
+    /// Most random moves played before labelling a position
+    #[config(default = 80)]
+    pub max_random_moves: usize,
+    /// Player whose choices are used as labels
+    #[config(default = "Opponent::MoveRank2")]
+    pub teacher: Opponent,
+    #[config(default = 5)]
+    pub epochs: usize,
+    #[config(default = 256)]
+    pub batch_size: usize,
+    #[config(default = 1e-3)]
+    pub learning_rate: f64,
+    #[config(default = 0)]
+    pub seed: u64,
+}
+
+/// A position and the teacher's move
+#[derive(Debug, Clone)]
+pub struct Labelled {
+    pub state: Vec<f32>,
+    /// 0 for legal moves and a large negative number for illegal, as in training
+    pub mask: Vec<f32>,
+    pub action: usize,
+}
+
+/// Generate and label positions, split across the available threads
+pub fn label_positions(config: &PretrainConfig) -> Vec<Labelled> {
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let per_thread = config.positions.div_ceil(threads);
+    std::thread::scope(|s| {
+        let handles = (0..threads)
+            .map(|t| {
+                let count = per_thread.min(config.positions.saturating_sub(t * per_thread));
+                s.spawn(move || label_chunk(config, config.seed + t as u64, count))
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect()
+    })
+}
+
+fn label_chunk(config: &PretrainConfig, seed: u64, count: usize) -> Vec<Labelled> {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut teacher = config.teacher.player();
+    let mut positions = Vec::with_capacity(count);
+    while positions.len() < count {
+        let mut gs = Gamestate::new_2_player_with_seed(rng.gen(), rng.gen_range(0..2));
+        for _ in 0..rng.gen_range(0..=config.max_random_moves) {
+            let m = *gs.get_moves().choose(&mut rng).unwrap();
+            if gs.play_move(m) == State::RoundEnd && gs.end_round().game_over() {
+                break;
+            }
+        }
+        if gs.state() != State::RoundActive {
+            continue;
+        }
+        let moves = gs.get_moves();
+        let mut mask = vec![-1e8f32; 180];
+        for m in &moves {
+            mask[m.to_index()] = 0.0;
+        }
+        let action = teacher.pick_move(&gs, moves).to_index();
+        positions.push(Labelled {
+            state: StateFeaturesV1.encode(&gs),
+            mask,
+            action,
+        });
+    }
+    positions
+}
+
+/// Train the policy to predict the labelled moves
+pub fn pretrain<B: AutodiffBackend>(
+    mut ppo: PPOMoveSelector<B>,
+    positions: &mut [Labelled],
+    config: &PretrainConfig,
+) -> PPOMoveSelector<B> {
+    let mut optimiser = AdamConfig::new().init();
+    let mut rng = SmallRng::seed_from_u64(config.seed);
+    for epoch in 0..config.epochs {
+        positions.shuffle(&mut rng);
+        let mut total_loss = 0.0;
+        let mut correct = 0;
+        for batch in positions.chunks(config.batch_size) {
+            let (states, masks, actions) = stack(batch, &ppo.device);
+            let logits = ppo.policy.action(states) + masks;
+            correct += logits
+                .clone()
+                .argmax(1)
+                .equal(actions.clone())
+                .int()
+                .sum()
+                .into_scalar()
+                .to_usize();
+            let loss = -log_softmax(logits, 1).gather(1, actions).mean();
+            total_loss += loss.clone().into_scalar().to_f32() * batch.len() as f32;
+            let grads = GradientsParams::from_grads(loss.backward(), &ppo.policy);
+            ppo.policy = optimiser.step(config.learning_rate, ppo.policy, grads);
+        }
+        println!(
+            "Pretrain epoch {epoch}: loss {:.4}, agrees with teacher {:.1}%",
+            total_loss / positions.len() as f32,
+            100.0 * correct as f32 / positions.len() as f32
+        );
+    }
+    ppo
+}
+
+/// Fraction of positions where the policy's most likely move is the teacher's
+pub fn accuracy<B: AutodiffBackend>(ppo: &PPOMoveSelector<B>, positions: &[Labelled]) -> f32 {
+    let (states, masks, actions) = stack::<B>(positions, &ppo.device);
+    let correct = (ppo.policy.action(states) + masks)
+        .detach()
+        .argmax(1)
+        .equal(actions)
+        .int()
+        .sum()
+        .into_scalar()
+        .to_usize();
+    correct as f32 / positions.len() as f32
+}
+
+type Batch<B> = (Tensor<B, 2>, Tensor<B, 2>, Tensor<B, 2, Int>);
+
+fn stack<B: Backend>(positions: &[Labelled], device: &B::Device) -> Batch<B> {
+    let n = positions.len();
+    let flat =
+        |f: fn(&Labelled) -> &[f32]| positions.iter().flat_map(f).copied().collect::<Vec<_>>();
+    let states = Tensor::<B, 1>::from_data(flat(|p| &p.state).as_slice(), device).reshape([n, 150]);
+    let masks = Tensor::<B, 1>::from_data(flat(|p| &p.mask).as_slice(), device).reshape([n, 180]);
+    let actions = positions
+        .iter()
+        .map(|p| p.action as i64)
+        .collect::<Vec<_>>();
+    let actions = Tensor::<B, 1, Int>::from_data(actions.as_slice(), device).reshape([n, 1]);
+    (states, masks, actions)
+}
+
+#[cfg(test)]
+mod test {
+    use burn::backend::{Autodiff, NdArray};
+
+    use super::{accuracy, label_positions, pretrain, PretrainConfig};
+    use crate::players::{
+        curriculum::Opponent,
+        ppo::{PPOMoveSelector, PolicyConfig, ValueConfig},
+    };
+
+    #[test]
+    fn learns_teacher_moves() {
+        // Fit a small set of positions labelled by a deterministic teacher
+        let config = PretrainConfig::new()
+            .with_positions(256)
+            .with_teacher(Opponent::MoveRank)
+            .with_epochs(0);
+        let positions = label_positions(&config);
+        assert_eq!(positions.len(), 256);
+        assert!(positions
+            .iter()
+            .all(|p| p.mask[p.action] == 0.0 && p.state.len() == 150));
+
+        let device = Default::default();
+        let ppo = PPOMoveSelector::<Autodiff<NdArray>>::new(
+            PolicyConfig {
+                input_size: 150,
+                hidden_size: 32,
+            },
+            ValueConfig {
+                input_size: 150,
+                hidden_size: 32,
+            },
+            &device,
+        );
+        let mut positions = positions;
+        let before = accuracy(&ppo, &positions);
+        let ppo = pretrain(
+            ppo,
+            &mut positions,
+            &config.with_epochs(20).with_batch_size(32),
+        );
+        assert!(accuracy(&ppo, &positions) > before);
+    }
+}
@@ -1,5 +1,8 @@
+use std::io::Write;
 use std::ops::AddAssign;
 
+use burn::config::Config;
+use burn::grad_clipping::GradientClippingConfig;
 use burn::module::Module;
 use burn::nn::loss::HuberLoss;
 use burn::optim::{AdamConfig, GradientsParams, Optimizer};
@@ -7,67 +10,290 @@ use burn::record::{self, DefaultFileRecorder, FullPrecisionSettings};
 use burn::tensor::activation::softmax;
 use burn::tensor::backend::AutodiffBackend;
 use burn::tensor::cast::ToElement as _;
-use burn::{prelude::Backend, tensor::Tensor};
+use burn::{
+    prelude::Backend,
+    tensor::{Int, Tensor},
+};
 use log::trace;
-use nalgebra::{DVector, SVector};
+use nalgebra::DVector;
 
+use crate::elo;
+use crate::error::{AzulError, ParseKind};
 use crate::gamestate::{Gamestate, State};
+use crate::opening::Opening;
+use crate::players::curriculum::{Curriculum, CurriculumConfig, Opponent};
+use crate::players::ppo::pretrain::{self, PretrainConfig};
 use crate::players::{ppo::PPOMoveSelector, Player};
-/// Train a PPO agent against another player
+use crate::runner::{gauntlet, MatchUpResult};
+
+/// How the PPO agent is rewarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewardScheme {
+    /// Change in predicted score after each move
+    Shaped,
+    /// 1 for a win, -1 for a loss and 0 for a draw, given at the end of the game
+    Terminal,
+    /// Final score difference, given at the end of the game
+    Margin,
+}
+
+impl std::str::FromStr for RewardScheme {
+    type Err = AzulError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shaped" => Ok(Self::Shaped),
+            "terminal" => Ok(Self::Terminal),
+            "margin" => Ok(Self::Margin),
+            _ => Err(AzulError::parse(ParseKind::RewardScheme, s)),
+        }
+    }
+}
+
+/// How the learning rate changes over training
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LrSchedule {
+    Constant,
+    /// Decrease linearly to the minimum by the last episode
+    Linear,
+    /// Follow half a cosine down to the minimum by the last episode
+    Cosine,
+}
+
+impl LrSchedule {
+    /// Learning rate for the episode
+    pub fn rate(&self, initial: f64, min: f64, episode: usize, episodes: usize) -> f64 {
+        let progress = episode as f64 / episodes.saturating_sub(1).max(1) as f64;
+        let scale = match self {
+            LrSchedule::Constant => 1.0,
+            LrSchedule::Linear => 1.0 - progress,
+            LrSchedule::Cosine => 0.5 * (1.0 + (std::f64::consts::PI * progress).cos()),
+        };
+        min + (initial - min) * scale
+    }
+}
+
+#[derive(Config, Debug)]
+pub struct TrainConfig {
+    /// Opponents to train against, in order
+    #[config(default = "CurriculumConfig::new()")]
+    pub curriculum: CurriculumConfig,
+    #[config(default = "RewardScheme::Shaped")]
+    pub reward: RewardScheme,
+    #[config(default = 1000)]
+    pub episodes: usize,
+    #[config(default = 40)]
+    pub games_per_episode: usize,
+    /// Passes over each episode's data
+    #[config(default = 5)]
+    pub epochs: usize,
+    #[config(default = 128)]
+    pub batch_size: usize,
+    /// Discount factor for shaped rewards, outcome rewards are not discounted
+    #[config(default = 0.99)]
+    pub gamma: f32,
+    /// PPO clipping range
+    #[config(default = 0.1)]
+    pub epsilon: f32,
+    #[config(default = 1e-3)]
+    pub learning_rate: f64,
+    /// Learning rate reached at the end of a decaying schedule
+    #[config(default = 0.0)]
+    pub min_learning_rate: f64,
+    #[config(default = "LrSchedule::Constant")]
+    pub lr_schedule: LrSchedule,
+    /// Clip the gradient norm of both networks before each optimiser step
+    pub grad_clip_norm: Option<f32>,
+    /// Stop after this many evaluations without a new best Elo estimate
+    pub patience: Option<usize>,
+    /// Stack each minibatch into 2-D tensors for a single forward and backward pass
+    #[config(default = false)]
+    pub batched: bool,
+    /// Pre-train the policy on heuristic labelled positions before RL
+    pub pretrain: Option<PretrainConfig>,
+    /// Directory to save policy checkpoints and metrics to
+    #[config(default = "String::from(\"ppo_large\")")]
+    pub checkpoint_dir: String,
+    /// Episodes between evaluations against the baseline suite
+    #[config(default = 10)]
+    pub eval_every: usize,
+    /// Game pairs played against each baseline when evaluating
+    #[config(default = 20)]
+    pub eval_games: u32,
+    #[config(default = "default_baselines()")]
+    pub baselines: Vec<Baseline>,
+    /// Opening moves played for both sides before the agent takes over,
+    /// so training games don't all start the same way
+    #[config(default = "Opening::default()")]
+    pub opening: Opening,
+    /// Seed for the agent's move sampling, and for its first weights when the `ppo`
+    /// binary creates it. GPU backends can still round differently between runs
+    #[config(default = 0)]
+    pub seed: u64,
+}
+
+/// Fixed opponent used to estimate the agent's Elo rating
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct Baseline {
+    pub opponent: Opponent,
+    /// Nominal rating that anchors the estimate
+    pub rating: f64,
+}
+
+fn default_baselines() -> Vec<Baseline> {
+    let baseline = |opponent, rating| Baseline { opponent, rating };
+    vec![
+        baseline(Opponent::Random, 1000.0),
+        baseline(Opponent::MoveRank, 1400.0),
+        baseline(Opponent::MoveRank2, 1500.0),
+        baseline(Opponent::Minimax(1), 1600.0),
+    ]
+}
+
+/// Line of `metrics.jsonl`, written after each episode and evaluation
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Metric {
+    Episode {
+        episode: usize,
+        learning_rate: f64,
+        /// Mean over the batches of the last epoch
+        policy_loss: f32,
+        critic_loss: f32,
+        /// Fraction of this episode's games won against the curriculum opponent
+        win_rate: f32,
+        opponent: Opponent,
+    },
+    Evaluation {
+        episode: usize,
+        elo: f64,
+        best_elo: f64,
+        results: Vec<MatchUpResult>,
+    },
+}
+
+/// Training waits between episodes while this file exists in the checkpoint directory
+pub const PAUSE_FILE: &str = "pause";
+/// Creating this file in the checkpoint directory runs an evaluation after the current episode
+pub const EVALUATE_FILE: &str = "evaluate";
+
+/// Called with every metric as it is written, eg to show it live
+pub type MetricObserver = Box<dyn FnMut(&Metric) + Send>;
+
+/// Train a PPO agent against a curriculum of opponents
 ///
 /// Runs a matchup, collecting state and rewards
 /// then trains the player based on outcome
 pub struct PPOTrainer<B: Backend> {
     ppo: PPOMoveSelector<B>,
-    opponent: Box<dyn Player<2, 6>>,
+    config: TrainConfig,
     device: B::Device,
+    observer: Option<MetricObserver>,
 }
 
 impl<B: AutodiffBackend> PPOTrainer<B> {
-    pub fn new(
-        ppo: PPOMoveSelector<B>,
-        opponent: Box<dyn Player<2, 6>>,
-        device: &B::Device,
-    ) -> Self {
+    pub fn new(ppo: PPOMoveSelector<B>, config: TrainConfig, device: &B::Device) -> Self {
         Self {
             ppo,
-            opponent,
+            config,
             device: device.clone(),
+            observer: None,
         }
     }
 
-    pub fn train(mut self) {
+    /// Pass every metric to `observer` as well as writing it to `metrics.jsonl`
+    pub fn with_observer(mut self, observer: impl FnMut(&Metric) + Send + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Train the agent, returning it once all episodes are complete
+    pub fn train(self) -> PPOMoveSelector<B> {
         // create optimiser for policy and critic
-        let mut policy_optimiser = AdamConfig::new().init();
-        // let mut critic_optimiser = AdamConfig::new().init();
-        let mut critic_optimiser = AdamConfig::new().init();
+        let adam = AdamConfig::new()
+            .with_grad_clipping(self.config.grad_clip_norm.map(GradientClippingConfig::Norm));
+        let mut policy_optimiser = adam.init();
+        let mut critic_optimiser = adam.init();
 
-        let mut ppo = self.ppo;
-        let mut opponent = self.opponent;
+        let mut ppo = self.ppo.with_seed(self.config.seed);
+        if let Some(config) = &self.config.pretrain {
+            let mut positions = pretrain::label_positions(config);
+            println!("Labelled {} positions for pre-training", positions.len());
+            ppo = pretrain::pretrain(ppo, &mut positions, config);
+        }
+        let mut curriculum = Curriculum::new(self.config.curriculum);
+        let mut opponent = curriculum.opponent();
         let device = self.device;
 
-        let gamma = 0.99;
-        let epsilon = 0.1;
-        let episodes = 1000;
-        let epochs = 5;
-        let batch_size = 128;
-        let games_per_episode = 40;
-        let learning_rate = 0.001;
+        let reward = self.config.reward;
+        // Outcome rewards are not discounted so every move in the game shares the credit
+        let gamma = match reward {
+            RewardScheme::Shaped => self.config.gamma,
+            RewardScheme::Terminal | RewardScheme::Margin => 1.0,
+        };
+        let epsilon = self.config.epsilon;
+        let episodes = self.config.episodes;
+        let epochs = self.config.epochs;
+        let batch_size = self.config.batch_size;
+        let games_per_episode = self.config.games_per_episode;
 
         // Create dir to store progress
-        let dir = std::path::Path::new("ppo_large");
+        let dir = std::path::Path::new(&self.config.checkpoint_dir);
         std::fs::create_dir_all(dir).unwrap();
-        let mut recorder: record::NamedMpkFileRecorder<FullPrecisionSettings> =
+        let recorder: record::NamedMpkFileRecorder<FullPrecisionSettings> =
             DefaultFileRecorder::default();
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("metrics.jsonl"))
+            .unwrap();
+        let mut metrics = (file, self.observer);
+        let baselines: Vec<Box<dyn Player<2, 6>>> = self
+            .config
+            .baselines
+            .iter()
+            .map(|b| b.opponent.player())
+            .collect();
+        let mut best_elo = f64::NEG_INFINITY;
+        let mut since_best = 0;
 
         for episode in 0..episodes {
-            println!("Episode: {}", episode);
+            let learning_rate = self.config.lr_schedule.rate(
+                self.config.learning_rate,
+                self.config.min_learning_rate,
+                episode,
+                episodes,
+            );
+            println!("Episode: {episode}, learning rate {learning_rate:.2e}");
             let mut data = Data::default();
-            let results = play_games(&mut ppo, &mut opponent, games_per_episode);
+            let results = play_games(
+                &mut ppo,
+                &mut opponent,
+                games_per_episode,
+                reward,
+                &self.config.opening,
+            );
+            let wins = results.iter().map(|r| r.score[0] > r.score[1]);
+            let win_rate = wins.clone().filter(|&w| w).count() as f32 / results.len() as f32;
+            let episode_opponent = curriculum.stage().opponent;
+            if curriculum.record(wins) {
+                println!(" Promoted to {:?}", curriculum.stage().opponent);
+                opponent = curriculum.opponent();
+            }
             // Convert each result into a batch and append to batch
+            // Shaped rewards are normalised per game, outcome rewards over the whole
+            // episode so that won and lost games keep their sign
+            let shaped = reward == RewardScheme::Shaped;
             for result in results {
-                let returns = returns(&device, &result.rewards, gamma);
-                let advantages = advantages(&device, &returns, &result.values);
+                let returns = returns(&device, &result.rewards, gamma, shaped);
+                let advantages = advantages(&returns, &result.values);
+                let advantages = if shaped {
+                    normalise(&device, &advantages)
+                } else {
+                    advantages
+                };
                 data += Data {
                     states: result.states,
                     returns,
@@ -82,49 +308,31 @@ impl<B: AutodiffBackend> PPOTrainer<B> {
                 data.states.len(),
                 games_per_episode
             );
+            if !shaped {
+                data.advantages = normalise(&device, &data.advantages);
+            }
             // Detach the tensors from the computation graph
             data.detach();
 
+            let stacked = self.config.batched.then(|| Stacked::new(&data));
+
+            let (mut policy_total, mut critic_total, mut batches) = (0.0, 0.0, 0);
             for epoch in 0..epochs {
+                let last_epoch = epoch + 1 == epochs;
                 let mut batch = 0;
                 // Iterate over batches of batch_size
                 while batch * batch_size < data.states.len() {
                     let start = batch * batch_size;
                     let end = ((batch + 1) * batch_size).min(data.states.len());
-                    let states = &data.states[start..end];
-                    let returns = &data.returns[start..end];
-                    let advantages = &data.advantages[start..end];
-                    let action_logs = &data.action_logs[start..end];
-                    let actions = &data.actions[start..end];
-                    let action_masks = &data.action_masks[start..end];
-
-                    // calculate softmax of masked actions of current policy and predicted value
-                    let (value_preds, action_log_new): (Vec<Tensor<B, 1>>, Vec<Tensor<B, 1>>) =
-                        states
-                            .iter()
-                            .zip(action_masks)
-                            .map(|(s, m)| {
-                                (
-                                    ppo.value(s.clone()),
-                                    softmax(ppo.action(s.clone()) + m.clone(), 0),
-                                )
-                            })
-                            .unzip();
-                    // calculate the surrogate loss
-                    let surrogate_loss = surrogate_loss(
-                        &device,
-                        action_logs,
-                        &action_log_new,
-                        advantages,
-                        epsilon,
-                        actions,
-                    );
-                    // println!("Surrogate loss: {:?}", surrogate_loss);
-                    // Get losses
-                    let (policy_loss, critic_loss) =
-                        calculate_losses(&device, surrogate_loss, returns.to_vec(), value_preds);
-                    // println!("Policy loss: {}", policy_loss);
-                    // println!("Critic loss: {}", critic_loss);
+                    let (policy_loss, critic_loss) = match &stacked {
+                        Some(stacked) => stacked.slice(start..end).losses(&ppo, epsilon),
+                        None => losses(&ppo, &data, start..end, epsilon, &device),
+                    };
+                    if last_epoch {
+                        policy_total += policy_loss.clone().into_scalar().to_f32();
+                        critic_total += critic_loss.clone().into_scalar().to_f32();
+                        batches += 1;
+                    }
                     let policy_grad = policy_loss.backward();
                     let gradient_params = GradientsParams::from_grads(policy_grad, &ppo.policy);
                     // println!("Gradient params: {:?}", gradient_params);
@@ -140,6 +348,7 @@ impl<B: AutodiffBackend> PPOTrainer<B> {
                         device: device.clone(),
                         policy,
                         value: critic,
+                        rng: ppo.rng,
                     };
                     batch += 1;
                 }
@@ -149,7 +358,82 @@ impl<B: AutodiffBackend> PPOTrainer<B> {
                 .clone()
                 .save_file(dir.join(format!("checkpoint_{episode}.pt")), &recorder)
                 .unwrap();
+            ppo.value
+                .clone()
+                .save_file(dir.join(format!("value_{episode}.pt")), &recorder)
+                .unwrap();
+
+            write_metric(
+                &mut metrics,
+                &Metric::Episode {
+                    episode,
+                    learning_rate,
+                    policy_loss: policy_total / batches.max(1) as f32,
+                    critic_loss: critic_total / batches.max(1) as f32,
+                    win_rate,
+                    opponent: episode_opponent,
+                },
+            );
+
+            // Evaluate against the baselines, keeping the best policy separately.
+            // An evaluation can also be requested by creating the evaluate file
+            let requested = std::fs::remove_file(dir.join(EVALUATE_FILE)).is_ok();
+            if requested || (episode + 1) % self.config.eval_every == 0 {
+                let results = gauntlet(&ppo, &baselines, self.config.eval_games, episode as u64);
+                let rated = self
+                    .config
+                    .baselines
+                    .iter()
+                    .zip(&results)
+                    .map(|(b, r)| (b.rating, *r))
+                    .collect::<Vec<_>>();
+                let elo = elo::performance(&rated);
+                if elo > best_elo {
+                    best_elo = elo;
+                    since_best = 0;
+                    ppo.policy
+                        .clone()
+                        .save_file(dir.join("best.pt"), &recorder)
+                        .unwrap();
+                    ppo.value
+                        .clone()
+                        .save_file(dir.join("best_value.pt"), &recorder)
+                        .unwrap();
+                } else {
+                    since_best += 1;
+                }
+                println!(" Elo estimate {elo:.0}, best {best_elo:.0}");
+                write_metric(
+                    &mut metrics,
+                    &Metric::Evaluation {
+                        episode,
+                        elo,
+                        best_elo,
+                        results,
+                    },
+                );
+                if self.config.patience.is_some_and(|p| since_best >= p) {
+                    println!(" No improvement in {since_best} evaluations, stopping");
+                    break;
+                }
+            }
+
+            if dir.join(PAUSE_FILE).exists() {
+                println!(" Paused, remove {:?} to continue", dir.join(PAUSE_FILE));
+                while dir.join(PAUSE_FILE).exists() {
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                }
+            }
         }
+        ppo
+    }
+}
+
+fn write_metric((file, observer): &mut (std::fs::File, Option<MetricObserver>), metric: &Metric) {
+    serde_json::to_writer(&mut *file, metric).unwrap();
+    writeln!(file).unwrap();
+    if let Some(observer) = observer {
+        observer(metric);
     }
 }
 
@@ -180,7 +464,107 @@ impl<B: Backend> Data<B> {
     }
 }
 
-fn returns<B: Backend>(device: &B::Device, rewards: &[f32], gamma: f32) -> Vec<Tensor<B, 1>> {
+/// Policy and critic losses for a range of the data, running the networks on each state
+fn losses<B: AutodiffBackend>(
+    ppo: &PPOMoveSelector<B>,
+    data: &Data<B>,
+    range: std::ops::Range<usize>,
+    epsilon: f32,
+    device: &B::Device,
+) -> (Tensor<B, 1>, Tensor<B, 1>) {
+    let states = &data.states[range.clone()];
+    let returns = &data.returns[range.clone()];
+    let advantages = &data.advantages[range.clone()];
+    let action_logs = &data.action_logs[range.clone()];
+    let actions = &data.actions[range.clone()];
+    let action_masks = &data.action_masks[range];
+
+    // calculate softmax of masked actions of current policy and predicted value
+    let (value_preds, action_log_new): (Vec<Tensor<B, 1>>, Vec<Tensor<B, 1>>) = states
+        .iter()
+        .zip(action_masks)
+        .map(|(s, m)| {
+            (
+                ppo.value(s.clone()),
+                softmax(ppo.action(s.clone()) + m.clone(), 0),
+            )
+        })
+        .unzip();
+    // calculate the surrogate loss
+    let surrogate_loss = surrogate_loss(
+        device,
+        action_logs,
+        &action_log_new,
+        advantages,
+        epsilon,
+        actions,
+    );
+    // Get losses
+    calculate_losses(device, surrogate_loss, returns.to_vec(), value_preds)
+}
+
+/// Episode data stacked into 2-D tensors, one row per state
+struct Stacked<B: Backend> {
+    states: Tensor<B, 2>,
+    returns: Tensor<B, 2>,
+    advantages: Tensor<B, 2>,
+    action_logs: Tensor<B, 2>,
+    actions: Tensor<B, 2, Int>,
+    action_masks: Tensor<B, 2>,
+}
+
+impl<B: Backend> Stacked<B> {
+    fn new(data: &Data<B>) -> Self {
+        let device = data.states[0].device();
+        let actions = data.actions.iter().map(|&a| a as i64).collect::<Vec<_>>();
+        Self {
+            states: Tensor::stack(data.states.clone(), 0),
+            returns: Tensor::stack(data.returns.clone(), 0),
+            advantages: Tensor::stack(data.advantages.clone(), 0),
+            action_logs: Tensor::stack(data.action_logs.clone(), 0),
+            actions: Tensor::<B, 1, Int>::from_data(actions.as_slice(), &device).unsqueeze_dim(1),
+            action_masks: Tensor::stack(data.action_masks.clone(), 0),
+        }
+    }
+
+    fn slice(&self, range: std::ops::Range<usize>) -> Self {
+        Self {
+            states: self.states.clone().slice([range.clone()]),
+            returns: self.returns.clone().slice([range.clone()]),
+            advantages: self.advantages.clone().slice([range.clone()]),
+            action_logs: self.action_logs.clone().slice([range.clone()]),
+            actions: self.actions.clone().slice([range.clone()]),
+            action_masks: self.action_masks.clone().slice([range]),
+        }
+    }
+
+    /// Policy and critic losses, the same as the per state path
+    fn losses(self, ppo: &PPOMoveSelector<B>, epsilon: f32) -> (Tensor<B, 1>, Tensor<B, 1>) {
+        let new = softmax(
+            ppo.policy.action(self.states.clone()) + self.action_masks,
+            1,
+        );
+        let ratio = (new - self.action_logs).exp();
+        let s1 = ratio.clone() * self.advantages.clone();
+        let s2 = ratio.clamp(1.0 - epsilon, 1.0 + epsilon) * self.advantages;
+        let policy_loss = -s1.min_pair(s2).gather(1, self.actions).sum();
+
+        let value_preds = ppo.value.value(self.states);
+        let huber = HuberLoss {
+            delta: 1.0,
+            lin_bias: 0.0,
+        };
+        let critic_loss = huber.forward(self.returns, value_preds, burn::nn::loss::Reduction::Sum);
+        (policy_loss, critic_loss)
+    }
+}
+
+fn returns<B: Backend>(
+    device: &B::Device,
+    rewards: &[f32],
+    gamma: f32,
+    normalise: bool,
+) -> Vec<Tensor<B, 1>> {
     // Calculate the discounted rewards for each state
 
     let mut returns = DVector::<f32>::zeros(rewards.len());
@@ -191,9 +575,13 @@ fn returns<B: Backend>(device: &B::Device, rewards: &[f32], gamma: f32) -> Vec<T
     }
 
     // Remove mean and divide by std
-    let mean = returns.mean();
-    let std = returns.variance().sqrt() + 1e-8;
-    let returns = returns.map(|r| (r - mean) / std);
+    let returns = if normalise {
+        let mean = returns.mean();
+        let std = returns.variance().sqrt() + 1e-8;
+        returns.map(|r| (r - mean) / std)
+    } else {
+        returns
+    };
 
     returns
         .iter()
@@ -201,17 +589,16 @@ fn returns<B: Backend>(device: &B::Device, rewards: &[f32], gamma: f32) -> Vec<T
         .collect()
 }
 
-fn advantages<B: Backend>(
-    device: &B::Device,
-    returns: &[Tensor<B, 1>],
-    values: &[Tensor<B, 1>],
-) -> Vec<Tensor<B, 1>> {
-    let advantages = values
+fn advantages<B: Backend>(returns: &[Tensor<B, 1>], values: &[Tensor<B, 1>]) -> Vec<Tensor<B, 1>> {
+    values
         .iter()
         .zip(returns.iter())
         .map(|(v, r)| r.clone() - v.clone().detach())
-        .collect::<Vec<_>>();
-    // Normalise by mean and std
+        .collect()
+}
+
+/// Normalise advantages by mean and std
+fn normalise<B: Backend>(device: &B::Device, advantages: &[Tensor<B, 1>]) -> Vec<Tensor<B, 1>> {
     let mean: f32 = advantages
         .iter()
         .map(|a| a.clone().into_scalar().to_f32())
@@ -244,8 +631,8 @@ fn surrogate_loss<B: Backend>(
     actions: &[usize],
 ) -> Vec<Tensor<B, 1>> {
     action_log_new
-        .into_iter()
-        .zip(action_log_old.into_iter())
+        .iter()
+        .zip(action_log_old)
         .zip(advantages.iter())
         .zip(actions)
         .map(|(((new, old), adv), &action)| {
@@ -289,11 +676,13 @@ fn play_games<B: Backend>(
     ppo: &mut PPOMoveSelector<B>,
     opponent: &mut Box<dyn Player<2, 6>>,
     num_games: usize,
+    reward: RewardScheme,
+    opening: &Opening,
 ) -> Vec<GameResult<B>> {
     let mut results = Vec::with_capacity(num_games);
     let mut scores = Vec::new();
     for seed in 0..num_games {
-        let result = play_game(ppo, opponent, Some(seed as u64));
+        let result = play_game(ppo, opponent, Some(seed as u64), reward, opening);
         scores.push(result.score);
         results.push(result);
     }
@@ -309,14 +698,15 @@ fn play_game<B: Backend>(
     ppo: &mut PPOMoveSelector<B>,
     opponent: &mut Box<dyn Player<2, 6>>,
     seed: Option<u64>,
+    reward: RewardScheme,
+    opening: &Opening,
 ) -> GameResult<B> {
     let mut result = GameResult::default();
     // Create a random game
-    let mut gs = if let Some(seed) = seed {
-        Gamestate::new_2_player_with_seed(seed, 0)
-    } else {
-        Gamestate::new_2_player()
-    };
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut gs = Gamestate::new_2_player_with_seed(seed, 0);
+    // Opening moves aren't the agent's choice, so aren't trained on
+    opening.play(&mut gs, seed);
 
     // Play the game
     loop {
@@ -336,7 +726,9 @@ fn play_game<B: Backend>(
                 let state = gs.play_move(pick.picked_move);
                 let score = gs.boards()[0].predicted_score as f32;
                 let delta = (score - prev_score) / 10.0;
-                if score == 0.0 {
+                if reward != RewardScheme::Shaped {
+                    result.rewards.push(0.0);
+                } else if score == 0.0 {
                     result.rewards.push(delta.min(-1.0));
                 } else {
                     result.rewards.push(delta);
@@ -352,13 +744,22 @@ fn play_game<B: Backend>(
         };
         if state == State::RoundEnd {
             trace!("Round ended");
-            if gs.end_round() == State::GameEnd {
+            if gs.end_round().game_over() {
                 trace!("Game ended");
                 break;
             }
         }
     }
     result.score = gs.scores();
+    // Give the outcome to the agent's last move
+    let margin = result.score[0] as f32 - result.score[1] as f32;
+    if let Some(last) = result.rewards.last_mut() {
+        match reward {
+            RewardScheme::Shaped => (),
+            RewardScheme::Terminal => *last = margin.signum(),
+            RewardScheme::Margin => *last = margin / 10.0,
+        }
+    }
     result
 }
 
@@ -379,3 +780,68 @@ struct GameResult<B: Backend> {
     /// The scores
     score: [u8; 2],
 }
+
+#[cfg(test)]
+mod test {
+    use burn::backend::{Autodiff, NdArray};
+    use burn::tensor::{cast::ToElement as _, Tensor};
+
+    use super::{
+        advantages, losses, normalise, play_game, returns, Data, LrSchedule, RewardScheme, Stacked,
+    };
+    use crate::players::{
+        ppo::{PPOMoveSelector, PolicyConfig, ValueConfig},
+        FirstMovePlayer, Player,
+    };
+
+    #[test]
+    fn lr_schedules() {
+        for schedule in [LrSchedule::Linear, LrSchedule::Cosine] {
+            assert_eq!(schedule.rate(1e-3, 1e-5, 0, 100), 1e-3);
+            assert!((schedule.rate(1e-3, 1e-5, 99, 100) - 1e-5).abs() < 1e-12);
+        }
+        assert_eq!(LrSchedule::Constant.rate(1e-3, 0.0, 50, 100), 1e-3);
+        assert!((LrSchedule::Linear.rate(1.0, 0.0, 2, 5) - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn batched_losses_match() {
+        let device = Default::default();
+        let mut ppo = PPOMoveSelector::<Autodiff<NdArray>>::new(
+            PolicyConfig {
+                input_size: 150,
+                hidden_size: 16,
+            },
+            ValueConfig {
+                input_size: 150,
+                hidden_size: 16,
+            },
+            &device,
+        );
+        let mut opponent: Box<dyn Player<2, 6>> = Box::new(FirstMovePlayer);
+        let result = play_game(
+            &mut ppo,
+            &mut opponent,
+            Some(0),
+            RewardScheme::Shaped,
+            &Default::default(),
+        );
+        let returns = returns(&device, &result.rewards, 0.99, true);
+        let advantages = normalise(&device, &advantages(&returns, &result.values));
+        let mut data = Data {
+            states: result.states,
+            returns,
+            advantages,
+            action_logs: result.action_logs,
+            actions: result.actions,
+            action_masks: result.action_masks,
+        };
+        data.detach();
+
+        let (policy, critic) = losses(&ppo, &data, 2..12, 0.1, &device);
+        let (batched_policy, batched_critic) = Stacked::new(&data).slice(2..12).losses(&ppo, 0.1);
+        let value = |t: Tensor<Autodiff<NdArray>, 1>| t.into_scalar().to_f32();
+        assert!((value(policy) - value(batched_policy)).abs() < 1e-4);
+        assert!((value(critic) - value(batched_critic)).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,193 @@
+//! Prioritised experience replay for training
+//!
+//! Items are sampled with probability proportional to `priority ^ alpha`, where the priority
+//! is the magnitude of the item's last TD error. Importance sampling weights correct for the
+//! bias this introduces, with `beta` usually annealed towards 1 over training.
+//! Once full, new items replace the oldest
+
+use rand::Rng;
+
+/// Replay buffer with proportional prioritised sampling
+#[derive(Debug, Clone)]
+pub struct PrioritisedReplay<T> {
+    items: Vec<T>,
+    tree: SumTree,
+    /// Index the next item is written to
+    next: usize,
+    /// Priority given to new items so they are sampled at least once
+    max_priority: f32,
+    /// How strongly to prioritise, 0 is uniform
+    pub alpha: f32,
+    /// Strength of the importance sampling correction, 1 is full correction
+    pub beta: f32,
+    /// Added to TD errors so no item has zero probability
+    pub epsilon: f32,
+}
+
+/// An item chosen by [PrioritisedReplay::sample]
+#[derive(Debug)]
+pub struct Sample<'a, T> {
+    /// Index to pass to [PrioritisedReplay::update]
+    pub index: usize,
+    /// Importance sampling weight, scaled so the largest in the batch is 1
+    pub weight: f32,
+    pub item: &'a T,
+}
+
+impl<T> PrioritisedReplay<T> {
+    pub fn new(capacity: usize, alpha: f32, beta: f32) -> Self {
+        assert!(capacity > 0, "Replay capacity must be positive");
+        Self {
+            items: Vec::with_capacity(capacity),
+            tree: SumTree::new(capacity),
+            next: 0,
+            max_priority: 1.0,
+            alpha,
+            beta,
+            epsilon: 1e-5,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.tree.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Add an item with the highest priority seen so far
+    pub fn push(&mut self, item: T) {
+        if self.items.len() < self.capacity() {
+            self.items.push(item);
+        } else {
+            self.items[self.next] = item;
+        }
+        self.tree.set(self.next, self.max_priority.powf(self.alpha));
+        self.next = (self.next + 1) % self.capacity();
+    }
+
+    /// Sample `n` items, drawing one from each of `n` equal slices of the total priority
+    pub fn sample(&self, n: usize, rng: &mut impl Rng) -> Vec<Sample<'_, T>> {
+        if self.is_empty() || n == 0 {
+            return Vec::new();
+        }
+        let total = self.tree.total();
+        let segment = total / n as f32;
+        let indices = (0..n)
+            .map(|i| {
+                let target = segment * (i as f32 + rng.gen::<f32>());
+                self.tree.find(target.min(total)).min(self.len() - 1)
+            })
+            .collect::<Vec<_>>();
+        // w = (N * P(i)) ^ -beta, normalised by the largest in the batch
+        let weights = indices
+            .iter()
+            .map(|&i| (self.len() as f32 * self.tree.get(i) / total).powf(-self.beta))
+            .collect::<Vec<_>>();
+        let max = weights.iter().cloned().fold(f32::MIN_POSITIVE, f32::max);
+        indices
+            .into_iter()
+            .zip(weights)
+            .map(|(index, weight)| Sample {
+                index,
+                weight: weight / max,
+                item: &self.items[index],
+            })
+            .collect()
+    }
+
+    /// Set new priorities from the TD errors of sampled items
+    pub fn update(&mut self, index: usize, td_error: f32) {
+        let priority = td_error.abs() + self.epsilon;
+        self.max_priority = self.max_priority.max(priority);
+        self.tree.set(index, priority.powf(self.alpha));
+    }
+}
+
+/// Binary tree where each node is the sum of its children,
+/// for sampling leaves in proportion to their value in log time
+#[derive(Debug, Clone)]
+struct SumTree {
+    capacity: usize,
+    /// Node 1 is the root, leaves start at `capacity`
+    nodes: Vec<f32>,
+}
+
+impl SumTree {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            nodes: vec![0.0; 2 * capacity],
+        }
+    }
+
+    fn total(&self) -> f32 {
+        self.nodes[1]
+    }
+
+    fn get(&self, index: usize) -> f32 {
+        self.nodes[index + self.capacity]
+    }
+
+    fn set(&mut self, index: usize, value: f32) {
+        let mut node = index + self.capacity;
+        self.nodes[node] = value;
+        while node > 1 {
+            node /= 2;
+            self.nodes[node] = self.nodes[2 * node] + self.nodes[2 * node + 1];
+        }
+    }
+
+    /// Index of the leaf where the running sum of values reaches `target`
+    fn find(&self, mut target: f32) -> usize {
+        let mut node = 1;
+        while node < self.capacity {
+            let left = 2 * node;
+            if target <= self.nodes[left] || self.nodes[left + 1] == 0.0 {
+                node = left;
+            } else {
+                target -= self.nodes[left];
+                node = left + 1;
+            }
+        }
+        node - self.capacity
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::PrioritisedReplay;
+
+    #[test]
+    fn prioritised_replay() {
+        let mut replay = PrioritisedReplay::new(4, 1.0, 1.0);
+        for i in 0..6 {
+            replay.push(i);
+        }
+        // Oldest items replaced
+        assert_eq!(replay.len(), 4);
+        let mut items = replay.items.clone();
+        items.sort();
+        assert_eq!(items, [2, 3, 4, 5]);
+
+        // Make a single item much more likely
+        for i in 0..4 {
+            replay.update(i, if replay.items[i] == 3 { 100.0 } else { 0.0 });
+        }
+        let mut rng = SmallRng::seed_from_u64(0);
+        let samples = replay.sample(100, &mut rng);
+        let threes = samples.iter().filter(|s| *s.item == 3).count();
+        assert!(threes > 95);
+        // Frequently sampled items have smaller weights
+        let three = samples.iter().find(|s| *s.item == 3).unwrap();
+        assert!(samples.iter().all(|s| s.weight >= three.weight));
+        assert!(samples.iter().all(|s| s.weight <= 1.0));
+    }
+}
@@ -0,0 +1,241 @@
+//! Tuning [HeuristicEvaluator] weights with SPSA
+//!
+//! Each step plays the weights nudged in a random direction against the weights nudged
+//! the opposite way on the same seeds, then moves along that direction in proportion
+//! to the score difference. Every few steps the tuned weights play the best set so far
+//! and replace it only if they score better, so noisy steps can't make the kept weights worse
+
+use std::path::Path;
+
+use burn::config::Config;
+use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
+
+use crate::{
+    error::AzulError,
+    players::{
+        minimax::{HeuristicEvaluator, Minimaxer, HEURISTIC_WEIGHTS},
+        Player,
+    },
+    runner::Runner,
+};
+
+#[derive(Config, Debug)]
+pub struct TunerConfig {
+    /// SPSA steps to take
+    #[config(default = 200)]
+    pub iterations: u32,
+    /// Game pairs played for each step
+    #[config(default = 10)]
+    pub pairs: u32,
+    /// Steps between matches against the best weights
+    #[config(default = 10)]
+    pub check_every: u32,
+    /// Game pairs played against the best weights
+    #[config(default = 50)]
+    pub check_pairs: u32,
+    /// Size of the first step for a one point score difference
+    #[config(default = 0.05)]
+    pub step: f32,
+    /// Size of the first perturbation
+    #[config(default = 0.2)]
+    pub perturbation: f32,
+    /// Search depth of the players
+    #[config(default = 1)]
+    pub depth: u8,
+    /// Seed for the steps and for the games against the best weights
+    #[config(default = 0)]
+    pub seed: u64,
+}
+
+/// Weights kept by the tuner, saved as json after every improvement
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct WeightSets {
+    /// Every accepted set, best last
+    pub sets: Vec<TunedWeights>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TunedWeights {
+    /// Step the weights were accepted at
+    pub iteration: u32,
+    /// Average score against the previous best
+    pub score: f64,
+    pub weights: HeuristicEvaluator,
+}
+
+impl WeightSets {
+    pub fn load(path: &Path) -> Result<Self, AzulError> {
+        let file = std::fs::File::open(path).map_err(|e| AzulError::load(path, e))?;
+        serde_json::from_reader(file).map_err(|e| AzulError::load(path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), AzulError> {
+        let file = std::fs::File::create(path).map_err(|e| AzulError::save(path, e))?;
+        serde_json::to_writer_pretty(file, self).map_err(|e| AzulError::save(path, e))
+    }
+
+    /// Most recently accepted weights
+    pub fn best(&self) -> Option<&HeuristicEvaluator> {
+        self.sets.last().map(|s| &s.weights)
+    }
+}
+
+pub struct Tuner {
+    config: TunerConfig,
+    /// Weights being tuned
+    theta: [f32; HEURISTIC_WEIGHTS],
+    /// Weights to beat
+    best: HeuristicEvaluator,
+    sets: WeightSets,
+    iteration: u32,
+    rng: SmallRng,
+}
+
+impl Tuner {
+    /// Start tuning from the best of `sets`, or the default weights if it is empty
+    pub fn new(config: TunerConfig, sets: WeightSets) -> Self {
+        let best = sets.best().cloned().unwrap_or_default();
+        Self {
+            theta: best.weights(),
+            best,
+            iteration: sets.sets.last().map_or(0, |s| s.iteration),
+            sets,
+            rng: SmallRng::seed_from_u64(config.seed),
+            config,
+        }
+    }
+
+    pub fn best(&self) -> &HeuristicEvaluator {
+        &self.best
+    }
+
+    pub fn weight_sets(&self) -> &WeightSets {
+        &self.sets
+    }
+
+    pub fn iteration(&self) -> u32 {
+        self.iteration
+    }
+
+    /// Take one SPSA step, returning the score of the positive perturbation against the negative
+    pub fn step(&mut self) -> f64 {
+        // Gains from Spall's guidelines, with the step decay offset by a tenth of the run
+        let k = self.iteration as f32 + 1.0;
+        let a = self.config.step / (k + self.config.iterations as f32 / 10.0).powf(0.602);
+        let c = self.config.perturbation / k.powf(0.101);
+        let delta: [f32; HEURISTIC_WEIGHTS] =
+            std::array::from_fn(|_| if self.rng.gen() { 1.0 } else { -1.0 });
+        let plus = std::array::from_fn(|i| self.theta[i] + c * delta[i]);
+        let minus = std::array::from_fn(|i| self.theta[i] - c * delta[i]);
+
+        let seed = self.rng.next_u64();
+        let score = self.play(&plus, &minus, self.config.pairs, seed);
+        for (theta, d) in self.theta.iter_mut().zip(delta) {
+            *theta += a * score as f32 / (2.0 * c * d);
+        }
+        self.iteration += 1;
+        score
+    }
+
+    /// Play the tuned weights against the best, keeping them if they score better.
+    /// Returns the average score and whether they were accepted
+    pub fn check(&mut self) -> (f64, bool) {
+        let best = self.best.weights();
+        let score = self.play(
+            &self.theta,
+            &best,
+            self.config.check_pairs,
+            self.config.seed,
+        );
+        let accepted = score > 0.0;
+        if accepted {
+            self.best = HeuristicEvaluator::from_weights(&self.theta);
+            self.sets.sets.push(TunedWeights {
+                iteration: self.iteration,
+                score,
+                weights: self.best.clone(),
+            });
+        } else {
+            // Carry on from the best weights rather than drifting further
+            self.theta = best;
+        }
+        (score, accepted)
+    }
+
+    /// Run every step, checking against the best weights as configured.
+    /// `accepted` is called with the weight sets after every improvement, eg to save them
+    pub fn run(
+        &mut self,
+        mut accepted: impl FnMut(&Self) -> Result<(), AzulError>,
+    ) -> Result<(), AzulError> {
+        for _ in 0..self.config.iterations {
+            self.step();
+            if self
+                .iteration
+                .is_multiple_of(self.config.check_every.max(1))
+                && self.check().1
+            {
+                accepted(self)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Average score of `a` against `b` over game pairs
+    fn play(
+        &self,
+        a: &[f32; HEURISTIC_WEIGHTS],
+        b: &[f32; HEURISTIC_WEIGHTS],
+        pairs: u32,
+        seed: u64,
+    ) -> f64 {
+        let players = [self.player(a), self.player(b)];
+        Runner::new_2_player(players, Some(seed))
+            .run_matchup(pairs)
+            .average_score()
+    }
+
+    fn player(&self, weights: &[f32; HEURISTIC_WEIGHTS]) -> Box<dyn Player<2, 6>> {
+        Box::new(Minimaxer::new(
+            minimaxer::negamax::SearchOptions {
+                max_depth: Some(self.config.depth),
+                alpha_beta: true,
+                ..Default::default()
+            },
+            "Heuristic",
+            HeuristicEvaluator::from_weights(weights),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Tuner, TunerConfig, WeightSets};
+    use crate::players::minimax::HeuristicEvaluator;
+
+    #[test]
+    fn tune() {
+        let config = TunerConfig::new()
+            .with_iterations(4)
+            .with_pairs(2)
+            .with_check_every(2)
+            .with_check_pairs(2);
+        let mut tuner = Tuner::new(config, WeightSets::default());
+        let mut saved = 0;
+        tuner
+            .run(|t| {
+                saved += 1;
+                assert_eq!(t.weight_sets().best(), Some(t.best()));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(tuner.iteration(), 4);
+        assert_eq!(tuner.weight_sets().sets.len(), saved);
+
+        let weights = HeuristicEvaluator::default();
+        assert_eq!(
+            HeuristicEvaluator::from_weights(&weights.weights()),
+            weights
+        );
+    }
+}
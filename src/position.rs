@@ -0,0 +1,285 @@
+//! Positions written on one line, for sharing in bug reports and test fixtures
+//!
+//! Space separated fields, like FEN in chess:
+//! ```text
+//! 2 1 -/BYRK/BBYY/YKWW/BBYR - 10,12,10,14,14 0,0,3,0,2 2:-/B/R/BB/KK:B..../W..../...../...../.....:1 0:-/B/KK/YYY/W:..R../...../...../...../.....:RRR
+//! ```
+//! 1. Round, followed by `!` once the game is over
+//! 2. Player to move
+//! 3. Tiles in each factory, separated by `/`, with `-` for an empty factory
+//! 4. Tiles in the centre, starting with `1` while the first player tile is there
+//! 5. Number of tiles of each colour in the bag
+//! 6. The same for the lid, or `-` when it's empty
+//! 7. A field for each board: score, pattern lines, wall and floor separated by `:`.
+//!    The floor starts with `1` if it holds the first player tile
+//! 8. The [Variant] when it isn't standard
+//!
+//! Handicaps, the rng and the history of the game are left out
+
+use rand::{Rng, SeedableRng};
+
+use crate::{
+    error::{AzulError, ParseKind, PositionError},
+    gamestate::Gamestate,
+    playerboard::{wall::Wall, PlayerBoard, RowIndex},
+    tiles::{Tile, TileGroup},
+    variant::Variant,
+};
+
+impl<const P: usize, const F: usize, R: Rng + Clone, const C: usize> Gamestate<P, F, R, C> {
+    /// The position on one line, see [crate::position]
+    pub fn to_string_position(&self) -> String {
+        let mut fields = vec![
+            format!(
+                "{}{}",
+                self.round(),
+                if self.state() == crate::gamestate::State::GameEnd {
+                    "!"
+                } else {
+                    ""
+                }
+            ),
+            self.current_player().to_string(),
+            self.factories()[1..]
+                .iter()
+                .map(|f| f.unwrap_or_default().to_string())
+                .collect::<Vec<_>>()
+                .join("/"),
+            with_marker(self.first_player_tile(), &self.centre()),
+            counts(self.tilebag()),
+            if self.lid().total() == 0 {
+                "-".to_string()
+            } else {
+                counts(self.lid())
+            },
+        ];
+        fields.extend(self.boards().iter().map(board_field));
+        if !self.variant().is_standard() {
+            fields.push(self.variant().to_string());
+        }
+        fields.join(" ")
+    }
+}
+
+impl<const P: usize, const F: usize, R: Rng + SeedableRng + Clone, const C: usize>
+    Gamestate<P, F, R, C>
+{
+    /// Read a position from [Gamestate::to_string_position].
+    /// Future deals come from a seed of 0, see [Gamestate::reseed]
+    pub fn from_position_str(s: &str) -> Result<Self, AzulError> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 6 + P && fields.len() != 7 + P {
+            return Err(PositionError::FieldCount {
+                expected: 6 + P,
+                found: fields.len(),
+            }
+            .into());
+        }
+        let variant: Variant<C> = match fields.get(6 + P) {
+            Some(variant) => variant.parse()?,
+            None => Variant::standard(),
+        };
+        let (round, game_over) = match fields[0].strip_suffix('!') {
+            Some(round) => (round, true),
+            None => (fields[0], false),
+        };
+        let mut builder = Self::builder()
+            .with_variant(variant)
+            .with_round(
+                round
+                    .parse()
+                    .map_err(|_| AzulError::parse(ParseKind::Round, round))?,
+            )
+            .with_game_over(game_over)
+            .with_current_player(
+                fields[1]
+                    .parse()
+                    .map_err(|_| AzulError::parse(ParseKind::Player, fields[1]))?,
+            );
+
+        let factories = fields[2].split('/').collect::<Vec<_>>();
+        if factories.len() != F - 1 {
+            return Err(PositionError::FactoryCount {
+                expected: F - 1,
+                found: factories.len(),
+            }
+            .into());
+        }
+        for (i, factory) in factories.into_iter().enumerate() {
+            builder = builder.with_factory(i + 1, factory.parse()?);
+        }
+        let (in_centre, centre) = without_marker(fields[3])?;
+        builder = builder
+            .with_centre(centre)
+            .with_bag(parse_counts(fields[4])?);
+        if fields[5] != "-" {
+            builder = builder.with_lid(parse_counts(fields[5])?);
+        }
+
+        let mut first_player = in_centre.then_some(None);
+        for (player, field) in fields[6..6 + P].iter().enumerate() {
+            let parts = field.split(':').collect::<Vec<_>>();
+            let [score, rows, wall_text, floor] = parts[..] else {
+                return Err(PositionError::BoardFields(player).into());
+            };
+            let rows = rows.split('/').collect::<Vec<_>>();
+            if rows.len() != C {
+                return Err(PositionError::RowCount {
+                    expected: C,
+                    found: rows.len(),
+                }
+                .into());
+            }
+            for (row, text) in RowIndex::rows(C).zip(rows) {
+                if text == "-" {
+                    continue;
+                }
+                let tiles: TileGroup<C> = text.parse()?;
+                let tile = Tile::colours(C)
+                    .find(|&t| tiles.get_count(t) == tiles.total())
+                    .ok_or(PositionError::MixedRow { player, row })?;
+                builder = builder.with_row(player, row, tile, tiles.total());
+            }
+            let mut wall = Wall::with_placement(variant.wall, variant.placement);
+            wall.place_tiles_text(wall_text)?;
+            let (on_floor, floor) = without_marker(floor)?;
+            if on_floor {
+                if first_player.is_some() {
+                    return Err(PositionError::FirstPlayerTiles.into());
+                }
+                first_player = Some(Some(player as u8));
+            }
+            builder = builder
+                .with_wall(player, wall)
+                .with_floor(player, floor)
+                .with_score(
+                    player,
+                    score
+                        .parse()
+                        .map_err(|_| AzulError::parse(ParseKind::Score, score))?,
+                );
+        }
+        builder
+            .with_first_player_tile(first_player.flatten())
+            .build()
+    }
+}
+
+/// Score, pattern lines, wall and floor
+fn board_field<const C: usize>(board: &PlayerBoard<C>) -> String {
+    let rows = board
+        .rows
+        .iter()
+        .map(|row| match row.tile() {
+            Some(tile) => tile.letter().to_string().repeat(row.count() as usize),
+            None => "-".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    format!(
+        "{}:{rows}:{}:{}",
+        board.score,
+        board.wall.tiles_text(),
+        board.floor
+    )
+}
+
+/// Tiles with `1` in front for the first player tile
+fn with_marker<const C: usize>(first_player_tile: bool, tiles: &TileGroup<C>) -> String {
+    match (first_player_tile, tiles.total()) {
+        (true, 0) => "1".to_string(),
+        (true, _) => format!("1{tiles}"),
+        (false, _) => tiles.to_string(),
+    }
+}
+
+fn without_marker<const C: usize>(text: &str) -> Result<(bool, TileGroup<C>), AzulError> {
+    match text.strip_prefix('1') {
+        Some("") => Ok((true, TileGroup::new_empty())),
+        Some(tiles) => Ok((true, tiles.parse()?)),
+        None => Ok((false, text.parse()?)),
+    }
+}
+
+fn counts<const C: usize>(tiles: &TileGroup<C>) -> String {
+    tiles
+        .counts()
+        .iter()
+        .map(u8::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn parse_counts<const C: usize>(text: &str) -> Result<TileGroup<C>, AzulError> {
+    let counts = text
+        .split(',')
+        .map(|c| {
+            c.parse::<u8>()
+                .map_err(|_| AzulError::parse(ParseKind::Count, c))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if counts.len() != C {
+        return Err(PositionError::ColourCount {
+            expected: C,
+            found: counts.len(),
+        }
+        .into());
+    }
+    let mut tiles = TileGroup::new_empty();
+    for (tile, count) in Tile::colours(C).zip(counts) {
+        tiles.add_tiles(tile, count);
+    }
+    Ok(tiles)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::{AzulError, PositionError},
+        gamestate::{Gamestate, State},
+        players::{MoveRankPlayer2, Player},
+    };
+
+    #[test]
+    fn position_string() {
+        let mut gs = Gamestate::new_2_player_with_seed(12, 1);
+        let mut player = MoveRankPlayer2;
+        while gs.state() != State::GameEnd {
+            let text = gs.to_string_position();
+            assert_eq!(text.lines().count(), 1);
+            let loaded = Gamestate::<2, 6>::from_position_str(&text).unwrap();
+            assert_eq!(loaded.to_string_position(), text);
+            // Same position, apart from the rng
+            assert_eq!(loaded.boards(), gs.boards());
+            assert_eq!(loaded.get_moves(), gs.get_moves());
+            assert_eq!(loaded.tilebag(), gs.tilebag());
+
+            let m = player.pick_move(&gs, gs.get_moves());
+            if gs.play_move(m) == State::RoundEnd {
+                gs.end_round();
+            }
+        }
+        let text = gs.to_string_position();
+        assert!(text.starts_with(&format!("{}!", gs.round())));
+        assert_eq!(
+            Gamestate::<2, 6>::from_position_str(&text).unwrap().state(),
+            State::GameEnd
+        );
+
+        assert_eq!(
+            Gamestate::<2, 6>::from_position_str("1 0"),
+            Err(PositionError::FieldCount {
+                expected: 8,
+                found: 2
+            }
+            .into())
+        );
+        let mut fields = text.split(' ').collect::<Vec<_>>();
+        // The bag has a tile too many
+        fields[4] = "20,20,20,20,20";
+        assert!(matches!(
+            Gamestate::<2, 6>::from_position_str(&fields.join(" ")),
+            Err(AzulError::Position(PositionError::TileTotal { .. }))
+        ));
+    }
+}
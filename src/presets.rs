@@ -0,0 +1,126 @@
+//! Named difficulty levels
+//!
+//! Every front end creates computer opponents from a [Difficulty],
+//! so a level plays the same wherever it is chosen and is tuned in one place
+
+use std::{fmt, time::Duration};
+
+use minimaxer::negamax::SearchOptions;
+
+use crate::{
+    error::{AzulError, ParseKind},
+    players::{
+        minimax::{Minimaxer, ScoreEvaluator},
+        MoveRankPlayer2, Player, ThrottledPlayer,
+    },
+};
+
+/// Difficulty of a computer opponent, from weakest to strongest
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    strum::EnumIter,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    /// Heuristic that plays a random move half the time
+    Beginner,
+    /// Heuristic that plays a random move one move in five
+    Easy,
+    /// Best immediate score gain
+    Medium,
+    /// Alpha-beta search 2 moves ahead
+    Hard,
+    /// Iterative deepening search for a second a move
+    #[default]
+    Expert,
+}
+
+impl Difficulty {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "beginner",
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Expert => "expert",
+        }
+    }
+
+    /// Create an opponent playing at this level
+    pub fn player(&self) -> Box<dyn Player<2, 6>> {
+        match self {
+            Difficulty::Beginner => Box::new(ThrottledPlayer::new(MoveRankPlayer2, 0.5)),
+            Difficulty::Easy => Box::new(ThrottledPlayer::new(MoveRankPlayer2, 0.2)),
+            Difficulty::Medium => Box::new(MoveRankPlayer2),
+            Difficulty::Hard => Box::new(Minimaxer::new(
+                SearchOptions {
+                    max_depth: Some(2),
+                    alpha_beta: true,
+                    ..Default::default()
+                },
+                "Hard",
+                ScoreEvaluator,
+            )),
+            Difficulty::Expert => Box::new(Minimaxer::new(
+                SearchOptions {
+                    alpha_beta: true,
+                    max_time: Some(Duration::from_secs(1)),
+                    iterative: true,
+                    ..Default::default()
+                },
+                "Expert",
+                ScoreEvaluator,
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = AzulError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use strum::IntoEnumIterator;
+        Self::iter()
+            .find(|d| d.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| AzulError::parse(ParseKind::Difficulty, s))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use strum::IntoEnumIterator;
+
+    use super::Difficulty;
+    use crate::runner::Runner;
+
+    #[test]
+    fn levels() {
+        for d in Difficulty::iter() {
+            assert_eq!(d.name().parse::<Difficulty>(), Ok(d));
+        }
+        assert!("impossible".parse::<Difficulty>().is_err());
+
+        let result = Runner::new_2_player(
+            [Difficulty::Medium.player(), Difficulty::Beginner.player()],
+            Some(1),
+        )
+        .run_matchup(10);
+        assert!(result.winner_count.player0 > result.winner_count.player1);
+    }
+}
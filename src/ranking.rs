@@ -0,0 +1,117 @@
+//! Ranking every legal move of a position
+//!
+//! [rank_moves] scores all the moves for the player to move, best first, so hints,
+//! annotations and players share one ranking rather than each working out their own
+
+use crate::{
+    analysis::{self, Limit},
+    gamestate::{Gamestate, Move, MoveDetailed, State},
+};
+
+/// How moves are scored by [rank_moves]
+#[derive(Clone, Copy)]
+pub enum RankingStrategy<'a> {
+    /// Change in predicted score, see [heuristic_score]
+    Heuristic,
+    /// Predicted score difference after an alpha-beta search, see [analysis::analyse]
+    Search(Limit),
+    /// Probability given to the move by a policy network
+    Policy(&'a dyn MovePolicy),
+}
+
+/// Policy that gives each legal move a probability of being played
+pub trait MovePolicy {
+    fn move_probabilities(&self, gs: &Gamestate<2, 6>, moves: &[Move]) -> Vec<f32>;
+}
+
+/// Move with its score for the player making it, higher is better
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankedMove {
+    pub m: Move,
+    pub score: f32,
+}
+
+/// Every legal move with its score, best first.
+/// Moves that score the same keep their move generation order
+pub fn rank_moves(gs: &Gamestate<2, 6>, strategy: RankingStrategy) -> Vec<RankedMove> {
+    if gs.state() != State::RoundActive {
+        return Vec::new();
+    }
+    let moves = gs.get_moves();
+    let scores = match strategy {
+        RankingStrategy::Heuristic => moves.iter().map(|m| heuristic_score(gs, m)).collect(),
+        RankingStrategy::Search(limit) => {
+            // Analysis is from player 0's point of view
+            let sign = if gs.current_player() == 0 { 1.0 } else { -1.0 };
+            let lines = analysis::analyse(gs, limit).lines;
+            moves
+                .iter()
+                .map(|m| {
+                    lines
+                        .iter()
+                        .find(|l| l.pv[0] == *m)
+                        .map_or(f32::NEG_INFINITY, |l| sign * l.eval)
+                })
+                .collect()
+        }
+        RankingStrategy::Policy(policy) => policy.move_probabilities(gs, &moves),
+    };
+    let mut ranked = moves
+        .into_iter()
+        .zip(scores)
+        .map(|(m, score)| RankedMove { m, score })
+        .collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked
+}
+
+/// Change in the mover's predicted score, breaking ties in favour of taking the
+/// first player tile and then of rows over the floor
+pub fn heuristic_score<const P: usize, const F: usize>(gs: &Gamestate<P, F>, m: &Move) -> f32 {
+    detailed_score(&gs.detail_move(*m))
+}
+
+/// [heuristic_score] of a move from [Gamestate::get_moves_detailed]
+pub fn detailed_score(m: &MoveDetailed) -> f32 {
+    let mut score = m.placed.score_delta as f32;
+    // Both tie breaks are less than a point, so never outweigh the score
+    if m.fp {
+        score += 0.5;
+    }
+    if m.row.is_some() {
+        score += 0.25;
+    }
+    score
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rank_moves, RankingStrategy};
+    use crate::{
+        analysis::Limit,
+        gamestate::Gamestate,
+        players::{MoveRankPlayer2, Player},
+    };
+
+    #[test]
+    fn rankings() {
+        let mut gs = Gamestate::new_2_player_with_seed(5, 0);
+        gs.play_move(gs.get_moves()[3]);
+        let moves = gs.get_moves();
+
+        let heuristic = rank_moves(&gs, RankingStrategy::Heuristic);
+        assert_eq!(heuristic.len(), moves.len());
+        assert!(heuristic.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(
+            heuristic[0].m,
+            MoveRankPlayer2.pick_move(&gs, moves.clone())
+        );
+
+        // The best searched move is the analysis' best, scored for player 1
+        let search = rank_moves(&gs, RankingStrategy::Search(Limit::Depth(2)));
+        let best = crate::analysis::analyse(&gs, Limit::Depth(2));
+        let best = best.best().unwrap();
+        assert_eq!(search[0].m, best.pv[0]);
+        assert_eq!(search[0].score, -best.eval);
+    }
+}
@@ -0,0 +1,183 @@
+//! Replaying games from their seed and moves
+//!
+//! Every deal in a game follows from its seed, so a [Replay] of the seed, the rules and
+//! the moves played rebuilds the exact [Gamestate] at any ply. Replays are saved as json,
+//! by the GUI and by a [Runner](crate::runner::Runner) with
+//! [Runner::with_replays](crate::runner::Runner::with_replays)
+
+use std::path::Path;
+
+use crate::{
+    error::{AzulError, ReplayError},
+    gamestate::{Gamestate, Move, State},
+    opening::StartPosition,
+    playerboard::Handicap,
+    variant::Variant,
+};
+
+/// Seed, rules and moves of a 2 player game
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub first_player: u8,
+    #[serde(default)]
+    pub variant: Variant,
+    #[serde(default)]
+    pub handicaps: [Handicap; 2],
+    /// Moves in the order they were played, as from [Move::to_index]
+    pub moves: Vec<usize>,
+}
+
+impl Replay {
+    /// Replay of a standard game with no moves yet
+    pub fn new(seed: u64, first_player: u8) -> Self {
+        Self {
+            seed,
+            first_player,
+            variant: Variant::standard(),
+            handicaps: Default::default(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Replay of `gs`, which must have been started from [Replay::start] or with
+    /// the same seed, first player and rules, and recorded its moves from the start
+    pub fn from_game(seed: u64, first_player: u8, gs: &Gamestate<2, 6>) -> Self {
+        Self {
+            seed,
+            first_player,
+            variant: *gs.variant(),
+            handicaps: gs.handicaps(),
+            moves: gs.move_history().iter().map(|p| p.m.to_index()).collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, AzulError> {
+        let file = std::fs::File::open(path).map_err(|e| AzulError::load(path, e))?;
+        serde_json::from_reader(file).map_err(|e| AzulError::load(path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), AzulError> {
+        let file = std::fs::File::create(path).map_err(|e| AzulError::save(path, e))?;
+        serde_json::to_writer_pretty(file, self).map_err(|e| AzulError::save(path, e))
+    }
+
+    /// Number of moves
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    pub fn push(&mut self, m: Move) {
+        self.moves.push(m.to_index());
+    }
+
+    /// The game before any moves, recording the moves played from here on.
+    /// Fails if the variant can't be played by two players
+    pub fn start(&self) -> Result<Gamestate<2, 6>, AzulError> {
+        let mut gs = Gamestate::new_2_player_with_seed(self.seed, self.first_player);
+        gs.set_variant(self.variant)?;
+        gs.set_handicaps(self.handicaps);
+        gs.record_moves(true);
+        Ok(gs)
+    }
+
+    /// The game after the first `ply` moves, with the round ended if they finished it.
+    /// Fails if `ply` is past the end or a move isn't legal
+    pub fn position(&self, ply: usize) -> Result<Gamestate<2, 6>, AzulError> {
+        let moves = self
+            .moves
+            .get(..ply)
+            .ok_or(ReplayError::TooShort(self.len()))?;
+        let mut gs = self.start()?;
+        for (i, &index) in moves.iter().enumerate() {
+            play_index(&mut gs, index).ok_or(ReplayError::IllegalIndex { ply: i + 1, index })?;
+        }
+        Ok(gs)
+    }
+
+    /// The game before each move, followed by the position after the last
+    pub fn positions(&self) -> Result<Vec<Gamestate<2, 6>>, AzulError> {
+        let mut gs = self.start()?;
+        let mut positions = Vec::with_capacity(self.len() + 1);
+        for (i, &index) in self.moves.iter().enumerate() {
+            positions.push(gs.clone());
+            play_index(&mut gs, index).ok_or(ReplayError::IllegalIndex { ply: i + 1, index })?;
+        }
+        positions.push(gs);
+        Ok(positions)
+    }
+
+    /// Start position for a [Runner](crate::runner::Runner) after the first `ply` moves,
+    /// see [Runner::with_start_positions](crate::runner::Runner::with_start_positions).
+    /// Only the seed and moves are kept, the runner's rules and first players are used
+    pub fn start_position(&self, ply: usize) -> StartPosition {
+        StartPosition {
+            seed: self.seed,
+            moves: self.moves[..ply.min(self.len())].to_vec(),
+            eval: 0.0,
+        }
+    }
+}
+
+/// Play the move with `index` and end the round if it finishes it.
+/// None if the move isn't legal
+fn play_index(gs: &mut Gamestate<2, 6>, index: usize) -> Option<()> {
+    if gs.state() != State::RoundActive {
+        return None;
+    }
+    let m = gs.get_moves().into_iter().find(|m| m.to_index() == index)?;
+    if gs.play_move(m) == State::RoundEnd {
+        gs.end_round();
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Replay;
+    use crate::{
+        error::ReplayError,
+        gamestate::State,
+        players::{MoveRankPlayer2, Player},
+    };
+
+    #[test]
+    fn replay() {
+        let mut replay = Replay::new(9, 1);
+        let mut gs = replay.start().unwrap();
+        let mut player = MoveRankPlayer2;
+        let mut positions = vec![gs.clone()];
+        while gs.state() != State::GameEnd {
+            let m = player.pick_move(&gs, gs.get_moves());
+            replay.push(m);
+            if gs.play_move(m) == State::RoundEnd {
+                gs.end_round();
+            }
+            positions.push(gs.clone());
+        }
+        assert_eq!(Replay::from_game(9, 1, &gs), replay);
+        assert_eq!(replay.positions().unwrap(), positions);
+        assert_eq!(replay.position(10).unwrap(), positions[10]);
+        assert_eq!(replay.position(replay.len()).unwrap().scores(), gs.scores());
+        assert_eq!(
+            replay.position(replay.len() + 1),
+            Err(ReplayError::TooShort(replay.len()).into())
+        );
+
+        let json = serde_json::to_string(&replay).unwrap();
+        assert_eq!(serde_json::from_str::<Replay>(&json).unwrap(), replay);
+        replay.moves[3] = usize::MAX;
+        assert_eq!(
+            replay.positions(),
+            Err(ReplayError::IllegalIndex {
+                ply: 4,
+                index: usize::MAX
+            }
+            .into())
+        );
+    }
+}
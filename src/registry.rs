@@ -0,0 +1,323 @@
+//! Named player configurations
+//!
+//! Front ends create players by name through a [Registry], so a name plays the same
+//! wherever it is used. Names are the built in [ENGINES], the [Difficulty] levels,
+//! `heuristic:<file>` for weights saved by tuning, `value:<file>` for a PPO value network,
+//! or entries of a registry file:
+//! ```toml
+//! [players.deep]
+//! engine = "minimax"
+//! depth = 4
+//! elo = 1650
+//!
+//! [players.tuned]
+//! engine = "heuristic"
+//! model = "weights.json"
+//! time = "500ms"
+//! description = "Weights from the long tuning run"
+//! ```
+//! Relative model paths are relative to the registry file
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use minimaxer::negamax::SearchOptions;
+
+use crate::{
+    error::{AzulError, ParseKind},
+    players::{
+        beam::BeamPlayer,
+        flat_mc::FlatMcPlayer,
+        minimax::{HeuristicEvaluator, Minimaxer, ScoreEvaluator, SearchConfig},
+        ppo::{ValueConfig, ValueEvaluator},
+        tuning::WeightSets,
+        FirstMovePlayer, MoveRankPlayer, MoveRankPlayer2, Player, RandomPlayer,
+    },
+    presets::Difficulty,
+};
+
+/// Engines that need no other settings, as well as `value` which needs a model
+pub const ENGINES: [&str; 9] = [
+    "minimax",
+    "adaptive",
+    "heuristic",
+    "flatmc",
+    "beam",
+    "rank",
+    "rank2",
+    "first",
+    "random",
+];
+
+/// Environment variable naming the registry file
+pub const REGISTRY_ENV: &str = "AZUL_REGISTRY";
+
+/// Registry file used when [REGISTRY_ENV] is not set, if it exists
+pub const DEFAULT_REGISTRY: &str = "players.toml";
+
+/// Rollouts per move for `flatmc` without a width
+const FLAT_MC_ROLLOUTS: usize = 16;
+
+/// Positions kept at each ply by `beam` without a width
+const BEAM_WIDTH: usize = 8;
+
+/// Hidden layer size of the value networks trained by the `ppo` binary
+const VALUE_HIDDEN: usize = 320;
+
+/// How to build a player
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlayerConfig {
+    /// Built in engine or difficulty, see [ENGINES]
+    pub engine: String,
+    /// Time per move for searching engines, eg `500ms`, otherwise the caller's time
+    pub time: Option<String>,
+    /// Search to a fixed depth rather than for a time, for `minimax`, `heuristic`
+    /// and `adaptive`, or the most plies searched by `beam` and `value`
+    pub depth: Option<u8>,
+    /// Positions kept at each ply by `beam` and `value`, or rollouts per move for `flatmc`
+    pub width: Option<usize>,
+    /// Weights for `heuristic` or the value network for `value`
+    pub model: Option<PathBuf>,
+    /// Seed for `random` and `flatmc`, which otherwise play differently every run
+    pub seed: Option<u64>,
+    /// Expected rating, eg to start a ladder from
+    pub elo: Option<f64>,
+    pub description: Option<String>,
+}
+
+impl PlayerConfig {
+    /// Configuration of a built in name, with any `:<file>` suffix as the model
+    pub fn from_name(name: &str) -> Self {
+        let (engine, model) = match name.split_once(':') {
+            Some((engine, model)) => (engine, Some(PathBuf::from(model))),
+            None => (name, None),
+        };
+        Self {
+            engine: engine.to_string(),
+            model,
+            ..Default::default()
+        }
+    }
+
+    /// Build the player, searching for `time` a move unless configured otherwise
+    pub fn player(&self, time: Duration) -> Result<Box<dyn Player<2, 6>>, AzulError> {
+        let time = match &self.time {
+            Some(time) => parse_duration(time)?,
+            None => time,
+        };
+        Ok(match self.engine.as_str() {
+            "minimax" => Box::new(self.search(ScoreEvaluator, "Minimaxer", time)),
+            "adaptive" => Box::new(
+                self.search(ScoreEvaluator, "Adaptive", time)
+                    .with_phases(SearchConfig::new()),
+            ),
+            "heuristic" => {
+                let weights = match &self.model {
+                    Some(path) => {
+                        let sets = WeightSets::load(path)?;
+                        sets.best()
+                            .cloned()
+                            .ok_or_else(|| AzulError::load(path, "no weights"))?
+                    }
+                    None => HeuristicEvaluator::default(),
+                };
+                Box::new(self.search(weights, "Heuristic", time))
+            }
+            "flatmc" => {
+                let player = FlatMcPlayer::new(self.width.unwrap_or(FLAT_MC_ROLLOUTS) as u32);
+                match self.seed {
+                    Some(seed) => Box::new(player.with_seed(seed)),
+                    None => Box::new(player),
+                }
+            }
+            "beam" => Box::new(self.beam(ScoreEvaluator)),
+            "value" => {
+                let path = self
+                    .model
+                    .as_ref()
+                    .ok_or_else(|| AzulError::MissingModel(self.engine.clone()))?;
+                let evaluator = ValueEvaluator::<burn::backend::NdArray>::from_file(
+                    ValueConfig::new(150, VALUE_HIDDEN),
+                    path,
+                    &Default::default(),
+                )?;
+                Box::new(self.beam(evaluator))
+            }
+            "rank" => Box::new(MoveRankPlayer),
+            "rank2" => Box::new(MoveRankPlayer2),
+            "first" => Box::new(FirstMovePlayer),
+            "random" => Box::new(
+                self.seed
+                    .map_or_else(RandomPlayer::new, RandomPlayer::with_seed),
+            ),
+            other => match other.parse::<Difficulty>() {
+                Ok(difficulty) => difficulty.player(),
+                Err(_) => return Err(AzulError::UnknownEngine(other.to_string())),
+            },
+        })
+    }
+
+    /// Alpha-beta search to the configured depth, or iterative deepening for `time`
+    fn search<E>(&self, evaluator: E, name: &str, time: Duration) -> Minimaxer<E> {
+        let opts = match self.depth {
+            Some(depth) => SearchOptions {
+                max_depth: Some(depth),
+                alpha_beta: true,
+                ..Default::default()
+            },
+            None => SearchOptions {
+                alpha_beta: true,
+                max_time: Some(time),
+                iterative: true,
+                ..Default::default()
+            },
+        };
+        Minimaxer::new(opts, name, evaluator)
+    }
+
+    fn beam<E>(&self, evaluator: E) -> BeamPlayer<E> {
+        let beam = BeamPlayer::new(self.width.unwrap_or(BEAM_WIDTH), evaluator);
+        match self.depth {
+            Some(depth) => beam.with_max_depth(depth),
+            None => beam,
+        }
+    }
+}
+
+/// Player configurations by name, stored as toml
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Registry {
+    #[serde(default)]
+    pub players: BTreeMap<String, PlayerConfig>,
+}
+
+impl Registry {
+    pub fn load(path: &Path) -> Result<Self, AzulError> {
+        let text = std::fs::read_to_string(path).map_err(|e| AzulError::load(path, e))?;
+        let mut registry: Self = toml::from_str(&text).map_err(|e| AzulError::load(path, e))?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for (name, config) in &mut registry.players {
+            if ENGINES.contains(&name.as_str()) || name.parse::<Difficulty>().is_ok() {
+                return Err(AzulError::load(
+                    path,
+                    format!("{name} is already a built in engine"),
+                ));
+            }
+            if let Some(model) = &mut config.model {
+                *model = dir.join(&*model);
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), AzulError> {
+        let text = toml::to_string_pretty(self).map_err(|e| AzulError::save(path, e))?;
+        std::fs::write(path, text).map_err(|e| AzulError::save(path, e))
+    }
+
+    /// Load the file named by [REGISTRY_ENV], or [DEFAULT_REGISTRY] if it exists,
+    /// otherwise an empty registry with only the built in names
+    pub fn load_default() -> Result<Self, AzulError> {
+        match Self::default_path() {
+            Some(path) => Self::load(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// File [Registry::load_default] reads, if any
+    pub fn default_path() -> Option<PathBuf> {
+        match std::env::var_os(REGISTRY_ENV) {
+            Some(path) => Some(path.into()),
+            None => Some(PathBuf::from(DEFAULT_REGISTRY)).filter(|p| p.exists()),
+        }
+    }
+
+    /// Configuration of a registered or built in name
+    pub fn config(&self, name: &str) -> PlayerConfig {
+        self.players
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| PlayerConfig::from_name(name))
+    }
+
+    /// Create the player with a registered or built in name,
+    /// searching for `time` a move unless configured otherwise
+    pub fn player(&self, name: &str, time: Duration) -> Result<Box<dyn Player<2, 6>>, AzulError> {
+        self.config(name).player(time)
+    }
+
+    /// Registered names, in order
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.players.keys()
+    }
+}
+
+/// Parse durations such as `500ms`, `5s` or `2m`
+pub fn parse_duration(s: &str) -> Result<Duration, AzulError> {
+    let (value, unit) = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .map(|i| s.split_at(i))
+        .unwrap_or((s, "s"));
+    let value: f64 = value
+        .parse()
+        .map_err(|_| AzulError::parse(ParseKind::Duration, s))?;
+    let secs = match unit {
+        "ms" => value / 1000.0,
+        "s" => value,
+        "m" => value * 60.0,
+        _ => return Err(AzulError::parse(ParseKind::Duration, s)),
+    };
+    Ok(Duration::from_secs_f64(secs))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::{PlayerConfig, Registry, ENGINES};
+    use crate::error::AzulError;
+
+    #[test]
+    fn registry() {
+        let registry: Registry = toml::from_str(
+            r#"
+            [players.shallow]
+            engine = "minimax"
+            depth = 1
+            elo = 1200
+
+            [players.wide]
+            engine = "beam"
+            width = 2
+            depth = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(registry.names().collect::<Vec<_>>(), ["shallow", "wide"]);
+        assert_eq!(registry.config("shallow").elo, Some(1200.0));
+        let time = Duration::from_millis(10);
+        for name in registry.names().chain(&ENGINES.map(String::from)) {
+            registry.player(name, time).unwrap();
+        }
+        registry.player("hard", time).unwrap();
+        assert!(matches!(
+            registry.player("unknown", time),
+            Err(AzulError::UnknownEngine(name)) if name == "unknown"
+        ));
+        assert!(matches!(
+            registry.player("value", time),
+            Err(AzulError::MissingModel(_))
+        ));
+
+        assert_eq!(
+            PlayerConfig::from_name("heuristic:weights.json").model,
+            Some("weights.json".into())
+        );
+        let saved = toml::to_string(&registry).unwrap();
+        assert_eq!(toml::from_str::<Registry>(&saved).unwrap(), registry);
+    }
+}
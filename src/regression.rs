@@ -0,0 +1,230 @@
+//! Finding where an engine's behaviour changed
+//!
+//! A [Trace] records the move an engine picks and a fixed depth evaluation at every position
+//! of games played from known seeds. Tracing again with a changed engine, eg another build
+//! or config, follows the moves of the first trace so the same positions are visited,
+//! and [diff] lists every position where the move or the evaluation differs.
+//!
+//! Players that search for a time rather than to a depth can pick different moves on
+//! every run, so only trace engines with a fixed depth when looking for regressions
+
+use std::path::Path;
+
+use crate::{
+    analysis::Limit,
+    error::AzulError,
+    gamestate::{Gamestate, State},
+    players::Player,
+    ranking::{rank_moves, RankingStrategy},
+};
+
+/// Move picked at a position and its evaluation
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Decision {
+    /// Move index, as from [crate::gamestate::Move::to_index]
+    pub m: usize,
+    /// Evaluation of the position for the player to move
+    pub eval: f32,
+}
+
+/// Decisions at each position of a game, in order
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GameTrace {
+    pub seed: u64,
+    pub decisions: Vec<Decision>,
+}
+
+/// Decisions of an engine over a set of games, saved as json
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Trace {
+    /// Name of the engine traced
+    pub engine: String,
+    /// Crate version of the program that traced it
+    pub version: String,
+    /// Search depth of the evaluations
+    pub depth: u8,
+    pub games: Vec<GameTrace>,
+}
+
+impl Trace {
+    pub fn new(engine: &str, depth: u8) -> Self {
+        Self {
+            engine: engine.to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            depth,
+            games: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, AzulError> {
+        let file = std::fs::File::open(path).map_err(|e| AzulError::load(path, e))?;
+        serde_json::from_reader(file).map_err(|e| AzulError::load(path, e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), AzulError> {
+        let file = std::fs::File::create(path).map_err(|e| AzulError::save(path, e))?;
+        serde_json::to_writer_pretty(file, self).map_err(|e| AzulError::save(path, e))
+    }
+}
+
+/// Play the game dealt from `seed`, recording `player`'s move and the evaluation to `depth`
+/// at each position. With `follow`, the moves of another trace are played instead of the
+/// player's so both visit the same positions, stopping if a followed move isn't legal
+pub fn trace_game(
+    player: &mut dyn Player<2, 6>,
+    seed: u64,
+    depth: u8,
+    follow: Option<&GameTrace>,
+) -> GameTrace {
+    let mut gs = Gamestate::new_2_player_with_seed(seed, 0);
+    let mut decisions = Vec::new();
+    while gs.state() != State::GameEnd {
+        let moves = gs.get_moves();
+        let eval = rank_moves(&gs, RankingStrategy::Search(Limit::Depth(depth)))
+            .first()
+            .map_or(0.0, |r| r.score);
+        let picked = player.pick_move(&gs, moves.clone());
+        decisions.push(Decision {
+            m: picked.to_index(),
+            eval,
+        });
+        let played = match follow {
+            Some(trace) => {
+                let Some(index) = trace.decisions.get(decisions.len() - 1).map(|d| d.m) else {
+                    break;
+                };
+                let Some(&m) = moves.iter().find(|m| m.to_index() == index) else {
+                    break;
+                };
+                m
+            }
+            None => picked,
+        };
+        if gs.play_move(played) == State::RoundEnd {
+            gs.end_round();
+        }
+    }
+    GameTrace { seed, decisions }
+}
+
+/// Position where two traces differ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difference {
+    pub seed: u64,
+    /// Moves played before the position
+    pub ply: usize,
+    pub baseline: Decision,
+    pub candidate: Decision,
+}
+
+impl Difference {
+    pub fn move_changed(&self) -> bool {
+        self.baseline.m != self.candidate.m
+    }
+
+    /// Change in evaluation from the baseline
+    pub fn eval_change(&self) -> f32 {
+        self.candidate.eval - self.baseline.eval
+    }
+}
+
+/// Comparison of a candidate trace with a baseline
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceDiff {
+    /// Positions in both traces
+    pub positions: usize,
+    /// Positions where the move or evaluation differs, in order
+    pub differences: Vec<Difference>,
+    /// Games that are missing from one trace or cover different positions
+    pub mismatched: Vec<u64>,
+}
+
+impl TraceDiff {
+    pub fn moves_changed(&self) -> usize {
+        self.differences.iter().filter(|d| d.move_changed()).count()
+    }
+
+    /// Largest change in evaluation, in either direction
+    pub fn largest_eval_change(&self) -> f32 {
+        self.differences
+            .iter()
+            .map(|d| d.eval_change().abs())
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Positions where `candidate` differs from `baseline`, ignoring changes in evaluation
+/// of at most `tolerance`. Games are matched by seed
+pub fn diff(baseline: &Trace, candidate: &Trace, tolerance: f32) -> TraceDiff {
+    let mut result = TraceDiff::default();
+    for game in &baseline.games {
+        let Some(other) = candidate.games.iter().find(|g| g.seed == game.seed) else {
+            result.mismatched.push(game.seed);
+            continue;
+        };
+        if other.decisions.len() != game.decisions.len() {
+            result.mismatched.push(game.seed);
+        }
+        for (ply, (&baseline, &candidate)) in
+            game.decisions.iter().zip(&other.decisions).enumerate()
+        {
+            result.positions += 1;
+            let difference = Difference {
+                seed: game.seed,
+                ply,
+                baseline,
+                candidate,
+            };
+            if difference.move_changed() || difference.eval_change().abs() > tolerance {
+                result.differences.push(difference);
+            }
+        }
+    }
+    result.mismatched.extend(
+        candidate
+            .games
+            .iter()
+            .filter(|g| !baseline.games.iter().any(|b| b.seed == g.seed))
+            .map(|g| g.seed),
+    );
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{diff, trace_game, Trace};
+    use crate::players::{MoveRankPlayer, MoveRankPlayer2};
+
+    #[test]
+    fn trace_diff() {
+        let mut baseline = Trace::new("rank2", 1);
+        let mut same = Trace::new("rank2", 1);
+        let mut other = Trace::new("rank", 1);
+        for seed in 0..2 {
+            let game = trace_game(&mut MoveRankPlayer2, seed, 1, None);
+            same.games
+                .push(trace_game(&mut MoveRankPlayer2, seed, 1, Some(&game)));
+            other
+                .games
+                .push(trace_game(&mut MoveRankPlayer, seed, 1, Some(&game)));
+            baseline.games.push(game);
+        }
+        assert_eq!(same, baseline);
+        let unchanged = diff(&baseline, &same, 0.0);
+        assert!(unchanged.differences.is_empty() && unchanged.mismatched.is_empty());
+        assert_eq!(
+            unchanged.positions,
+            baseline
+                .games
+                .iter()
+                .map(|g| g.decisions.len())
+                .sum::<usize>()
+        );
+
+        // Following the baseline keeps the positions, so only the moves differ
+        let changed = diff(&baseline, &other, 0.0);
+        assert!(changed.mismatched.is_empty());
+        assert!(changed.moves_changed() > 0);
+        assert_eq!(changed.largest_eval_change(), 0.0);
+    }
+}
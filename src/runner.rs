@@ -3,6 +3,11 @@
 use std::{
     iter::Sum,
     ops::{Add, AddAssign},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::Sender,
+    },
+    time::{Duration, Instant},
 };
 
 use log::{debug, info};
@@ -10,8 +15,16 @@ use rand::{rngs::SmallRng, Rng, RngCore, SeedableRng};
 use rand_distr::Bernoulli;
 
 use crate::{
+    adjudication::{Adjudication, AdjudicationCount, AdjudicationReason, Adjudicator},
+    error::{AzulError, PopulationError},
     gamestate::{Gamestate, State},
+    memory,
+    observer::GameObserver,
+    opening::{Opening, StartPosition},
+    playerboard::Handicap,
     players::{EvolvingPlayer, Player},
+    record::Replay,
+    variant::Variant,
 };
 
 /// Game runner
@@ -22,6 +35,52 @@ use crate::{
 pub struct Runner<const P: usize, const F: usize> {
     players: [Box<dyn Player<P, F>>; P],
     rng: rand::prelude::SmallRng,
+    /// Where to report games as they are played, and the names to report
+    progress: Option<(Sender<Progress>, [String; 2])>,
+    /// Id of the game being played, for progress reports
+    game: u64,
+    /// Rules of 2 player games
+    variant: Variant,
+    /// Handicap of whichever player moves second in 2 player games
+    second_handicap: Handicap,
+    /// Chooses the first moves of 2 player games instead of the players
+    opening: Opening,
+    /// Positions to start 2 player game pairs from in turn, rather than new games
+    positions: Vec<StartPosition>,
+    /// Index of the next position to play
+    next_position: usize,
+    /// Resources used by each seat in the current matchup
+    usage: [ResourceUsage; P],
+    /// When to end 2 player games early
+    adjudication: Option<Adjudication>,
+    /// Replays of the 2 player games played, when keeping them
+    replays: Option<Vec<Replay>>,
+    /// Told about every event of every game
+    observers: Vec<Box<dyn GameObserver<P, F>>>,
+}
+
+/// Ids for progress reports, unique across runners in the process
+static NEXT_GAME: AtomicU64 = AtomicU64::new(0);
+
+/// Report of a game being played by a [Runner], for watching long runs
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Progress {
+    Started {
+        game: u64,
+        players: [String; 2],
+        seed: u64,
+        first_player: u8,
+    },
+    /// Move played, as from [crate::gamestate::Move::to_index]
+    Move { game: u64, index: usize },
+    Finished {
+        game: u64,
+        scores: [u8; 2],
+        /// Why the game was ended early, if it was
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        adjudication: Option<AdjudicationReason>,
+    },
 }
 
 impl Runner<2, 6> {
@@ -30,12 +89,122 @@ impl Runner<2, 6> {
         Self {
             players,
             rng: SmallRng::seed_from_u64(seed.unwrap_or(rand::thread_rng().next_u64())),
+            progress: None,
+            game: 0,
+            variant: Variant::standard(),
+            second_handicap: Handicap::default(),
+            opening: Opening::default(),
+            positions: Vec::new(),
+            next_position: 0,
+            usage: [ResourceUsage::default(); 2],
+            adjudication: None,
+            replays: None,
+            observers: Vec::new(),
+        }
+    }
+
+    /// Send every game's moves and result to `sender` as they are played
+    ///
+    /// Players are reported as `names` rather than [Player::name],
+    /// which is often the same for differently configured players
+    pub fn with_progress(mut self, sender: Sender<Progress>, names: [String; 2]) -> Self {
+        self.progress = Some((sender, names));
+        self
+    }
+
+    /// Play every game with the rules of `variant`.
+    /// Fails if two players can't play by them
+    pub fn with_variant(mut self, variant: Variant) -> Result<Self, AzulError> {
+        variant.check_players(2)?;
+        self.variant = variant;
+        Ok(self)
+    }
+
+    /// Give the player moving second `handicap` in every game,
+    /// to test whether it makes up for moving first
+    pub fn with_second_player_handicap(mut self, handicap: Handicap) -> Self {
+        self.second_handicap = handicap;
+        self
+    }
+
+    /// Play the first moves of every game with `opening`, so players don't only meet
+    /// in their favourite openings. Both games of a pair open the same way
+    pub fn with_opening(mut self, opening: Opening) -> Self {
+        self.opening = opening;
+        self
+    }
+
+    /// Start each game pair from the next of `positions`, going back to the first
+    /// after the last, so results depend less on the luck of the deal.
+    /// Replaces any opening
+    ///
+    /// Fails if a position can't be played with either player first,
+    /// with the rules of any variant set before
+    pub fn with_start_positions(
+        mut self,
+        positions: Vec<StartPosition>,
+    ) -> Result<Self, AzulError> {
+        for (i, position) in positions.iter().enumerate() {
+            for first_player in 0..2 {
+                let mut gs = Gamestate::new_2_player_with_seed(position.seed, first_player);
+                gs.set_variant(self.variant)?;
+                position
+                    .play(&mut gs)
+                    .map_err(|e| AzulError::StartPosition {
+                        index: i,
+                        source: Box::new(e),
+                    })?;
+            }
         }
+        self.positions = positions;
+        self.next_position = 0;
+        Ok(self)
+    }
+
+    /// End games that are decided before they finish, see [Adjudication]
+    pub fn with_adjudication(mut self, adjudication: Adjudication) -> Self {
+        self.adjudication = Some(adjudication);
+        self
+    }
+
+    /// Keep a [Replay] of every game, see [Runner::take_replays]
+    pub fn with_replays(mut self) -> Self {
+        self.replays = Some(Vec::new());
+        self
+    }
+
+    /// Tell `observer` about the deals, moves and round ends of every game
+    pub fn with_observer(mut self, observer: Box<dyn GameObserver<2, 6>>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// Replays of the games played since the last call, in order
+    pub fn take_replays(&mut self) -> Vec<Replay> {
+        self.replays
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
     }
 
     /// Run the matchup between the two players
-    fn run_matchup(&mut self, games: u32) -> MatchUpResult {
-        (0..games)
+    /// Each game is played as a pair so both players start once
+    pub fn run_matchup(&mut self, games: u32) -> MatchUpResult {
+        self.usage = Default::default();
+        let mut result: MatchUpResult = (0..games)
+            .map(|_| {
+                let seed = self.rng.next_u64();
+                self.play_game_pair(seed)
+            })
+            .sum();
+        result.usage = self.usage;
+        result
+    }
+
+    /// Measure the advantage of moving first over `pairs` seeds,
+    /// each played once with either player starting
+    pub fn run_first_move_study(&mut self, pairs: u32) -> FirstMoveStudy {
+        (0..pairs)
             .map(|_| {
                 let seed = self.rng.next_u64();
                 self.play_game_pair(seed)
@@ -45,34 +214,219 @@ impl Runner<2, 6> {
 
     /// Play a pair of games with each player starting first
     fn play_game_pair(&mut self, seed: u64) -> GamePairResult {
-        let g1 = self.play_game(seed, 0);
-        let g2 = self.play_game(seed, 1);
+        let position = match self.positions.len() {
+            0 => None,
+            n => {
+                self.next_position += 1;
+                Some(self.positions[(self.next_position - 1) % n].clone())
+            }
+        };
+        let g1 = self.play_game(seed, 0, position.as_ref());
+        let g2 = self.play_game(seed, 1, position.as_ref());
         GamePairResult::new([g1, g2])
     }
 
-    fn play_game(&mut self, seed: u64, first_player: u8) -> GameResult {
+    /// Play a game from `seed`, or from `position` if there is one
+    fn play_game(
+        &mut self,
+        seed: u64,
+        first_player: u8,
+        position: Option<&StartPosition>,
+    ) -> GameResult {
+        let seed = position.map_or(seed, |p| p.seed);
         let mut gs = Gamestate::new_2_player_with_seed(seed, first_player);
-        while self.play_round(&mut gs) {}
-        GameResult::new(&gs)
+        gs.set_variant(self.variant)
+            .expect("Variant was checked by with_variant");
+        let mut handicaps = [Handicap::default(); 2];
+        handicaps[1 - first_player as usize] = self.second_handicap;
+        gs.set_handicaps(handicaps);
+        gs.record_moves(self.replays.is_some());
+        if let Some((_, names)) = &self.progress {
+            let players = names.clone();
+            self.game = NEXT_GAME.fetch_add(1, Ordering::Relaxed);
+            self.report(|game| Progress::Started {
+                game,
+                players,
+                seed,
+                first_player,
+            });
+        }
+        // Observers see the moves of the opening played one by one
+        let mut before_start = (!self.observers.is_empty()).then(|| gs.clone());
+        if let Some(gs) = &before_start {
+            self.observers.iter_mut().for_each(|o| o.on_deal(gs));
+        }
+        let start = match position {
+            Some(position) => position
+                .play(&mut gs)
+                .expect("Start positions were checked"),
+            None => self.opening.play(&mut gs, seed),
+        };
+        for m in start {
+            self.report(|game| Progress::Move {
+                game,
+                index: m.to_index(),
+            });
+            if let Some(gs) = &mut before_start {
+                let player = gs.current_player();
+                gs.play_move(m);
+                self.observers
+                    .iter_mut()
+                    .for_each(|o| o.on_move(gs, player, &m));
+            }
+        }
+        let adjudicated = match self.adjudication {
+            Some(adjudication) => self.play_adjudicated(&mut gs, Adjudicator::new(adjudication)),
+            None => {
+                while self.play_round(&mut gs) {}
+                None
+            }
+        };
+        debug!("Game from seed {seed} finished\n{gs}");
+        self.observers.iter_mut().for_each(|o| o.on_game_end(&gs));
+        if let Some(replays) = &mut self.replays {
+            replays.push(Replay::from_game(seed, first_player, &gs));
+        }
+        let (scores, adjudication) = match adjudicated {
+            Some((reason, scores)) => (scores, Some(reason)),
+            None => (gs.scores(), None),
+        };
+        self.report(|game| Progress::Finished {
+            game,
+            scores,
+            adjudication,
+        });
+        let winner = match adjudication {
+            Some(_) => Winner::new(&scores.map(u16::from), &gs.completed_rows().map(u16::from)),
+            None => Winner::of_game(&gs),
+        };
+        GameResult {
+            adjudication,
+            ..GameResult::new(scores.map(u16::from), winner)
+        }
     }
 
-    pub fn play_round(&mut self, gs: &mut Gamestate<2, 6>) -> bool {
+    /// Play the game until it ends or `adjudicator` decides it,
+    /// returning the reason and scores if it was decided
+    fn play_adjudicated(
+        &mut self,
+        gs: &mut Gamestate<2, 6>,
+        mut adjudicator: Adjudicator,
+    ) -> Option<(AdjudicationReason, [u8; 2])> {
         loop {
-            let moves = gs.get_moves();
-            let move_ = self.players[gs.current_player() as usize].pick_move(&gs, moves);
-            if gs.play_move(move_) == State::RoundEnd {
-                return gs.end_round() != State::GameEnd;
+            if self.play_turn(gs) == State::RoundEnd && self.end_round(gs) {
+                return None;
+            }
+            if let Some(decision) = adjudicator.check(gs) {
+                return Some(decision);
             }
         }
     }
 }
+
+impl<const P: usize, const F: usize> Runner<P, F> {
+    fn report(&mut self, progress: impl FnOnce(u64) -> Progress) {
+        if let Some((sender, _)) = &self.progress {
+            if sender.send(progress(self.game)).is_err() {
+                // Nobody is watching any more
+                self.progress = None;
+            }
+        }
+    }
+
+    pub fn play_round(&mut self, gs: &mut Gamestate<P, F>) -> bool {
+        loop {
+            if self.play_turn(gs) == State::RoundEnd {
+                return !self.end_round(gs);
+            }
+        }
+    }
+
+    /// Score the round and deal the next, returning whether the game is over
+    fn end_round(&mut self, gs: &mut Gamestate<P, F>) -> bool {
+        let summary = gs.end_round();
+        for observer in self.observers.iter_mut() {
+            observer.on_round_end(gs, &summary);
+            if !summary.game_over() {
+                observer.on_deal(gs);
+            }
+        }
+        summary.game_over()
+    }
+
+    /// Have the player to move pick a move and play it
+    fn play_turn(&mut self, gs: &mut Gamestate<P, F>) -> State {
+        let moves = gs.get_moves();
+        let player = gs.current_player() as usize;
+        let start = Instant::now();
+        let (move_, bytes) = memory::measure(|| self.players[player].pick_move(gs, moves));
+        self.usage[player].record(start.elapsed(), bytes);
+        self.report(|game| Progress::Move {
+            game,
+            index: move_.to_index(),
+        });
+        let state = gs.play_move(move_);
+        for observer in self.observers.iter_mut() {
+            observer.on_move(gs, player as u8, &move_);
+        }
+        state
+    }
+}
+
+impl Runner<4, 10> {
+    /// Create a runner for team games, seats 0 and 2 against seats 1 and 3
+    pub fn new_4_player_teams(players: [Box<dyn Player<4, 10>>; 4], seed: Option<u64>) -> Self {
+        Self {
+            players,
+            rng: SmallRng::seed_from_u64(seed.unwrap_or(rand::thread_rng().next_u64())),
+            progress: None,
+            game: 0,
+            variant: Variant::standard(),
+            second_handicap: Handicap::default(),
+            opening: Opening::default(),
+            positions: Vec::new(),
+            next_position: 0,
+            usage: [ResourceUsage::default(); 4],
+            adjudication: None,
+            replays: None,
+            observers: Vec::new(),
+        }
+    }
+
+    /// Run team games, with results from the point of view of seats 0 and 2.
+    /// Each game is played as a pair so both teams start once
+    pub fn run_team_matchup(&mut self, games: u32) -> MatchUpResult {
+        self.usage = Default::default();
+        let mut result: MatchUpResult = (0..games)
+            .map(|_| {
+                let seed = self.rng.next_u64();
+                let g1 = self.play_team_game(seed, 0);
+                let g2 = self.play_team_game(seed, 1);
+                GamePairResult::new([g1, g2])
+            })
+            .sum();
+        let [a, b, c, d] = self.usage;
+        result.usage = [a + c, b + d];
+        result
+    }
+
+    fn play_team_game(&mut self, seed: u64, first_player: u8) -> GameResult {
+        let mut gs = Gamestate::new_4_player_teams_with_seed(seed, first_player);
+        while self.play_round(&mut gs) {}
+        GameResult::new(gs.team_scores(), Winner::of_game(&gs))
+    }
+}
+
+/// Result of a game between two players or two teams
 #[derive(Debug, Clone, Copy)]
 struct GameResult {
-    scores: [u8; 2],
+    scores: [u16; 2],
     winner: Winner,
+    /// Why the game was ended early, if it was
+    adjudication: Option<AdjudicationReason>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Winner {
     Player0,
     Player1,
@@ -80,13 +434,23 @@ enum Winner {
 }
 
 impl Winner {
-    fn new(score: &[u8; 2]) -> Self {
-        match score[0].cmp(&score[1]) {
+    /// Higher score wins, with ties broken by complete rows
+    fn new(score: &[u16; 2], rows: &[u16; 2]) -> Self {
+        match score[0].cmp(&score[1]).then(rows[0].cmp(&rows[1])) {
             std::cmp::Ordering::Less => Self::Player1,
             std::cmp::Ordering::Greater => Self::Player0,
             std::cmp::Ordering::Equal => Self::Draw,
         }
     }
+
+    /// Winner of a finished two player or two team game, see [Gamestate::winner]
+    fn of_game<const P: usize, const F: usize>(gs: &Gamestate<P, F>) -> Self {
+        match gs.winner().map(|seat| gs.team(seat)) {
+            Some(0) => Self::Player0,
+            Some(_) => Self::Player1,
+            None => Self::Draw,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
@@ -117,10 +481,12 @@ impl AddAssign<Winner> for WinnerCount {
 }
 
 impl GameResult {
-    fn new(gs: &Gamestate<2, 6>) -> Self {
-        let scores = gs.scores();
-        let winner = Winner::new(&scores);
-        Self { scores, winner }
+    fn new(scores: [u16; 2], winner: Winner) -> Self {
+        Self {
+            scores,
+            winner,
+            adjudication: None,
+        }
     }
 }
 
@@ -142,11 +508,68 @@ impl GamePairResult {
     }
 }
 
+/// Time and memory a player used picking its moves
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ResourceUsage {
+    pub moves: u32,
+    /// Total seconds spent picking moves
+    pub think_time: f64,
+    /// Seconds taken by the slowest move
+    pub max_move_time: f64,
+    /// Most bytes allocated at once while picking a move,
+    /// when the binary uses a [TrackingAllocator](crate::memory::TrackingAllocator)
+    pub peak_bytes: Option<u64>,
+}
+
+impl ResourceUsage {
+    pub fn record(&mut self, time: Duration, bytes: Option<u64>) {
+        let time = time.as_secs_f64();
+        self.moves += 1;
+        self.think_time += time;
+        self.max_move_time = self.max_move_time.max(time);
+        self.peak_bytes = self.peak_bytes.max(bytes);
+    }
+
+    /// Average seconds per move
+    pub fn average_move_time(&self) -> f64 {
+        if self.moves == 0 {
+            0.0
+        } else {
+            self.think_time / self.moves as f64
+        }
+    }
+}
+
+impl Add for ResourceUsage {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            moves: self.moves + rhs.moves,
+            think_time: self.think_time + rhs.think_time,
+            max_move_time: self.max_move_time.max(rhs.max_move_time),
+            peak_bytes: self.peak_bytes.max(rhs.peak_bytes),
+        }
+    }
+}
+
+impl AddAssign for ResourceUsage {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
 pub struct MatchUpResult {
     pub games: u32,
     pub score: f64,
     pub winner_count: WinnerCount,
+    /// Resources used by each player, missing from results saved before they were measured
+    #[serde(default)]
+    pub usage: [ResourceUsage; 2],
+    /// Games ended early by adjudication
+    #[serde(default)]
+    pub adjudications: AdjudicationCount,
 }
 
 impl MatchUpResult {
@@ -163,6 +586,8 @@ impl MatchUpResult {
             games: self.games,
             score: -self.score,
             winner_count: self.winner_count.invert(),
+            usage: [self.usage[1], self.usage[0]],
+            adjudications: self.adjudications,
         }
     }
 }
@@ -173,6 +598,22 @@ impl AddAssign<GamePairResult> for MatchUpResult {
         self.score += rhs.score;
         self.winner_count += rhs.results[0].winner;
         self.winner_count += rhs.results[1].winner;
+        for reason in rhs.results.iter().filter_map(|r| r.adjudication) {
+            self.adjudications += reason;
+        }
+    }
+}
+
+impl AddAssign for MatchUpResult {
+    fn add_assign(&mut self, rhs: Self) {
+        self.games += rhs.games;
+        self.score += rhs.score;
+        self.winner_count.player0 += rhs.winner_count.player0;
+        self.winner_count.player1 += rhs.winner_count.player1;
+        self.winner_count.draw += rhs.winner_count.draw;
+        self.usage[0] += rhs.usage[0];
+        self.usage[1] += rhs.usage[1];
+        self.adjudications += rhs.adjudications;
     }
 }
 
@@ -186,6 +627,142 @@ impl Sum<GamePairResult> for MatchUpResult {
     }
 }
 
+/// Mean and spread of a measurement taken once per game pair
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct Sample {
+    pub count: u32,
+    pub sum: f64,
+    pub sum_sq: f64,
+}
+
+impl Sample {
+    pub fn add(&mut self, x: f64) {
+        self.count += 1;
+        self.sum += x;
+        self.sum_sq += x * x;
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+
+    /// Sample standard deviation
+    pub fn std_dev(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        let n = self.count as f64;
+        ((self.sum_sq - self.sum * self.sum / n) / (n - 1.0))
+            .max(0.0)
+            .sqrt()
+    }
+
+    /// 95% confidence interval of the mean, from the normal approximation
+    pub fn interval(&self) -> (f64, f64) {
+        let half = if self.count == 0 {
+            0.0
+        } else {
+            1.96 * self.std_dev() / (self.count as f64).sqrt()
+        };
+        (self.mean() - half, self.mean() + half)
+    }
+}
+
+impl AddAssign for Sample {
+    fn add_assign(&mut self, rhs: Self) {
+        self.count += rhs.count;
+        self.sum += rhs.sum;
+        self.sum_sq += rhs.sum_sq;
+    }
+}
+
+/// Advantage of the player moving first, from [Runner::run_first_move_study]
+///
+/// Both games of a pair share a seed with the seats swapped, so differences in
+/// player strength cancel and each pair is one independent measurement
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize)]
+pub struct FirstMoveStudy {
+    /// Share of the pair won by the first player, draws counting half
+    pub win_rate: Sample,
+    /// First player's score minus the second player's, averaged over the pair
+    pub margin: Sample,
+}
+
+impl FirstMoveStudy {
+    pub fn games(&self) -> u32 {
+        self.margin.count * 2
+    }
+
+    /// Cohen's d of the score margin, 0 when moving first makes no difference
+    pub fn effect_size(&self) -> f64 {
+        let sd = self.margin.std_dev();
+        if sd == 0.0 {
+            0.0
+        } else {
+            self.margin.mean() / sd
+        }
+    }
+}
+
+impl AddAssign<GamePairResult> for FirstMoveStudy {
+    fn add_assign(&mut self, rhs: GamePairResult) {
+        // Player 0 moves first in the first game and player 1 in the second
+        let [g0, g1] = rhs.results;
+        let points = |winner, first| match winner {
+            Winner::Draw => 0.5,
+            w if w == first => 1.0,
+            _ => 0.0,
+        };
+        self.win_rate
+            .add((points(g0.winner, Winner::Player0) + points(g1.winner, Winner::Player1)) / 2.0);
+        let margin =
+            |r: GameResult, first: usize| r.scores[first] as f64 - r.scores[1 - first] as f64;
+        self.margin.add((margin(g0, 0) + margin(g1, 1)) / 2.0);
+    }
+}
+
+impl AddAssign for FirstMoveStudy {
+    fn add_assign(&mut self, rhs: Self) {
+        self.win_rate += rhs.win_rate;
+        self.margin += rhs.margin;
+    }
+}
+
+impl Sum<GamePairResult> for FirstMoveStudy {
+    fn sum<I: Iterator<Item = GamePairResult>>(iter: I) -> Self {
+        let mut result = Self::default();
+        for r in iter {
+            result += r;
+        }
+        result
+    }
+}
+
+/// Play a player against each of a fixed set of opponents
+///
+/// Results are from the player's perspective, in the same order as the opponents
+pub fn gauntlet(
+    player: &(dyn Player<2, 6> + 'static),
+    opponents: &[Box<dyn Player<2, 6>>],
+    games: u32,
+    seed: u64,
+) -> Vec<MatchUpResult> {
+    opponents
+        .iter()
+        .map(|opponent| {
+            let players = [
+                dyn_clone::clone_box(player),
+                dyn_clone::clone_box(&**opponent),
+            ];
+            Runner::new_2_player(players, Some(seed)).run_matchup(games)
+        })
+        .collect()
+}
+
 /// Rank a list of players by running them all against each other
 pub struct PlayerRanker {
     players: Vec<Box<dyn Player<2, 6>>>,
@@ -241,6 +818,10 @@ pub struct Population<T> {
     players: Option<Vec<T>>,
     ranked_players: Option<Vec<(T, f64, MatchUpResult)>>,
     opponent: Box<dyn Player<2, 6>>,
+    /// Opening for the ranking games, see [Runner::with_opening]
+    opening: Opening,
+    /// Draws the mutations and crossovers
+    rng: SmallRng,
 }
 
 impl<T: Clone + EvolvingPlayer + Player<2, 6> + 'static> Population<T> {
@@ -249,23 +830,44 @@ impl<T: Clone + EvolvingPlayer + Player<2, 6> + 'static> Population<T> {
             players: Some(players),
             ranked_players: None,
             opponent,
+            opening: Opening::default(),
+            rng: SmallRng::from_entropy(),
         }
     }
 
-    /// Rank a vec of players by playing them against each other
-    pub fn rank_players(&mut self, games: u32) -> (T, f64, MatchUpResult) {
+    /// Evolve the same way every run
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = SmallRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Rank players on games opened by `opening`, so they aren't only tuned for one opening
+    pub fn with_opening(mut self, opening: Opening) -> Self {
+        self.opening = opening;
+        self
+    }
+
+    /// Rank a vec of players by playing them against each other.
+    /// Fails if the population is empty or was ranked without evolving since
+    pub fn rank_players(&mut self, games: u32) -> Result<(T, f64, MatchUpResult), AzulError> {
+        match &self.players {
+            None => return Err(PopulationError::AlreadyRanked.into()),
+            Some(players) if players.is_empty() => return Err(PopulationError::Empty.into()),
+            Some(_) => (),
+        }
         // Create vec of ranked players against the opponent
         let mut players = self
             .players
             .take()
-            .unwrap()
+            .expect("Checked above")
             .into_iter()
             .map(|p| {
                 // compare the player to opponent
                 let mut runner = Runner::new_2_player(
                     [Box::new(p.clone()), dyn_clone::clone_box(&*self.opponent)],
                     Some(0),
-                );
+                )
+                .with_opening(self.opening.clone());
                 let result = runner.run_matchup(games);
                 (p, 0.0, result)
             })
@@ -290,17 +892,26 @@ impl<T: Clone + EvolvingPlayer + Player<2, 6> + 'static> Population<T> {
             |a, b| match b.2.winner_count.player0.cmp(&a.2.winner_count.player0) {
                 std::cmp::Ordering::Less => std::cmp::Ordering::Less,
                 std::cmp::Ordering::Greater => std::cmp::Ordering::Greater,
-                std::cmp::Ordering::Equal => b.2.score.partial_cmp(&a.2.score).unwrap(),
+                std::cmp::Ordering::Equal => b.2.score.total_cmp(&a.2.score),
             },
         );
-        let best = players.first().unwrap().clone();
+        let best = players[0].clone();
         self.ranked_players = Some(players);
-        best
+        Ok(best)
     }
 
-    pub fn evolve(&mut self) {
-        let mut rng = rand::rngs::SmallRng::from_entropy();
-        let ranked_players = self.ranked_players.take().unwrap();
+    /// Replace the ranked players with the next generation.
+    /// Fails unless there are at least 20 ranked players, so the top 10% can cross over
+    pub fn evolve(&mut self) -> Result<(), AzulError> {
+        match &self.ranked_players {
+            None => return Err(PopulationError::NotRanked.into()),
+            Some(players) if players.len() < 20 => {
+                return Err(PopulationError::TooSmall(players.len()).into())
+            }
+            Some(_) => (),
+        }
+        let rng = &mut self.rng;
+        let ranked_players = self.ranked_players.take().expect("Checked above");
         let mut next_pop = Vec::with_capacity(ranked_players.len());
         // Keep the top 10% of players
         let top = ranked_players.len() / 10;
@@ -312,7 +923,7 @@ impl<T: Clone + EvolvingPlayer + Player<2, 6> + 'static> Population<T> {
         // Mutate the top 10% of players 6 times
         for (player, _, _) in ranked_players.iter().take(top) {
             for _ in 0..6 {
-                next_pop.push(player.mutate(prob, &mut rng));
+                next_pop.push(player.mutate(prob, rng));
             }
         }
 
@@ -327,7 +938,7 @@ impl<T: Clone + EvolvingPlayer + Player<2, 6> + 'static> Population<T> {
             };
             let player1 = &ranked_players[i].0;
             let player2 = &ranked_players[j].0;
-            next_pop.push(player1.crossover(player2, prob));
+            next_pop.push(player1.crossover(player2, prob, rng));
         }
 
         // Create last players randomly
@@ -335,16 +946,167 @@ impl<T: Clone + EvolvingPlayer + Player<2, 6> + 'static> Population<T> {
         //     next_pop.push(T::birth())
         // }
         self.players = Some(next_pop);
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::players::{MoveRankPlayer2, MoveWeightPlayer, RandomPlayer};
+    use crate::players::{MoveRankPlayer, MoveRankPlayer2, MoveWeightPlayer, Player, RandomPlayer};
 
     use super::{Population, Runner};
 
+    #[test]
+    fn team_matchup() {
+        let result = Runner::new_4_player_teams(
+            [
+                Box::new(MoveRankPlayer),
+                Box::new(RandomPlayer::new()),
+                Box::new(MoveRankPlayer),
+                Box::new(RandomPlayer::new()),
+            ],
+            Some(2),
+        )
+        .run_team_matchup(5);
+        assert_eq!(result.games, 10);
+        assert!(result.winner_count.player0 > result.winner_count.player1);
+        // Both teams moved and the test binary doesn't track allocations
+        assert!(result
+            .usage
+            .iter()
+            .all(|u| u.moves > 0 && u.peak_bytes.is_none()));
+        assert!(result.usage[0].max_move_time <= result.usage[0].think_time);
+    }
+
+    #[test]
+    fn first_move_study() {
+        let players: [Box<dyn Player<2, 6>>; 2] =
+            [Box::new(MoveRankPlayer2), Box::new(MoveRankPlayer2)];
+        let study = Runner::new_2_player(players, Some(4)).run_first_move_study(20);
+        assert_eq!(study.games(), 40);
+        let (low, high) = study.margin.interval();
+        assert!(low <= study.margin.mean() && study.margin.mean() <= high);
+        assert!((0.0..=1.0).contains(&study.win_rate.mean()));
+
+        // A head start for the second player outweighs moving first
+        let players: [Box<dyn Player<2, 6>>; 2] =
+            [Box::new(MoveRankPlayer2), Box::new(MoveRankPlayer2)];
+        let handicapped = Runner::new_2_player(players, Some(4))
+            .with_second_player_handicap("start=30".parse().unwrap())
+            .run_first_move_study(20);
+        assert!(handicapped.win_rate.mean() < study.win_rate.mean());
+        assert!(handicapped.effect_size() < 0.0);
+    }
+
+    #[test]
+    fn start_positions() {
+        use crate::{
+            error::AzulError,
+            gamestate::Gamestate,
+            opening::{Opening, StartPosition},
+        };
+
+        let mut gs = Gamestate::new_2_player_with_seed(5, 0);
+        let moves = Opening::random(2).play(&mut gs, 5);
+        let position = StartPosition {
+            seed: 5,
+            moves: moves.iter().map(|m| m.to_index()).collect(),
+            eval: 0.0,
+        };
+        let players = || -> [Box<dyn Player<2, 6>>; 2] {
+            [Box::new(MoveRankPlayer2), Box::new(MoveRankPlayer2)]
+        };
+        let bad = StartPosition {
+            moves: vec![usize::MAX],
+            ..position.clone()
+        };
+        assert!(matches!(
+            Runner::new_2_player(players(), None).with_start_positions(vec![position.clone(), bad]),
+            Err(AzulError::StartPosition { index: 1, .. })
+        ));
+        let result = Runner::new_2_player(players(), Some(1))
+            .with_start_positions(vec![position])
+            .unwrap()
+            .run_matchup(3);
+        assert_eq!(result.games, 6);
+        // Each side of the position is played by the same player once
+        assert_eq!(result.average_score(), 0.0);
+    }
+
+    #[test]
+    fn observers() {
+        use std::{cell::RefCell, rc::Rc};
+
+        use crate::{
+            gamestate::{Gamestate, Move, RoundSummary, State},
+            observer::GameObserver,
+            opening::Opening,
+            record::Replay,
+        };
+
+        #[derive(Default)]
+        struct Log(Rc<RefCell<Vec<String>>>);
+
+        impl GameObserver<2, 6> for Log {
+            fn on_deal(&mut self, gs: &Gamestate<2, 6>) {
+                self.0.borrow_mut().push(format!("deal {}", gs.round()));
+            }
+
+            fn on_move(&mut self, gs: &Gamestate<2, 6>, player: u8, _m: &Move) {
+                assert!(gs.state() != State::GameEnd);
+                self.0.borrow_mut().push(format!("move {player}"));
+            }
+
+            fn on_round_end(&mut self, _gs: &Gamestate<2, 6>, summary: &RoundSummary<2>) {
+                self.0.borrow_mut().push(format!("end {}", summary.round));
+            }
+
+            fn on_game_end(&mut self, gs: &Gamestate<2, 6>) {
+                self.0.borrow_mut().push(format!("game {:?}", gs.scores()));
+            }
+        }
+
+        let log = Log::default();
+        let events = log.0.clone();
+        let players: [Box<dyn Player<2, 6>>; 2] =
+            [Box::new(MoveRankPlayer2), Box::new(RandomPlayer::new())];
+        let mut runner = Runner::new_2_player(players, Some(5))
+            .with_opening(Opening::random(3))
+            .with_observer(Box::new(log))
+            .with_replays();
+        runner.run_matchup(1);
+        let replays = runner.take_replays();
+        let events = events.borrow();
+        let count = |prefix: &str| events.iter().filter(|e| e.starts_with(prefix)).count();
+        assert_eq!(count("game"), 2);
+        assert_eq!(
+            count("move"),
+            replays.iter().map(Replay::len).sum::<usize>()
+        );
+        assert_eq!(count("deal"), count("end"));
+        assert_eq!(events[0], "deal 1");
+        assert_eq!(events[1], "move 0");
+    }
+
+    #[test]
+    fn replays() {
+        let players: [Box<dyn Player<2, 6>>; 2] =
+            [Box::new(MoveRankPlayer2), Box::new(RandomPlayer::new())];
+        let mut runner = Runner::new_2_player(players, Some(3)).with_replays();
+        let result = runner.run_matchup(2);
+        let replays = runner.take_replays();
+        assert_eq!(replays.len(), result.games as usize);
+        assert!(runner.take_replays().is_empty());
+        // Playing a replay back reaches the end of the game
+        for replay in &replays {
+            let gs = replay.position(replay.len()).unwrap();
+            assert_eq!(gs.state(), crate::gamestate::State::GameEnd);
+        }
+        assert_eq!(replays[0].first_player, 0);
+        assert_eq!(replays[1].first_player, 1);
+    }
+
     #[test]
     fn test_compare_players() {
         let player1 = Box::new(crate::players::MoveRankPlayer);
@@ -359,12 +1121,33 @@ mod test {
         let players = (0..100).map(|_| MoveWeightPlayer::new_random()).collect();
         let opponent = Box::new(MoveRankPlayer2::new());
         let mut population = Population::new(players, opponent);
-        let best = population.rank_players(10);
+        assert!(population.evolve().is_err());
+        let best = population.rank_players(10).unwrap();
         dbg!(&best);
+        assert!(population.rank_players(10).is_err());
         for _ in 0..10 {
-            population.evolve();
-            let best = population.rank_players(10);
+            population.evolve().unwrap();
+            let best = population.rank_players(10).unwrap();
             dbg!(&best.2.winner_count.player0);
         }
     }
+
+    #[test]
+    fn small_population() {
+        use crate::error::{AzulError, PopulationError};
+
+        let opponent = Box::new(MoveRankPlayer2::new());
+        let mut population = Population::new(Vec::<MoveWeightPlayer>::new(), opponent.clone());
+        assert!(matches!(
+            population.rank_players(1),
+            Err(AzulError::Population(PopulationError::Empty))
+        ));
+        let players = (0..5).map(|_| MoveWeightPlayer::new_random()).collect();
+        let mut population = Population::new(players, opponent);
+        population.rank_players(1).unwrap();
+        assert_eq!(
+            population.evolve(),
+            Err(PopulationError::TooSmall(5).into())
+        );
+    }
 }
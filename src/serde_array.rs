@@ -0,0 +1,19 @@
+//! Serde for arrays sized by a const generic, which serde only supports for fixed sizes.
+//! Use with `#[serde(with = "crate::serde_array")]`, the array is written as a sequence
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+pub fn serialize<S: Serializer, T: Serialize, const N: usize>(
+    array: &[T; N],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(array)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>, const N: usize>(
+    deserializer: D,
+) -> Result<[T; N], D::Error> {
+    Vec::<T>::deserialize(deserializer)?
+        .try_into()
+        .map_err(|v: Vec<T>| D::Error::custom(format!("Expected {N} elements, got {}", v.len())))
+}
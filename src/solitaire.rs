@@ -0,0 +1,164 @@
+//! Single player score attack
+//!
+//! One board drafts from the usual 2 player factories. After each move a scripted opponent
+//! takes the largest group of one colour from the factories, sending the rest to the centre
+//! as a greedy second player would, and those tiles go in the lid.
+//! Rounds end automatically and the final score is compared to a par value
+
+use crate::{
+    gamestate::{Gamestate, Move, Source, State},
+    players::Player,
+    tiles::Tile,
+};
+
+/// Score to beat, a little above what [MoveRankPlayer2](crate::players::MoveRankPlayer2) averages
+pub const DEFAULT_PAR: u8 = 60;
+
+/// Tiles removed by the scripted opponent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptedTake {
+    pub source: Source,
+    pub tile: Tile,
+    pub count: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solitaire {
+    gs: Gamestate<1, 6>,
+    par: u8,
+    /// Most recent move of the scripted opponent
+    last_take: Option<ScriptedTake>,
+}
+
+impl Solitaire {
+    pub fn new(seed: u64, par: u8) -> Self {
+        Self {
+            gs: Gamestate::new(seed, 0),
+            par,
+            last_take: None,
+        }
+    }
+
+    pub fn gamestate(&self) -> &Gamestate<1, 6> {
+        &self.gs
+    }
+
+    pub fn par(&self) -> u8 {
+        self.par
+    }
+
+    pub fn last_take(&self) -> Option<ScriptedTake> {
+        self.last_take
+    }
+
+    pub fn score(&self) -> u8 {
+        self.gs.scores()[0]
+    }
+
+    /// Score relative to par, positive when beaten
+    pub fn result(&self) -> i16 {
+        self.score() as i16 - self.par as i16
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.gs.state() == State::GameEnd
+    }
+
+    /// Moves available to the player, empty once the game is over
+    pub fn get_moves(&self) -> Vec<Move> {
+        match self.gs.state() {
+            State::RoundActive => self.gs.get_moves(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Play the player's move followed by the scripted take, ending the round if needed.
+    /// Returns [State::RoundActive] when it is the player's turn again
+    pub fn play_move(&mut self, m: Move) -> State {
+        let mut state = self.gs.play_move(m);
+        if state == State::RoundActive {
+            self.last_take = scripted_take(&self.gs);
+            if let Some(take) = self.last_take {
+                state = self
+                    .gs
+                    .discard(take.source, take.tile)
+                    .expect("Scripted take is legal");
+            }
+        }
+        if state == State::RoundEnd {
+            state = self.gs.end_round().state;
+        }
+        state
+    }
+
+    /// Play the rest of the game with `player`, returning the final score
+    pub fn play(&mut self, player: &mut dyn Player<1, 6>) -> u8 {
+        while !self.is_over() {
+            let m = player.pick_move(&self.gs, self.get_moves());
+            self.play_move(m);
+        }
+        self.score()
+    }
+}
+
+/// Move of the scripted opponent: the most tiles of one colour in any factory,
+/// or in the centre once the factories are empty.
+/// Ties go to the first factory, then the first colour
+pub fn scripted_take(gs: &Gamestate<1, 6>) -> Option<ScriptedTake> {
+    let largest = |sources: std::ops::Range<usize>| {
+        let mut best: Option<ScriptedTake> = None;
+        for i in sources {
+            let Some(factory) = gs.factories()[i] else {
+                continue;
+            };
+            for (tile, count) in factory.iter() {
+                if count > best.map_or(0, |b| b.count) {
+                    best = Some(ScriptedTake {
+                        source: Source(i as u8),
+                        tile,
+                        count,
+                    });
+                }
+            }
+        }
+        best
+    };
+    largest(1..gs.factories().len()).or_else(|| largest(0..1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{scripted_take, Solitaire};
+    use crate::{
+        gamestate::{Source, State},
+        players::MoveRankPlayer2,
+    };
+
+    #[test]
+    fn scripted_game() {
+        let mut game = Solitaire::new(3, 20);
+        let take = scripted_take(game.gamestate()).unwrap();
+        assert_ne!(take.source, Source(0));
+        assert!(game.gamestate().factories()[1..]
+            .iter()
+            .flatten()
+            .all(|f| f.counts().iter().all(|&c| c <= take.count)));
+
+        let m = game.get_moves()[0];
+        assert_eq!(game.play_move(m), State::RoundActive);
+        assert!(game.last_take().is_some());
+        // Player moved once and the script once
+        assert_eq!(
+            game.gamestate().factories()[1..]
+                .iter()
+                .filter(|f| f.is_none())
+                .count(),
+            2
+        );
+
+        let score = game.play(&mut MoveRankPlayer2);
+        assert!(game.is_over());
+        assert!(game.get_moves().is_empty());
+        assert_eq!(game.result(), score as i16 - 20);
+    }
+}
@@ -0,0 +1,217 @@
+//! Following games reported by a [Runner](crate::runner::Runner) while they are played
+//!
+//! Progress is written as JSON lines, e.g. to `progress.jsonl` by `azul tournament --spectate`.
+//! [LogTail] reads new lines as they are appended and [Spectator] keeps the
+//! running games, standings and recent results up to date
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use serde::de::DeserializeOwned;
+
+use crate::{
+    gamestate::Gamestate,
+    online,
+    runner::Progress,
+    view::{PlayerView, Viewer},
+};
+
+/// Number of finished games kept in [Spectator::recent]
+const RECENT: usize = 20;
+
+/// Game in progress
+#[derive(Debug, Clone)]
+pub struct LiveGame {
+    pub players: [String; 2],
+    /// Rebuilt from the seed, so only shown through [LiveGame::view]
+    gs: Gamestate<2, 6>,
+}
+
+impl LiveGame {
+    /// The game as spectators see it, without the future deals
+    pub fn view(&self) -> PlayerView<'_, 2, 6> {
+        self.gs.view(Viewer::Spectator)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FinishedGame {
+    pub players: [String; 2],
+    pub scores: [u8; 2],
+}
+
+/// Results for one player across all finished games
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Standing {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Total of own score minus opponent's score
+    pub margin: i64,
+}
+
+impl Standing {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// One point per win and half per draw
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + 0.5 * self.draws as f64
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Spectator {
+    /// Running games by id
+    pub games: BTreeMap<u64, LiveGame>,
+    pub standings: BTreeMap<String, Standing>,
+    /// Most recently finished first
+    pub recent: VecDeque<FinishedGame>,
+}
+
+impl Spectator {
+    pub fn update(&mut self, progress: Progress) {
+        match progress {
+            Progress::Started {
+                game,
+                players,
+                seed,
+                first_player,
+            } => {
+                let gs = Gamestate::new_2_player_with_seed(seed, first_player);
+                self.games.insert(game, LiveGame { players, gs });
+            }
+            Progress::Move { game, index } => {
+                if let Some(live) = self.games.get_mut(&game) {
+                    if online::apply_index(&mut live.gs, index).is_none() {
+                        // Out of sync, stop following the game
+                        self.games.remove(&game);
+                    }
+                }
+            }
+            Progress::Finished { game, scores, .. } => {
+                // Players are unknown for games started before the log was opened
+                let Some(live) = self.games.remove(&game) else {
+                    return;
+                };
+                for (i, name) in live.players.iter().enumerate() {
+                    let standing = self.standings.entry(name.clone()).or_default();
+                    let (own, other) = (scores[i], scores[1 - i]);
+                    match own.cmp(&other) {
+                        std::cmp::Ordering::Greater => standing.wins += 1,
+                        std::cmp::Ordering::Equal => standing.draws += 1,
+                        std::cmp::Ordering::Less => standing.losses += 1,
+                    }
+                    standing.margin += own as i64 - other as i64;
+                }
+                self.recent.push_front(FinishedGame {
+                    players: live.players,
+                    scores,
+                });
+                self.recent.truncate(RECENT);
+            }
+        }
+    }
+
+    /// Players ordered by points, then by margin
+    pub fn table(&self) -> Vec<(&String, &Standing)> {
+        let mut table = self.standings.iter().collect::<Vec<_>>();
+        table.sort_by(|a, b| {
+            b.1.points()
+                .total_cmp(&a.1.points())
+                .then(b.1.margin.cmp(&a.1.margin))
+        });
+        table
+    }
+}
+
+/// Reads lines appended to a JSON lines file, such as progress or training metrics
+#[derive(Debug)]
+pub struct LogTail {
+    path: PathBuf,
+    offset: u64,
+    /// Incomplete last line
+    partial: String,
+}
+
+impl LogTail {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            offset: 0,
+            partial: String::new(),
+        }
+    }
+
+    /// Lines written since the last call.
+    /// Lines that fail to parse are skipped
+    pub fn read<T: DeserializeOwned>(&mut self) -> io::Result<Vec<T>> {
+        let mut file = File::open(&self.path)?;
+        if file.metadata()?.len() < self.offset {
+            // File was replaced, start again
+            self.offset = 0;
+            self.partial.clear();
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut new = String::new();
+        self.offset += file.read_to_string(&mut new)? as u64;
+        self.partial.push_str(&new);
+        let Some(end) = self.partial.rfind('\n') else {
+            return Ok(Vec::new());
+        };
+        let complete = self.partial.drain(..=end).collect::<String>();
+        Ok(complete
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use super::Spectator;
+    use crate::{
+        players::{MoveRankPlayer, RandomPlayer},
+        runner::{Progress, Runner},
+    };
+
+    #[test]
+    fn follows_runner() {
+        let (sender, receiver) = mpsc::channel();
+        let result = Runner::new_2_player(
+            [Box::new(MoveRankPlayer), Box::new(RandomPlayer::new())],
+            Some(1),
+        )
+        .with_progress(sender, ["rank".into(), "random".into()])
+        .run_matchup(2);
+
+        let mut spectator = Spectator::default();
+        let mut finished = 0;
+        for progress in receiver.try_iter() {
+            if let Progress::Finished { game, scores, .. } = &progress {
+                // Replaying the moves reaches the same scores
+                let mut gs = spectator.games[game].gs.clone();
+                gs.end_round();
+                assert_eq!(gs.scores(), *scores);
+                finished += 1;
+            }
+            spectator.update(progress);
+        }
+        assert_eq!(finished, result.games);
+        assert!(spectator.games.is_empty());
+        let table = spectator.table();
+        assert_eq!(table.len(), 2);
+        assert_eq!(
+            table.iter().map(|(_, s)| s.games()).sum::<u32>(),
+            2 * result.games
+        );
+        assert_eq!(spectator.recent.len(), result.games as usize);
+    }
+}
@@ -1,21 +1,98 @@
 use std::{
+    fmt,
     iter::Zip,
-    ops::{Add, AddAssign},
-    path::Iter,
+    ops::{AddAssign, Sub, SubAssign},
 };
 
 use rand::Rng;
 use strum::IntoEnumIterator;
 
+use crate::error::{AzulError, ParseKind};
+
 /// Types of tiles
 /// These are in the order as they appear on the first row of the wall
-#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::EnumIter)]
+///
+/// The standard game uses the first 5, [Tile::Orange] is only used by the
+/// experimental 6 colour variant
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, strum::EnumIter, serde::Serialize, serde::Deserialize,
+)]
 pub enum Tile {
     Blue,
     Yellow,
     Red,
     Black,
     White,
+    Orange,
+}
+
+impl Tile {
+    /// Every tile, in order
+    pub const ALL: [Tile; 6] = [
+        Tile::Blue,
+        Tile::Yellow,
+        Tile::Red,
+        Tile::Black,
+        Tile::White,
+        Tile::Orange,
+    ];
+
+    /// The first `count` tiles, those used in a game with `count` colours
+    pub fn colours(count: usize) -> std::iter::Take<TileIter> {
+        Tile::iter().take(count)
+    }
+
+    /// Letter used in text formats, `K` for black
+    pub fn letter(&self) -> char {
+        match self {
+            Tile::Blue => 'B',
+            Tile::Yellow => 'Y',
+            Tile::Red => 'R',
+            Tile::Black => 'K',
+            Tile::White => 'W',
+            Tile::Orange => 'O',
+        }
+    }
+
+    /// Tile for a letter from [Tile::letter], in either case
+    pub fn from_letter(letter: char) -> Option<Tile> {
+        Tile::iter().find(|t| t.letter() == letter.to_ascii_uppercase())
+    }
+
+    /// ANSI escape code for the tile's colour in a terminal.
+    /// Black is shown green, as in the TUI, to stand out on dark backgrounds
+    pub fn ansi_colour(&self) -> &'static str {
+        match self {
+            Tile::Blue => "\x1b[34m",
+            Tile::Yellow => "\x1b[33m",
+            Tile::Red => "\x1b[31m",
+            Tile::Black => "\x1b[32m",
+            Tile::White => "\x1b[37m",
+            Tile::Orange => "\x1b[91m",
+        }
+    }
+
+    /// Write the space on the wall for this tile, as a lower case letter,
+    /// dimmed in colour for `{:#}`
+    pub(crate) fn fmt_space(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let letter = self.letter().to_ascii_lowercase();
+        if f.alternate() {
+            write!(f, "\x1b[2m{}{letter}\x1b[0m", self.ansi_colour())
+        } else {
+            write!(f, "{letter}")
+        }
+    }
+}
+
+/// The tile's [Tile::letter], in colour for `{:#}`
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "\x1b[1m{}{}\x1b[0m", self.ansi_colour(), self.letter())
+        } else {
+            write!(f, "{}", self.letter())
+        }
+    }
 }
 
 impl From<Tile> for usize {
@@ -30,43 +107,71 @@ impl From<&Tile> for u8 {
     }
 }
 
-impl From<usize> for Tile {
-    fn from(value: usize) -> Self {
+impl TryFrom<usize> for Tile {
+    type Error = AzulError;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
         match value {
-            0 => Tile::Blue,
-            1 => Tile::Yellow,
-            2 => Tile::Red,
-            3 => Tile::Black,
-            4 => Tile::White,
-            _ => unreachable!(),
+            0 => Ok(Tile::Blue),
+            1 => Ok(Tile::Yellow),
+            2 => Ok(Tile::Red),
+            3 => Ok(Tile::Black),
+            4 => Ok(Tile::White),
+            5 => Ok(Tile::Orange),
+            _ => Err(AzulError::Tile(value)),
         }
     }
 }
 /// Stores a selection of tiles for bag or centre factory
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub struct TileGroup {
-    counts: [u8; 5],
+///
+/// Holds `C` colours of tile, 5 in the standard game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct TileGroup<const C: usize = 5> {
+    #[serde(with = "crate::serde_array")]
+    counts: [u8; C],
+}
+
+impl<const C: usize> Default for TileGroup<C> {
+    fn default() -> Self {
+        Self { counts: [0; C] }
+    }
 }
 
-impl AddAssign for TileGroup {
+impl<const C: usize> AddAssign for TileGroup<C> {
     fn add_assign(&mut self, other: Self) {
-        for (count, tile) in other.into_iter() {
+        for (tile, count) in other.iter() {
             self.counts[tile as usize] += count;
         }
     }
 }
 
-impl TileGroup {
+/// Take away the tiles of another group, panicking if there aren't enough,
+/// see [TileGroup::checked_sub] and [TileGroup::saturating_sub]
+impl<const C: usize> Sub for TileGroup<C> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.checked_sub(other)
+            .expect("Not enough tiles to take away")
+    }
+}
+
+impl<const C: usize> SubAssign for TileGroup<C> {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl<const C: usize> TileGroup<C> {
     /// Access counts directly
-    pub fn counts(&self) -> &[u8; 5] {
+    pub fn counts(&self) -> &[u8; C] {
         &self.counts
     }
 
     /// Create a new bag of tiles
     pub fn new_bag() -> Self {
-        Self {
-            counts: [20, 20, 20, 20, 20],
-        }
+        Self { counts: [20; C] }
     }
 
     /// Create a new centre factory
@@ -77,7 +182,7 @@ impl TileGroup {
     /// Empty and return the tiles in the group
     pub fn empty(&mut self) -> Self {
         let counts = self.counts;
-        self.counts = [0; 5];
+        self.counts = [0; C];
         Self { counts }
     }
 
@@ -86,23 +191,66 @@ impl TileGroup {
         self.counts.iter().sum()
     }
 
-    /// Take all tiles of a certain type from the group
+    /// Take all tiles of a certain type from the group, none if the group
+    /// has no such colour
     pub fn take_tile(&mut self, tile: Tile) -> u8 {
-        let count = self.counts[tile as usize];
-        self.counts[tile as usize] = 0;
-        count
+        self.count_mut(tile).map_or(0, std::mem::take)
+    }
+
+    /// Take exactly `count` tiles of a type, returning how many were taken.
+    /// Fails without changing the group if there are fewer
+    pub fn try_take_tiles(&mut self, tile: Tile, count: u8) -> Result<u8, AzulError> {
+        let available = self.get_count(tile);
+        if available < count {
+            return Err(AzulError::NotEnoughTiles {
+                tile,
+                wanted: count,
+                available,
+            });
+        }
+        if let Some(left) = self.count_mut(tile) {
+            *left -= count;
+        }
+        Ok(count)
+    }
+
+    /// Take up to `count` tiles of a type, returning how many were taken
+    pub fn saturating_take_tiles(&mut self, tile: Tile, count: u8) -> u8 {
+        let taken = count.min(self.get_count(tile));
+        if let Some(left) = self.count_mut(tile) {
+            *left -= taken;
+        }
+        taken
     }
 
-    /// Select a random tile from the group
+    /// Tiles left after taking away `other`, or None if any colour runs short
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        let mut counts = self.counts;
+        for (count, taken) in counts.iter_mut().zip(other.counts) {
+            *count = count.checked_sub(taken)?;
+        }
+        Some(Self { counts })
+    }
+
+    /// Tiles left after taking away as many of `other` as there are
+    pub fn saturating_sub(self, other: Self) -> Self {
+        let mut counts = self.counts;
+        for (count, taken) in counts.iter_mut().zip(other.counts) {
+            *count = count.saturating_sub(taken);
+        }
+        Self { counts }
+    }
+
+    /// Select a random tile from the group using any [Rng]
     /// Returns None if the group is empty
-    pub fn random_tile(&mut self, rng: &mut rand::prelude::SmallRng) -> Option<Tile> {
+    pub fn random_tile<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<Tile> {
         let total = self.total();
         if total == 0 {
             return None;
         }
         let n = rng.gen_range(0..total);
         let mut sum = 0;
-        for (count, tile) in self.into_iter() {
+        for (tile, count) in self.iter() {
             sum += count;
             if n < sum {
                 self.counts[tile as usize] -= 1;
@@ -112,30 +260,194 @@ impl TileGroup {
         unreachable!()
     }
 
+    /// Every group of `count` tiles that could be drawn at random from this one,
+    /// with its probability. Draws the whole group when there are fewer than `count`
+    pub fn draws(&self, count: u8) -> Vec<(Self, f64)> {
+        let count = count.min(self.total());
+        // Partial draws over the colours so far, weighted by the ways of making them
+        let mut draws = vec![(Self::new_empty(), 0, 1.0)];
+        for (tile, available) in self.iter() {
+            draws = draws
+                .into_iter()
+                .flat_map(|(group, drawn, ways)| {
+                    (0..=available.min(count - drawn)).map(move |n| {
+                        let mut group = group;
+                        group.add_tiles(tile, n);
+                        (group, drawn + n, ways * choose(available, n))
+                    })
+                })
+                .collect();
+        }
+        let total = choose(self.total(), count);
+        draws
+            .into_iter()
+            .filter(|&(_, drawn, _)| drawn == count)
+            .map(|(group, _, ways)| (group, ways / total))
+            .collect()
+    }
+
     /// Add a tile to the group
     pub fn add_tile(&mut self, tile: Tile) {
-        self.counts[tile as usize] += 1;
+        self.add_tiles(tile, 1);
     }
 
-    /// Add multiple tiles to the group
+    /// Add multiple tiles to the group.
+    /// Panics if the group has no such colour, as the tiles would be lost
     pub fn add_tiles(&mut self, tile: Tile, count: u8) {
-        self.counts[tile as usize] += count;
+        *self
+            .count_mut(tile)
+            .unwrap_or_else(|| panic!("No {tile:?} tiles in a game of {C} colours")) += count;
     }
 
     /// Vec of each tile in group in [Tile] order
     pub fn tile_vec(&self) -> Vec<Tile> {
-        self.into_iter()
-            .flat_map(|(c, t)| std::iter::repeat(t).take(*c as usize))
+        self.iter()
+            .flat_map(|(t, c)| std::iter::repeat_n(t, c as usize))
             .collect()
     }
 
-    /// Get the number of a certain tile in the group
+    /// Each colour with its count, in [Tile] order
+    pub fn iter(&self) -> impl Iterator<Item = (Tile, u8)> {
+        Tile::iter().zip(self.counts)
+    }
+
+    /// Whether there are no tiles
+    pub fn is_empty(&self) -> bool {
+        self.counts.iter().all(|&c| c == 0)
+    }
+
+    /// Colour with the most tiles and its count, the first in [Tile] order on a tie.
+    /// None if the group is empty
+    pub fn most_common(&self) -> Option<(Tile, u8)> {
+        self.iter()
+            .filter(|&(_, c)| c > 0)
+            .reduce(|best, next| if next.1 > best.1 { next } else { best })
+    }
+
+    /// Get the number of a certain tile in the group, 0 if the group
+    /// has no such colour
     pub fn get_count(&self, tile: Tile) -> u8 {
-        self.counts[tile as usize]
+        self.counts.get(tile as usize).copied().unwrap_or(0)
+    }
+
+    /// Count of a colour, None beyond the first `C`
+    fn count_mut(&mut self, tile: Tile) -> Option<&mut u8> {
+        self.counts.get_mut(tile as usize)
+    }
+}
+
+/// Counts the tiles still to come, to give the chances of seeing them in the next deal
+///
+/// The next deal draws from the bag, then from the lid once the bag is empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileTracker<const C: usize = 5> {
+    bag: TileGroup<C>,
+    lid: TileGroup<C>,
+    /// Tiles dealt to the factories each round
+    dealt: u8,
+}
+
+impl<const C: usize> TileTracker<C> {
+    /// Track the tiles in `bag` and `lid`, with `dealt` tiles going out in the next deal
+    pub fn new(bag: TileGroup<C>, lid: TileGroup<C>, dealt: u8) -> Self {
+        Self { bag, lid, dealt }
+    }
+
+    /// Number of tiles that will be dealt, fewer than a full deal near the end of the game
+    pub fn dealt(&self) -> u8 {
+        self.dealt.min(self.bag.total() + self.lid.total())
+    }
+
+    /// Chance of each number of `tile` in the next deal, indexed by the number
+    pub fn distribution(&self, tile: Tile) -> Vec<f64> {
+        let dealt = self.dealt();
+        let from_bag = dealt.min(self.bag.total());
+        let mut probs = vec![0.0; dealt as usize + 1];
+        for (n, p) in hypergeometric(&self.bag, tile, from_bag)
+            .into_iter()
+            .enumerate()
+        {
+            if from_bag == dealt {
+                probs[n] = p;
+                continue;
+            }
+            // The whole bag came out, so the rest are from the lid
+            let rest = hypergeometric(&self.lid, tile, dealt - from_bag);
+            for (m, q) in rest.into_iter().enumerate() {
+                probs[n + m] += p * q;
+            }
+        }
+        probs
+    }
+
+    /// Chance of at least `count` of `tile` in the next deal
+    pub fn prob_at_least(&self, tile: Tile, count: u8) -> f64 {
+        self.distribution(tile).iter().skip(count as usize).sum()
+    }
+
+    /// Average number of `tile` in the next deal
+    pub fn expected(&self, tile: Tile) -> f64 {
+        self.distribution(tile)
+            .iter()
+            .enumerate()
+            .map(|(n, p)| n as f64 * p)
+            .sum()
+    }
+}
+
+/// Chance of each number of `tile` when drawing `count` tiles from `group`
+fn hypergeometric<const C: usize>(group: &TileGroup<C>, tile: Tile, count: u8) -> Vec<f64> {
+    let total = group.total();
+    let matching = group.get_count(tile);
+    let ways = choose(total, count);
+    (0..=count)
+        .map(|n| {
+            if n > matching || count - n > total - matching {
+                0.0
+            } else {
+                choose(matching, n) * choose(total - matching, count - n) / ways
+            }
+        })
+        .collect()
+}
+
+/// Number of ways to pick `k` of `n` things
+fn choose(n: u8, k: u8) -> f64 {
+    (0..k).fold(1.0, |ways, i| ways * (n - i) as f64 / (i + 1) as f64)
+}
+
+/// Letters of the tiles in [Tile] order, or `-` when empty
+impl<const C: usize> fmt::Display for TileGroup<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "-");
+        }
+        self.tile_vec()
+            .iter()
+            .try_for_each(|tile| fmt::Display::fmt(tile, f))
     }
 }
 
-impl<'a> IntoIterator for &'a TileGroup {
+/// Reads the letters written by Display, `-` for no tiles
+impl<const C: usize> std::str::FromStr for TileGroup<C> {
+    type Err = AzulError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut group = Self::new_empty();
+        if s == "-" {
+            return Ok(group);
+        }
+        for c in s.chars() {
+            match Tile::from_letter(c) {
+                Some(tile) if (tile as usize) < C => group.add_tile(tile),
+                _ => return Err(AzulError::parse(ParseKind::Tiles, s)),
+            }
+        }
+        Ok(group)
+    }
+}
+
+impl<'a, const C: usize> IntoIterator for &'a TileGroup<C> {
     type Item = (&'a u8, Tile);
     type IntoIter = Zip<std::slice::Iter<'a, u8>, TileIter>;
 
@@ -152,8 +464,8 @@ mod test {
 
     #[test]
     fn take_tiles() {
-        let mut tg = TileGroup::new_bag();
-        let mut tg_2 = TileGroup::new_empty();
+        let mut tg: TileGroup = TileGroup::new_bag();
+        let mut tg_2: TileGroup = TileGroup::new_empty();
         assert_eq!(tg.total(), 100);
         let mut rng = rand::prelude::SmallRng::from_entropy();
         for _ in 0..100 {
@@ -169,4 +481,86 @@ mod test {
         // assert_eq!(tg_2.red, 20);
         // assert_eq!(tg_2.white, 20);
     }
+
+    #[test]
+    fn iteration() {
+        let tiles: TileGroup = "BRRK".parse().unwrap();
+        assert_eq!(
+            tiles.iter().collect::<Vec<_>>(),
+            [
+                (Tile::Blue, 1),
+                (Tile::Yellow, 0),
+                (Tile::Red, 2),
+                (Tile::Black, 1),
+                (Tile::White, 0)
+            ]
+        );
+        assert_eq!(tiles.most_common(), Some((Tile::Red, 2)));
+        let tiles: TileGroup = "WKWK".parse().unwrap();
+        assert_eq!(tiles.most_common(), Some((Tile::Black, 2)));
+        assert!(!tiles.is_empty());
+        assert!(TileGroup::<6>::new_empty().is_empty());
+        assert_eq!(TileGroup::<6>::new_empty().most_common(), None);
+    }
+
+    #[test]
+    fn subtraction() {
+        let mut bag: TileGroup = TileGroup::new_bag();
+        let taken: TileGroup = "BBRW".parse().unwrap();
+        let left = bag - taken;
+        assert_eq!(left.counts(), &[18, 20, 19, 20, 19]);
+        assert_eq!(left.checked_sub(bag), None);
+        assert_eq!(taken.saturating_sub(left), TileGroup::new_empty());
+        bag -= taken;
+        assert_eq!(bag, left);
+
+        assert_eq!(bag.try_take_tiles(Tile::Red, 19), Ok(19));
+        assert_eq!(
+            bag.try_take_tiles(Tile::Red, 1),
+            Err(AzulError::NotEnoughTiles {
+                tile: Tile::Red,
+                wanted: 1,
+                available: 0
+            })
+        );
+        assert_eq!(bag.saturating_take_tiles(Tile::Blue, 30), 18);
+        assert_eq!(bag.total(), 59);
+    }
+
+    #[test]
+    fn draws() {
+        let tg: TileGroup = "RRRBB".parse().unwrap();
+        let draws = tg.draws(2);
+        assert_eq!(draws.len(), 3);
+        assert!((draws.iter().map(|d| d.1).sum::<f64>() - 1.0).abs() < 1e-9);
+        let red_blue = draws
+            .iter()
+            .find(|(g, _)| g.to_string() == "BR" || g.to_string() == "RB")
+            .unwrap();
+        assert!((red_blue.1 - 0.6).abs() < 1e-9);
+        // Asking for more than there are draws them all
+        assert_eq!(tg.draws(9), vec![(tg, 1.0)]);
+        assert_eq!(TileGroup::<5>::new_bag().draws(4).len(), 70);
+    }
+
+    #[test]
+    fn tracker() {
+        let tracker: TileTracker =
+            TileTracker::new(TileGroup::new_bag(), TileGroup::new_empty(), 20);
+        assert!((tracker.expected(Tile::Red) - 4.0).abs() < 1e-9);
+        assert!((tracker.prob_at_least(Tile::Red, 0) - 1.0).abs() < 1e-9);
+        assert!(tracker.prob_at_least(Tile::Red, 4) > tracker.prob_at_least(Tile::Red, 5));
+
+        // The last 2 tiles of the bag are both dealt, then 2 of the 4 in the lid
+        let tracker: TileTracker =
+            TileTracker::new("RR".parse().unwrap(), "RRBB".parse().unwrap(), 20);
+        assert_eq!(tracker.dealt(), 6);
+        assert_eq!(tracker.prob_at_least(Tile::Red, 4), 1.0);
+        assert_eq!(tracker.prob_at_least(Tile::Blue, 3), 0.0);
+        let tracker = TileTracker {
+            dealt: 4,
+            ..tracker
+        };
+        assert!((tracker.prob_at_least(Tile::Red, 4) - 1.0 / 6.0).abs() < 1e-9);
+    }
 }
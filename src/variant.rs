@@ -0,0 +1,165 @@
+//! Rule variants
+//!
+//! A [Variant] is stored on the [Gamestate](crate::gamestate::Gamestate) and in game records,
+//! so anything that depends on the rules reads them from one place.
+//! Written as comma separated rules, eg `teams,rounds=12`, or `standard` for the base game.
+//! `grey` plays on the grey side of the board, choosing the wall column of each tile,
+//! and `rounds=none` plays on until a wall row is complete
+
+use std::fmt;
+
+use crate::{
+    error::{AzulError, ParseKind},
+    playerboard::wall::{WallPattern, WallPlacement},
+};
+
+/// Rounds played before the game ends without a completed row
+pub const STANDARD_MAX_ROUNDS: u16 = 10;
+
+/// Rules of a game with `C` colours
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Variant<const C: usize = 5> {
+    /// Even and odd seats play as two teams that share their score
+    pub teams: bool,
+    /// Colour arrangement of every wall
+    pub wall: WallPattern<C>,
+    /// Whether tiles go to the pattern's column or one chosen by the player
+    pub placement: WallPlacement,
+    /// The game ends after this round even if no row is complete,
+    /// or only once a row is complete when `None`
+    pub max_rounds: Option<u16>,
+}
+
+impl<const C: usize> Variant<C> {
+    pub fn standard() -> Self {
+        Self {
+            teams: false,
+            wall: WallPattern::default(),
+            placement: WallPlacement::Pattern,
+            max_rounds: Some(STANDARD_MAX_ROUNDS),
+        }
+    }
+
+    pub fn is_standard(&self) -> bool {
+        *self == Self::standard()
+    }
+
+    /// Check that a game of `players` players can use these rules
+    pub fn check_players(&self, players: usize) -> Result<(), AzulError> {
+        if self.teams && !players.is_multiple_of(2) {
+            return Err(AzulError::OddTeams(players));
+        }
+        Ok(())
+    }
+}
+
+impl<const C: usize> Default for Variant<C> {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl<const C: usize> fmt::Display for Variant<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let standard = Self::standard();
+        let mut rules = Vec::new();
+        if self.teams {
+            rules.push("teams".to_string());
+        }
+        if self.placement == WallPlacement::Free {
+            rules.push("grey".to_string());
+        }
+        if self.wall != standard.wall {
+            rules.push(format!("wall={}", self.wall));
+        }
+        if self.max_rounds != standard.max_rounds {
+            match self.max_rounds {
+                Some(rounds) => rules.push(format!("rounds={rounds}")),
+                None => rules.push("rounds=none".to_string()),
+            }
+        }
+        if rules.is_empty() {
+            write!(f, "standard")
+        } else {
+            write!(f, "{}", rules.join(","))
+        }
+    }
+}
+
+impl<const C: usize> std::str::FromStr for Variant<C> {
+    type Err = AzulError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut variant = Self::standard();
+        for rule in s.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+            match rule.split_once('=') {
+                None if rule == "standard" => (),
+                None if rule == "teams" => variant.teams = true,
+                None if rule == "grey" => variant.placement = WallPlacement::Free,
+                Some(("wall", pattern)) => variant.wall = pattern.parse()?,
+                Some(("rounds", "none")) => variant.max_rounds = None,
+                Some(("rounds", rounds)) => {
+                    variant.max_rounds = Some(
+                        rounds
+                            .parse()
+                            .ok()
+                            .filter(|&r| r > 0)
+                            .ok_or_else(|| AzulError::parse(ParseKind::Rounds, rounds))?,
+                    )
+                }
+                _ => return Err(AzulError::parse(ParseKind::Rule, rule)),
+            }
+        }
+        Ok(variant)
+    }
+}
+
+impl<const C: usize> serde::Serialize for Variant<C> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, const C: usize> serde::Deserialize<'de> for Variant<C> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Variant;
+    use crate::error::{AzulError, ParseKind};
+
+    #[test]
+    fn parse_and_display() {
+        let standard: Variant = "standard".parse().unwrap();
+        assert!(standard.is_standard());
+        assert_eq!(standard.to_string(), "standard");
+
+        let text = "teams,wall=BYRKW/YRKWB/RKWBY/KWBYR/WBYRK,rounds=12";
+        let variant: Variant = text.parse().unwrap();
+        assert!(variant.teams);
+        assert_eq!(variant.max_rounds, Some(12));
+        assert_eq!(variant.to_string(), text);
+        assert_eq!(
+            serde_json::from_str::<Variant>(&serde_json::to_string(&variant).unwrap()).unwrap(),
+            variant
+        );
+
+        let grey: Variant = "grey".parse().unwrap();
+        assert_eq!(grey.placement, super::WallPlacement::Free);
+        assert_eq!(grey.to_string(), "grey");
+        assert_eq!(
+            "giant".parse::<Variant>(),
+            Err(AzulError::parse(ParseKind::Rule, "giant"))
+        );
+        assert!("rounds=0".parse::<Variant>().is_err());
+        let endless: Variant = "rounds=none".parse().unwrap();
+        assert_eq!(endless.max_rounds, None);
+        assert_eq!(endless.to_string(), "rounds=none");
+        assert!("wall=BYRKW".parse::<Variant>().is_err());
+    }
+}
@@ -0,0 +1,186 @@
+//! What a seat or a spectator can see of a game
+//!
+//! A [Gamestate] holds the rng that decides every future deal, so handing one out tells
+//! its holder how the rest of the game will be dealt. Front ends show games through a
+//! [PlayerView] instead, which only gives the table: boards, factories, scores and the
+//! counts of tiles left in the bag. Every board is public in the current rules, but
+//! [PlayerView::can_see] is where variants with hidden information would hide them
+
+use rand::Rng;
+
+use crate::{
+    gamestate::{GameRng, Gamestate, Move, State},
+    playerboard::{wall::WallPattern, PlayerBoard},
+    tiles::TileGroup,
+    variant::Variant,
+};
+
+/// Who a [PlayerView] is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Viewer {
+    /// Player in this seat
+    Seat(u8),
+    /// Someone watching, who sees what every player can see
+    Spectator,
+}
+
+/// Part of a game visible to a [Viewer], see [Gamestate::view]
+#[derive(Debug)]
+pub struct PlayerView<'a, const P: usize, const F: usize, R = GameRng, const C: usize = 5> {
+    gs: &'a Gamestate<P, F, R, C>,
+    viewer: Viewer,
+}
+
+impl<const P: usize, const F: usize, R, const C: usize> Gamestate<P, F, R, C> {
+    /// The game as `viewer` sees it
+    pub fn view(&self, viewer: Viewer) -> PlayerView<'_, P, F, R, C> {
+        PlayerView { gs: self, viewer }
+    }
+}
+
+impl<const P: usize, const F: usize, R: Rng + Clone, const C: usize> PlayerView<'_, P, F, R, C> {
+    pub fn viewer(&self) -> Viewer {
+        self.viewer
+    }
+
+    /// Whether the viewer can see the board of `seat`
+    pub fn can_see(&self, seat: usize) -> bool {
+        seat < P
+    }
+
+    /// Board of `seat`, if the viewer can see it
+    pub fn board(&self, seat: usize) -> Option<&PlayerBoard<C>> {
+        self.can_see(seat).then(|| &self.gs.boards()[seat])
+    }
+
+    /// Scores of the boards the viewer can see
+    pub fn scores(&self) -> [Option<u8>; P] {
+        std::array::from_fn(|i| self.board(i).map(|b| b.score))
+    }
+
+    pub fn state(&self) -> State {
+        self.gs.state()
+    }
+
+    pub fn round(&self) -> u16 {
+        self.gs.round()
+    }
+
+    pub fn current_player(&self) -> u8 {
+        self.gs.current_player()
+    }
+
+    /// Whether the viewer is the player to move
+    pub fn to_move(&self) -> bool {
+        self.gs.state() == State::RoundActive
+            && self.viewer == Viewer::Seat(self.gs.current_player())
+    }
+
+    /// Whether the first player tile is still in the centre
+    pub fn first_player_tile(&self) -> bool {
+        self.gs.first_player_tile()
+    }
+
+    pub fn factories(&self) -> &[Option<TileGroup<C>>; F] {
+        self.gs.factories()
+    }
+
+    pub fn centre(&self) -> TileGroup<C> {
+        self.gs.centre()
+    }
+
+    /// Tiles left in the bag, which anyone can count from the table,
+    /// but not the order they will be drawn in
+    pub fn bag(&self) -> &TileGroup<C> {
+        self.gs.tilebag()
+    }
+
+    /// Tiles discarded since the bag was last refilled
+    pub fn lid(&self) -> &TileGroup<C> {
+        self.gs.lid()
+    }
+
+    pub fn variant(&self) -> &Variant<C> {
+        self.gs.variant()
+    }
+
+    pub fn wall_pattern(&self) -> &WallPattern<C> {
+        self.gs.wall_pattern()
+    }
+
+    /// Legal moves if the viewer is to move, otherwise none
+    pub fn moves(&self) -> Vec<Move> {
+        if self.to_move() {
+            self.gs.get_moves()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Same as the [Gamestate], with `hidden` in place of boards the viewer can't see
+impl<const P: usize, const F: usize, R: Rng + Clone, const C: usize> std::fmt::Display
+    for PlayerView<'_, P, F, R, C>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Round {}, centre ", self.round())?;
+        let centre = self.centre();
+        if self.first_player_tile() {
+            write!(f, "1")?;
+        }
+        if !self.first_player_tile() || !centre.is_empty() {
+            std::fmt::Display::fmt(&centre, f)?;
+        }
+        write!(f, "  Factories")?;
+        for (i, factory) in self.factories().iter().enumerate().skip(1) {
+            write!(f, " {i}:")?;
+            std::fmt::Display::fmt(&factory.unwrap_or_default(), f)?;
+        }
+        write!(f, "\nBag")?;
+        fmt_counts(self.bag(), f)?;
+        write!(f, "  Lid")?;
+        fmt_counts(self.lid(), f)?;
+        for i in 0..P {
+            let to_move = self.state() == State::RoundActive && i == self.current_player() as usize;
+            write!(f, "\nPlayer {i}{}: ", if to_move { " to move" } else { "" })?;
+            match self.board(i) {
+                Some(board) => std::fmt::Display::fmt(board, f)?,
+                None => write!(f, "hidden")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Number of each colour after its letter, eg ` B4 Y0 R12 K3 W1`
+fn fmt_counts<const C: usize>(
+    tiles: &TileGroup<C>,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    for (tile, count) in tiles.iter() {
+        write!(f, " ")?;
+        std::fmt::Display::fmt(&tile, f)?;
+        write!(f, "{count}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Viewer;
+    use crate::gamestate::Gamestate;
+
+    #[test]
+    fn views() {
+        let gs = Gamestate::new_2_player_with_seed(4, 1);
+        let spectator = gs.view(Viewer::Spectator);
+        assert!(spectator
+            .to_string()
+            .starts_with("Round 1, centre 1  Factories"));
+        assert_eq!(spectator.scores(), [Some(0), Some(0)]);
+        assert!(spectator.moves().is_empty());
+        assert!(gs.view(Viewer::Seat(0)).moves().is_empty());
+        assert_eq!(gs.view(Viewer::Seat(1)).moves(), gs.get_moves());
+        assert_eq!(spectator.bag(), gs.tilebag());
+    }
+}
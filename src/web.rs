@@ -0,0 +1,307 @@
+//! Web page for following long runs from a browser
+//!
+//! Enabled with the `web-dashboard` feature. [WebDashboard::serve] answers HTTP requests
+//! on a background thread: `/` is a page that polls `/status` for the progress, standings,
+//! charts and recent games that the running process reports
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{mpsc, Arc, Mutex},
+};
+
+use crate::{error::AzulError, players::ppo::train::Metric, runner::Progress};
+
+/// Games kept in the recent games list
+const RECENT_GAMES: usize = 20;
+
+const PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Azul dashboard</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+td, th { padding: 2px 10px; text-align: right; }
+td:first-child, th:first-child { text-align: left; }
+.chart { display: inline-block; margin: 0 1em 1em 0; }
+svg { border: 1px solid #ccc; }
+</style>
+</head>
+<body>
+<h1 id="title"></h1>
+<p id="progress"></p>
+<h2>Standings</h2>
+<table id="standings"></table>
+<h2>Charts</h2>
+<div id="charts"></div>
+<h2>Recent games</h2>
+<ol id="games" reversed></ol>
+<script>
+const W = 400, H = 200;
+function chart(name, points) {
+  const xs = points.map(p => p[0]), ys = points.map(p => p[1]);
+  const [x0, x1] = [Math.min(...xs), Math.max(...xs)];
+  const [y0, y1] = [Math.min(...ys), Math.max(...ys)];
+  const sx = x => (x - x0) / ((x1 - x0) || 1) * (W - 10) + 5;
+  const sy = y => H - 5 - (y - y0) / ((y1 - y0) || 1) * (H - 10);
+  const line = points.map(p => sx(p[0]).toFixed(1) + ',' + sy(p[1]).toFixed(1)).join(' ');
+  return `<div class="chart"><b>${name}</b> ${ys[ys.length - 1].toPrecision(4)}
+    <small>(${y0.toPrecision(3)} to ${y1.toPrecision(3)})</small><br>
+    <svg width="${W}" height="${H}"><polyline points="${line}" fill="none" stroke="steelblue"/></svg></div>`;
+}
+function row(cells, tag) {
+  return '<tr>' + cells.map(c => `<${tag}>${c}</${tag}>`).join('') + '</tr>';
+}
+async function update() {
+  try {
+    const status = await (await fetch('/status')).json();
+    document.title = status.title;
+    document.getElementById('title').textContent = status.title;
+    document.getElementById('progress').textContent = status.progress;
+    document.getElementById('standings').innerHTML = row(status.standings.header, 'th')
+      + status.standings.rows.map(r => row(r, 'td')).join('');
+    document.getElementById('charts').innerHTML = Object.entries(status.charts)
+      .filter(([_, points]) => points.length > 0)
+      .map(([name, points]) => chart(name, points)).join('');
+    document.getElementById('games').innerHTML = status.games.slice().reverse()
+      .map(g => `<li>${g}</li>`).join('');
+  } catch (e) {
+    document.getElementById('progress').textContent = 'Lost connection: ' + e;
+  }
+}
+update();
+setInterval(update, 2000);
+</script>
+</body>
+</html>
+"#;
+
+/// Rows of text with a header, shown as the standings
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Everything the page shows, sent as json from `/status`
+#[derive(Debug, Default, serde::Serialize)]
+struct Status {
+    title: String,
+    progress: String,
+    standings: Table,
+    /// Points of each chart as (x, y)
+    charts: BTreeMap<String, Vec<[f64; 2]>>,
+    /// Descriptions of finished games, most recent last
+    games: VecDeque<String>,
+}
+
+/// Handle to a running dashboard, cloned to report from several threads
+#[derive(Debug, Clone)]
+pub struct WebDashboard {
+    status: Arc<Mutex<Status>>,
+    addr: SocketAddr,
+}
+
+impl WebDashboard {
+    /// Serve the dashboard on `addr`, eg `127.0.0.1:8080`, until the process exits
+    pub fn serve(addr: &str, title: impl Into<String>) -> Result<Self, AzulError> {
+        let (addr, listener) = TcpListener::bind(addr)
+            .and_then(|listener| Ok((listener.local_addr()?, listener)))
+            .map_err(|e| AzulError::Listen {
+                addr: addr.to_string(),
+                reason: e.to_string(),
+            })?;
+        let status = Arc::new(Mutex::new(Status {
+            title: title.into(),
+            ..Default::default()
+        }));
+        let shared = status.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A browser that goes away mid request doesn't matter
+                let _ = respond(stream, &shared);
+            }
+        });
+        Ok(Self { status, addr })
+    }
+
+    /// Address being served, useful when binding to port 0
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Clear everything shown, to start showing a new run
+    pub fn reset(&self, title: impl Into<String>) {
+        *self.status.lock().unwrap() = Status {
+            title: title.into(),
+            ..Default::default()
+        };
+    }
+
+    pub fn set_progress(&self, progress: impl Into<String>) {
+        self.status.lock().unwrap().progress = progress.into();
+    }
+
+    pub fn set_standings(&self, standings: Table) {
+        self.status.lock().unwrap().standings = standings;
+    }
+
+    /// Add a point to the end of `chart`
+    pub fn add_point(&self, chart: &str, x: f64, y: f64) {
+        let mut status = self.status.lock().unwrap();
+        status
+            .charts
+            .entry(chart.to_string())
+            .or_default()
+            .push([x, y]);
+    }
+
+    /// Add a finished game to the recent games
+    pub fn add_game(&self, game: impl Into<String>) {
+        let mut status = self.status.lock().unwrap();
+        if status.games.len() == RECENT_GAMES {
+            status.games.pop_front();
+        }
+        status.games.push_back(game.into());
+    }
+
+    /// Show a PPO training metric
+    pub fn add_metric(&self, metric: &Metric) {
+        match metric {
+            Metric::Episode {
+                episode,
+                learning_rate,
+                policy_loss,
+                critic_loss,
+                win_rate,
+                opponent,
+            } => {
+                let x = *episode as f64;
+                self.add_point("Policy loss", x, *policy_loss as f64);
+                self.add_point("Critic loss", x, *critic_loss as f64);
+                self.add_point("Win rate", x, *win_rate as f64);
+                self.set_progress(format!(
+                    "Episode {episode} against {opponent:?}, learning rate {learning_rate:.2e}"
+                ));
+            }
+            Metric::Evaluation {
+                episode,
+                elo,
+                best_elo,
+                results,
+            } => {
+                let x = *episode as f64;
+                self.add_point("Elo", x, *elo);
+                self.add_point("Best Elo", x, *best_elo);
+                self.add_game(format!(
+                    "Episode {episode} evaluation: Elo {elo:.0}, average margins {}",
+                    results
+                        .iter()
+                        .map(|r| format!("{:+.1}", r.average_score()))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+    }
+
+    /// Channel for [crate::runner::Runner::with_progress], adding each finished game
+    /// to the recent games
+    pub fn progress_sender(&self) -> mpsc::Sender<Progress> {
+        let (sender, receiver) = mpsc::channel();
+        let dashboard = self.clone();
+        std::thread::spawn(move || {
+            let mut playing = HashMap::new();
+            for progress in receiver {
+                match progress {
+                    Progress::Started { game, players, .. } => {
+                        playing.insert(game, players);
+                    }
+                    Progress::Finished {
+                        game,
+                        scores,
+                        adjudication,
+                    } => {
+                        if let Some([a, b]) = playing.remove(&game) {
+                            let mut text = format!("{a} {} - {} {b}", scores[0], scores[1]);
+                            if adjudication.is_some() {
+                                text += " (adjudicated)";
+                            }
+                            dashboard.add_game(text);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        });
+        sender
+    }
+}
+
+/// Answer one request
+fn respond(stream: TcpStream, status: &Mutex<Status>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Skip the headers, nothing here needs them
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let (code, content_type, body) = match request.split_whitespace().nth(1) {
+        Some("/") => ("200 OK", "text/html; charset=utf-8", PAGE.to_string()),
+        Some("/status") => (
+            "200 OK",
+            "application/json",
+            serde_json::to_string(&*status.lock().unwrap())?,
+        ),
+        _ => ("404 Not Found", "text/plain", "Not found".to_string()),
+    };
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {code}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+
+    use super::{Table, WebDashboard};
+
+    #[test]
+    fn serves_status() {
+        let dashboard = WebDashboard::serve("127.0.0.1:0", "Test run").unwrap();
+        dashboard.set_progress("Halfway");
+        dashboard.add_point("Score", 1.0, 2.5);
+        dashboard.add_game("A 50 - 40 B");
+        dashboard.set_standings(Table {
+            header: vec!["Player".into()],
+            rows: vec![vec!["A".into()]],
+        });
+
+        let get = |path: &str| {
+            let mut stream = std::net::TcpStream::connect(dashboard.addr()).unwrap();
+            write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let status = get("/status");
+        assert!(status.starts_with("HTTP/1.1 200 OK"));
+        let json: serde_json::Value =
+            serde_json::from_str(status.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!(json["title"], "Test run");
+        assert_eq!(json["charts"]["Score"][0][1], 2.5);
+        assert_eq!(json["games"][0], "A 50 - 40 B");
+        assert!(get("/").contains("<html>"));
+        assert!(get("/missing").starts_with("HTTP/1.1 404"));
+    }
+}
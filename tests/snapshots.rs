@@ -0,0 +1,114 @@
+//! Snapshot regression tests of full seeded games
+//!
+//! Plays fixed-seed games between deterministic players and compares the full
+//! transcript of moves and scores against the files in `tests/snapshots`.
+//! Any change to dealing, move generation order or scoring shows up as a diff
+//! against these files.
+//!
+//! Run with `UPDATE_SNAPSHOTS=1` to regenerate the snapshots after an
+//! intentional change in engine behaviour.
+
+// SmallRng is a different algorithm on 32 bit targets, so deals will not match
+#![cfg(target_pointer_width = "64")]
+
+use std::{fmt::Write, path::PathBuf};
+
+use azul_tiles_rs::{
+    gamestate::{Destination, Gamestate, Move, State},
+    players::{FirstMovePlayer, MoveRankPlayer2, Player},
+};
+
+/// Play a full game and record every move, round end and the final scores
+fn transcript(seed: u64, mut players: [Box<dyn Player<2, 6>>; 2]) -> String {
+    let mut gs = Gamestate::new_2_player_with_seed(seed, 0);
+    let mut out = String::new();
+    writeln!(
+        out,
+        "seed {} : {} vs {}",
+        seed,
+        players[0].name(),
+        players[1].name()
+    )
+    .unwrap();
+    loop {
+        writeln!(out, "round {}", gs.round()).unwrap();
+        for (i, factory) in gs.factories().iter().enumerate().skip(1) {
+            writeln!(out, "  factory {} {:?}", i, factory.unwrap().counts()).unwrap();
+        }
+        loop {
+            let player = gs.current_player();
+            let moves = gs.get_moves();
+            let n_moves = moves.len();
+            let m = players[player as usize].pick_move(&gs, moves);
+            writeln!(out, "  p{} {} ({} moves)", player, format_move(&m), n_moves).unwrap();
+            if gs.play_move(m) == State::RoundEnd {
+                break;
+            }
+        }
+        let state = gs.end_round().state;
+        writeln!(out, "  scores {:?}", gs.scores()).unwrap();
+        if state == State::GameEnd {
+            break;
+        }
+    }
+    writeln!(out, "final {:?}", gs.scores()).unwrap();
+    out
+}
+
+fn format_move(m: &Move) -> String {
+    let dest = match m.destination {
+        Destination::Row(row) => format!("row {}", row as usize + 1),
+        Destination::Floor => "floor".into(),
+    };
+    format!("{} x {:?} from {} to {}", m.count, m.tile, m.source.0, dest)
+}
+
+fn check_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("Missing snapshot {path:?}, run with UPDATE_SNAPSHOTS=1"));
+    if expected != actual {
+        let line = expected
+            .lines()
+            .zip(actual.lines())
+            .position(|(e, a)| e != a)
+            .unwrap_or(expected.lines().count().min(actual.lines().count()));
+        panic!(
+            "Snapshot {} differs at line {}\nexpected: {:?}\nactual:   {:?}",
+            name,
+            line + 1,
+            expected.lines().nth(line),
+            actual.lines().nth(line)
+        );
+    }
+}
+
+#[test]
+fn rank_vs_rank() {
+    for seed in [1, 42] {
+        let t = transcript(seed, [Box::new(MoveRankPlayer2), Box::new(MoveRankPlayer2)]);
+        check_snapshot(&format!("rank_vs_rank_{seed}"), &t);
+    }
+}
+
+#[test]
+fn first_vs_rank() {
+    let t = transcript(7, [Box::new(FirstMovePlayer), Box::new(MoveRankPlayer2)]);
+    check_snapshot("first_vs_rank_7", &t);
+}
+
+#[test]
+fn perft() {
+    // Canonical move tree sizes from the start of seeded games
+    let gs = Gamestate::new_2_player_with_seed(0, 0);
+    let counts = (1..=3)
+        .map(|depth| azul_tiles_rs::analysis::perft(&gs, depth))
+        .collect::<Vec<_>>();
+    assert_eq!(counts, [72, 4752, 241404]);
+}